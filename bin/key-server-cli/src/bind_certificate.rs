@@ -0,0 +1,31 @@
+//! Binding of client certificate identities to accounts.
+
+use lock_keeper::types::database::account::AccountName;
+use lock_keeper_key_server::{
+    server::{client_certificate::ClientCertificate, database::DataStore},
+    LockKeeperServerError,
+};
+
+/// Bind the client certificate `identity` to the account. After this,
+/// requests for the account are only accepted over connections whose client
+/// certificate has a bound identity.
+pub(crate) async fn run<DB: DataStore>(
+    db: &DB,
+    account_name: &AccountName,
+    identity: &str,
+) -> Result<(), LockKeeperServerError> {
+    if !ClientCertificate::is_valid_identity(identity) {
+        return Err(LockKeeperServerError::InvalidClientCertificate);
+    }
+
+    let account = db
+        .find_account_by_name(account_name)
+        .await?
+        .ok_or(LockKeeperServerError::InvalidAccount)?;
+
+    db.create_certificate_binding(identity, account.id())
+        .await?;
+    println!("Bound client certificate identity {identity} to {account_name}.");
+
+    Ok(())
+}
@@ -3,6 +3,7 @@
 use lock_keeper_key_server::LockKeeperServerError;
 use serde::{Deserialize, Serialize};
 use std::{
+    net::SocketAddr,
     path::{Path, PathBuf},
     str::FromStr,
 };
@@ -11,8 +12,29 @@ use std::{
 #[serde(deny_unknown_fields, rename_all = "snake_case")]
 pub struct Config {
     pub server: PathBuf,
+    /// Which persistence backend to use. Defaults to Postgres.
+    #[serde(default)]
+    pub backend: Backend,
+    /// Path to the config file for the selected backend.
     pub database: PathBuf,
-    pub session_cache: PathBuf,
+    /// Path to the session cache config file. Only used by the Postgres
+    /// backend. The SQLite backend keeps sessions in its database file.
+    pub session_cache: Option<PathBuf>,
+    /// Address of the HTTP listener that serves Prometheus metrics at
+    /// `/metrics`. Metrics are not served if this is not set.
+    pub metrics_address: Option<SocketAddr>,
+}
+
+/// Persistence backends the key server can run against.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// Separate Postgres databases for data and sessions.
+    #[default]
+    Postgres,
+    /// A single SQLite database file. Only suitable for single-node
+    /// deployments.
+    Sqlite,
 }
 
 impl Config {
@@ -30,3 +52,57 @@ impl FromStr for Config {
         toml::from_str(config_string)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backend_defaults_to_postgres() {
+        let config = Config::from_str(
+            r#"
+            server = "Server.toml"
+            database = "Postgres.toml"
+            session_cache = "SessionCache.toml"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.backend, Backend::Postgres);
+        assert_eq!(
+            config.session_cache,
+            Some(PathBuf::from("SessionCache.toml"))
+        );
+    }
+
+    #[test]
+    fn sqlite_backend_does_not_need_session_cache() {
+        let config = Config::from_str(
+            r#"
+            server = "Server.toml"
+            backend = "sqlite"
+            database = "Sqlite.toml"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.backend, Backend::Sqlite);
+        assert_eq!(config.session_cache, None);
+        assert_eq!(config.metrics_address, None);
+    }
+
+    #[test]
+    fn metrics_address_is_parsed() {
+        let config = Config::from_str(
+            r#"
+            server = "Server.toml"
+            backend = "sqlite"
+            database = "Sqlite.toml"
+            metrics_address = "127.0.0.1:9100"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.metrics_address,
+            Some(SocketAddr::from(([127, 0, 0, 1], 9100)))
+        );
+    }
+}
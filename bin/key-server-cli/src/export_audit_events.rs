@@ -0,0 +1,69 @@
+//! Export of the audit events of every account for a time range.
+
+use std::{fs::File, io::BufWriter, path::Path};
+
+use lock_keeper::types::{
+    audit_event::{AuditEventOptions, EventType},
+    audit_export::{AuditEventWriter, AuditExportFormat},
+};
+use lock_keeper_key_server::{server::database::DataStore, LockKeeperServerError};
+use time::{format_description::well_known::Rfc3339, Date, OffsetDateTime};
+
+/// Number of events read from the database at a time.
+const PAGE_SIZE: u32 = 1000;
+
+/// Write the audit events of every account between `after` and `before` to
+/// `output`.
+pub(crate) async fn run<DB: DataStore>(
+    db: &DB,
+    format: AuditExportFormat,
+    output: &Path,
+    after: Option<OffsetDateTime>,
+    before: Option<OffsetDateTime>,
+) -> Result<(), LockKeeperServerError> {
+    let file = File::create(output).map_err(|e| LockKeeperServerError::FileIo(e, output.into()))?;
+    let mut writer = AuditEventWriter::new(BufWriter::new(file), format)?;
+
+    let mut options = AuditEventOptions {
+        after_date: after,
+        before_date: before,
+        limit: Some(PAGE_SIZE),
+        ..Default::default()
+    };
+    loop {
+        let events = db
+            .find_all_audit_events(EventType::All, options.clone())
+            .await?;
+        for event in &events {
+            writer.write(event)?;
+        }
+
+        match events.last() {
+            Some(last_event) if events.len() == PAGE_SIZE as usize => {
+                options.after_audit_event_id = Some(last_event.audit_event_id);
+            }
+            _ => break,
+        }
+    }
+
+    let events_written = writer.finish()?;
+    println!(
+        "Exported {events_written} audit events to {}.",
+        output.display()
+    );
+
+    Ok(())
+}
+
+/// Parse an RFC 3339 timestamp, or a date in `YYYY-MM-DD` format which is
+/// taken as midnight UTC.
+pub(crate) fn parse_date(date: &str) -> Result<OffsetDateTime, String> {
+    if let Ok(timestamp) = OffsetDateTime::parse(date, &Rfc3339) {
+        return Ok(timestamp);
+    }
+
+    let format = time::macros::format_description!("[year]-[month]-[day]");
+    Date::parse(date, &format)
+        .map(|date| date.midnight().assume_utc())
+        .map_err(|e| format!("Invalid date {date:?}: {e}"))
+}
@@ -0,0 +1,49 @@
+//! Import of audit events from an archive written by the audit retention task.
+
+use std::{collections::HashMap, path::Path};
+
+use lock_keeper::crypto::AuditPublicKey;
+use lock_keeper_key_server::{
+    server::{audit_archive::AuditArchive, database::DataStore},
+    LockKeeperServerError,
+};
+
+/// Check the archive at `input` against the server's audit key and store its
+/// events again. Events that are already stored and events of accounts that
+/// have since been deleted are skipped.
+pub(crate) async fn run<DB: DataStore>(
+    db: &DB,
+    audit_public_key: &AuditPublicKey,
+    input: &Path,
+) -> Result<(), LockKeeperServerError> {
+    let archive = AuditArchive::read(input, audit_public_key)?;
+    let archived = archive.events.len();
+
+    let mut account_exists = HashMap::new();
+    let mut events = Vec::with_capacity(archived);
+    for event in archive.events {
+        let exists = match account_exists.get(&event.account_id) {
+            Some(exists) => *exists,
+            None => {
+                let exists = db.find_account(event.account_id).await?.is_some();
+                let _ = account_exists.insert(event.account_id, exists);
+                exists
+            }
+        };
+        if exists {
+            events.push(event);
+        }
+    }
+    let deleted_accounts = archived - events.len();
+
+    let imported = db.import_audit_events(&events).await?;
+    println!(
+        "Imported {imported} of {archived} audit events from {}.",
+        input.display()
+    );
+    if deleted_accounts > 0 {
+        println!("Skipped {deleted_accounts} events of deleted accounts.");
+    }
+
+    Ok(())
+}
@@ -155,19 +155,140 @@
 //! - **trace**: Very verbose information that allows you to trace the execution
 //!   of a program at a
 //! fine granularity, e.g. program control flow.
+//!
+//! ## Reloading Config
+//! Sending `SIGHUP` to the server re-reads the server config file. TLS
+//! certificates, keys and revocation lists, `max_blob_size`, `receive_timeout`,
+//! `max_message_size`, the concurrency limits, `release_toml_path`, and the
+//! stdout log level are applied to new connections and requests without a
+//! restart. Revocation lists are also reloaded on their own shortly after
+//! their files change. Audit retention settings apply from the next retention run, and
+//! audit retention can be turned on or off as long as `audit_key` is set.
+//! If the new config can't be loaded, the error is logged and the server keeps
+//! running with its current config. All other settings, including log file
+//! paths, require a restart.
+//!
+//! ## Persistence Backends
+//! The binary config selects a persistence backend with the `backend` key:
+//! - `postgres` (the default): `database` points to a Postgres config file and
+//!   `session_cache` points to a session cache config file.
+//! - `sqlite`: `database` points to a SQLite config file. Data and sessions
+//!   are stored in a single database file and `session_cache` is not used.
+//!   This is intended for single-node deployments only.
+//!
+//! ## Shutting Down
+//! On `SIGTERM` or Ctrl-C the server stops accepting new connections and
+//! requests and waits up to `shutdown_timeout` (30 seconds by default) for
+//! running operations to finish. Operations still running after that are
+//! aborted and recorded with an `Aborted` audit event.
+//!
+//! ## Message Limits
+//! Each operation waits up to `receive_timeout` (30 seconds by default) for
+//! every message from the client, and rejects messages larger than
+//! `max_message_size` bytes (2 MiB by default) before decoding them. Either
+//! violation ends the operation with a `Failed` audit event whose failure code
+//! is `receive-timeout` or `message-too-large`.
+//!
+//! ## Concurrency Limits
+//! The server handles at most `max_concurrent_requests` requests at a time
+//! (512 by default), and at most `max_concurrent_requests_per_account`
+//! authenticated requests for a single account (16 by default). The account is
+//! the one the request's session was created for, so requests that aren't
+//! authenticated, like registration and login, only count against the first
+//! limit. Requests over either limit are rejected right away with
+//! `RESOURCE_EXHAUSTED`, and can be retried later.
+//!
+//! ## Metrics
+//! If `metrics_address` is set in the binary config, the server serves
+//! Prometheus metrics in the text format at `/metrics` on that address, e.g.
+//! `metrics_address = "127.0.0.1:9100"`. The available metrics are listed in
+//! [`lock_keeper_key_server::server::metrics`]. The listener uses plain HTTP,
+//! so it should only be reachable by the metrics collector.
+//!
+//! ## Distributed Tracing
+//! If `[logging.otlp]` is set in the server config, spans from our crates are
+//! exported to the OpenTelemetry collector at its `endpoint`, e.g.
+//! `endpoint = "http://localhost:4317"`. The client sends the W3C `traceparent`
+//! of its span with every request, and the span that handles the request (and
+//! the task spawned for the operation) continues that trace. Spans that haven't
+//! been exported yet are sent when the server exits.
+//!
+//! ## Verifying Audit Logs
+//! Audit events are hash-chained, and the server signs periodic checkpoints
+//! with the key set by `audit_key` in the server config. The
+//! `verify-audit-chain` subcommand connects to the configured backend and
+//! checks the audit log of one account against those checkpoints, without
+//! starting the server:
+//! ```text
+//! key-server-cli dev/config/local/Binary.toml verify-audit-chain --account-name alice
+//! ```
+//! It exits with a non-zero status if events were modified, removed, or
+//! truncated.
+//!
+//! ## Exporting Audit Logs
+//! The `export-audit-events` subcommand writes the audit events of every
+//! account to a JSON Lines or CSV file, e.g. for ingestion by a SIEM. Use
+//! `--after` and `--before` to limit the export to a time range:
+//! ```text
+//! key-server-cli dev/config/local/Binary.toml export-audit-events --format csv --output audit.csv --after 2023-01-01
+//! ```
+//! The schema of both formats is documented in
+//! [`lock_keeper::types::audit_export`].
+//!
+//! ## Archiving Audit Logs
+//! If `audit_retention` is set in the server config, the server moves audit
+//! events older than the retention period into signed, compressed archive
+//! files and deletes them from the database. The `import-audit-archive`
+//! subcommand checks an archive against the server's audit key and stores its
+//! events again, e.g. for an investigation:
+//! ```text
+//! key-server-cli dev/config/local/Binary.toml import-audit-archive --input audit-events-1-1000.jsonl.gz
+//! ```
+//! Imported events keep their IDs and hash chain, so `verify-audit-chain`
+//! covers them again. They are still expired, so the server archives them
+//! again on its next retention run.
+//!
+//! ## Binding Client Certificates
+//! If `client_auth` is enabled in the server's TLS config, the
+//! `bind-certificate` subcommand binds an identity from a client certificate
+//! to an account. From then on, requests for that account are only accepted
+//! over connections whose certificate has a bound identity:
+//! ```text
+//! key-server-cli dev/config/local/Binary.toml bind-certificate --account-name alice --identity "subject:CN=client, O=Bolt Labs"
+//! ```
+//! The identity formats are listed in
+//! [`lock_keeper_key_server::server::client_certificate::ClientCertificate`].
 
+mod bind_certificate;
 mod config;
+mod export_audit_events;
+mod import_audit_archive;
+mod otlp;
+mod size_rolling;
+mod verify_audit_chain;
 
 use std::{
     ffi::OsStr,
+    io::{self, Write},
     path::{Path, PathBuf},
 };
 
-use config::Config;
+use config::{Backend, Config};
+use otlp::OtlpGuard;
+use size_rolling::SizeRollingWriter;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use lock_keeper::{
+    crypto::{AuditPublicKey, AuditSigningKey},
+    types::{audit_export::AuditExportFormat, database::account::AccountName},
+};
 use lock_keeper_key_server::{
-    config::Config as ServerConfig, server::start_lock_keeper_server, LockKeeperServerError,
+    config::{
+        Config as ServerConfig, ConfigFile as ServerConfigFile, LogFormat, LogRotation,
+        LogRotationConfig,
+    },
+    server::{metrics::Metrics, start_lock_keeper_server, ReloadConfig},
+    LockKeeperServerError,
 };
 use lock_keeper_postgres::{
     Config as PostgresConfig, ConfigFile as DatabaseConfigFile, PostgresDB,
@@ -176,11 +297,19 @@ use lock_keeper_session_cache_sql::{
     config::{Config as SessionConfig, ConfigFile as SessionConfigFile},
     PostgresSessionCache,
 };
+use lock_keeper_sqlite::{Config as SqliteConfig, SqliteDB};
+use time::OffsetDateTime;
+
+use tracing::{error, info, warn, Level, Subscriber};
+use tracing_appender::{
+    self,
+    non_blocking::{NonBlocking, WorkerGuard},
+    rolling::{RollingFileAppender, Rotation},
+};
 
-use tracing::{info, warn, Level};
-use tracing_appender::{self, non_blocking::WorkerGuard};
-
-use tracing_subscriber::{filter::Targets, prelude::*};
+use tracing_subscriber::{
+    filter::Targets, prelude::*, registry::LookupSpan, reload, Layer, Registry,
+};
 
 #[derive(Parser)] //Should not derive debug, contains secrets
 pub struct Cli {
@@ -209,17 +338,172 @@ pub struct Cli {
     /// Base64 encoded opaque server key
     #[clap(long, env=ServerConfig::OPAQUE_SERVER_SETUP)]
     pub opaque_server_setup: Option<String>,
+
+    /// Run a maintenance command instead of starting the server
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Check that the audit log of an account has not been tampered with
+    VerifyAuditChain {
+        /// Name of the account whose audit log is checked
+        #[clap(long)]
+        account_name: String,
+    },
+    /// Export the audit events of every account to a JSON Lines or CSV file
+    ExportAuditEvents {
+        /// Output format, either `jsonl` or `csv`
+        #[clap(long, default_value = "jsonl")]
+        format: AuditExportFormat,
+        /// File to write the events to
+        #[clap(long)]
+        output: PathBuf,
+        /// Only export events from this time on (RFC 3339 or YYYY-MM-DD)
+        #[clap(long, value_parser = export_audit_events::parse_date)]
+        after: Option<OffsetDateTime>,
+        /// Only export events up to this time (RFC 3339 or YYYY-MM-DD)
+        #[clap(long, value_parser = export_audit_events::parse_date)]
+        before: Option<OffsetDateTime>,
+    },
+    /// Store the audit events of an archive written by audit retention again
+    ImportAuditArchive {
+        /// Archive file to import
+        #[clap(long)]
+        input: PathBuf,
+    },
+    /// Bind a client certificate identity to an account
+    BindCertificate {
+        /// Name of the account the identity is bound to
+        #[clap(long)]
+        account_name: String,
+        /// Certificate identity, e.g. `subject:CN=client, O=Bolt Labs` or
+        /// `dns:client.example.com`
+        #[clap(long)]
+        identity: String,
+    },
 }
 
 #[tokio::main]
 pub async fn main() {
-    if let Err(e) = run_main().await {
-        eprintln!("Server error: {e}");
+    let mut cli = Cli::parse();
+    match cli.command.take() {
+        Some(command) => {
+            if let Err(e) = run_command(cli, command).await {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        }
+        None => {
+            if let Err(e) = run_main(cli).await {
+                eprintln!("Server error: {e}");
+            }
+        }
+    }
+}
+
+pub async fn run_command(cli: Cli, command: Command) -> Result<(), LockKeeperServerError> {
+    let config = Config::from_file(&cli.config)?;
+
+    match command {
+        Command::VerifyAuditChain { account_name } => {
+            let audit_public_key = audit_public_key(&config.server)?;
+            let account_name = AccountName::from(account_name.as_str());
+
+            match config.backend {
+                Backend::Postgres => {
+                    let postgres = connect_postgres(
+                        cli.database_username,
+                        cli.database_password,
+                        &config.database,
+                    )
+                    .await;
+                    verify_audit_chain::run(&postgres, &audit_public_key, &account_name).await
+                }
+                Backend::Sqlite => {
+                    let sqlite = connect_sqlite(&config.database).await;
+                    verify_audit_chain::run(&sqlite, &audit_public_key, &account_name).await
+                }
+            }
+        }
+        Command::ExportAuditEvents {
+            format,
+            output,
+            after,
+            before,
+        } => match config.backend {
+            Backend::Postgres => {
+                let postgres = connect_postgres(
+                    cli.database_username,
+                    cli.database_password,
+                    &config.database,
+                )
+                .await;
+                export_audit_events::run(&postgres, format, &output, after, before).await
+            }
+            Backend::Sqlite => {
+                let sqlite = connect_sqlite(&config.database).await;
+                export_audit_events::run(&sqlite, format, &output, after, before).await
+            }
+        },
+        Command::ImportAuditArchive { input } => {
+            let audit_public_key = audit_public_key(&config.server)?;
+
+            match config.backend {
+                Backend::Postgres => {
+                    let postgres = connect_postgres(
+                        cli.database_username,
+                        cli.database_password,
+                        &config.database,
+                    )
+                    .await;
+                    import_audit_archive::run(&postgres, &audit_public_key, &input).await
+                }
+                Backend::Sqlite => {
+                    let sqlite = connect_sqlite(&config.database).await;
+                    import_audit_archive::run(&sqlite, &audit_public_key, &input).await
+                }
+            }
+        }
+        Command::BindCertificate {
+            account_name,
+            identity,
+        } => {
+            let account_name = AccountName::from(account_name.as_str());
+
+            match config.backend {
+                Backend::Postgres => {
+                    let postgres = connect_postgres(
+                        cli.database_username,
+                        cli.database_password,
+                        &config.database,
+                    )
+                    .await;
+                    bind_certificate::run(&postgres, &account_name, &identity).await
+                }
+                Backend::Sqlite => {
+                    let sqlite = connect_sqlite(&config.database).await;
+                    bind_certificate::run(&sqlite, &account_name, &identity).await
+                }
+            }
+        }
     }
 }
 
-pub async fn run_main() -> Result<(), LockKeeperServerError> {
-    let cli = Cli::parse();
+/// Read the public half of the audit key set in the server config file.
+fn audit_public_key(server_config: &Path) -> Result<AuditPublicKey, LockKeeperServerError> {
+    let audit_key_path = ServerConfigFile::from_file(server_config)?
+        .audit_key
+        .ok_or(LockKeeperServerError::AuditKeyMissing)?;
+    let audit_public_key = AuditSigningKey::read_from_file(audit_key_path)?
+        .public_key()
+        .map_err(lock_keeper::LockKeeperError::from)?;
+
+    Ok(audit_public_key)
+}
+
+pub async fn run_main(cli: Cli) -> Result<(), LockKeeperServerError> {
     let private_key_bytes = cli
         .private_key
         .map(String::into_bytes)
@@ -244,40 +528,124 @@ pub async fn run_main() -> Result<(), LockKeeperServerError> {
     let config = Config::from_file(&cli.config)?;
 
     let server_config = ServerConfig::from_file(
-        config.server,
-        private_key_bytes,
+        &config.server,
+        private_key_bytes.clone(),
         remote_storage_key_bytes,
         opaque_server_setup_bytes,
     )?;
 
     // We keep `_logging` around for the lifetime of the server. On drop, this value
     // will ensure that our logs are flushed.
-    let _logging = init_logging(&server_config)?;
+    let (_logging, stdout_filter) = init_logging(&server_config)?;
 
     info!("Sever started!");
     info!("Logging config settings: {:?}", server_config.logging);
 
-    let database_config = get_database_config(
-        cli.database_username,
-        cli.database_password,
-        &config.database,
-    );
+    // Re-read the server config file on SIGHUP
+    let server_config_path = config.server;
+    let reload_config: ReloadConfig = Box::new(move |current| {
+        let config_file = ServerConfigFile::from_file(&server_config_path)?;
+        let new_config = current.reload(config_file, private_key_bytes.clone())?;
+
+        let filter = our_targets_filter(new_config.logging.stdout_log_level);
+        if let Err(e) = stdout_filter.reload(filter) {
+            warn!("Failed to update stdout log level: {}", e);
+        }
+
+        Ok(new_config)
+    });
+
+    let metrics = Metrics::new()?;
+    if let Some(metrics_address) = config.metrics_address {
+        let metrics_server = metrics.serve(metrics_address)?;
+        let handle = tokio::spawn(async move {
+            if let Err(e) = metrics_server.await {
+                error!("Metrics listener stopped: {}", e);
+            }
+        });
+
+        // We don't want to await this so we'll just drop the handle to make `clippy`
+        // happy.
+        std::mem::drop(handle);
+    }
+
+    match config.backend {
+        Backend::Postgres => {
+            let postgres = connect_postgres(
+                cli.database_username,
+                cli.database_password,
+                &config.database,
+            )
+            .await;
+
+            let session_cache_path = config
+                .session_cache
+                .expect("The postgres backend requires a session cache config.");
+            let session_config = get_session_cache_config(
+                cli.session_cache_username,
+                cli.session_cache_password,
+                &session_cache_path,
+            );
+            info!("Session cache config settings: {:?}", session_config);
+            let session_cache = PostgresSessionCache::connect(session_config)
+                .await
+                .expect("Failed connecting to session cache.");
+
+            start_lock_keeper_server(
+                server_config,
+                postgres,
+                session_cache,
+                Some(reload_config),
+                metrics,
+            )
+            .await?;
+        }
+        Backend::Sqlite => {
+            if config.session_cache.is_some() {
+                warn!("Session cache config is ignored by the sqlite backend.");
+            }
+
+            let sqlite = connect_sqlite(&config.database).await;
+            let session_cache = sqlite.session_cache();
+
+            start_lock_keeper_server(
+                server_config,
+                sqlite,
+                session_cache,
+                Some(reload_config),
+                metrics,
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn connect_postgres(
+    cli_username: Option<String>,
+    cli_password: Option<String>,
+    path: &Path,
+) -> PostgresDB {
+    let database_config = get_database_config(cli_username, cli_password, path);
     info!("Database config settings: {:?}", database_config);
-    let postgres = PostgresDB::connect(database_config)
+    PostgresDB::connect(database_config)
         .await
-        .expect("Failed connecting to database.");
-
-    let session_config = get_session_cache_config(
-        cli.session_cache_username,
-        cli.session_cache_password,
-        &config.session_cache,
-    );
-    info!("Session cache config settings: {:?}", session_config);
-    let session_cache = PostgresSessionCache::connect(session_config)
+        .expect("Failed connecting to database.")
+}
+
+async fn connect_sqlite(path: &Path) -> SqliteDB {
+    let sqlite_config = SqliteConfig::from_file(path).unwrap_or_else(|e| {
+        panic!(
+            "Failed to read database config file. File: {}. Reason {}",
+            path.display(),
+            e
+        )
+    });
+    info!("Database config settings: {:?}", sqlite_config);
+    SqliteDB::connect(sqlite_config)
         .await
-        .expect("Failed connecting to session cache.");
-    start_lock_keeper_server(server_config, postgres, session_cache).await?;
-    Ok(())
+        .expect("Failed connecting to database.")
 }
 
 fn get_database_config(
@@ -359,8 +727,12 @@ fn get_session_cache_config(
 struct LoggingGuards {
     _all_layer_guard: Option<WorkerGuard>,
     _server_layer_guard: Option<WorkerGuard>,
+    _otlp_guard: Option<OtlpGuard>,
 }
 
+/// Handle for changing the stdout log level while the server is running.
+type StdoutFilterHandle = reload::Handle<Targets, Registry>;
+
 /// Initialize our logging with different logging layers:
 /// 1) Log all INFO-level messages (or higher) from our key_server* crates to
 /// standard out.
@@ -368,41 +740,58 @@ struct LoggingGuards {
 /// crates to the path specified by `server_logs`.
 /// 3) (OPTIONAL) Log all messages ((TRACE or higher) from any crate to the path
 /// specified by `all_logs`.
+/// 4) (OPTIONAL) Export all INFO-level spans (or higher) from our key_server*
+/// crates to the OpenTelemetry collector specified by `otlp`.
 ///
 /// Returns an object which should be kept around for the lifetime of the
-/// program.
-fn init_logging(config: &ServerConfig) -> Result<LoggingGuards, LockKeeperServerError> {
-    // Log info level events generated from lock keeper into stdout.
-    let stdout_layer = tracing_subscriber::fmt::layer()
-        .pretty()
-        .with_filter(our_targets_filter(config.logging.stdout_log_level));
+/// program, and a handle for changing the stdout log level.
+fn init_logging(
+    config: &ServerConfig,
+) -> Result<(LoggingGuards, StdoutFilterHandle), LockKeeperServerError> {
+    // Log info level events generated from lock keeper into stdout. The filter
+    // can be swapped out when the config is reloaded.
+    let (stdout_filter, stdout_filter_handle) =
+        reload::Layer::new(our_targets_filter(config.logging.stdout_log_level));
+    let stdout_layer = match config.logging.stdout_log_format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().pretty().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().boxed(),
+    }
+    .with_filter(stdout_filter);
+
+    // Export spans to an OpenTelemetry collector. Requests that carry a trace
+    // context continue the client's trace.
+    let (otlp_layer, _otlp_guard) = match &config.logging.otlp {
+        Some(otlp_config) => {
+            let tracer = otlp::init_tracer(otlp_config)?;
+            let otlp_layer = tracing_opentelemetry::layer()
+                .with_tracer(tracer)
+                .with_filter(our_targets_filter(Level::INFO));
+            (Some(otlp_layer), Some(OtlpGuard))
+        }
+        None => (None, None),
+    };
 
     let logging_guards = match &config.logging.log_files {
         Some(file_config) => {
-            let (all_logs_dir, all_logs_file) = get_paths(&file_config.all_logs_file_name)?;
-            let (server_logs_dir, server_logs_file) =
-                get_paths(&file_config.lock_keeper_logs_file_name)?;
-
             // This layers logs all events into a file.
-            let all_appender = tracing_appender::rolling::hourly(all_logs_dir, all_logs_file);
-            let (non_blocking, _all_layer_guard) = tracing_appender::non_blocking(all_appender);
-            let all_layer = tracing_subscriber::fmt::layer()
-                .json()
-                .with_writer(non_blocking);
+            let all_writer =
+                log_file_writer(&file_config.all_logs_file_name, &file_config.rotation)?;
+            let (non_blocking, _all_layer_guard) = tracing_appender::non_blocking(all_writer);
+            let all_layer = log_file_layer(file_config.all_logs_format, non_blocking);
 
             // Log all events generated from lock keeper into a file.
-            let server_appender =
-                tracing_appender::rolling::hourly(server_logs_dir, server_logs_file);
-            let (non_blocking, _server_layer_guard) =
-                tracing_appender::non_blocking(server_appender);
-            let server_layer = tracing_subscriber::fmt::layer()
-                .json()
-                .with_writer(non_blocking)
+            let server_writer = log_file_writer(
+                &file_config.lock_keeper_logs_file_name,
+                &file_config.rotation,
+            )?;
+            let (non_blocking, _server_layer_guard) = tracing_appender::non_blocking(server_writer);
+            let server_layer = log_file_layer(file_config.lock_keeper_logs_format, non_blocking)
                 .with_filter(our_targets_filter(Level::TRACE));
 
-            // Build our logging subscriber with all three of our layers.
+            // Build our logging subscriber with all of our layers.
             tracing_subscriber::registry()
                 .with(stdout_layer)
+                .with(otlp_layer)
                 .with(server_layer)
                 .with(all_layer)
                 .init();
@@ -410,17 +799,71 @@ fn init_logging(config: &ServerConfig) -> Result<LoggingGuards, LockKeeperServer
             LoggingGuards {
                 _all_layer_guard: Some(_all_layer_guard),
                 _server_layer_guard: Some(_server_layer_guard),
+                _otlp_guard,
             }
         }
         None => {
-            // Build our logging subscriber with just the stdout layer.
-            tracing_subscriber::registry().with(stdout_layer).init();
+            // Build our logging subscriber without the file layers.
+            tracing_subscriber::registry()
+                .with(stdout_layer)
+                .with(otlp_layer)
+                .init();
+
+            LoggingGuards {
+                _otlp_guard,
+                ..Default::default()
+            }
+        }
+    };
+
+    Ok((logging_guards, stdout_filter_handle))
+}
 
-            LoggingGuards::default()
+/// Open the log file at `path`, rotated as configured.
+fn log_file_writer(
+    path: &Path,
+    rotation: &LogRotationConfig,
+) -> Result<Box<dyn Write + Send>, LockKeeperServerError> {
+    let (dir, file_name) = get_paths(path)?;
+
+    let schedule = match rotation.schedule {
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Size { max_bytes } => {
+            let writer = SizeRollingWriter::new(path, max_bytes, rotation.max_files)
+                .map_err(|e| LockKeeperServerError::FileIo(e, path.into()))?;
+            return Ok(Box::new(writer));
         }
     };
 
-    Ok(logging_guards)
+    let mut builder = RollingFileAppender::builder()
+        .rotation(schedule)
+        .filename_prefix(file_name.to_string_lossy());
+    if let Some(max_files) = rotation.max_files {
+        builder = builder.max_log_files(max_files.get());
+    }
+    let appender = builder.build(dir).map_err(|e| {
+        LockKeeperServerError::FileIo(io::Error::new(io::ErrorKind::Other, e), path.into())
+    })?;
+
+    Ok(Box::new(appender))
+}
+
+/// Layer that writes events to a log file in `format`.
+fn log_file_layer<S>(format: LogFormat, writer: NonBlocking) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(writer)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_writer(writer)
+            .boxed(),
+    }
 }
 
 /// Return the path directory and the file name. Needed for passing to
@@ -445,4 +888,5 @@ fn our_targets_filter(level: Level) -> Targets {
         .with_target("lock_keeper_key_server", level)
         .with_target("lock_keeper", level)
         .with_target("lock_keeper_session_cache_sql", level)
+        .with_target("lock_keeper_sqlite", level)
 }
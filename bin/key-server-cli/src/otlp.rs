@@ -0,0 +1,135 @@
+//! Export of spans to an OpenTelemetry collector over OTLP.
+
+use lock_keeper_key_server::config::LoggingOtlpConfig;
+use opentelemetry::{
+    global, runtime,
+    sdk::{trace, Resource},
+    trace::TraceError,
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+
+/// Name of the service that exported spans are attributed to.
+const SERVICE_NAME: &str = "lock-keeper-key-server";
+
+/// Install an OTLP exporter for the collector in `config` as the global tracer
+/// provider and return a tracer for it. Spans are exported in batches from a
+/// background task.
+pub(crate) fn init_tracer(config: &LoggingOtlpConfig) -> Result<trace::Tracer, TraceError> {
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&config.endpoint),
+        )
+        .with_trace_config(
+            trace::config()
+                .with_resource(Resource::new([KeyValue::new("service.name", SERVICE_NAME)])),
+        )
+        .install_batch(runtime::Tokio)
+}
+
+/// Exports the spans that haven't been sent yet when dropped.
+pub(crate) struct OtlpGuard;
+
+impl Drop for OtlpGuard {
+    fn drop(&mut self) {
+        global::shutdown_tracer_provider();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lock_keeper::infrastructure::trace_context;
+    use opentelemetry_proto::tonic::{
+        collector::trace::v1::{
+            trace_service_server::{TraceService, TraceServiceServer},
+            ExportTraceServiceRequest, ExportTraceServiceResponse,
+        },
+        trace::v1::Span,
+    };
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{metadata::MetadataMap, transport::Server, Request, Response, Status};
+    use tracing::info_span;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::prelude::*;
+
+    /// Collector that keeps every span it receives.
+    #[derive(Default)]
+    struct CollectorStub {
+        spans: Arc<Mutex<Vec<Span>>>,
+    }
+
+    #[tonic::async_trait]
+    impl TraceService for CollectorStub {
+        async fn export(
+            &self,
+            request: Request<ExportTraceServiceRequest>,
+        ) -> Result<Response<ExportTraceServiceResponse>, Status> {
+            let spans = request
+                .into_inner()
+                .resource_spans
+                .into_iter()
+                .flat_map(|resource_spans| resource_spans.instrumentation_library_spans)
+                .flat_map(|library_spans| library_spans.spans);
+            self.spans.lock().unwrap().extend(spans);
+
+            Ok(Response::new(ExportTraceServiceResponse::default()))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn spans_continue_client_trace() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let collector = CollectorStub::default();
+        let spans = collector.spans.clone();
+        std::mem::drop(tokio::spawn(
+            Server::builder()
+                .add_service(TraceServiceServer::new(collector))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        ));
+
+        let tracer = init_tracer(&LoggingOtlpConfig {
+            endpoint: format!("http://{address}"),
+        })
+        .unwrap();
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+
+        let mut metadata = MetadataMap::new();
+        let _ = metadata.insert(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let request_span = info_span!("handle_request");
+            request_span.set_parent(trace_context::extract(&metadata));
+            request_span.in_scope(|| info_span!("operation").in_scope(|| {}));
+        });
+        // Shutting down blocks until the remaining spans are exported.
+        tokio::task::spawn_blocking(|| drop(OtlpGuard))
+            .await
+            .unwrap();
+
+        let spans = spans.lock().unwrap();
+        let find = |name: &str| spans.iter().find(|span| span.name == name).unwrap();
+        let request_span = find("handle_request");
+        let operation_span = find("operation");
+
+        let trace_id = hex::decode("4bf92f3577b34da6a3ce929d0e0e4736").unwrap();
+        assert_eq!(request_span.trace_id, trace_id);
+        assert_eq!(
+            request_span.parent_span_id,
+            hex::decode("00f067aa0ba902b7").unwrap()
+        );
+        assert_eq!(operation_span.trace_id, trace_id);
+        assert_eq!(operation_span.parent_span_id, request_span.span_id);
+    }
+}
@@ -0,0 +1,162 @@
+//! Log file writer that rotates by size. `tracing_appender` only rotates on a
+//! schedule.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+/// Writes to `path` until the file would grow past `max_bytes`, then renames
+/// it to `path.1` and starts a new file. Files that were already rotated move
+/// up by one suffix, and files past `max_files` are deleted.
+pub(crate) struct SizeRollingWriter {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: u64,
+    max_files: Option<NonZeroUsize>,
+}
+
+impl SizeRollingWriter {
+    pub(crate) fn new(
+        path: impl Into<PathBuf>,
+        max_bytes: u64,
+        max_files: Option<NonZeroUsize>,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            file,
+            size,
+            max_bytes,
+            max_files,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        // Rotated files that are kept, not counting the one being written.
+        let kept = match self.max_files {
+            Some(max_files) => max_files.get() - 1,
+            None => (1..)
+                .find(|index| !self.rotated_path(*index).exists())
+                .unwrap_or(1),
+        };
+
+        if kept == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let oldest = self.rotated_path(kept);
+            if oldest.exists() {
+                fs::remove_file(oldest)?;
+            }
+            for index in (1..kept).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+
+        self.file = open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRollingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A single write larger than `max_bytes` still goes into one file.
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_directory() -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("lock-keeper-size-rolling-{nanos}"))
+    }
+
+    fn contents(path: impl AsRef<Path>) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn files_are_rotated_by_size() {
+        let directory = temp_directory();
+        let path = directory.join("server.log");
+        let mut writer = SizeRollingWriter::new(&path, 10, NonZeroUsize::new(3)).unwrap();
+
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert_eq!(contents(&path), "fourth\n");
+        assert_eq!(contents(directory.join("server.log.1")), "third\n");
+        assert_eq!(contents(directory.join("server.log.2")), "second\n");
+        // Only three files are kept.
+        assert!(!directory.join("server.log.3").exists());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn every_file_is_kept_without_max_files() {
+        let directory = temp_directory();
+        let path = directory.join("server.log");
+        let mut writer = SizeRollingWriter::new(&path, 4, None).unwrap();
+
+        for line in ["one\n", "two\n", "six\n"] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(contents(&path), "six\n");
+        assert_eq!(contents(directory.join("server.log.1")), "two\n");
+        assert_eq!(contents(directory.join("server.log.2")), "one\n");
+
+        // Writing continues in the existing file after a restart.
+        let mut writer = SizeRollingWriter::new(&path, 8, None).unwrap();
+        writer.write_all(b"ten\n").unwrap();
+        assert_eq!(contents(&path), "six\nten\n");
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
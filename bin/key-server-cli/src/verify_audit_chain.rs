@@ -0,0 +1,40 @@
+//! Offline verification of an account's audit log.
+
+use lock_keeper::{
+    crypto::AuditPublicKey,
+    types::{
+        audit_chain::verify_audit_chain,
+        audit_event::{AuditEventOptions, EventType},
+        database::account::AccountName,
+    },
+};
+use lock_keeper_key_server::{server::database::DataStore, LockKeeperServerError};
+
+/// Check that the audit events stored for the account form an unbroken hash
+/// chain that matches every checkpoint signed with the server's audit key.
+pub(crate) async fn run<DB: DataStore>(
+    db: &DB,
+    audit_public_key: &AuditPublicKey,
+    account_name: &AccountName,
+) -> Result<(), LockKeeperServerError> {
+    let account = db
+        .find_account_by_name(account_name)
+        .await?
+        .ok_or(LockKeeperServerError::InvalidAccount)?;
+
+    // Checkpoints are fetched first so that they never cover events that are
+    // newer than the ones we check.
+    let checkpoints = db.find_audit_checkpoints(account.id()).await?;
+    let events = db
+        .find_audit_events(account.id(), EventType::All, AuditEventOptions::default())
+        .await?;
+
+    verify_audit_chain(&events, &checkpoints, audit_public_key)?;
+    println!(
+        "Audit log for {account_name} verified: {} events, {} checkpoints.",
+        events.len(),
+        checkpoints.len()
+    );
+
+    Ok(())
+}
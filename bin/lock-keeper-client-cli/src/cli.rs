@@ -71,6 +71,7 @@ impl Cli {
             server_uri: server_uri.clone(),
             ca_chain: ca_chain.clone(),
             client_auth,
+            message_encoding: None,
         };
         Ok(Config::from_config_file(config_file, None)?)
     }
@@ -1,5 +1,6 @@
 pub mod authenticate;
 pub mod export;
+pub mod export_audit_events;
 pub mod generate;
 pub mod get_audit_events;
 pub mod health;
@@ -19,6 +20,7 @@ pub mod wait;
 
 pub use authenticate::Authenticate;
 pub use export::Export;
+pub use export_audit_events::ExportAuditEvents;
 pub use generate::Generate;
 pub use get_audit_events::GetAuditEvents;
 pub use health::Health;
@@ -154,6 +156,7 @@ pub fn get_cmd_functions<F: GetCmdFunction>() -> Vec<F::FunctionSignature> {
     vec![
         F::get_function::<Authenticate>(),
         F::get_function::<Export>(),
+        F::get_function::<ExportAuditEvents>(),
         F::get_function::<Generate>(),
         F::get_function::<GetAuditEvents>(),
         F::get_function::<Health>(),
@@ -0,0 +1,80 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    cli_command::{CliCommand, GetAuditEvents},
+    state::State,
+};
+use anyhow::Error;
+use async_trait::async_trait;
+use lock_keeper::types::audit_export::AuditExportFormat;
+use lock_keeper_client::LockKeeperClient;
+
+#[derive(Debug)]
+pub struct ExportAuditEvents {
+    format: AuditExportFormat,
+    path: PathBuf,
+    query: GetAuditEvents,
+}
+
+#[async_trait]
+impl CliCommand for ExportAuditEvents {
+    async fn execute(self: Box<Self>, state: &mut State) -> Result<Duration, Error> {
+        let (event_type, options) = self.query.into_options(state)?;
+
+        let credentials = state.get_credentials()?;
+
+        // Authenticate user to the key server
+        let lock_keeper_client = LockKeeperClient::authenticated_client(
+            &credentials.account_name,
+            &credentials.password,
+            &state.config,
+        )
+        .await
+        .result?;
+
+        let file = BufWriter::new(File::create(&self.path)?);
+
+        let now = SystemTime::now();
+        let events_written = lock_keeper_client
+            .export_audit_event_log(event_type, options, self.format, file)
+            .await
+            .result?;
+        let elapsed = now.elapsed()?;
+
+        println!(
+            "Exported {events_written} audit events to {}",
+            self.path.display()
+        );
+
+        Ok(elapsed)
+    }
+
+    fn parse_command_args(slice: &[&str]) -> Option<Self> {
+        match slice {
+            [format, path, options @ ..] => Some(ExportAuditEvents {
+                format: format.parse().ok()?,
+                path: PathBuf::from(path),
+                query: GetAuditEvents::parse_command_args(options)?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn format() -> &'static str {
+        "export-audit [jsonl|csv] [path] [query_options (see help)]"
+    }
+
+    fn aliases() -> Vec<&'static str> {
+        vec!["export-audit"]
+    }
+
+    fn description() -> &'static str {
+        "Exports audit events for the authenticated user to a JSON Lines or CSV file.
+         Accepts the same query options as the audit command."
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    net::IpAddr,
     str::FromStr,
     time::{Duration, SystemTime},
 };
@@ -6,7 +7,8 @@ use std::{
 use crate::{cli_command::CliCommand, state::State};
 use anyhow::{anyhow, Error};
 use async_trait::async_trait;
-use lock_keeper::types::audit_event::{AuditEventOptions, EventType};
+use futures::TryStreamExt;
+use lock_keeper::types::audit_event::{AuditEventOptions, EventType, FailureCode};
 use lock_keeper_client::LockKeeperClient;
 use time::{format_description::well_known::Iso8601, OffsetDateTime};
 use uuid::Uuid;
@@ -18,11 +20,20 @@ pub struct GetAuditEvents {
     request_id: Option<Uuid>,
     before_date: Option<OffsetDateTime>,
     after_date: Option<OffsetDateTime>,
+    failure_code: Option<FailureCode>,
+    peer_address: Option<IpAddr>,
+    client_identity: Option<String>,
+    after_audit_event_id: Option<i64>,
+    limit: Option<u32>,
 }
 
-#[async_trait]
-impl CliCommand for GetAuditEvents {
-    async fn execute(self: Box<Self>, state: &mut State) -> Result<Duration, Error> {
+impl GetAuditEvents {
+    /// Convert the parsed query options into the filters sent to the key
+    /// server, looking up the IDs of any named keys.
+    pub(crate) fn into_options(
+        self,
+        state: &State,
+    ) -> Result<(EventType, AuditEventOptions), Error> {
         let mut key_ids = Vec::new();
         for key_name in self.key_names {
             key_ids.push(state.get_key_id(&key_name)?.key_id.clone());
@@ -33,8 +44,22 @@ impl CliCommand for GetAuditEvents {
             request_id: self.request_id,
             before_date: self.before_date,
             after_date: self.after_date,
+            failure_code: self.failure_code,
+            peer_address: self.peer_address,
+            client_identity: self.client_identity,
+            after_audit_event_id: self.after_audit_event_id,
+            limit: self.limit,
         };
 
+        Ok((self.event_type, options))
+    }
+}
+
+#[async_trait]
+impl CliCommand for GetAuditEvents {
+    async fn execute(self: Box<Self>, state: &mut State) -> Result<Duration, Error> {
+        let (event_type, options) = self.into_options(state)?;
+
         let credentials = state.get_credentials()?;
 
         // Authenticate user to the key server
@@ -48,18 +73,19 @@ impl CliCommand for GetAuditEvents {
 
         let now = SystemTime::now();
         // If successful, proceed to generate a secret with the established session
-        let audit_event_log = lock_keeper_client
-            .retrieve_audit_event_log(self.event_type, options)
+        let mut audit_events = lock_keeper_client
+            .retrieve_audit_event_log(event_type, options)
             .await
             .result?;
-        let elapsed = now.elapsed()?;
 
+        // Print events as their pages arrive
         println!("Audit Events:");
-        for event in audit_event_log {
+        while let Some(event) = audit_events.try_next().await? {
             println!("----------------------------------");
             println!("{event}");
         }
         println!("----------------------------------");
+        let elapsed = now.elapsed()?;
 
         Ok(elapsed)
     }
@@ -72,6 +98,11 @@ impl CliCommand for GetAuditEvents {
                 request_id: None,
                 before_date: None,
                 after_date: None,
+                failure_code: None,
+                peer_address: None,
+                client_identity: None,
+                after_audit_event_id: None,
+                limit: None,
             }),
             options => {
                 let mut result = GetAuditEvents {
@@ -80,6 +111,11 @@ impl CliCommand for GetAuditEvents {
                     request_id: None,
                     before_date: None,
                     after_date: None,
+                    failure_code: None,
+                    peer_address: None,
+                    client_identity: None,
+                    after_audit_event_id: None,
+                    limit: None,
                 };
 
                 for option in options {
@@ -104,6 +140,21 @@ impl CliCommand for GetAuditEvents {
                         ParsedAuditEventOption::AfterDate(before) => {
                             result.after_date = Some(before);
                         }
+                        ParsedAuditEventOption::FailureCode(failure_code) => {
+                            result.failure_code = Some(failure_code);
+                        }
+                        ParsedAuditEventOption::PeerAddress(peer_address) => {
+                            result.peer_address = Some(peer_address);
+                        }
+                        ParsedAuditEventOption::ClientIdentity(client_identity) => {
+                            result.client_identity = Some(client_identity);
+                        }
+                        ParsedAuditEventOption::AfterAuditEventId(audit_event_id) => {
+                            result.after_audit_event_id = Some(audit_event_id);
+                        }
+                        ParsedAuditEventOption::Limit(limit) => {
+                            result.limit = Some(limit);
+                        }
                     }
                 }
 
@@ -133,6 +184,11 @@ impl CliCommand for GetAuditEvents {
             - type:event_type (all, key-only, system-only)
             - keys:key_name1,key_name_2
             - request-id:uuid
+            - failure:failure_code (e.g. key-not-found, permission-denied)
+            - peer:ip_address
+            - identity:client_identity (e.g. dns:client.example.com)
+            - after-id:audit_event_id
+            - limit:max_events
         "
     }
 }
@@ -158,6 +214,15 @@ fn parse_option(text: &str) -> Result<ParsedAuditEventOption, Error> {
             option_value,
         )?)),
         "after" => Ok(ParsedAuditEventOption::AfterDate(parse_date(option_value)?)),
+        "failure" => Ok(ParsedAuditEventOption::FailureCode(option_value.parse()?)),
+        "peer" => Ok(ParsedAuditEventOption::PeerAddress(option_value.parse()?)),
+        "identity" => Ok(ParsedAuditEventOption::ClientIdentity(
+            option_value.to_string(),
+        )),
+        "after-id" => Ok(ParsedAuditEventOption::AfterAuditEventId(
+            option_value.parse()?,
+        )),
+        "limit" => Ok(ParsedAuditEventOption::Limit(option_value.parse()?)),
         _ => anyhow::bail!(ERROR_MESSAGE),
     }
 }
@@ -181,4 +246,9 @@ enum ParsedAuditEventOption {
     RequestId(Uuid),
     BeforeDate(OffsetDateTime),
     AfterDate(OffsetDateTime),
+    FailureCode(FailureCode),
+    PeerAddress(IpAddr),
+    ClientIdentity(String),
+    AfterAuditEventId(i64),
+    Limit(u32),
 }
@@ -10,6 +10,8 @@ pub struct Quit {}
 #[async_trait]
 impl CliCommand for Quit {
     async fn execute(self: Box<Self>, _state: &mut State) -> Result<Duration, Error> {
+        // Export the spans that haven't been sent yet before exiting.
+        opentelemetry::global::shutdown_tracer_provider();
         std::process::exit(0)
     }
 
@@ -8,8 +8,14 @@ mod storage;
 use std::str::FromStr;
 
 use clap::Parser;
+use opentelemetry::{
+    global, runtime,
+    sdk::{trace, Resource},
+    KeyValue,
+};
+use opentelemetry_otlp::{WithExportConfig, OTEL_EXPORTER_OTLP_ENDPOINT};
 use tracing::info;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{prelude::*, EnvFilter};
 
 use crate::scripting::Script;
 
@@ -20,10 +26,19 @@ pub async fn main() {
     let config = cli.client_config().unwrap();
     let script = parse_script(&cli).unwrap();
 
-    tracing_subscriber::fmt::Subscriber::builder()
-        .with_env_filter(EnvFilter::from_default_env())
-        .with_target(false)
-        .without_time()
+    // Export spans to an OpenTelemetry collector if one is configured. The
+    // server continues the traces of our requests.
+    let otlp_layer = std::env::var(OTEL_EXPORTER_OTLP_ENDPOINT)
+        .is_ok()
+        .then(|| tracing_opentelemetry::layer().with_tracer(init_tracer().unwrap()));
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_target(false)
+                .without_time()
+                .with_filter(EnvFilter::from_default_env()),
+        )
+        .with(otlp_layer)
         .init();
     info!("Starting client CLI ");
 
@@ -33,6 +48,24 @@ pub async fn main() {
             .unwrap(),
         None => app::run(config, cli.storage_path).await.unwrap(),
     }
+
+    // Export the spans that haven't been sent yet.
+    global::shutdown_tracer_provider();
+}
+
+/// Install an OTLP exporter for the collector set by the standard
+/// `OTEL_EXPORTER_OTLP_*` environment variables and return a tracer for it.
+fn init_tracer() -> anyhow::Result<trace::Tracer> {
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_env())
+        .with_trace_config(trace::config().with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            "lock-keeper-client-cli",
+        )])))
+        .install_batch(runtime::Tokio)?;
+
+    Ok(tracer)
 }
 
 fn parse_script(cli: &cli::Cli) -> anyhow::Result<Option<Script>> {
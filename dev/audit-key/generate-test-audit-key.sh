@@ -0,0 +1,13 @@
+# This script should be run with `cargo make audit-key` from the root of this repo
+# It generates an Ed25519 key to be used on the server side
+# The key is used for signing audit log checkpoints
+
+SCRIPT_DIR="dev/audit-key"
+KEY_DIR=$SCRIPT_DIR/gen
+rm -rf $KEY_DIR
+
+mkdir -p $KEY_DIR
+chmod 700 $KEY_DIR
+
+#Create audit key, i.e. 32 random bytes that are used as an Ed25519 seed
+openssl rand -out $KEY_DIR/audit.key 32
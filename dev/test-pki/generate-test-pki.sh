@@ -136,3 +136,10 @@ cat $KEY_DIR/certs/server.crt $KEY_DIR/ca/signing-ca.crt $KEY_DIR/ca/root-ca.crt
 # Create client chain
 cat $KEY_DIR/certs/client.crt $KEY_DIR/ca/signing-ca.crt $KEY_DIR/ca/root-ca.crt > \
     $KEY_DIR/certs/client.chain
+
+# Create an empty certificate revocation list for the signing CA. Point the server's
+# `crl_path` at this file or directory to test client certificate revocation.
+openssl ca -gencrl \
+    -batch \
+    -config $SCRIPT_DIR/configs/signing-ca.conf \
+    -out $KEY_DIR/crl/signing-ca.crl
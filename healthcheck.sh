@@ -1,3 +1,13 @@
+# Readiness endpoints answer plain HTTP, so curl's exit code is the result:
+# it fails if the server is down or responds with 503 because a component
+# (database, session cache, release file) is not ready.
+case $1 in
+  */readyz)
+    curl -fsS $1
+    exit $?
+    ;;
+esac
+
 # Accept the 52 error code as valid because the grpc
 # server can't respond to curl requests. If the server
 # was not running, we would receive a different error code.
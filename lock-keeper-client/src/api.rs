@@ -6,12 +6,14 @@
 //! sent to a separate machine.
 
 mod authenticate;
+mod authenticate_service_account;
 mod create_storage_key;
 mod delete_key;
 mod generate_secret;
 mod get_user_id;
 mod import;
 mod register;
+mod register_service_credential;
 mod remote_generate_signing_key;
 mod remote_sign_bytes;
 mod retrieve;
@@ -23,24 +25,33 @@ use crate::{
     client::Password, config::Config, response::Metadata, LockKeeperClient, LockKeeperClientError,
     LockKeeperResponse,
 };
+use futures::TryStreamExt;
 use lock_keeper::{
     constants::METADATA,
-    crypto::{Export, Import, KeyId, Secret, Signable, Signature},
+    crypto::{
+        AuditPublicKey, Export, Import, KeyId, Secret, ServiceCredentialKeyPair,
+        ServiceCredentialPublicKey, Signable, Signature,
+    },
+    infrastructure::trace_context,
     rpc::SessionStatus,
     types::{
+        audit_chain::verify_audit_chain,
         audit_event::{AuditEvent, AuditEventOptions, EventType},
+        audit_export::{AuditEventWriter, AuditExportFormat},
         database::account::AccountName,
         operations::{retrieve_secret::RetrieveContext, ClientAction, RequestMetadata},
     },
 };
 use rand::{rngs::StdRng, SeedableRng};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{io::Write, sync::Arc};
 use tokio::sync::Mutex;
+use tracing::instrument;
 use uuid::Uuid;
 
 pub use self::{
     generate_secret::GenerateResult, remote_generate_signing_key::RemoteGenerateResult,
+    retrieve_audit_events::AuditEventStream,
 };
 
 /// Wrapper for secrets prepared for local storage
@@ -50,26 +61,30 @@ pub struct LocalStorage<T> {
 }
 
 impl LockKeeperClient {
-    /// Ping the server to make sure it is running and reachable.
+    /// Make sure the server is reachable and ready to handle requests, i.e.
+    /// its database, session cache, and release file are available.
     pub async fn health(config: &Config) -> Result<(), LockKeeperClientError> {
         use lock_keeper::rpc::Empty;
 
-        let mut client = Self::connect(config).await?;
-        match client.health(Empty {}).await {
-            Ok(response) => {
-                if response.into_inner() == (Empty {}) {
-                    Ok(())
-                } else {
-                    Err(LockKeeperClientError::HealthCheckFailed(
-                        "Invalid response from health check method.".to_string(),
-                    ))
-                }
-            }
-            Err(e) => Err(e.into()),
+        let mut client = Self::connect(config).await?.rpc_client;
+        let status = client.readiness(Empty {}).await?.into_inner();
+        if status.is_ready {
+            return Ok(());
         }
+
+        let failures = status
+            .components
+            .iter()
+            .filter(|component| !component.is_ready)
+            .map(|component| format!("{} is not ready ({})", component.name, component.error))
+            .collect::<Vec<_>>();
+        Err(LockKeeperClientError::HealthCheckFailed(
+            failures.join(", "),
+        ))
     }
 
     /// Check to see if the client's session is still valid.
+    #[instrument(skip_all)]
     pub async fn check_session(&self) -> Result<SessionStatus, LockKeeperClientError> {
         use lock_keeper::rpc::Empty;
 
@@ -80,6 +95,7 @@ impl LockKeeperClient {
         let _ = request
             .metadata_mut()
             .insert(METADATA, (&metadata).try_into()?);
+        trace_context::inject(request.metadata_mut());
 
         let result = self
             .tonic_client()
@@ -91,6 +107,7 @@ impl LockKeeperClient {
     }
 
     /// Expire the current session and session key for this user.
+    #[instrument(skip_all)]
     pub async fn logout(&self) -> LockKeeperResponse<()> {
         // Create channel to send messages to server
         let request_id = Uuid::new_v4();
@@ -104,6 +121,7 @@ impl LockKeeperClient {
     /// user.
     ///
     /// Output: If successful, returns a [`LockKeeperClient`].
+    #[instrument(skip_all)]
     pub async fn authenticated_client(
         account_name: &AccountName,
         password: &Password,
@@ -116,6 +134,36 @@ impl LockKeeperClient {
         }
     }
 
+    /// Authenticate to the Lock Keeper key server as a service account, using
+    /// a credential previously registered with
+    /// [`LockKeeperClient::register_service_credential()`].
+    ///
+    /// The returned client can only perform the actions the credential was
+    /// granted. Operations that need the account's master key, such as
+    /// generating or retrieving secrets, are not available.
+    ///
+    /// Output: If successful, returns a [`LockKeeperClient`].
+    #[instrument(skip_all)]
+    pub async fn authenticated_service_client(
+        account_name: &AccountName,
+        credential_id: Uuid,
+        key_pair: &ServiceCredentialKeyPair,
+        config: &Config,
+    ) -> LockKeeperResponse<Self> {
+        let request_id = Uuid::new_v4();
+        LockKeeperResponse {
+            result: Self::authenticate_service_account(
+                account_name,
+                credential_id,
+                key_pair,
+                config,
+                request_id,
+            )
+            .await,
+            metadata: Some(Metadata { request_id }),
+        }
+    }
+
     /// Register a new user who has not yet interacted with the service.
     ///
     /// This only needs to be called once per user; future sessions can be
@@ -123,6 +171,7 @@ impl LockKeeperClient {
     ///
     /// Output: Returns Ok if successful. To perform further operations, use
     /// [`Self::authenticated_client()`].
+    #[instrument(skip_all)]
     pub async fn register(
         account_name: &AccountName,
         password: &Password,
@@ -142,15 +191,23 @@ impl LockKeeperClient {
         request_id: Uuid,
     ) -> Result<(), LockKeeperClientError> {
         let rng = Arc::new(Mutex::new(StdRng::from_entropy()));
-        let mut client = Self::connect(config).await?;
-        let metadata = RequestMetadata::new(account_name, ClientAction::Register, None, request_id);
-
-        let client_channel = Self::create_unauthenticated_channel(&mut client, &metadata).await?;
+        let mut connection = Self::connect(config).await?;
+        let metadata = RequestMetadata::new(
+            account_name,
+            ClientAction::Register,
+            None,
+            request_id,
+            connection.message_encoding,
+        );
+
+        let client_channel =
+            Self::create_unauthenticated_channel(&mut connection.rpc_client, &metadata).await?;
         let master_key =
             Self::handle_registration(client_channel, rng.clone(), account_name, password).await?;
 
         let client =
-            Self::authenticate(Some(client), account_name, password, config, request_id).await?;
+            Self::authenticate(Some(connection), account_name, password, config, request_id)
+                .await?;
         // After authenticating we can create the storage key
         let request_metadata = client.create_metadata(ClientAction::CreateStorageKey, request_id);
         let client_channel = LockKeeperClient::create_authenticated_channel(
@@ -167,7 +224,50 @@ impl LockKeeperClient {
         Ok(())
     }
 
+    /// Register a service credential for this account. A non-interactive
+    /// client holding the matching [`ServiceCredentialKeyPair`] can then log
+    /// in with [`LockKeeperClient::authenticated_service_client()`].
+    ///
+    /// `allowed_actions` may only contain actions that run entirely on the
+    /// server (see [`ClientAction::is_service_action()`]).
+    ///
+    /// Output: If successful, returns the ID of the new credential.
+    #[instrument(skip_all)]
+    pub async fn register_service_credential(
+        &self,
+        public_key: ServiceCredentialPublicKey,
+        allowed_actions: Vec<ClientAction>,
+    ) -> LockKeeperResponse<Uuid> {
+        let request_id = Uuid::new_v4();
+        LockKeeperResponse {
+            result: self
+                .register_service_credential_helper(public_key, allowed_actions, request_id)
+                .await,
+            metadata: Some(Metadata { request_id }),
+        }
+    }
+
+    async fn register_service_credential_helper(
+        &self,
+        public_key: ServiceCredentialPublicKey,
+        allowed_actions: Vec<ClientAction>,
+        request_id: Uuid,
+    ) -> Result<Uuid, LockKeeperClientError> {
+        let metadata = self.create_metadata(ClientAction::RegisterServiceCredential, request_id);
+        let client_channel = Self::create_authenticated_channel(
+            &mut self.tonic_client(),
+            &metadata,
+            self.session_key().clone(),
+            self.rng.clone(),
+        )
+        .await?;
+
+        self.handle_register_service_credential(client_channel, public_key, allowed_actions)
+            .await
+    }
+
     /// Delete a key from the key servers.
+    #[instrument(skip_all)]
     pub async fn delete_key(&self, key_id: &KeyId) -> LockKeeperResponse<()> {
         let request_id = Uuid::new_v4();
         LockKeeperResponse {
@@ -197,6 +297,7 @@ impl LockKeeperClient {
     ///
     /// Calling this function on a signing key will generate an error.
     /// Output: If successful, returns the requested key material in byte form.
+    #[instrument(skip_all)]
     pub async fn export_secret(&self, key_id: &KeyId) -> LockKeeperResponse<Export> {
         let request_id = Uuid::new_v4();
         LockKeeperResponse {
@@ -236,6 +337,7 @@ impl LockKeeperClient {
     ///
     /// Calling this function on an arbitrary key will generated an error.
     /// Output: If successful, returns the requested key material in byte form.
+    #[instrument(skip_all)]
     pub async fn export_signing_key(&self, key_id: &KeyId) -> LockKeeperResponse<Export> {
         let request_id = Uuid::new_v4();
         LockKeeperResponse {
@@ -268,6 +370,7 @@ impl LockKeeperClient {
 
     /// Generate an arbitrary secret client-side, store this secret in the key
     /// server.
+    #[instrument(skip_all)]
     pub async fn generate_secret(&self) -> LockKeeperResponse<GenerateResult> {
         let request_id = Uuid::new_v4();
         LockKeeperResponse {
@@ -294,6 +397,7 @@ impl LockKeeperClient {
     }
 
     /// Import signing key material to the key server
+    #[instrument(skip_all)]
     pub async fn import_signing_key(&self, key_material: Import) -> LockKeeperResponse<KeyId> {
         let request_id = Uuid::new_v4();
         LockKeeperResponse {
@@ -323,6 +427,7 @@ impl LockKeeperClient {
 
     /// Retrieve a server-encrypted blob from server specified by the given
     /// `key_id`.
+    #[instrument(skip_all)]
     pub async fn retrieve_server_encrypted_blob(
         &self,
         key_id: &KeyId,
@@ -357,6 +462,7 @@ impl LockKeeperClient {
     /// Retrieve a secret from the key server by [`KeyId`]
     ///
     /// This operation will fail if it is called on a signing key.
+    #[instrument(skip_all)]
     pub async fn retrieve_secret(
         &self,
         key_id: &KeyId,
@@ -391,6 +497,7 @@ impl LockKeeperClient {
     }
 
     /// Request that the server generate a new signing key.
+    #[instrument(skip_all)]
     pub async fn remote_generate(&self) -> LockKeeperResponse<RemoteGenerateResult> {
         let request_id = Uuid::new_v4();
         LockKeeperResponse {
@@ -419,6 +526,7 @@ impl LockKeeperClient {
     /// Sign an arbitrary blob of bytes with a remotely generated
     /// [`SigningKeyPair`][lock_keeper::crypto::SigningKeyPair] and return the
     /// resulting [`Signature`].
+    #[instrument(skip_all)]
     pub async fn remote_sign_bytes(
         &self,
         key_id: KeyId,
@@ -465,12 +573,18 @@ impl LockKeeperClient {
     /// If specified, the [`KeyId`] must correspond to a key owned by the
     /// authenticated account.
     ///
-    /// Output: if successful, returns a [`String`] representation of the logs.
+    /// Events are streamed from the key server one page at a time, in order of
+    /// their `audit_event_id`. Use [`AuditEventOptions::limit`] and
+    /// [`AuditEventOptions::after_audit_event_id`] to fetch part of a long log.
+    ///
+    /// Output: if successful, returns an [`AuditEventStream`] of the matching
+    /// events.
+    #[instrument(skip_all)]
     pub async fn retrieve_audit_event_log(
         &self,
         event_type: EventType,
         options: AuditEventOptions,
-    ) -> LockKeeperResponse<Vec<AuditEvent>> {
+    ) -> LockKeeperResponse<AuditEventStream> {
         let request_id = Uuid::new_v4();
         LockKeeperResponse {
             result: self
@@ -485,6 +599,91 @@ impl LockKeeperClient {
         event_type: EventType,
         options: AuditEventOptions,
         request_id: Uuid,
+    ) -> Result<AuditEventStream, LockKeeperClientError> {
+        let metadata = self.create_metadata(ClientAction::RetrieveAuditEvents, request_id);
+        let client_channel = Self::create_authenticated_channel(
+            &mut self.tonic_client(),
+            &metadata,
+            self.session_key().clone(),
+            self.rng.clone(),
+        )
+        .await?;
+        let (_, audit_events) = self
+            .handle_retrieve_audit_events(client_channel, event_type, options)
+            .await?;
+        Ok(audit_events)
+    }
+
+    /// Export the audit events that match `options` to `writer`, for
+    /// ingestion by other systems such as a SIEM.
+    ///
+    /// Events are written as they are received from the key server, using the
+    /// schema documented in [`lock_keeper::types::audit_export`].
+    ///
+    /// Output: if successful, returns the number of events written.
+    #[instrument(skip_all)]
+    pub async fn export_audit_event_log(
+        &self,
+        event_type: EventType,
+        options: AuditEventOptions,
+        format: AuditExportFormat,
+        writer: impl Write + Send,
+    ) -> LockKeeperResponse<u64> {
+        let request_id = Uuid::new_v4();
+        LockKeeperResponse {
+            result: self
+                .export_audit_event_log_helper(event_type, options, format, writer, request_id)
+                .await,
+            metadata: Some(Metadata { request_id }),
+        }
+    }
+
+    async fn export_audit_event_log_helper(
+        &self,
+        event_type: EventType,
+        options: AuditEventOptions,
+        format: AuditExportFormat,
+        writer: impl Write + Send,
+        request_id: Uuid,
+    ) -> Result<u64, LockKeeperClientError> {
+        let mut audit_events = self
+            .retrieve_audit_event_log_helper(event_type, options, request_id)
+            .await?;
+
+        let mut writer = AuditEventWriter::new(writer, format)?;
+        while let Some(event) = audit_events.try_next().await? {
+            writer.write(&event)?;
+        }
+        Ok(writer.finish()?)
+    }
+
+    /// Retrieve every audit event for the authenticated account and check
+    /// that the log has not been tampered with.
+    ///
+    /// The events must form an unbroken hash chain that matches every
+    /// checkpoint signed by the key server. `audit_public_key` is the public
+    /// half of the key server's audit key.
+    ///
+    /// Output: if successful, returns the verified audit events. Returns
+    /// [`LockKeeperClientError::AuditChain`] if verification fails.
+    #[instrument(skip_all)]
+    pub async fn retrieve_verified_audit_event_log(
+        &self,
+        audit_public_key: &AuditPublicKey,
+    ) -> LockKeeperResponse<Vec<AuditEvent>> {
+        let request_id = Uuid::new_v4();
+        LockKeeperResponse {
+            result: self
+                .retrieve_verified_audit_event_log_helper(audit_public_key, request_id)
+                .await,
+            metadata: Some(Metadata { request_id }),
+        }
+    }
+
+    async fn retrieve_verified_audit_event_log_helper(
+        &self,
+        audit_public_key: &AuditPublicKey,
+        request_id: Uuid,
     ) -> Result<Vec<AuditEvent>, LockKeeperClientError> {
         let metadata = self.create_metadata(ClientAction::RetrieveAuditEvents, request_id);
         let client_channel = Self::create_authenticated_channel(
@@ -494,8 +693,17 @@ impl LockKeeperClient {
             self.rng.clone(),
         )
         .await?;
-        self.handle_retrieve_audit_events(client_channel, event_type, options)
-            .await
+        let (checkpoints, audit_events) = self
+            .handle_retrieve_audit_events(
+                client_channel,
+                EventType::All,
+                AuditEventOptions::default(),
+            )
+            .await?;
+        let audit_events: Vec<AuditEvent> = audit_events.try_collect().await?;
+
+        verify_audit_chain(&audit_events, &checkpoints, audit_public_key)?;
+        Ok(audit_events)
     }
 
     /// Store a server-encrypted blob. This function will return a `[KeyId]`
@@ -503,6 +711,7 @@ impl LockKeeperClient {
     ///
     /// Note: The server has a maximum configurable blob size. The server will
     /// reject the store request if the blob size is too large.
+    #[instrument(skip_all)]
     pub async fn store_server_encrypted_blob(
         &self,
         data_blob: Vec<u8>,
@@ -87,6 +87,6 @@ async fn authenticate_finish(
     Ok(AuthenticateResult {
         session_id: server_finish.session_id,
         session_key,
-        master_key,
+        master_key: Some(master_key),
     })
 }
@@ -0,0 +1,69 @@
+use crate::{
+    channel::{Channel, Unauthenticated},
+    client::{AuthenticateResult, LockKeeperClient},
+    LockKeeperClientError,
+};
+use lock_keeper::{
+    crypto::{ServiceAuthTranscript, ServiceCredentialKeyPair, ServiceKeyExchange},
+    types::{
+        database::account::AccountName,
+        operations::authenticate_service_account::{client, server},
+    },
+};
+use rand::rngs::StdRng;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+impl LockKeeperClient {
+    pub(crate) async fn handle_service_account_authentication(
+        mut channel: Channel<Unauthenticated>,
+        rng: Arc<Mutex<StdRng>>,
+        account_name: &AccountName,
+        credential_id: Uuid,
+        key_pair: &ServiceCredentialKeyPair,
+        request_id: Uuid,
+    ) -> Result<AuthenticateResult, LockKeeperClientError> {
+        // Handle start step
+        channel
+            .send(client::AuthenticateStart {
+                account_name: account_name.clone(),
+                credential_id,
+            })
+            .await?;
+        let server_start: server::AuthenticateStart = channel.receive().await?;
+
+        // Handle finish step
+        let key_exchange = {
+            let mut rng = rng.lock().await;
+            ServiceKeyExchange::new(&mut *rng)
+        };
+        let key_share = key_exchange.key_share();
+
+        let transcript = ServiceAuthTranscript::new(
+            request_id,
+            account_name,
+            credential_id,
+            &server_start.challenge,
+            &server_start.key_share,
+            &key_share,
+        );
+        let signature = key_pair.sign(transcript.as_bytes())?;
+
+        channel
+            .send(client::AuthenticateFinish {
+                key_share,
+                signature,
+            })
+            .await?;
+        let server_finish: server::AuthenticateFinish = channel.receive().await?;
+
+        let session_key = key_exchange.finish(&server_start.key_share, &transcript)?;
+
+        Ok(AuthenticateResult {
+            session_id: server_finish.session_id,
+            session_key,
+            master_key: None,
+        })
+    }
+}
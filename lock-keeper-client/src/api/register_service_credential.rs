@@ -0,0 +1,32 @@
+use crate::{
+    channel::{Authenticated, Channel},
+    LockKeeperClient, LockKeeperClientError,
+};
+use lock_keeper::{
+    crypto::ServiceCredentialPublicKey,
+    types::operations::{
+        register_service_credential::{client, server},
+        ClientAction,
+    },
+};
+use rand::rngs::StdRng;
+use uuid::Uuid;
+
+impl LockKeeperClient {
+    pub(crate) async fn handle_register_service_credential(
+        &self,
+        mut channel: Channel<Authenticated<StdRng>>,
+        public_key: ServiceCredentialPublicKey,
+        allowed_actions: Vec<ClientAction>,
+    ) -> Result<Uuid, LockKeeperClientError> {
+        channel
+            .send(client::Request {
+                public_key,
+                allowed_actions,
+            })
+            .await?;
+
+        let response: server::Response = channel.receive().await?;
+        Ok(response.credential_id)
+    }
+}
@@ -2,19 +2,27 @@ use crate::{
     channel::{Authenticated, Channel},
     LockKeeperClient, LockKeeperClientError,
 };
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
 use lock_keeper::types::{
+    audit_chain::AuditCheckpoint,
     audit_event::{AuditEvent, AuditEventOptions, EventType},
     operations::retrieve_audit_events::{client, server},
 };
 use rand::rngs::StdRng;
 
+/// Audit events streamed from the key server one page at a time.
+pub type AuditEventStream = BoxStream<'static, Result<AuditEvent, LockKeeperClientError>>;
+
 impl LockKeeperClient {
     pub(crate) async fn handle_retrieve_audit_events(
         &self,
         mut channel: Channel<Authenticated<StdRng>>,
         event_type: EventType,
         options: AuditEventOptions,
-    ) -> Result<Vec<AuditEvent>, LockKeeperClientError> {
+    ) -> Result<(Vec<AuditCheckpoint>, AuditEventStream), LockKeeperClientError> {
         // Send audit event request and filters
         let client_request = client::Request {
             event_type,
@@ -22,8 +30,35 @@ impl LockKeeperClient {
         };
         channel.send(client_request).await?;
 
-        // Receive audit event log and return
+        // Receive checkpoints before the first page of events
         let server_response: server::Response = channel.receive().await?;
-        Ok(server_response.summary_record)
+
+        // Receive pages until the server says there are no more. The channel is
+        // dropped after the last page.
+        let pages = stream::try_unfold(Some(channel), receive_page);
+        let audit_events = pages
+            .map_ok(|audit_events| stream::iter(audit_events.into_iter().map(Ok)))
+            .try_flatten()
+            .boxed();
+
+        Ok((server_response.checkpoints, audit_events))
     }
 }
+
+/// Channel to receive the next page of audit events from, if there is one.
+type NextPage = Option<Channel<Authenticated<StdRng>>>;
+
+/// Receive the next page of audit events. Returns the channel along with the
+/// page if the server will send more pages.
+async fn receive_page(
+    channel: NextPage,
+) -> Result<Option<(Vec<AuditEvent>, NextPage)>, LockKeeperClientError> {
+    let mut channel = match channel {
+        Some(channel) => channel,
+        None => return Ok(None),
+    };
+    let page: server::Page = channel.receive().await?;
+    let channel = page.has_more.then_some(channel);
+
+    Ok(Some((page.audit_events, channel)))
+}
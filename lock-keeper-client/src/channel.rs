@@ -5,9 +5,9 @@ use tokio_stream::StreamExt;
 use tonic::{Response, Streaming};
 
 use lock_keeper::{
-    crypto::{Encrypted, OpaqueSessionKey},
+    crypto::{Encrypted, MessageContext, MessageDirection, OpaqueSessionKey},
     rpc::Message,
-    types::operations::ConvertMessage,
+    types::operations::{ConvertMessage, RequestMetadata},
     LockKeeperError,
 };
 
@@ -31,6 +31,8 @@ pub struct Channel<AUTH> {
     /// receiving end. The server can send messages to it and we will
     /// receive them.
     receiver: Streaming<Message>,
+    /// Metadata sent to the server with the request that opened this channel.
+    metadata: RequestMetadata,
     auth: AUTH,
 }
 
@@ -40,6 +42,10 @@ pub struct Channel<AUTH> {
 pub struct Authenticated<RNG: CryptoRng + RngCore> {
     pub session_key: OpaqueSessionKey,
     pub rng: Arc<Mutex<RNG>>,
+    /// Number of messages sent to the server so far.
+    sent_messages: u64,
+    /// Number of messages received from the server so far.
+    received_messages: u64,
 }
 
 /// Passed to channel type as the `AUTH` generic parameter.
@@ -51,10 +57,12 @@ impl Channel<Unauthenticated> {
     pub fn new(
         sender: Sender<Message>,
         response: Response<Streaming<Message>>,
+        metadata: RequestMetadata,
     ) -> Result<Self, LockKeeperError> {
         Ok(Self {
             sender,
             receiver: response.into_inner(),
+            metadata,
             auth: Unauthenticated,
         })
     }
@@ -67,7 +75,13 @@ impl Channel<Unauthenticated> {
         Channel {
             sender: self.sender,
             receiver: self.receiver,
-            auth: Authenticated { session_key, rng },
+            metadata: self.metadata,
+            auth: Authenticated {
+                session_key,
+                rng,
+                sent_messages: 0,
+                received_messages: 0,
+            },
         }
     }
 
@@ -76,8 +90,8 @@ impl Channel<Unauthenticated> {
         match self.receiver.next().await {
             Some(message) => {
                 let message = message?;
-                let result =
-                    R::from_message(message).map_err(|_| LockKeeperError::InvalidMessage)?;
+                let result = R::from_message(message, self.metadata.message_encoding())
+                    .map_err(|_| LockKeeperError::InvalidMessage)?;
                 Ok(result)
             }
             None => Err(LockKeeperError::NoMessageReceived),
@@ -87,7 +101,7 @@ impl Channel<Unauthenticated> {
     /// Send a message across the channel. This function accepts any type that
     /// can be converted to a `Message`.
     pub async fn send(&mut self, message: impl ConvertMessage) -> Result<(), LockKeeperError> {
-        let payload = message.to_message()?;
+        let payload = message.to_message(self.metadata.message_encoding())?;
         Ok(self.sender.send(payload).await?)
     }
 }
@@ -98,11 +112,20 @@ impl<RNG: CryptoRng + RngCore> Channel<Authenticated<RNG>> {
         match self.receiver.next().await {
             Some(message) => {
                 let message = message?;
-                let encrypted_message: Encrypted<Message> =
-                    Encrypted::<Message>::try_from_message(message)?;
-                let message = encrypted_message.decrypt_message(&self.auth.session_key)?;
-                let result =
-                    R::from_message(message).map_err(|_| LockKeeperError::InvalidMessage)?;
+                let encrypted_message: Encrypted<Message> = Encrypted::<Message>::try_from_message(
+                    message,
+                    self.metadata.message_encoding(),
+                )?;
+                let context = MessageContext {
+                    metadata: &self.metadata,
+                    direction: MessageDirection::ServerToClient,
+                    sequence_number: self.auth.received_messages,
+                };
+                let message =
+                    encrypted_message.decrypt_message(&self.auth.session_key, &context)?;
+                self.auth.received_messages += 1;
+                let result = R::from_message(message, self.metadata.message_encoding())
+                    .map_err(|_| LockKeeperError::InvalidMessage)?;
                 Ok(result)
             }
             None => Err(LockKeeperError::NoMessageReceived),
@@ -112,17 +135,24 @@ impl<RNG: CryptoRng + RngCore> Channel<Authenticated<RNG>> {
     /// Send a message across the channel. This function accepts any type that
     /// can be converted to a `Message`.
     pub async fn send(&mut self, message: impl ConvertMessage) -> Result<(), LockKeeperError> {
-        let message = message.to_message()?;
+        let message = message.to_message(self.metadata.message_encoding())?;
 
+        let context = MessageContext {
+            metadata: &self.metadata,
+            direction: MessageDirection::ClientToServer,
+            sequence_number: self.auth.sent_messages,
+        };
         let encrypted_message = {
             let mut rng = self.auth.rng.lock().await;
             self.auth
                 .session_key
-                .encrypt(&mut *rng, message)
+                .encrypt(&mut *rng, message, &context)
                 .map_err(LockKeeperError::Crypto)?
         }
-        .try_into_message()?;
+        .try_into_message(self.metadata.message_encoding())?;
 
-        Ok(self.sender.send(encrypted_message).await?)
+        self.sender.send(encrypted_message).await?;
+        self.auth.sent_messages += 1;
+        Ok(())
     }
 }
@@ -9,14 +9,15 @@ use http_body::combinators::UnsyncBoxBody;
 use hyper::client::HttpConnector;
 use hyper_rustls::HttpsConnector;
 use lock_keeper::{
-    constants::METADATA,
-    crypto::{MasterKey, OpaqueSessionKey, StorageKey},
-    rpc::lock_keeper_rpc_client::LockKeeperRpcClient,
+    constants::{METADATA, PROTOCOL_VERSION},
+    crypto::{MasterKey, OpaqueSessionKey, ServiceCredentialKeyPair, StorageKey},
+    infrastructure::trace_context,
+    rpc::{lock_keeper_rpc_client::LockKeeperRpcClient, Empty, ServerInfo},
     types::{
         database::account::{AccountName, UserId},
         operations::{
             logout::server as logout_server, retrieve_storage_key::server, ClientAction,
-            RequestMetadata,
+            MessageEncoding, RequestMetadata,
         },
     },
 };
@@ -24,7 +25,7 @@ use rand::{rngs::StdRng, SeedableRng};
 use std::{str::FromStr, sync::Arc};
 use tokio::sync::{mpsc, Mutex};
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::Request;
+use tonic::{Code, Request};
 use uuid::Uuid;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
@@ -72,11 +73,22 @@ pub struct LockKeeperClient {
     config: Config,
     account_name: AccountName,
     user_id: UserId,
-    master_key: MasterKey,
+    /// Not available to service accounts, which authenticate without a
+    /// password.
+    master_key: Option<MasterKey>,
     tonic_client: LockKeeperRpcClient<LockKeeperRpcClientInner>,
+    /// Encoding negotiated with the server in [`LockKeeperClient::connect`].
+    message_encoding: MessageEncoding,
     pub(crate) rng: Arc<Mutex<StdRng>>,
 }
 
+/// Connection to the key server, along with the message encoding both sides
+/// support.
+pub(crate) struct Connection {
+    pub(crate) rpc_client: LockKeeperRpcClient<LockKeeperRpcClientInner>,
+    pub(crate) message_encoding: MessageEncoding,
+}
+
 /// Connection type used by `LockKeeperRpcClient`.
 /// This would normally be `tonic::transport:Channel` but TLS makes it more
 /// complicated.
@@ -88,7 +100,7 @@ type LockKeeperRpcClientInner = hyper::Client<
 pub(crate) struct AuthenticateResult {
     pub(crate) session_id: Uuid,
     pub(crate) session_key: OpaqueSessionKey,
-    pub(crate) master_key: MasterKey,
+    pub(crate) master_key: Option<MasterKey>,
 }
 
 #[allow(unused)]
@@ -114,11 +126,13 @@ impl LockKeeperClient {
 
     /// Create a `tonic` client object and return it to the client app.
     ///
-    /// The returned client should be stored as part of the [`LockKeeperClient`]
-    /// state.
-    pub(crate) async fn connect(
-        config: &Config,
-    ) -> Result<LockKeeperRpcClient<LockKeeperRpcClientInner>> {
+    /// Fails with [`LockKeeperClientError::IncompatibleProtocolVersion`] if the
+    /// server doesn't support this client's protocol version. The message
+    /// encoding from `config` is used if the server supports it.
+    ///
+    /// The returned connection should be stored as part of the
+    /// [`LockKeeperClient`] state.
+    pub(crate) async fn connect(config: &Config) -> Result<Connection> {
         let connector = hyper_rustls::HttpsConnectorBuilder::new()
             .with_tls_config(config.tls_config.clone())
             .https_or_http()
@@ -126,30 +140,50 @@ impl LockKeeperClient {
             .build();
 
         let client = hyper::Client::builder().http2_only(true).build(connector);
-        let rpc_client = LockKeeperRpcClient::with_origin(client, config.server_uri.clone());
+        let mut rpc_client = LockKeeperRpcClient::with_origin(client, config.server_uri.clone());
+
+        let server_info = match rpc_client.get_server_info(Empty {}).await {
+            Ok(response) => response.into_inner(),
+            // Servers that predate protocol versions don't have this endpoint.
+            Err(status) if status.code() == Code::Unimplemented => ServerInfo {
+                min_protocol_version: 0,
+                max_protocol_version: 0,
+                message_encodings: Vec::new(),
+            },
+            Err(status) => return Err(status.into()),
+        };
+        check_protocol_version(&server_info)?;
 
-        Ok(rpc_client)
+        Ok(Connection {
+            rpc_client,
+            message_encoding: choose_message_encoding(&server_info, config.message_encoding),
+        })
     }
 
     pub(crate) async fn authenticate(
-        mut client: Option<LockKeeperRpcClient<LockKeeperRpcClientInner>>,
+        connection: Option<Connection>,
         account_name: &AccountName,
         password: &Password,
         config: &Config,
         request_id: Uuid,
     ) -> Result<Self> {
         // Authenticate with key server
-        let mut client = match client {
-            Some(client) => client,
+        let mut connection = match connection {
+            Some(connection) => connection,
             None => Self::connect(config).await?,
         };
 
-        let metadata =
-            RequestMetadata::new(account_name, ClientAction::Authenticate, None, request_id);
+        let metadata = RequestMetadata::new(
+            account_name,
+            ClientAction::Authenticate,
+            None,
+            request_id,
+            connection.message_encoding,
+        );
         let mut rng = StdRng::from_entropy();
         let rng_arc_mutex = Arc::new(Mutex::new(rng));
         let mut client_channel =
-            Self::create_unauthenticated_channel(&mut client, &metadata).await?;
+            Self::create_unauthenticated_channel(&mut connection.rpc_client, &metadata).await?;
         let mut auth_result = Self::handle_authentication(
             client_channel,
             rng_arc_mutex.clone(),
@@ -158,18 +192,80 @@ impl LockKeeperClient {
         )
         .await?;
 
+        Self::from_authenticate_result(
+            connection,
+            account_name,
+            config,
+            rng_arc_mutex,
+            auth_result,
+            request_id,
+        )
+        .await
+    }
+
+    pub(crate) async fn authenticate_service_account(
+        account_name: &AccountName,
+        credential_id: Uuid,
+        key_pair: &ServiceCredentialKeyPair,
+        config: &Config,
+        request_id: Uuid,
+    ) -> Result<Self> {
+        let mut connection = Self::connect(config).await?;
+
+        let metadata = RequestMetadata::new(
+            account_name,
+            ClientAction::AuthenticateServiceAccount,
+            None,
+            request_id,
+            connection.message_encoding,
+        );
+        let rng_arc_mutex = Arc::new(Mutex::new(StdRng::from_entropy()));
+        let client_channel =
+            Self::create_unauthenticated_channel(&mut connection.rpc_client, &metadata).await?;
+        let auth_result = Self::handle_service_account_authentication(
+            client_channel,
+            rng_arc_mutex.clone(),
+            account_name,
+            credential_id,
+            key_pair,
+            request_id,
+        )
+        .await?;
+
+        Self::from_authenticate_result(
+            connection,
+            account_name,
+            config,
+            rng_arc_mutex,
+            auth_result,
+            request_id,
+        )
+        .await
+    }
+
+    /// Finish setting up a [`LockKeeperClient`] once a session has been
+    /// established.
+    async fn from_authenticate_result(
+        mut connection: Connection,
+        account_name: &AccountName,
+        config: &Config,
+        rng: Arc<Mutex<StdRng>>,
+        auth_result: AuthenticateResult,
+        request_id: Uuid,
+    ) -> Result<Self> {
         // Get user ID over an authenticated channel
         let metadata = RequestMetadata::new(
             account_name,
             ClientAction::GetUserId,
             Some(&auth_result.session_id),
             request_id,
+            connection.message_encoding,
         );
         let mut authenticated_channel = Self::create_authenticated_channel(
-            &mut client,
+            &mut connection.rpc_client,
             &metadata,
             auth_result.session_key.clone(),
-            rng_arc_mutex.clone(),
+            rng.clone(),
         )
         .await?;
         let user_id = Self::handle_get_user_id(authenticated_channel).await?;
@@ -182,8 +278,9 @@ impl LockKeeperClient {
         let client = LockKeeperClient {
             session,
             config: config.clone(),
-            tonic_client: client,
-            rng: rng_arc_mutex,
+            tonic_client: connection.rpc_client,
+            message_encoding: connection.message_encoding,
+            rng,
             account_name: account_name.clone(),
             user_id,
             master_key: auth_result.master_key,
@@ -201,6 +298,7 @@ impl LockKeeperClient {
             action,
             Some(&self.session.session_id),
             request_id,
+            self.message_encoding,
         )
     }
 
@@ -225,12 +323,17 @@ impl LockKeeperClient {
         let (tx, rx) = mpsc::channel(2);
         let mut stream = Request::new(ReceiverStream::new(rx));
 
-        // Serialize metadata and set as tonic request metadata
+        // Serialize metadata and set as tonic request metadata, along with the
+        // trace context so the server can continue our trace
         let _ = stream.metadata_mut().insert(METADATA, metadata.try_into()?);
+        trace_context::inject(stream.metadata_mut());
 
         // Server returns its own channel that is uses to send responses
         let server_response = match metadata.action() {
             ClientAction::Authenticate => client.authenticate(stream).await,
+            ClientAction::AuthenticateServiceAccount => {
+                client.authenticate_service_account(stream).await
+            }
             ClientAction::Register => client.register(stream).await,
 
             // These actions generate an error because they should be on an authenticated channel
@@ -242,6 +345,7 @@ impl LockKeeperClient {
             | ClientAction::GetUserId
             | ClientAction::ImportSigningKey
             | ClientAction::Logout
+            | ClientAction::RegisterServiceCredential
             | ClientAction::RemoteGenerateSigningKey
             | ClientAction::RemoteSignBytes
             | ClientAction::RetrieveSecret
@@ -259,7 +363,7 @@ impl LockKeeperClient {
             }
         }?;
 
-        let mut channel = Channel::new(tx, server_response)?;
+        let mut channel = Channel::new(tx, server_response, metadata.clone())?;
         Ok(channel)
     }
 
@@ -285,8 +389,10 @@ impl LockKeeperClient {
         let (tx, rx) = mpsc::channel(2);
         let mut stream = Request::new(ReceiverStream::new(rx));
 
-        // Serialize metadata and set as tonic request metadata
+        // Serialize metadata and set as tonic request metadata, along with the
+        // trace context so the server can continue our trace
         let _ = stream.metadata_mut().insert(METADATA, metadata.try_into()?);
+        trace_context::inject(stream.metadata_mut());
 
         // Server returns its own channel that is uses to send responses
         let server_response = match metadata.action() {
@@ -299,6 +405,9 @@ impl LockKeeperClient {
             ClientAction::ImportSigningKey => client.import_signing_key(stream).await,
             ClientAction::Logout => client.logout(stream).await,
             ClientAction::Register => client.register(stream).await,
+            ClientAction::RegisterServiceCredential => {
+                client.register_service_credential(stream).await
+            }
             ClientAction::RemoteGenerateSigningKey => client.remote_generate(stream).await,
             ClientAction::RemoteSignBytes => client.remote_sign_bytes(stream).await,
             ClientAction::RetrieveServerEncryptedBlob => {
@@ -313,7 +422,7 @@ impl LockKeeperClient {
             }
 
             // These actions generate an error because they should be on an unauthenticated channel
-            ClientAction::Authenticate | ClientAction::Register => {
+            ClientAction::Authenticate | ClientAction::AuthenticateServiceAccount => {
                 return Err(LockKeeperClientError::UnauthenticatedChannelNeeded)
             }
 
@@ -323,8 +432,8 @@ impl LockKeeperClient {
             }
         }?;
 
-        let mut channel =
-            Channel::new(tx, server_response)?.into_authenticated(session_key, rng.clone());
+        let mut channel = Channel::new(tx, server_response, metadata.clone())?
+            .into_authenticated(session_key, rng.clone());
         Ok(channel)
     }
 
@@ -365,13 +474,50 @@ impl LockKeeperClient {
         let response: server::Response = channel.receive().await?;
 
         // Decrypt storage_key
+        let master_key = self
+            .master_key
+            .clone()
+            .ok_or(LockKeeperClientError::MasterKeyUnavailable)?;
         let storage_key = response
             .ciphertext
-            .decrypt_storage_key(self.master_key.clone(), self.user_id())?;
+            .decrypt_storage_key(master_key, self.user_id())?;
         Ok(storage_key)
     }
 }
 
+/// Make sure that the server accepts [`PROTOCOL_VERSION`].
+fn check_protocol_version(server_info: &ServerInfo) -> Result<()> {
+    if (server_info.min_protocol_version..=server_info.max_protocol_version)
+        .contains(&PROTOCOL_VERSION)
+    {
+        Ok(())
+    } else {
+        Err(LockKeeperClientError::IncompatibleProtocolVersion {
+            client_version: PROTOCOL_VERSION,
+            server_min: server_info.min_protocol_version,
+            server_max: server_info.max_protocol_version,
+        })
+    }
+}
+
+/// Use `preferred` if the server supports it and fall back to JSON, which
+/// every server supports, otherwise.
+fn choose_message_encoding(
+    server_info: &ServerInfo,
+    preferred: MessageEncoding,
+) -> MessageEncoding {
+    let preferred_name = preferred.to_string();
+    if server_info
+        .message_encodings
+        .iter()
+        .any(|encoding| *encoding == preferred_name)
+    {
+        preferred
+    } else {
+        MessageEncoding::Json
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -388,4 +534,58 @@ mod tests {
         assert_ne!(password_bytes, after_drop);
         Ok(())
     }
+
+    #[test]
+    fn incompatible_servers_are_refused() {
+        let supported = ServerInfo {
+            min_protocol_version: PROTOCOL_VERSION,
+            max_protocol_version: PROTOCOL_VERSION + 1,
+            message_encodings: Vec::new(),
+        };
+        assert!(check_protocol_version(&supported).is_ok());
+
+        let too_new = ServerInfo {
+            min_protocol_version: PROTOCOL_VERSION + 1,
+            max_protocol_version: PROTOCOL_VERSION + 2,
+            message_encodings: Vec::new(),
+        };
+        assert!(matches!(
+            check_protocol_version(&too_new),
+            Err(LockKeeperClientError::IncompatibleProtocolVersion {
+                client_version: PROTOCOL_VERSION,
+                ..
+            })
+        ));
+
+        let unversioned = ServerInfo {
+            min_protocol_version: 0,
+            max_protocol_version: 0,
+            message_encodings: Vec::new(),
+        };
+        assert!(check_protocol_version(&unversioned).is_err());
+    }
+
+    #[test]
+    fn json_is_used_if_server_lacks_preferred_encoding() {
+        let mut server_info = ServerInfo {
+            min_protocol_version: PROTOCOL_VERSION,
+            max_protocol_version: PROTOCOL_VERSION,
+            message_encodings: vec!["json".to_string(), "bincode".to_string()],
+        };
+        assert_eq!(
+            choose_message_encoding(&server_info, MessageEncoding::Bincode),
+            MessageEncoding::Bincode
+        );
+        assert_eq!(
+            choose_message_encoding(&server_info, MessageEncoding::Json),
+            MessageEncoding::Json
+        );
+
+        // Servers that predate message encodings don't list any.
+        server_info.message_encodings.clear();
+        assert_eq!(
+            choose_message_encoding(&server_info, MessageEncoding::Bincode),
+            MessageEncoding::Json
+        );
+    }
 }
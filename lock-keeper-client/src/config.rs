@@ -1,4 +1,4 @@
-use lock_keeper::infrastructure::pem_utils;
+use lock_keeper::{infrastructure::pem_utils, types::operations::MessageEncoding};
 use rustls::{ClientConfig, RootCertStore};
 use serde::{Deserialize, Serialize};
 use std::{
@@ -14,6 +14,9 @@ use crate::LockKeeperClientError;
 pub struct Config {
     pub server_uri: Uri,
     pub tls_config: ClientConfig,
+    /// Encoding to use if the server supports it. Otherwise, messages are sent
+    /// as JSON.
+    pub message_encoding: MessageEncoding,
 }
 
 impl Config {
@@ -33,6 +36,7 @@ impl Config {
         Ok(Self {
             server_uri: Uri::from_str(&config.server_uri)?,
             tls_config: config.tls_config(private_key_bytes)?,
+            message_encoding: config.message_encoding.unwrap_or(MessageEncoding::Bincode),
         })
     }
 }
@@ -42,6 +46,7 @@ impl std::fmt::Debug for Config {
         f.debug_struct("Config")
             .field("server_uri", &self.server_uri)
             .field("tls_config", &"[Does not implement Debug]")
+            .field("message_encoding", &self.message_encoding)
             .finish()
     }
 }
@@ -53,6 +58,9 @@ pub struct ConfigFile {
     pub server_uri: String,
     pub ca_chain: PathBuf,
     pub client_auth: Option<ClientAuth>,
+    /// Preferred message encoding. Defaults to `bincode`.
+    #[serde(default)]
+    pub message_encoding: Option<MessageEncoding>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -117,6 +125,7 @@ mod tests {
         let config_str = r#"
         server_uri = "https://localhost:1113"
         ca_chain = "signing-ca.chain"
+        message_encoding = "json"
         
         [client_auth]
         certificate_chain = "client.chain"
@@ -128,6 +137,7 @@ mod tests {
             server_uri,
             ca_chain,
             client_auth,
+            message_encoding,
         } = ConfigFile::from_str(config_str).unwrap();
 
         let client_auth = client_auth.unwrap();
@@ -136,5 +146,6 @@ mod tests {
         assert_eq!(ca_chain, PathBuf::from("signing-ca.chain"));
         assert_eq!(client_auth.private_key, Some(PathBuf::from("client.key")));
         assert_eq!(client_auth.certificate_chain, PathBuf::from("client.chain"));
+        assert_eq!(message_encoding, Some(MessageEncoding::Json));
     }
 }
@@ -1,4 +1,4 @@
-use lock_keeper::LockKeeperError;
+use lock_keeper::{error::ErrorCode, LockKeeperError};
 use thiserror::Error;
 use tonic::{Code, Status};
 
@@ -31,18 +31,63 @@ pub enum LockKeeperClientError {
     InvalidKeyRetrieved,
     #[error("Session is expired or invalid")]
     InvalidSession,
+    #[error("Session has expired")]
+    SessionExpired,
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Key not found")]
+    KeyNotFound,
+    #[error("Secret has a different type than expected")]
+    WrongSecretType,
+    #[error("Blob is larger than the server allows")]
+    BlobTooLarge,
+    #[error("Storage key is already set")]
+    StorageKeyAlreadySet,
+    #[error("Storage key is not set")]
+    StorageKeyNotSet,
+    #[error("Server is handling too many requests")]
+    RateLimited,
+    #[error("Server does not support this client's protocol version")]
+    UnsupportedProtocolVersion,
+    #[error("Server timed out waiting for the next message")]
+    ReceiveTimeout,
+    #[error("Message is larger than the server allows")]
+    MessageTooLarge,
     #[error("An unauthenticated channel is needed for this action")]
     UnauthenticatedChannelNeeded,
     #[error("An authenticated channel is needed for this action")]
     AuthenticatedChannelNeeded,
     #[error("No channel is needed for this action")]
     OperationDoesNotRequireChannel,
+    #[error("Invalid service credential")]
+    InvalidServiceCredential,
+    #[error("Service credentials cannot be granted the requested actions")]
+    InvalidServiceCredentialScope,
+    #[error("This session is not permitted to perform the requested action")]
+    ActionNotPermitted,
+    #[error("Master key is not available to service accounts")]
+    MasterKeyUnavailable,
+    #[error("Client certificate is not bound to this account")]
+    CertificateAccountMismatch,
+    #[error("Server is shutting down")]
+    ServerShuttingDown,
+    #[error(
+        "Server supports protocol versions {server_min} to {server_max}, \
+         but this client uses version {client_version}"
+    )]
+    IncompatibleProtocolVersion {
+        client_version: u32,
+        server_min: u32,
+        server_max: u32,
+    },
 
     // Wrapped errors
     #[error(transparent)]
     LockKeeper(LockKeeperError),
     #[error(transparent)]
     LockKeeperCrypto(#[from] lock_keeper::crypto::CryptoError),
+    #[error("Audit log verification failed: {0}")]
+    AuditChain(#[from] lock_keeper::types::audit_chain::AuditChainError),
     #[error(transparent)]
     Io(#[from] std::io::Error),
     #[error(transparent)]
@@ -73,10 +118,57 @@ impl From<opaque_ke::errors::ProtocolError> for LockKeeperClientError {
 // Convert `tonic::Status` errors to a more useful error type
 impl From<Status> for LockKeeperClientError {
     fn from(status: Status) -> Self {
+        match ErrorCode::from_status(&status) {
+            Some(error_code) => Self::from_error_code(error_code, status),
+            None => Self::from_status_message(status),
+        }
+    }
+}
+
+impl LockKeeperClientError {
+    fn from_error_code(error_code: ErrorCode, status: Status) -> Self {
+        match error_code {
+            ErrorCode::InvalidRequest => Self::InvalidRequest(status.message().to_string()),
+            ErrorCode::AccountAlreadyRegistered => Self::AccountAlreadyRegistered,
+            ErrorCode::InvalidAccount => Self::InvalidAccount,
+            ErrorCode::AuthenticationFailed => Self::InvalidLogin,
+            ErrorCode::InvalidServiceCredential => Self::InvalidServiceCredential,
+            ErrorCode::InvalidServiceCredentialScope => Self::InvalidServiceCredentialScope,
+            ErrorCode::SessionExpired => Self::SessionExpired,
+            ErrorCode::InvalidSession => Self::InvalidSession,
+            ErrorCode::ActionNotPermitted => Self::ActionNotPermitted,
+            ErrorCode::CertificateAccountMismatch => Self::CertificateAccountMismatch,
+            ErrorCode::KeyNotFound => Self::KeyNotFound,
+            ErrorCode::WrongSecretType => Self::WrongSecretType,
+            ErrorCode::BlobTooLarge => Self::BlobTooLarge,
+            ErrorCode::StorageKeyAlreadySet => Self::StorageKeyAlreadySet,
+            ErrorCode::StorageKeyNotSet => Self::StorageKeyNotSet,
+            ErrorCode::UnsupportedProtocolVersion => Self::UnsupportedProtocolVersion,
+            ErrorCode::ShuttingDown => Self::ServerShuttingDown,
+            ErrorCode::RateLimited => Self::RateLimited,
+            ErrorCode::MessageOutOfSequence => {
+                Self::LockKeeper(LockKeeperError::MessageOutOfSequence)
+            }
+            ErrorCode::NoMessageReceived => Self::LockKeeper(LockKeeperError::NoMessageReceived),
+            ErrorCode::ReceiveTimeout => Self::ReceiveTimeout,
+            ErrorCode::MessageTooLarge => Self::MessageTooLarge,
+            ErrorCode::Internal => Self::TonicStatus(status),
+        }
+    }
+
+    /// Guess the error from the status message for servers that don't send
+    /// an [`ErrorCode`].
+    fn from_status_message(status: Status) -> Self {
         match (status.code(), status.message()) {
             (Code::InvalidArgument, "Account already registered") => Self::AccountAlreadyRegistered,
             (Code::InvalidArgument, "Invalid account") => Self::InvalidAccount,
+            (Code::InvalidArgument, "Invalid service credential") => Self::InvalidServiceCredential,
+            (Code::PermissionDenied, "Client certificate is not bound to this account") => {
+                Self::CertificateAccountMismatch
+            }
+            (Code::PermissionDenied, _) => Self::ActionNotPermitted,
             (Code::Unauthenticated, _) => Self::InvalidSession,
+            (Code::Unavailable, "Server is shutting down") => Self::ServerShuttingDown,
             (Code::Unknown, "connection error: received fatal alert: CertificateRequired") => {
                 Self::ClientAuthMissing
             }
@@ -94,3 +186,50 @@ impl From<LockKeeperError> for LockKeeperClientError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_codes_are_decoded() {
+        let status = ErrorCode::KeyNotFound.attach(Status::internal("No such entry in table."));
+        assert!(matches!(status.into(), LockKeeperClientError::KeyNotFound));
+
+        let status = ErrorCode::SessionExpired.attach(Status::unauthenticated("No session"));
+        assert!(matches!(
+            status.into(),
+            LockKeeperClientError::SessionExpired
+        ));
+
+        let status = ErrorCode::InvalidRequest.attach(Status::invalid_argument("Invalid message"));
+        assert!(matches!(
+            status.into(),
+            LockKeeperClientError::InvalidRequest(message) if message == "Invalid message"
+        ));
+
+        // Only internal errors are left as a status.
+        for error_code in (1..).map_while(|code| ErrorCode::try_from(code).ok()) {
+            let error = LockKeeperClientError::from(error_code.attach(Status::internal("")));
+            assert_eq!(
+                matches!(error, LockKeeperClientError::TonicStatus(_)),
+                error_code == ErrorCode::Internal
+            );
+        }
+    }
+
+    #[test]
+    fn statuses_without_error_codes_are_guessed() {
+        let status = Status::invalid_argument("Invalid account");
+        assert!(matches!(
+            status.into(),
+            LockKeeperClientError::InvalidAccount
+        ));
+
+        let status = Status::internal("No such entry in table.");
+        assert!(matches!(
+            status.into(),
+            LockKeeperClientError::TonicStatus(_)
+        ));
+    }
+}
@@ -1,5 +1,7 @@
 use lock_keeper::{
-    config::opaque::OpaqueCipherSuite, crypto::RemoteStorageKey, infrastructure::pem_utils,
+    config::opaque::OpaqueCipherSuite,
+    crypto::{AuditSigningKey, RemoteStorageKey},
+    infrastructure::pem_utils,
 };
 use opaque_ke::{keypair::PrivateKey, Ristretto255, ServerSetup};
 use rand::{rngs::StdRng, SeedableRng};
@@ -10,14 +12,22 @@ use rustls::{
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use std::{
-    net::IpAddr,
+    net::{IpAddr, SocketAddr},
+    num::NonZeroUsize,
     path::{Path, PathBuf},
     str::FromStr,
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard},
+    time::Duration,
 };
-use tracing::Level;
+use tokio_rustls::TlsAcceptor;
+use tracing::{warn, Level};
 
 use crate::{
-    server::opaque_storage::create_or_retrieve_server_setup_opaque, LockKeeperServerError,
+    server::{
+        certificate_revocation::RevocationCheckingVerifier,
+        opaque_storage::create_or_retrieve_server_setup_opaque,
+    },
+    LockKeeperServerError,
 };
 
 /// Server configuration with all fields ready to use
@@ -25,6 +35,9 @@ use crate::{
 pub struct Config {
     pub address: IpAddr,
     pub port: u16,
+    /// Address of the HTTP listener that serves readiness checks at
+    /// `/readyz`. The listener isn't started if this is `None`.
+    pub readiness_address: Option<SocketAddr>,
     pub tls_config: Option<ServerConfig>,
     pub opaque_server_setup: ServerSetup<OpaqueCipherSuite, PrivateKey<Ristretto255>>,
     pub remote_storage_key: RemoteStorageKey,
@@ -33,6 +46,29 @@ pub struct Config {
     /// Maximum size allowed for the store sever-encrypted blob endpoint.
     /// This size  bounded by types lengths that can be represented as a u16.
     pub max_blob_size: u16,
+    /// How long to wait for running operations to finish when the server
+    /// shuts down.
+    pub shutdown_timeout: Duration,
+    /// How long an operation waits for each message from the client.
+    pub receive_timeout: Duration,
+    /// Maximum size in bytes of a message received from the client, before it
+    /// is decoded.
+    pub max_message_size: usize,
+    /// Maximum number of requests handled at the same time.
+    pub max_concurrent_requests: usize,
+    /// Maximum number of authenticated requests handled at the same time for
+    /// one account.
+    pub max_concurrent_requests_per_account: usize,
+    /// Key used to sign audit log checkpoints. No checkpoints are created if
+    /// this is `None`.
+    pub audit_key: Option<AuditSigningKey>,
+    /// Minimum time between audit log checkpoints for an account.
+    pub audit_checkpoint_interval: Duration,
+    /// External destinations that audit events are sent to.
+    pub audit_sinks: Vec<AuditSinkConfig>,
+    /// How long audit events are kept before they are archived. Events are
+    /// kept forever if this is `None`.
+    pub audit_retention: Option<AuditRetentionConfig>,
 }
 
 impl Config {
@@ -44,9 +80,7 @@ impl Config {
         remote_storage_key_bytes: Option<Vec<u8>>,
         opaque_server_setup_bytes: Option<Vec<u8>>,
     ) -> Result<Self, LockKeeperServerError> {
-        let config_string = std::fs::read_to_string(&config_path)
-            .map_err(|e| LockKeeperServerError::FileIo(e, config_path.as_ref().to_path_buf()))?;
-        let config_file = ConfigFile::from_str(&config_string)?;
+        let config_file = ConfigFile::from_file(config_path)?;
         Self::from_config_file(
             config_file,
             private_key_bytes,
@@ -75,19 +109,161 @@ impl Config {
             opaque_server_setup_bytes,
         )?;
 
+        let audit_key = config
+            .audit_key
+            .map(AuditSigningKey::read_from_file)
+            .transpose()?;
+        // Archives are signed and anchored with checkpoints, which needs the
+        // audit key.
+        if config.audit_retention.is_some() && audit_key.is_none() {
+            return Err(LockKeeperServerError::AuditKeyMissing);
+        }
+
         Ok(Self {
             remote_storage_key,
             address: config.address,
             port: config.port,
+            readiness_address: config.readiness_address,
             tls_config,
             opaque_server_setup,
             logging: config.logging,
             release_toml_path: config.release_toml_path,
             max_blob_size: config.max_blob_size,
+            shutdown_timeout: config.shutdown_timeout,
+            receive_timeout: config.receive_timeout,
+            max_message_size: config.max_message_size,
+            max_concurrent_requests: config.max_concurrent_requests,
+            max_concurrent_requests_per_account: config.max_concurrent_requests_per_account,
+            audit_key,
+            audit_checkpoint_interval: config.audit_checkpoint_interval,
+            audit_sinks: config.audit_sinks,
+            audit_retention: config.audit_retention,
+        })
+    }
+
+    /// Builds an updated config from a re-read config file while the server
+    /// is running.
+    ///
+    /// TLS certificates and keys, logging, `release_toml_path`,
+    /// `max_blob_size`, `shutdown_timeout`, `receive_timeout`,
+    /// `max_message_size`, the concurrency limits, `audit_checkpoint_interval`,
+    /// and the audit retention settings are taken from the new config file.
+    /// Audit retention can also be turned on or off, but turning it on fails
+    /// if the server was started without an audit key. The address, port,
+    /// readiness address, remote storage key, audit key, audit sinks, and
+    /// OPAQUE server setup are kept from `self` since they can only change on
+    /// restart. The same goes for turning TLS on or off. Changes to those
+    /// settings are logged and ignored.
+    pub fn reload(
+        &self,
+        config: ConfigFile,
+        private_key_bytes: Option<Vec<u8>>,
+    ) -> Result<Self, LockKeeperServerError> {
+        if config.address != self.address || config.port != self.port {
+            warn!("Changing the server address or port requires a restart. Ignoring change.");
+        }
+
+        if config.readiness_address != self.readiness_address {
+            warn!("Changing the readiness address requires a restart. Ignoring change.");
+        }
+
+        let tls_config = match (&self.tls_config, &config.tls_config) {
+            (Some(_), Some(tls_config)) => Some(tls_config.into_rustls_config(private_key_bytes)?),
+            (None, None) => None,
+            (current, _) => {
+                warn!("Enabling or disabling TLS requires a restart. Ignoring change.");
+                current.clone()
+            }
+        };
+
+        if config.audit_sinks != self.audit_sinks {
+            warn!("Changing audit sinks requires a restart. Ignoring change.");
+        }
+
+        if config.audit_retention.is_some() && self.audit_key.is_none() {
+            return Err(LockKeeperServerError::AuditKeyMissing);
+        }
+
+        Ok(Self {
+            address: self.address,
+            port: self.port,
+            readiness_address: self.readiness_address,
+            tls_config,
+            opaque_server_setup: self.opaque_server_setup.clone(),
+            remote_storage_key: self.remote_storage_key.clone(),
+            logging: config.logging,
+            release_toml_path: config.release_toml_path,
+            max_blob_size: config.max_blob_size,
+            shutdown_timeout: config.shutdown_timeout,
+            receive_timeout: config.receive_timeout,
+            max_message_size: config.max_message_size,
+            max_concurrent_requests: config.max_concurrent_requests,
+            max_concurrent_requests_per_account: config.max_concurrent_requests_per_account,
+            audit_key: self.audit_key.clone(),
+            audit_checkpoint_interval: config.audit_checkpoint_interval,
+            audit_sinks: self.audit_sinks.clone(),
+            audit_retention: config.audit_retention,
         })
     }
 }
 
+/// Holds the [`Config`] currently used by the server so that it can be
+/// replaced while the server is running.
+///
+/// Requests take a snapshot of the config when they start, so replacing it
+/// does not affect requests that are already in progress.
+#[derive(Clone)]
+pub(crate) struct SharedConfig {
+    current: Arc<RwLock<CurrentConfig>>,
+}
+
+struct CurrentConfig {
+    config: Arc<Config>,
+    tls_acceptor: Option<TlsAcceptor>,
+}
+
+impl CurrentConfig {
+    fn new(config: Config) -> Self {
+        let tls_acceptor = config
+            .tls_config
+            .clone()
+            .map(|tls| TlsAcceptor::from(Arc::new(tls)));
+
+        Self {
+            config: Arc::new(config),
+            tls_acceptor,
+        }
+    }
+}
+
+impl SharedConfig {
+    pub(crate) fn new(config: Config) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(CurrentConfig::new(config))),
+        }
+    }
+
+    /// Returns the config currently in use.
+    pub(crate) fn current(&self) -> Arc<Config> {
+        self.read().config.clone()
+    }
+
+    /// Returns the TLS acceptor for new connections.
+    pub(crate) fn tls_acceptor(&self) -> Option<TlsAcceptor> {
+        self.read().tls_acceptor.clone()
+    }
+
+    /// Replace the config used for new requests and connections.
+    pub(crate) fn replace(&self, config: Config) {
+        let current = CurrentConfig::new(config);
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = current;
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, CurrentConfig> {
+        self.current.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
 impl std::fmt::Debug for Config {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Config")
@@ -106,6 +282,11 @@ impl std::fmt::Debug for Config {
 pub struct ConfigFile {
     pub address: IpAddr,
     pub port: u16,
+    /// Address of a plain HTTP listener that serves readiness checks at
+    /// `/readyz`, for load balancers that can't call the `Readiness` RPC.
+    /// Not served if this is not set.
+    #[serde(default)]
+    pub readiness_address: Option<SocketAddr>,
     /// The remote storage key can be provided as a file or passed to
     /// the [`Config`] constructors.
     pub remote_storage_key: Option<PathBuf>,
@@ -115,6 +296,54 @@ pub struct ConfigFile {
     pub release_toml_path: PathBuf,
     pub tls_config: Option<TlsConfig>,
     pub max_blob_size: u16,
+    /// How long to wait for running operations to finish on shutdown before
+    /// aborting them. Defaults to 30 seconds.
+    #[serde(
+        default = "ConfigFile::default_shutdown_timeout",
+        with = "humantime_serde"
+    )]
+    pub shutdown_timeout: Duration,
+    /// How long an operation waits for each message from the client before
+    /// it fails. Defaults to 30 seconds.
+    #[serde(
+        default = "ConfigFile::default_receive_timeout",
+        with = "humantime_serde"
+    )]
+    pub receive_timeout: Duration,
+    /// Maximum size in bytes of a message from the client. Larger messages
+    /// fail the operation before they are decoded. Defaults to 2 MiB.
+    #[serde(default = "ConfigFile::default_max_message_size")]
+    pub max_message_size: usize,
+    /// Maximum number of requests handled at the same time. Further requests
+    /// are rejected with `RESOURCE_EXHAUSTED`. Defaults to 512.
+    #[serde(default = "ConfigFile::default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Maximum number of authenticated requests handled at the same time for
+    /// one account. Further requests for the account are rejected with
+    /// `RESOURCE_EXHAUSTED`. Defaults to 16.
+    #[serde(default = "ConfigFile::default_max_concurrent_requests_per_account")]
+    pub max_concurrent_requests_per_account: usize,
+    /// File containing the 32-byte Ed25519 seed used to sign audit log
+    /// checkpoints. Audit events are always hash-chained, but checkpoints are
+    /// only created if this is set.
+    #[serde(default)]
+    pub audit_key: Option<PathBuf>,
+    /// Minimum time between audit log checkpoints for an account. Defaults to
+    /// 1 hour.
+    #[serde(
+        default = "ConfigFile::default_audit_checkpoint_interval",
+        with = "humantime_serde"
+    )]
+    pub audit_checkpoint_interval: Duration,
+    /// External destinations that every audit event is also sent to, in
+    /// addition to the database. Defaults to none.
+    #[serde(default)]
+    pub audit_sinks: Vec<AuditSinkConfig>,
+    /// Archive and remove audit events once they are older than the
+    /// retention period. Requires `audit_key`. Events are kept forever if
+    /// this is not set.
+    #[serde(default)]
+    pub audit_retention: Option<AuditRetentionConfig>,
 }
 
 impl FromStr for ConfigFile {
@@ -126,6 +355,36 @@ impl FromStr for ConfigFile {
 }
 
 impl ConfigFile {
+    fn default_shutdown_timeout() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    fn default_receive_timeout() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    fn default_max_message_size() -> usize {
+        2 * 1024 * 1024
+    }
+
+    fn default_max_concurrent_requests() -> usize {
+        512
+    }
+
+    fn default_max_concurrent_requests_per_account() -> usize {
+        16
+    }
+
+    fn default_audit_checkpoint_interval() -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+
+    pub fn from_file(config_path: impl AsRef<Path>) -> Result<Self, LockKeeperServerError> {
+        let config_string = std::fs::read_to_string(&config_path)
+            .map_err(|e| LockKeeperServerError::FileIo(e, config_path.as_ref().to_path_buf()))?;
+        Self::from_str(&config_string)
+    }
+
     pub fn remote_storage_key_config(
         &self,
         remote_storage_key_bytes: Option<Vec<u8>>,
@@ -148,14 +407,178 @@ impl ConfigFile {
 pub struct LoggingConfig {
     #[serde_as(as = "DisplayFromStr")]
     pub stdout_log_level: Level,
+    /// Defaults to text.
+    #[serde(default)]
+    pub stdout_log_format: LogFormat,
     pub log_files: Option<LoggingFileConfig>,
+    /// Export spans to an OpenTelemetry collector. This can only change on
+    /// restart.
+    pub otlp: Option<LoggingOtlpConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(deny_unknown_fields, rename_all = "snake_case")]
 pub struct LoggingFileConfig {
     pub lock_keeper_logs_file_name: PathBuf,
+    /// Defaults to JSON.
+    #[serde(default = "LogFormat::json")]
+    pub lock_keeper_logs_format: LogFormat,
     pub all_logs_file_name: PathBuf,
+    /// Defaults to JSON.
+    #[serde(default = "LogFormat::json")]
+    pub all_logs_format: LogFormat,
+    /// Applies to both log files. Defaults to hourly rotation that keeps
+    /// every file.
+    #[serde(default)]
+    pub rotation: LogRotationConfig,
+}
+
+/// Format of the lines written to a log sink.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable text. Stdout uses the multi-line pretty format and log
+    /// files use one line per event.
+    #[default]
+    Text,
+    /// One JSON object per event.
+    Json,
+}
+
+impl LogFormat {
+    fn json() -> Self {
+        Self::Json
+    }
+}
+
+/// When log files are rotated and how many of them are kept.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct LogRotationConfig {
+    #[serde(flatten)]
+    pub schedule: LogRotation,
+    /// Number of files kept for each log, including the one being written.
+    /// The oldest files are deleted. Every file is kept if this is not set.
+    pub max_files: Option<NonZeroUsize>,
+}
+
+/// Kind of [`LogRotationConfig`], selected by its `type` field.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LogRotation {
+    /// Start a new file every hour. The hour is appended to the file name.
+    #[default]
+    Hourly,
+    /// Start a new file every day. The date is appended to the file name.
+    Daily,
+    /// Start a new file once the current one would grow past `max_bytes`.
+    /// Older files get the suffixes `.1`, `.2`, and so on, with `.1` being
+    /// the most recent.
+    Size { max_bytes: u64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub struct LoggingOtlpConfig {
+    /// URL of the collector's OTLP gRPC endpoint, e.g.
+    /// `http://localhost:4317`.
+    pub endpoint: String,
+}
+
+/// An external destination for audit events. See
+/// [`audit_sink`](crate::server::audit_sink) for how events are delivered.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct AuditSinkConfig {
+    #[serde(flatten)]
+    pub destination: AuditSinkDestination,
+    /// Number of events that can wait for delivery before new events are
+    /// dropped. Defaults to 1024.
+    #[serde(default = "AuditSinkConfig::default_buffer_size")]
+    pub buffer_size: usize,
+    /// Number of times a failed delivery is retried before the event is
+    /// dropped. Defaults to 5.
+    #[serde(default = "AuditSinkConfig::default_max_retries")]
+    pub max_retries: u32,
+    /// Delay before the first retry. It doubles with every retry, up to one
+    /// minute. Defaults to 1 second.
+    #[serde(
+        default = "AuditSinkConfig::default_retry_delay",
+        with = "humantime_serde"
+    )]
+    pub retry_delay: Duration,
+}
+
+impl AuditSinkConfig {
+    fn default_buffer_size() -> usize {
+        1024
+    }
+
+    fn default_max_retries() -> u32 {
+        5
+    }
+
+    fn default_retry_delay() -> Duration {
+        Duration::from_secs(1)
+    }
+}
+
+/// Kind of [`AuditSinkConfig`], selected by its `type` field.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditSinkDestination {
+    /// Append one JSON object per line to `path`.
+    File { path: PathBuf },
+    /// Send RFC 5424 messages to the syslog daemon's Unix datagram socket.
+    /// Defaults to `/dev/log` and facility 13 (log audit).
+    Syslog {
+        #[serde(default = "AuditSinkDestination::default_syslog_socket")]
+        socket: PathBuf,
+        #[serde(default = "AuditSinkDestination::default_syslog_facility")]
+        facility: u8,
+    },
+    /// POST each event as a JSON object to `url`.
+    Webhook { url: String },
+}
+
+impl AuditSinkDestination {
+    fn default_syslog_socket() -> PathBuf {
+        PathBuf::from("/dev/log")
+    }
+
+    fn default_syslog_facility() -> u8 {
+        13
+    }
+}
+
+/// Retention policy for audit events.
+///
+/// Events older than `retention_period` are written to signed archive files
+/// in `archive_directory` and then deleted from the database. The most recent
+/// event of each account is always kept so that new events stay chained to
+/// it. Archives can be imported again with the `import-audit-archive`
+/// subcommand of `key-server-cli`.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub struct AuditRetentionConfig {
+    /// How long audit events are kept in the database, e.g. `"400days"`.
+    #[serde(with = "humantime_serde")]
+    pub retention_period: Duration,
+    /// Directory that archive files are written to. It is created if it
+    /// doesn't exist.
+    pub archive_directory: PathBuf,
+    /// How often to look for expired events. Defaults to 1 hour.
+    #[serde(
+        default = "AuditRetentionConfig::default_check_interval",
+        with = "humantime_serde"
+    )]
+    pub check_interval: Duration,
+}
+
+impl AuditRetentionConfig {
+    fn default_check_interval() -> Duration {
+        Duration::from_secs(60 * 60)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -164,8 +587,18 @@ pub struct TlsConfig {
     /// [`Config`] constructors.
     pub private_key: Option<PathBuf>,
     pub certificate_chain: PathBuf,
+    /// Require clients to present a certificate signed by one of our roots.
+    /// Certificate identities can be bound to accounts with
+    /// [`DataStore::create_certificate_binding`](crate::server::database::DataStore::create_certificate_binding),
+    /// e.g. with the `bind-certificate` subcommand of `key-server-cli`.
     #[serde(default)]
     pub client_auth: bool,
+    /// Certificate revocation list, or a directory of them, used to reject
+    /// revoked client certificates. Only used if `client_auth` is enabled.
+    /// The lists are read again when the files change or the config is
+    /// reloaded.
+    #[serde(default)]
+    pub crl_path: Option<PathBuf>,
 }
 
 impl TlsConfig {
@@ -189,7 +622,11 @@ impl TlsConfig {
                 client_auth_roots.add(root)?;
             }
 
-            AllowAnyAuthenticatedClient::new(client_auth_roots)
+            let verifier = AllowAnyAuthenticatedClient::new(client_auth_roots);
+            match &self.crl_path {
+                Some(crl_path) => Arc::new(RevocationCheckingVerifier::new(verifier, crl_path)?),
+                None => verifier,
+            }
         } else {
             NoClientAuth::new()
         };
@@ -214,16 +651,43 @@ mod tests {
         let config_str = r#"
             address = "127.0.0.2"
             port = 1114
+            readiness_address = "127.0.0.1:9101"
             opaque_path = "tests/gen/opaque"
             opaque_server_key = "tests/gen/opaque/server_setup"
             remote_storage_key = "test_sse.key"
             release_toml_path = "./boltlabs-release.toml"
             max_blob_size = 1024
+            shutdown_timeout = "10s"
+            receive_timeout = "5s"
+            max_message_size = 65536
+            max_concurrent_requests = 100
+            max_concurrent_requests_per_account = 4
+            audit_key = "audit.key"
+            audit_checkpoint_interval = "5m"
+
+            [[audit_sinks]]
+            type = "file"
+            path = "./dev/logs/audit.jsonl"
+
+            [[audit_sinks]]
+            type = "syslog"
+            max_retries = 2
+
+            [[audit_sinks]]
+            type = "webhook"
+            url = "https://siem.example.com/audit"
+            buffer_size = 10
+            retry_delay = "500ms"
+
+            [audit_retention]
+            retention_period = "400days"
+            archive_directory = "./dev/audit-archive"
 
             [tls_config]
             private_key = "test.key"
             certificate_chain = "test.crt"
             client_auth = false
+            crl_path = "test.crl"
 
             [logging]
             stdout_log_level = "INFO"
@@ -231,12 +695,22 @@ mod tests {
             [logging.log_files]
             lock_keeper_logs_file_name = "./dev/logs/server.log"
             all_logs_file_name = "./dev/logs/all.log"
+            all_logs_format = "text"
+
+            [logging.log_files.rotation]
+            type = "size"
+            max_bytes = 1048576
+            max_files = 5
+
+            [logging.otlp]
+            endpoint = "http://localhost:4317"
         "#;
 
         // Destructure so the test breaks when fields are added
         let ConfigFile {
             address,
             port,
+            readiness_address,
             remote_storage_key,
             tls_config,
             opaque_path,
@@ -244,30 +718,122 @@ mod tests {
             logging,
             release_toml_path,
             max_blob_size,
+            shutdown_timeout,
+            receive_timeout,
+            max_message_size,
+            max_concurrent_requests,
+            max_concurrent_requests_per_account,
+            audit_key,
+            audit_checkpoint_interval,
+            audit_sinks,
+            audit_retention,
         } = ConfigFile::from_str(config_str).unwrap();
 
         let tls_config = tls_config.unwrap();
 
         assert_eq!(address, IpAddr::from_str("127.0.0.2").unwrap());
         assert_eq!(port, 1114);
+        assert_eq!(
+            readiness_address,
+            Some(SocketAddr::from_str("127.0.0.1:9101").unwrap())
+        );
         assert_eq!(remote_storage_key, Some(PathBuf::from("test_sse.key")));
         assert_eq!(release_toml_path, PathBuf::from("./boltlabs-release.toml"));
         assert_eq!(tls_config.private_key, Some(PathBuf::from("test.key")));
         assert_eq!(tls_config.certificate_chain, PathBuf::from("test.crt"));
         assert!(!tls_config.client_auth);
+        assert_eq!(tls_config.crl_path, Some(PathBuf::from("test.crl")));
         assert_eq!(opaque_path, PathBuf::from("tests/gen/opaque"));
         assert_eq!(
             opaque_server_key.unwrap(),
             PathBuf::from("tests/gen/opaque/server_setup")
         );
         assert_eq!(max_blob_size, 1024);
+        assert_eq!(shutdown_timeout, Duration::from_secs(10));
+        assert_eq!(receive_timeout, Duration::from_secs(5));
+        assert_eq!(max_message_size, 65536);
+        assert_eq!(max_concurrent_requests, 100);
+        assert_eq!(max_concurrent_requests_per_account, 4);
+        assert_eq!(audit_key, Some(PathBuf::from("audit.key")));
+        assert_eq!(audit_checkpoint_interval, Duration::from_secs(5 * 60));
+        assert_eq!(
+            audit_sinks,
+            vec![
+                AuditSinkConfig {
+                    destination: AuditSinkDestination::File {
+                        path: PathBuf::from("./dev/logs/audit.jsonl"),
+                    },
+                    buffer_size: 1024,
+                    max_retries: 5,
+                    retry_delay: Duration::from_secs(1),
+                },
+                AuditSinkConfig {
+                    destination: AuditSinkDestination::Syslog {
+                        socket: PathBuf::from("/dev/log"),
+                        facility: 13,
+                    },
+                    buffer_size: 1024,
+                    max_retries: 2,
+                    retry_delay: Duration::from_secs(1),
+                },
+                AuditSinkConfig {
+                    destination: AuditSinkDestination::Webhook {
+                        url: "https://siem.example.com/audit".to_string(),
+                    },
+                    buffer_size: 10,
+                    max_retries: 5,
+                    retry_delay: Duration::from_millis(500),
+                },
+            ]
+        );
+        assert_eq!(
+            audit_retention,
+            Some(AuditRetentionConfig {
+                retention_period: Duration::from_secs(400 * 24 * 60 * 60),
+                archive_directory: PathBuf::from("./dev/audit-archive"),
+                check_interval: Duration::from_secs(60 * 60),
+            })
+        );
         let expected_log = LoggingConfig {
             stdout_log_level: Level::INFO,
+            stdout_log_format: LogFormat::Text,
             log_files: Some(LoggingFileConfig {
                 lock_keeper_logs_file_name: "./dev/logs/server.log".parse().unwrap(),
+                lock_keeper_logs_format: LogFormat::Json,
                 all_logs_file_name: "./dev/logs/all.log".parse().unwrap(),
+                all_logs_format: LogFormat::Text,
+                rotation: LogRotationConfig {
+                    schedule: LogRotation::Size {
+                        max_bytes: 1024 * 1024,
+                    },
+                    max_files: NonZeroUsize::new(5),
+                },
+            }),
+            otlp: Some(LoggingOtlpConfig {
+                endpoint: "http://localhost:4317".to_string(),
             }),
         };
         assert_eq!(logging, expected_log);
     }
+
+    #[test]
+    fn logging_config_defaults() {
+        let logging: LoggingConfig = toml::from_str(
+            r#"
+            stdout_log_level = "INFO"
+
+            [log_files]
+            lock_keeper_logs_file_name = "./dev/logs/server.log"
+            all_logs_file_name = "./dev/logs/all.log"
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(logging.stdout_log_format, LogFormat::Text);
+        let log_files = logging.log_files.unwrap();
+        assert_eq!(log_files.lock_keeper_logs_format, LogFormat::Json);
+        assert_eq!(log_files.all_logs_format, LogFormat::Json);
+        assert_eq!(log_files.rotation.schedule, LogRotation::Hourly);
+        assert_eq!(log_files.rotation.max_files, None);
+    }
 }
@@ -1,4 +1,12 @@
-use crate::server::{database::DatabaseError, session_cache::SessionCacheError};
+use crate::server::{
+    audit_archive::AuditArchiveError, audit_sink::AuditSinkError, database::DatabaseError,
+    session_cache::SessionCacheError,
+};
+use lock_keeper::{
+    error::ErrorCode,
+    types::{audit_chain::AuditChainError, audit_event::FailureCode, operations::ClientAction},
+    LockKeeperError,
+};
 use std::path::PathBuf;
 use thiserror::Error;
 use tonic::Status;
@@ -13,6 +21,8 @@ pub enum LockKeeperServerError {
     RemoteStorageKeyMissing,
     #[error("Opaque server setup was not provided.")]
     OpaqueServerSetupNotDefined,
+    #[error("Audit key was not provided.")]
+    AuditKeyMissing,
 
     #[error("Error in session keys cache.")]
     SessionCache(#[from] SessionCacheError),
@@ -22,6 +32,8 @@ pub enum LockKeeperServerError {
     InvalidOpaqueDirectory,
     #[error("Invalid log file path: {0}")]
     InvalidLogFilePath(PathBuf),
+    #[error("Invalid certificate revocation list: {0}")]
+    InvalidCertificateRevocationList(PathBuf),
 
     // Protocol errors
     #[error("Account already registered.")]
@@ -38,8 +50,30 @@ pub enum LockKeeperServerError {
     KeyNotFound,
     #[error("Session ID was not found in request metadata")]
     SessionIdNotFound,
+    #[error("Invalid service credential")]
+    InvalidServiceCredential,
+    #[error("Service credentials cannot be granted the {0} action")]
+    InvalidServiceCredentialScope(ClientAction),
+    #[error("This session is not permitted to perform the {0} action")]
+    ActionNotPermitted(ClientAction),
+    #[error("Client certificate is not bound to this account")]
+    CertificateAccountMismatch,
+    #[error("Client certificate could not be parsed")]
+    InvalidClientCertificate,
+    #[error("Server is shutting down")]
+    ShuttingDown,
+    #[error("Too many concurrent requests")]
+    RateLimited,
+    #[error("Protocol version {0} is not supported by this server")]
+    UnsupportedProtocolVersion(u32),
 
     // Wrapped errors
+    #[error("Audit archive error: {0}")]
+    AuditArchive(#[from] AuditArchiveError),
+    #[error("Audit log verification failed: {0}")]
+    AuditChain(#[from] AuditChainError),
+    #[error("Audit sink error: {0}")]
+    AuditSink(#[from] AuditSinkError),
     #[error(transparent)]
     Bincode(#[from] bincode::Error),
     #[error("Database error: {0}")]
@@ -61,6 +95,8 @@ pub enum LockKeeperServerError {
     #[error("OPAQUE protocol error: {}", .0)]
     OpaqueProtocol(opaque_ke::errors::ProtocolError),
     #[error(transparent)]
+    Prometheus(#[from] prometheus::Error),
+    #[error(transparent)]
     Rustls(#[from] rustls::Error),
     #[error(transparent)]
     StrumParseError(#[from] strum::ParseError),
@@ -71,6 +107,8 @@ pub enum LockKeeperServerError {
     #[error(transparent)]
     TonicTransport(#[from] tonic::transport::Error),
     #[error(transparent)]
+    Trace(#[from] opentelemetry::trace::TraceError),
+    #[error(transparent)]
     WebPki(#[from] tokio_rustls::webpki::Error),
 }
 
@@ -80,26 +118,143 @@ impl From<opaque_ke::errors::ProtocolError> for LockKeeperServerError {
     }
 }
 
+impl LockKeeperServerError {
+    /// Category of this error that is safe to store in audit events.
+    pub fn failure_code(&self) -> FailureCode {
+        use crate::server::session_cache::SessionCacheError::*;
+
+        match self {
+            LockKeeperServerError::InvalidAccount => FailureCode::InvalidAccount,
+            LockKeeperServerError::AccountAlreadyRegistered => {
+                FailureCode::AccountAlreadyRegistered
+            }
+            LockKeeperServerError::InvalidServiceCredential
+            | LockKeeperServerError::OpaqueProtocol(_)
+            | LockKeeperServerError::LockKeeper(LockKeeperError::OpaqueProtocol(_)) => {
+                FailureCode::AuthenticationFailed
+            }
+            LockKeeperServerError::SessionIdNotFound
+            | LockKeeperServerError::SessionCache(ExpiredSession)
+            | LockKeeperServerError::SessionCache(MissingSession) => FailureCode::InvalidSession,
+            LockKeeperServerError::InvalidServiceCredentialScope(_)
+            | LockKeeperServerError::ActionNotPermitted(_)
+            | LockKeeperServerError::CertificateAccountMismatch
+            | LockKeeperServerError::InvalidClientCertificate => FailureCode::PermissionDenied,
+            LockKeeperServerError::KeyNotFound
+            | LockKeeperServerError::Database(DatabaseError::NoEntry)
+            | LockKeeperServerError::Database(DatabaseError::IncorrectKeyMetadata) => {
+                FailureCode::KeyNotFound
+            }
+            LockKeeperServerError::StorageKeyAlreadySet
+            | LockKeeperServerError::StorageKeyNotSet
+            | LockKeeperServerError::LockKeeper(LockKeeperError::InvalidMessage)
+            | LockKeeperServerError::LockKeeper(LockKeeperError::MessageOutOfSequence)
+            | LockKeeperServerError::SessionCache(ReplayedRequest)
+            | LockKeeperServerError::LockKeeper(LockKeeperError::MetadataNotFound)
+            | LockKeeperServerError::LockKeeper(LockKeeperError::UnknownSecretType(_))
+            | LockKeeperServerError::LockKeeper(LockKeeperError::InvalidSecretType)
+            | LockKeeperServerError::Database(DatabaseError::WrongSecretType)
+            | LockKeeperServerError::UnsupportedProtocolVersion(_) => FailureCode::InvalidRequest,
+            LockKeeperServerError::BlobSizeTooLarge => FailureCode::PayloadTooLarge,
+            LockKeeperServerError::LockKeeper(LockKeeperError::NoMessageReceived) => {
+                FailureCode::ClientDisconnected
+            }
+            LockKeeperServerError::LockKeeper(LockKeeperError::ReceiveTimeout) => {
+                FailureCode::ReceiveTimeout
+            }
+            LockKeeperServerError::LockKeeper(LockKeeperError::MessageTooLarge { .. }) => {
+                FailureCode::MessageTooLarge
+            }
+            LockKeeperServerError::ShuttingDown => FailureCode::ShuttingDown,
+            _ => FailureCode::Internal,
+        }
+    }
+}
+
+impl LockKeeperServerError {
+    /// Code sent to the client when this error ends a request.
+    pub fn error_code(&self) -> ErrorCode {
+        use crate::server::session_cache::SessionCacheError::*;
+
+        match self {
+            LockKeeperServerError::AccountAlreadyRegistered => ErrorCode::AccountAlreadyRegistered,
+            LockKeeperServerError::InvalidAccount => ErrorCode::InvalidAccount,
+            LockKeeperServerError::OpaqueProtocol(_) => ErrorCode::AuthenticationFailed,
+            LockKeeperServerError::InvalidServiceCredential => ErrorCode::InvalidServiceCredential,
+            LockKeeperServerError::InvalidServiceCredentialScope(_) => {
+                ErrorCode::InvalidServiceCredentialScope
+            }
+            LockKeeperServerError::SessionCache(ExpiredSession) => ErrorCode::SessionExpired,
+            LockKeeperServerError::SessionIdNotFound
+            | LockKeeperServerError::SessionCache(MissingSession) => ErrorCode::InvalidSession,
+            LockKeeperServerError::SessionCache(ReplayedRequest) => ErrorCode::MessageOutOfSequence,
+            LockKeeperServerError::ActionNotPermitted(_) => ErrorCode::ActionNotPermitted,
+            LockKeeperServerError::CertificateAccountMismatch => {
+                ErrorCode::CertificateAccountMismatch
+            }
+            LockKeeperServerError::KeyNotFound
+            | LockKeeperServerError::Database(DatabaseError::NoEntry)
+            | LockKeeperServerError::Database(DatabaseError::IncorrectKeyMetadata) => {
+                ErrorCode::KeyNotFound
+            }
+            LockKeeperServerError::Database(DatabaseError::WrongSecretType) => {
+                ErrorCode::WrongSecretType
+            }
+            LockKeeperServerError::BlobSizeTooLarge => ErrorCode::BlobTooLarge,
+            LockKeeperServerError::StorageKeyAlreadySet => ErrorCode::StorageKeyAlreadySet,
+            LockKeeperServerError::StorageKeyNotSet => ErrorCode::StorageKeyNotSet,
+            LockKeeperServerError::UnsupportedProtocolVersion(_) => {
+                ErrorCode::UnsupportedProtocolVersion
+            }
+            LockKeeperServerError::ShuttingDown => ErrorCode::ShuttingDown,
+            LockKeeperServerError::RateLimited => ErrorCode::RateLimited,
+            LockKeeperServerError::TonicStatus(status) => {
+                ErrorCode::from_status(status).unwrap_or(ErrorCode::Internal)
+            }
+            LockKeeperServerError::LockKeeper(err) => err.error_code(),
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
 impl From<LockKeeperServerError> for Status {
     fn from(error: LockKeeperServerError) -> Status {
         use crate::server::session_cache::SessionCacheError::*;
 
-        match error {
+        let error_code = error.error_code();
+        let status = match error {
             LockKeeperServerError::SessionCache(ExpiredSession)
             | LockKeeperServerError::SessionCache(MissingSession) => {
                 Status::unauthenticated("No session for this user")
             }
+            LockKeeperServerError::SessionCache(ReplayedRequest) => {
+                Status::invalid_argument(LockKeeperError::MessageOutOfSequence.to_string())
+            }
             // Errors that are safe to return to the client
             LockKeeperServerError::AccountAlreadyRegistered
             | LockKeeperServerError::BlobSizeTooLarge
             | LockKeeperServerError::InvalidAccount
             | LockKeeperServerError::SessionIdNotFound
+            | LockKeeperServerError::InvalidServiceCredential
+            | LockKeeperServerError::InvalidServiceCredentialScope(_)
             | LockKeeperServerError::KeyNotFound => Status::invalid_argument(error.to_string()),
+            LockKeeperServerError::ActionNotPermitted(_)
+            | LockKeeperServerError::CertificateAccountMismatch => {
+                Status::permission_denied(error.to_string())
+            }
+            LockKeeperServerError::ShuttingDown => Status::unavailable(error.to_string()),
+            LockKeeperServerError::RateLimited => Status::resource_exhausted(error.to_string()),
+            LockKeeperServerError::UnsupportedProtocolVersion(_) => {
+                Status::failed_precondition(error.to_string())
+            }
 
             LockKeeperServerError::StorageKeyAlreadySet
             | LockKeeperServerError::StorageKeyNotSet => Status::internal(error.to_string()),
 
             LockKeeperServerError::TonicTransport(err) => Status::internal(err.to_string()),
+            LockKeeperServerError::Database(DatabaseError::WrongSecretType) => {
+                Status::invalid_argument(LockKeeperError::InvalidSecretType.to_string())
+            }
             LockKeeperServerError::Database(err) => err.into(),
             LockKeeperServerError::TonicStatus(status) => status,
             // These errors are are sanitized in the [`LockKeeperError`] module
@@ -109,20 +264,29 @@ impl From<LockKeeperServerError> for Status {
             LockKeeperServerError::FileIo(_, _)
             | LockKeeperServerError::MissingService
             | LockKeeperServerError::InvalidLogFilePath(_)
+            | LockKeeperServerError::InvalidCertificateRevocationList(_)
             | LockKeeperServerError::SessionCache(_)
             | LockKeeperServerError::Hyper(_)
             | LockKeeperServerError::Io(_)
             | LockKeeperServerError::InvalidOpaqueDirectory
+            | LockKeeperServerError::InvalidClientCertificate
             | LockKeeperServerError::Bincode(_)
             | LockKeeperServerError::OpaqueProtocol(_)
+            | LockKeeperServerError::Prometheus(_)
+            | LockKeeperServerError::Trace(_)
             | LockKeeperServerError::PrivateKeyMissing
             | LockKeeperServerError::OpaqueServerSetupNotDefined
             | LockKeeperServerError::RemoteStorageKeyMissing
+            | LockKeeperServerError::AuditKeyMissing
+            | LockKeeperServerError::AuditArchive(_)
+            | LockKeeperServerError::AuditChain(_)
+            | LockKeeperServerError::AuditSink(_)
             | LockKeeperServerError::EnvVar(_)
             | LockKeeperServerError::Rustls(_)
             | LockKeeperServerError::StrumParseError(_)
             | LockKeeperServerError::Toml(_)
             | LockKeeperServerError::WebPki(_) => Status::internal("Internal server error"),
-        }
+        };
+        error_code.attach(status)
     }
 }
@@ -1,4 +1,5 @@
 mod authenticate;
+mod authenticate_service_account;
 mod create_storage_key;
 mod delete_key;
 mod generate_secret;
@@ -6,6 +7,7 @@ mod get_user_id;
 mod import_signing_key;
 mod logout;
 mod register;
+mod register_service_credential;
 mod remote_generate_signing_key;
 mod remote_sign_bytes;
 mod retrieve_audit_events;
@@ -15,6 +17,7 @@ mod retrieve_storage_key;
 mod store_server_encrypted_blob;
 
 pub use authenticate::Authenticate;
+pub use authenticate_service_account::AuthenticateServiceAccount;
 pub use create_storage_key::CreateStorageKey;
 pub use delete_key::DeleteKey;
 pub use generate_secret::GenerateSecret;
@@ -22,6 +25,7 @@ pub use get_user_id::GetUserId;
 pub use import_signing_key::ImportSigningKey;
 pub use logout::Logout;
 pub use register::Register;
+pub use register_service_credential::RegisterServiceCredential;
 pub use remote_generate_signing_key::RemoteGenerateSigningKey;
 pub use remote_sign_bytes::RemoteSignBytes;
 pub use retrieve_audit_events::RetrieveAuditEvents;
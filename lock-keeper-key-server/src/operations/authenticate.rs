@@ -60,6 +60,7 @@ impl<DB: DataStore> Operation<Unauthenticated, DB> for Authenticate {
                     request_id,
                     ClientAction::Authenticate,
                     EventStatus::Failed,
+                    Some(e.failure_code()),
                 )
                 .await?;
             return Err(e);
@@ -75,7 +76,7 @@ impl<DB: DataStore> Operation<Unauthenticated, DB> for Authenticate {
 #[instrument(skip_all, err(Debug), fields(account_id))]
 async fn authenticate_start<DB: DataStore>(
     channel: &mut Channel<Unauthenticated>,
-    context: &Context<DB>,
+    context: &mut Context<DB>,
 ) -> Result<AuthenticateStartResult, LockKeeperServerError> {
     // Receive start message from client
     let start_message: client::AuthenticateStart = channel.receive().await?;
@@ -101,21 +102,19 @@ async fn authenticate_start<DB: DataStore>(
             request_id,
             ClientAction::Authenticate,
             EventStatus::Started,
+            None,
         )
         .await?;
+    context.started_account_id = Some(account_id);
 
-    let server_login_start_result = {
-        let mut local_rng = context.rng.lock().await;
-
-        ServerLogin::start(
-            &mut *local_rng,
-            &context.config.opaque_server_setup,
-            Some(account.server_registration),
-            start_message.credential_request,
-            start_message.account_name.as_bytes(),
-            ServerLoginStartParameters::default(),
-        )?
-    };
+    let server_login_start_result = ServerLogin::start(
+        &mut context.rng,
+        &context.config.opaque_server_setup,
+        Some(account.server_registration),
+        start_message.credential_request,
+        start_message.account_name.as_bytes(),
+        ServerLoginStartParameters::default(),
+    )?;
 
     let reply = server::AuthenticateStart {
         credential_response: server_login_start_result.message.clone(),
@@ -149,19 +148,16 @@ async fn authenticate_finish<DB: DataStore>(
         .finish(finish_message.credential_finalization)?;
 
     // Save session key into our cache.
-    let session_cache = context.session_cache.lock().await;
     let session_key = server_login_finish_result.session_key.try_into()?;
     // Encrypt the session key and generate a new session ID.
-    let encrypted_session_key = {
-        let mut rng = context.rng.lock().await;
-        context
-            .config
-            .remote_storage_key
-            .encrypt_session_key(&mut *rng, session_key)?
-    };
-
-    let session_id = session_cache
-        .create_session(start_result.account_id, encrypted_session_key)
+    let encrypted_session_key = context
+        .config
+        .remote_storage_key
+        .encrypt_session_key(&mut context.rng, session_key)?;
+
+    let session_id = context
+        .session_cache
+        .create_session(start_result.account_id, encrypted_session_key, None)
         .await?;
     logging::record_field("session_id", &session_id);
     info!("Session key established and saved.");
@@ -178,6 +174,7 @@ async fn authenticate_finish<DB: DataStore>(
             start_result.request_id,
             ClientAction::Authenticate,
             EventStatus::Successful,
+            None,
         )
         .await?;
 
@@ -0,0 +1,214 @@
+use crate::{
+    error::LockKeeperServerError,
+    server::{
+        channel::{Channel, Unauthenticated},
+        Context, Operation,
+    },
+};
+
+use crate::server::database::DataStore;
+use async_trait::async_trait;
+use lock_keeper::{
+    crypto::{ServiceAuthTranscript, ServiceKeyExchange},
+    infrastructure::logging,
+    types::{
+        audit_event::{EventStatus, FailureCode},
+        database::{account::AccountId, service_credential::ServiceCredential},
+        operations::{
+            authenticate_service_account::{client, server},
+            ClientAction,
+        },
+    },
+};
+use rand::RngCore;
+use tracing::{debug, info, instrument};
+use uuid::Uuid;
+
+struct AuthenticateStartResult {
+    credential: ServiceCredential,
+    transcript_start: client::AuthenticateStart,
+    challenge: [u8; 32],
+    key_exchange: ServiceKeyExchange,
+    server_key_share: Vec<u8>,
+    account_id: AccountId,
+    request_id: Uuid,
+}
+
+#[derive(Debug)]
+pub struct AuthenticateServiceAccount;
+
+#[async_trait]
+impl<DB: DataStore> Operation<Unauthenticated, DB> for AuthenticateServiceAccount {
+    /// Executes the server side of the service account challenge-response
+    /// login. This establishes a session key for client and server to use for
+    /// secure communication.
+    #[instrument(skip_all, err(Debug), fields(account_name))]
+    async fn operation(
+        self,
+        channel: &mut Channel<Unauthenticated>,
+        context: &mut Context<DB>,
+    ) -> Result<(), LockKeeperServerError> {
+        info!("Starting service account authentication protocol.");
+
+        let start_result = authenticate_start(channel, context).await?;
+
+        // As with password logins, log failed attempts for accounts we found.
+        let account_id = start_result.account_id;
+        let request_id = start_result.request_id;
+
+        if let Err(e) = authenticate_finish(channel, context, start_result).await {
+            context
+                .create_audit_event(
+                    account_id,
+                    request_id,
+                    ClientAction::AuthenticateServiceAccount,
+                    EventStatus::Failed,
+                    Some(e.failure_code()),
+                )
+                .await?;
+            return Err(e);
+        }
+
+        info!("Successfully completed service account authentication protocol.");
+        Ok(())
+    }
+}
+
+/// Looks up the service credential and sends the client a fresh challenge along
+/// with the server's key share.
+#[instrument(skip_all, err(Debug), fields(account_id, credential_id))]
+async fn authenticate_start<DB: DataStore>(
+    channel: &mut Channel<Unauthenticated>,
+    context: &mut Context<DB>,
+) -> Result<AuthenticateStartResult, LockKeeperServerError> {
+    let start_message: client::AuthenticateStart = channel.receive().await?;
+
+    let account = context
+        .db
+        .find_account_by_name(&start_message.account_name)
+        .await?
+        .ok_or(LockKeeperServerError::InvalidAccount)?;
+
+    logging::record_field("account_id", &account.account_id);
+    logging::record_field("credential_id", &start_message.credential_id);
+
+    let account_id = account.id();
+    let request_id = channel.metadata().request_id();
+
+    context
+        .create_audit_event(
+            account_id,
+            request_id,
+            ClientAction::AuthenticateServiceAccount,
+            EventStatus::Started,
+            None,
+        )
+        .await?;
+    context.started_account_id = Some(account_id);
+
+    let credential = match context
+        .db
+        .find_service_credential(account_id, start_message.credential_id)
+        .await?
+    {
+        Some(credential) => credential,
+        None => {
+            context
+                .create_audit_event(
+                    account_id,
+                    request_id,
+                    ClientAction::AuthenticateServiceAccount,
+                    EventStatus::Failed,
+                    Some(FailureCode::AuthenticationFailed),
+                )
+                .await?;
+            return Err(LockKeeperServerError::InvalidServiceCredential);
+        }
+    };
+    debug!("Service credential found.");
+
+    let mut challenge = [0_u8; 32];
+    context.rng.fill_bytes(&mut challenge);
+    let key_exchange = ServiceKeyExchange::new(&mut context.rng);
+    let server_key_share = key_exchange.key_share();
+
+    channel
+        .send(server::AuthenticateStart {
+            challenge,
+            key_share: server_key_share.clone(),
+        })
+        .await?;
+
+    Ok(AuthenticateStartResult {
+        credential,
+        transcript_start: start_message,
+        challenge,
+        key_exchange,
+        server_key_share,
+        account_id,
+        request_id,
+    })
+}
+
+/// Verifies the client's signature over the login transcript and creates a
+/// session scoped to the service credential.
+#[instrument(skip_all, err(Debug), fields(session_id))]
+async fn authenticate_finish<DB: DataStore>(
+    channel: &mut Channel<Unauthenticated>,
+    context: &mut Context<DB>,
+    start_result: AuthenticateStartResult,
+) -> Result<(), LockKeeperServerError> {
+    let finish_message: client::AuthenticateFinish = channel.receive().await?;
+
+    let transcript = ServiceAuthTranscript::new(
+        start_result.request_id,
+        &start_result.transcript_start.account_name,
+        start_result.credential.credential_id,
+        &start_result.challenge,
+        &start_result.server_key_share,
+        &finish_message.key_share,
+    );
+
+    start_result
+        .credential
+        .public_key
+        .verify(transcript.as_bytes(), &finish_message.signature)
+        .map_err(|_| LockKeeperServerError::InvalidServiceCredential)?;
+
+    let session_key = start_result
+        .key_exchange
+        .finish(&finish_message.key_share, &transcript)
+        .map_err(lock_keeper::LockKeeperError::from)?;
+
+    let encrypted_session_key = context
+        .config
+        .remote_storage_key
+        .encrypt_session_key(&mut context.rng, session_key)?;
+
+    let session_id = context
+        .session_cache
+        .create_session(
+            start_result.account_id,
+            encrypted_session_key,
+            Some(start_result.credential.credential_id),
+        )
+        .await?;
+    logging::record_field("session_id", &session_id);
+    info!("Session key established and saved.");
+
+    channel
+        .send(server::AuthenticateFinish { session_id })
+        .await?;
+
+    context
+        .create_audit_event(
+            start_result.account_id,
+            start_result.request_id,
+            ClientAction::AuthenticateServiceAccount,
+            EventStatus::Successful,
+            None,
+        )
+        .await?;
+
+    Ok(())
+}
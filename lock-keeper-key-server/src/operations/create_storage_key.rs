@@ -6,7 +6,7 @@ use crate::{
     },
 };
 
-use crate::server::database::DataStore;
+use crate::server::database::{DataStore, DataStoreTransaction};
 use async_trait::async_trait;
 use lock_keeper::{
     infrastructure::logging,
@@ -43,7 +43,7 @@ impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for CreateStorageKey {
 #[instrument(skip_all, err(Debug), fields(account_id))]
 async fn store_storage_key<DB: DataStore>(
     channel: &mut Channel<Authenticated<StdRng>>,
-    context: &Context<DB>,
+    context: &mut Context<DB>,
 ) -> Result<(), LockKeeperServerError> {
     info!("Storing storage key.");
     let client_message: client::SendStorageKey = channel.receive().await?;
@@ -55,17 +55,21 @@ async fn store_storage_key<DB: DataStore>(
         return Err(LockKeeperServerError::StorageKeyAlreadySet);
     }
 
-    let store_key_result = context
-        .db
-        .set_storage_key(account_id, client_message.storage_key.clone())
-        .await;
+    let store_key_result = async {
+        let mut transaction = context.db.begin_transaction().await?;
+        transaction
+            .set_storage_key(account_id, client_message.storage_key.clone())
+            .await?;
+        context.commit_with_audit_event(transaction, channel).await
+    }
+    .await;
 
     // Delete user if we fail to set the storage key.
     if let Err(e) = store_key_result {
         error!("Failed to set storage key for user.");
         context.db.delete_account(account_id).await?;
         info!("Deleted user due to failure to set storage key.");
-        return Err(e.into());
+        return Err(e);
     }
 
     let reply = server::CreateStorageKeyResult { success: true };
@@ -1,7 +1,7 @@
 use crate::{
     server::{
         channel::{Authenticated, Channel},
-        database::DataStore,
+        database::{DataStore, DataStoreTransaction},
         Context, Operation,
     },
     LockKeeperServerError,
@@ -29,10 +29,13 @@ impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for DeleteKey {
         let account_id = channel.account_id();
         context.key_id = Some(request.key_id.clone());
 
-        context
-            .db
+        let mut transaction = context.db.begin_transaction().await?;
+        transaction
             .delete_secret(account_id, &request.key_id)
             .await?;
+        context
+            .commit_with_audit_event(transaction, channel)
+            .await?;
 
         channel.send(server::Response { success: true }).await?;
 
@@ -6,7 +6,7 @@ use crate::{
     LockKeeperServerError,
 };
 
-use crate::server::database::DataStore;
+use crate::server::database::{DataStore, DataStoreTransaction};
 use async_trait::async_trait;
 use lock_keeper::{
     crypto::KeyId,
@@ -59,15 +59,12 @@ impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for GenerateSecret {
 #[instrument(skip_all, err(Debug))]
 async fn generate_key_id<DB: DataStore>(
     channel: &mut Channel<Authenticated<StdRng>>,
-    context: &Context<DB>,
+    context: &mut Context<DB>,
 ) -> Result<KeyId, LockKeeperServerError> {
     let user_id = channel.user_id();
 
     // Generate new KeyId
-    let key_id = {
-        let mut rng = context.rng.lock().await;
-        KeyId::generate(&mut *rng, user_id)?
-    };
+    let key_id = KeyId::generate(&mut context.rng, user_id)?;
     info!("New key_id generated: {:?}", key_id);
 
     // Serialize KeyId and send to client
@@ -85,7 +82,7 @@ async fn generate_key_id<DB: DataStore>(
 #[instrument(skip_all, err(Debug))]
 async fn store_key<DB: DataStore>(
     channel: &mut Channel<Authenticated<StdRng>>,
-    context: &Context<DB>,
+    context: &mut Context<DB>,
     key_id: &KeyId,
 ) -> Result<(), LockKeeperServerError> {
     // Receive Encrypted<Secret> from client
@@ -97,7 +94,11 @@ async fn store_key<DB: DataStore>(
         channel.account_id(),
         store_message.ciphertext,
     )?;
-    context.db.add_secret(secret).await?;
+    let mut transaction = context.db.begin_transaction().await?;
+    transaction.add_secret(secret).await?;
+    context
+        .commit_with_audit_event(transaction, channel)
+        .await?;
     info!("Client's cypher text stored successfully.");
 
     // Reply with the success:true if successful
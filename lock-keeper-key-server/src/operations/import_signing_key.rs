@@ -8,7 +8,7 @@ use crate::{
     LockKeeperServerError,
 };
 
-use crate::server::database::DataStore;
+use crate::server::database::{DataStore, DataStoreTransaction};
 use async_trait::async_trait;
 use lock_keeper::{
     crypto::KeyId,
@@ -37,23 +37,17 @@ impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for ImportSigningKey {
         let user_id = channel.user_id();
 
         // Generate new KeyId
-        let key_id = {
-            let mut rng = context.rng.lock().await;
-            KeyId::generate(&mut *rng, user_id)?
-        };
+        let key_id = KeyId::generate(&mut context.rng, user_id)?;
         context.key_id = Some(key_id.clone());
 
         // Make signing key out of bytes
         let signing_key = request.key_material.into_signing_key(user_id, &key_id)?;
 
         // encrypt key_pair
-        let encrypted_key_pair = {
-            let mut rng = context.rng.lock().await;
-            context
-                .config
-                .remote_storage_key
-                .encrypt_signing_key_pair(&mut *rng, signing_key)?
-        };
+        let encrypted_key_pair = context
+            .config
+            .remote_storage_key
+            .encrypt_signing_key_pair(&mut context.rng, signing_key)?;
 
         let secret = StoredSecret::from_remote_signing_key_pair(
             key_id.clone(),
@@ -62,7 +56,11 @@ impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for ImportSigningKey {
         )?;
 
         // Check validity of ciphertext and store in DB
-        context.db.add_secret(secret).await?;
+        let mut transaction = context.db.begin_transaction().await?;
+        transaction.add_secret(secret).await?;
+        context
+            .commit_with_audit_event(transaction, channel)
+            .await?;
 
         // Serialize KeyId and send to client
         let reply = server::Response {
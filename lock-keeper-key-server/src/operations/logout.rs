@@ -28,14 +28,7 @@ impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for Logout {
             .ok_or(SessionCacheError::MissingSession)?;
 
         // Expire the user's session key
-        {
-            context
-                .session_cache
-                .lock()
-                .await
-                .delete_session(*session_id)
-                .await?;
-        }
+        context.session_cache.delete_session(*session_id).await?;
 
         let reply = server::Response { success: true };
         channel.send(reply).await?;
@@ -5,7 +5,6 @@ use crate::{
         Context, Operation,
     },
 };
-use std::ops::DerefMut;
 
 use crate::server::database::DataStore;
 use async_trait::async_trait;
@@ -49,7 +48,7 @@ impl<DB: DataStore> Operation<Unauthenticated, DB> for Register {
 #[instrument(skip_all, err(Debug), fields(user_id))]
 async fn register_start<DB: DataStore>(
     channel: &mut Channel<Unauthenticated>,
-    context: &Context<DB>,
+    context: &mut Context<DB>,
 ) -> Result<AccountName, LockKeeperServerError> {
     // Receive start message from client
     let start_message: client::RegisterStart = channel.receive().await?;
@@ -91,7 +90,7 @@ async fn register_start<DB: DataStore>(
 async fn register_finish<DB: DataStore>(
     account_name: &AccountName,
     channel: &mut Channel<Unauthenticated>,
-    context: &Context<DB>,
+    context: &mut Context<DB>,
 ) -> Result<(), LockKeeperServerError> {
     // Receive finish message from client
     let finish_message: client::RegisterFinish = channel.receive().await?;
@@ -102,10 +101,7 @@ async fn register_finish<DB: DataStore>(
 
     loop {
         // Create a user ID for the new client
-        let user_id = {
-            let mut rng = context.rng.lock().await;
-            UserId::new(rng.deref_mut())?
-        };
+        let user_id = UserId::new(&mut context.rng)?;
 
         let user_id_exists = context.db.user_id_exists(&user_id).await?;
 
@@ -124,6 +120,7 @@ async fn register_finish<DB: DataStore>(
                     request_id,
                     ClientAction::Register,
                     EventStatus::Successful,
+                    None,
                 )
                 .await?;
             break;
@@ -0,0 +1,59 @@
+use crate::{
+    server::{
+        channel::{Authenticated, Channel},
+        database::DataStore,
+        Context, Operation,
+    },
+    LockKeeperServerError,
+};
+use async_trait::async_trait;
+use lock_keeper::{
+    infrastructure::logging,
+    types::operations::register_service_credential::{client, server},
+};
+use rand::rngs::StdRng;
+use tracing::{info, instrument};
+
+#[derive(Debug)]
+pub struct RegisterServiceCredential;
+
+#[async_trait]
+impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for RegisterServiceCredential {
+    #[instrument(skip_all, err(Debug), fields(credential_id))]
+    async fn operation(
+        self,
+        channel: &mut Channel<Authenticated<StdRng>>,
+        context: &mut Context<DB>,
+    ) -> Result<(), LockKeeperServerError> {
+        info!("Starting register service credential protocol.");
+        let request: client::Request = channel.receive().await?;
+
+        // Service credentials cannot derive the master key, so they may only be
+        // granted actions that run entirely on the server.
+        if let Some(action) = request
+            .allowed_actions
+            .iter()
+            .find(|action| !action.is_service_action())
+        {
+            return Err(LockKeeperServerError::InvalidServiceCredentialScope(
+                *action,
+            ));
+        }
+
+        let account_id = channel.account_id();
+        let credential = context
+            .db
+            .create_service_credential(account_id, &request.public_key, &request.allowed_actions)
+            .await?;
+        logging::record_field("credential_id", &credential.credential_id);
+
+        channel
+            .send(server::Response {
+                credential_id: credential.credential_id,
+            })
+            .await?;
+
+        info!("Successfully completed register service credential protocol.");
+        Ok(())
+    }
+}
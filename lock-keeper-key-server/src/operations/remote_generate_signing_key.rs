@@ -8,7 +8,7 @@ use crate::{
     LockKeeperServerError,
 };
 
-use crate::server::database::DataStore;
+use crate::server::database::{DataStore, DataStoreTransaction};
 use async_trait::async_trait;
 use lock_keeper::{
     crypto::{KeyId, SigningKeyPair},
@@ -36,26 +36,18 @@ impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for RemoteGenerateSigni
         info!("Starting remote generate protocol.");
         let user_id = channel.user_id();
 
-        // Create a scope for rng mutex
-        let (key_id, key_pair) = {
-            let mut rng = context.rng.lock().await;
-            let key_id = KeyId::generate(&mut *rng, user_id)?;
-            let key_pair = SigningKeyPair::remote_generate(&mut *rng, user_id, &key_id);
-            info!("Generated key ID: {:?}", key_id);
-            (key_id, key_pair)
-        };
+        let key_id = KeyId::generate(&mut context.rng, user_id)?;
+        let key_pair = SigningKeyPair::remote_generate(&mut context.rng, user_id, &key_id);
+        info!("Generated key ID: {:?}", key_id);
         context.key_id = Some(key_id.clone());
 
         let public_key = key_pair.public_key();
 
         // encrypt key_pair
-        let encrypted_key_pair = {
-            let mut rng = context.rng.lock().await;
-            context
-                .config
-                .remote_storage_key
-                .encrypt_signing_key_pair(&mut *rng, key_pair)?
-        };
+        let encrypted_key_pair = context
+            .config
+            .remote_storage_key
+            .encrypt_signing_key_pair(&mut context.rng, key_pair)?;
 
         let secret = StoredSecret::from_remote_signing_key_pair(
             key_id.clone(),
@@ -64,7 +56,11 @@ impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for RemoteGenerateSigni
         )?;
 
         // Store key in database
-        context.db.add_secret(secret).await?;
+        let mut transaction = context.db.begin_transaction().await?;
+        transaction.add_secret(secret).await?;
+        context
+            .commit_with_audit_event(transaction, channel)
+            .await?;
 
         channel
             .send(server::ReturnKeyId { key_id, public_key })
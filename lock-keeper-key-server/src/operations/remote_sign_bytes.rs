@@ -13,7 +13,10 @@ use async_trait::async_trait;
 
 use lock_keeper::{
     crypto::{Encrypted, Signable, SigningKeyPair},
-    types::operations::remote_sign_bytes::{client, server},
+    types::{
+        audit_event::PayloadHash,
+        operations::remote_sign_bytes::{client, server},
+    },
 };
 use rand::rngs::StdRng;
 use tracing::{info, instrument};
@@ -37,6 +40,8 @@ impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for RemoteSignBytes {
         info!("Starting remote sign protocol.");
         let request: client::RequestRemoteSign = channel.receive().await?;
         context.key_id = Some(request.key_id.clone());
+        context.audit_details.payload_size = Some(request.data.as_ref().len() as u64);
+        context.audit_details.payload_hash = Some(PayloadHash::of(request.data.as_ref()));
 
         let account_id = channel.account_id();
 
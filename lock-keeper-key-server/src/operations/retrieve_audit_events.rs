@@ -12,6 +12,9 @@ use lock_keeper::types::operations::retrieve_audit_events::{client, server};
 use rand::rngs::StdRng;
 use tracing::{info, instrument};
 
+/// Maximum number of audit events sent in a single message.
+const AUDIT_EVENT_PAGE_SIZE: u32 = 100;
+
 #[derive(Debug)]
 pub struct RetrieveAuditEvents;
 
@@ -29,16 +32,44 @@ impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for RetrieveAuditEvents
 
         let account_id = channel.account_id();
 
-        let audit_events = context
-            .db
-            .find_audit_events(account_id, request.event_type, request.options)
-            .await?;
+        // Checkpoints are sent first so that they never cover events that
+        // are newer than the ones returned.
+        let checkpoints = context.db.find_audit_checkpoints(account_id).await?;
+        channel.send(server::Response { checkpoints }).await?;
+
+        // Send the events one page at a time, using the last event of each
+        // page as the cursor for the next.
+        let mut options = request.options;
+        let mut remaining = options.limit;
+        loop {
+            let page_size = remaining.map_or(AUDIT_EVENT_PAGE_SIZE, |remaining| {
+                remaining.min(AUDIT_EVENT_PAGE_SIZE)
+            });
+            options.limit = Some(page_size);
+
+            let audit_events = context
+                .db
+                .find_audit_events(account_id, request.event_type, options.clone())
+                .await?;
+
+            let page_len = audit_events.len() as u32;
+            remaining = remaining.map(|remaining| remaining - page_len);
+            let has_more = page_len == page_size && remaining != Some(0);
+            if let Some(last_event) = audit_events.last() {
+                options.after_audit_event_id = Some(last_event.audit_event_id);
+            }
 
-        let reply = server::Response {
-            summary_record: audit_events,
-        };
+            channel
+                .send(server::Page {
+                    audit_events,
+                    has_more,
+                })
+                .await?;
+            if !has_more {
+                break;
+            }
+        }
 
-        channel.send(reply).await?;
         info!("Successfully completed retrieve audit events protocol");
         Ok(())
     }
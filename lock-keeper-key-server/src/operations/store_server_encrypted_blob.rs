@@ -1,7 +1,7 @@
 use crate::{
     server::{
         channel::{Authenticated, Channel},
-        database::DataStore,
+        database::{DataStore, DataStoreTransaction},
         Context, Operation,
     },
     LockKeeperServerError,
@@ -37,6 +37,8 @@ impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for StoreServerEncrypte
         info!("Starting store server-encrypted blob protocol.");
         let request: client::Request = channel.receive().await?;
         let user_id = channel.user_id();
+        // The blob is secret, so only its size is recorded.
+        context.audit_details.payload_size = Some(request.data_blob.len() as u64);
 
         // Check size of blob.
         if request.data_blob.len() > context.config.max_blob_size as usize {
@@ -44,26 +46,24 @@ impl<DB: DataStore> Operation<Authenticated<StdRng>, DB> for StoreServerEncrypte
         }
 
         // Generate new KeyId
-        let key_id = {
-            let mut rng = context.rng.lock().await;
-            KeyId::generate(&mut *rng, user_id)?
-        };
+        let key_id = KeyId::generate(&mut context.rng, user_id)?;
         context.key_id = Some(key_id.clone());
 
         // Create a new data blob from client's data.
         let blob = DataBlob::create(request.data_blob, user_id, &key_id)?;
 
-        let encrypted_blob = {
-            let mut rng = context.rng.lock().await;
-            context
-                .config
-                .remote_storage_key
-                .encrypt_data_blob(&mut *rng, blob)?
-        };
+        let encrypted_blob = context
+            .config
+            .remote_storage_key
+            .encrypt_data_blob(&mut context.rng, blob)?;
 
         let secret =
             StoredSecret::from_data_blob(key_id.clone(), channel.account_id(), encrypted_blob)?;
-        context.db.add_secret(secret).await?;
+        let mut transaction = context.db.begin_transaction().await?;
+        transaction.add_secret(secret).await?;
+        context
+            .commit_with_audit_event(transaction, channel)
+            .await?;
 
         channel.send(server::Response { key_id }).await?;
 
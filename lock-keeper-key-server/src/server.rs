@@ -1,67 +1,133 @@
+pub mod audit_archive;
+mod audit_retention;
+pub mod audit_sink;
+pub(crate) mod certificate_revocation;
 pub(crate) mod channel;
+pub mod client_certificate;
+pub(crate) mod concurrency;
 pub(crate) mod context;
 pub mod database;
+mod message_limit;
+pub mod metrics;
 pub(crate) mod opaque_storage;
 mod operation;
+pub(crate) mod readiness;
 mod service;
 pub mod session_cache;
+pub(crate) mod shutdown;
 
 pub(crate) use context::Context;
 pub(crate) use operation::Operation;
-pub use service::start_lock_keeper_server;
+pub use service::{start_lock_keeper_server, ReloadConfig};
 use tokio_stream::wrappers::ReceiverStream;
-use tracing::{debug, info, instrument};
+use tracing::{debug, info, instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-use crate::{config::Config, error::LockKeeperServerError, operations};
+use crate::{
+    config::{Config, SharedConfig},
+    error::LockKeeperServerError,
+    operations,
+};
 
 use lock_keeper::{
-    constants::METADATA,
-    rpc::{lock_keeper_rpc_server::LockKeeperRpc, Empty, SessionStatus},
-    types::{operations::RequestMetadata, Message, MessageStream},
+    constants::{METADATA, MIN_SUPPORTED_PROTOCOL_VERSION, PROTOCOL_VERSION},
+    infrastructure::{logging, trace_context},
+    rpc::{
+        lock_keeper_rpc_server::LockKeeperRpc, Empty, ReadinessStatus, ServerInfo, SessionStatus,
+    },
+    types::{
+        database::account::Account,
+        operations::{MessageEncoding, RequestMetadata},
+        Message, MessageStream,
+    },
+    LockKeeperError,
 };
 
-use crate::server::{database::DataStore, session_cache::SessionCache};
+use crate::server::{
+    database::DataStore,
+    session_cache::{Session, SessionCache},
+};
 use rand::{rngs::StdRng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::{path::Path, sync::Arc};
-use tokio::sync::Mutex;
+use strum::IntoEnumIterator;
 use tonic::{Request, Response, Status, Streaming};
 
 use self::{
-    channel::{Authenticated, Channel, Unauthenticated},
+    audit_sink::AuditSinks,
+    channel::{Authenticated, Channel, ChannelLimits, Unauthenticated},
+    client_certificate::ClientCertificate,
+    concurrency::ConcurrencyLimiter,
+    metrics::Metrics,
     operation::{handle_authenticated_request, handle_unauthenticated_request},
+    readiness::Readiness,
+    shutdown::ShutdownCoordinator,
 };
 
 pub struct LockKeeperKeyServer<DB: DataStore> {
-    config: Arc<Config>,
+    config: SharedConfig,
     db: Arc<DB>,
-    rng: Arc<Mutex<StdRng>>,
-    session_cache: Arc<Mutex<dyn SessionCache>>,
+    session_cache: Arc<dyn SessionCache>,
+    shutdown: ShutdownCoordinator,
+    concurrency: ConcurrencyLimiter,
+    audit_sinks: AuditSinks,
+    metrics: Metrics,
 }
 
 impl<DB: DataStore> LockKeeperKeyServer<DB> {
     pub fn new(
         db: Arc<DB>,
-        session_key_cache: Arc<Mutex<dyn SessionCache>>,
+        session_key_cache: Arc<dyn SessionCache>,
         config: Config,
     ) -> Result<Self, LockKeeperServerError> {
-        let rng = StdRng::from_entropy();
+        Self::with_shared_config(
+            db,
+            session_key_cache,
+            SharedConfig::new(config),
+            ShutdownCoordinator::default(),
+            Metrics::new()?,
+        )
+    }
+
+    /// Create a server whose config can be replaced while it is running and
+    /// whose operations are tracked by the given [`ShutdownCoordinator`].
+    /// Requests are recorded in `metrics`.
+    ///
+    /// Delivery tasks are started for the configured audit sinks, so this
+    /// must be called from within a Tokio runtime if there are any.
+    pub(crate) fn with_shared_config(
+        db: Arc<DB>,
+        session_key_cache: Arc<dyn SessionCache>,
+        config: SharedConfig,
+        shutdown: ShutdownCoordinator,
+        metrics: Metrics,
+    ) -> Result<Self, LockKeeperServerError> {
+        let audit_sinks = AuditSinks::from_config(&config.current().audit_sinks)?;
 
         Ok(Self {
-            config: Arc::new(config),
+            config,
             db,
-            rng: Arc::new(Mutex::new(rng)),
             session_cache: session_key_cache,
+            shutdown,
+            concurrency: ConcurrencyLimiter::new(metrics.clone()),
+            audit_sinks,
+            metrics,
         })
     }
 
     pub(crate) fn context(&self) -> Context<DB> {
         Context {
-            config: self.config.clone(),
+            config: self.config.current(),
             db: self.db.clone(),
-            rng: self.rng.clone(),
+            rng: StdRng::from_entropy(),
             key_id: None,
             session_cache: self.session_cache.clone(),
+            audit_details: Default::default(),
+            shutdown: self.shutdown.clone(),
+            audit_sinks: self.audit_sinks.clone(),
+            metrics: self.metrics.clone(),
+            success_recorded: false,
+            started_account_id: None,
         }
     }
 }
@@ -83,6 +149,7 @@ impl<DB: DataStore> LockKeeperKeyServer<DB> {
 #[tonic::async_trait]
 impl<DB: DataStore> LockKeeperRpc for LockKeeperKeyServer<DB> {
     type AuthenticateStream = MessageStream;
+    type AuthenticateServiceAccountStream = MessageStream;
     type CreateStorageKeyStream = MessageStream;
     type DeleteKeyStream = MessageStream;
     type GenerateSecretStream = MessageStream;
@@ -91,6 +158,7 @@ impl<DB: DataStore> LockKeeperRpc for LockKeeperKeyServer<DB> {
     type LogoutStream = MessageStream;
     type StoreServerEncryptedBlobStream = MessageStream;
     type RegisterStream = MessageStream;
+    type RegisterServiceCredentialStream = MessageStream;
     type RemoteGenerateStream = MessageStream;
     type RemoteSignBytesStream = MessageStream;
     type RetrieveServerEncryptedBlobStream = MessageStream;
@@ -102,29 +170,49 @@ impl<DB: DataStore> LockKeeperRpc for LockKeeperKeyServer<DB> {
         Ok(Response::new(Empty {}))
     }
 
-    #[instrument(skip_all, err(Debug))]
+    async fn readiness(&self, _: Request<Empty>) -> Result<Response<ReadinessStatus>, Status> {
+        let readiness = Readiness::new(
+            self.config.clone(),
+            self.db.clone(),
+            self.session_cache.clone(),
+        );
+        Ok(Response::new(readiness.check().await))
+    }
+
+    async fn get_server_info(&self, _: Request<Empty>) -> Result<Response<ServerInfo>, Status> {
+        Ok(Response::new(ServerInfo {
+            min_protocol_version: MIN_SUPPORTED_PROTOCOL_VERSION,
+            max_protocol_version: PROTOCOL_VERSION,
+            message_encodings: MessageEncoding::iter()
+                .map(|encoding| encoding.to_string())
+                .collect(),
+        }))
+    }
+
+    #[instrument(skip_all, err(Debug), fields(request_id, action))]
     async fn check_session(
         &self,
         request: Request<Empty>,
     ) -> Result<Response<SessionStatus>, Status> {
+        Span::current().set_parent(trace_context::extract(request.metadata()));
         info!("Checking that the authenticated user has a valid session.");
         let metadata: RequestMetadata = request
             .metadata()
             .get(METADATA)
-            .ok_or_else(|| Status::invalid_argument("Request is missing metadata"))?
+            .ok_or(LockKeeperError::MetadataNotFound)?
             .try_into()?;
+        logging::record_field("request_id", &metadata.request_id());
+        logging::record_field("action", &metadata.action());
+        check_protocol_version(&metadata)?;
 
         let is_session_valid = {
             match metadata.session_id() {
-                Some(id) => {
-                    let session_cache = self.session_cache.lock().await;
-                    session_cache.find_session(*id).await.is_ok()
-                }
+                Some(id) => self.session_cache.find_session(*id).await.is_ok(),
                 None => false,
             }
         };
 
-        let release_info = ReleaseInfo::from_toml_file(&self.config.release_toml_path)?;
+        let release_info = ReleaseInfo::from_toml_file(&self.config.current().release_toml_path)?;
 
         info!("Session check complete.");
         Ok(Response::new(SessionStatus {
@@ -152,6 +240,20 @@ impl<DB: DataStore> LockKeeperRpc for LockKeeperKeyServer<DB> {
         Ok(response)
     }
 
+    async fn authenticate_service_account(
+        &self,
+        request: Request<tonic::Streaming<Message>>,
+    ) -> Result<Response<Self::AuthenticateServiceAccountStream>, Status> {
+        let (channel, response) = self.create_unauthenticated_channel(request).await?;
+        handle_unauthenticated_request(
+            operations::AuthenticateServiceAccount,
+            self.context(),
+            channel,
+        )
+        .await?;
+        Ok(response)
+    }
+
     async fn logout(
         &self,
         request: Request<tonic::Streaming<Message>>,
@@ -206,6 +308,20 @@ impl<DB: DataStore> LockKeeperRpc for LockKeeperKeyServer<DB> {
         Ok(response)
     }
 
+    async fn register_service_credential(
+        &self,
+        request: Request<tonic::Streaming<Message>>,
+    ) -> Result<Response<Self::RegisterServiceCredentialStream>, Status> {
+        let (channel, response) = self.create_authenticated_channel(request).await?;
+        handle_authenticated_request(
+            operations::RegisterServiceCredential,
+            self.context(),
+            channel,
+        )
+        .await?;
+        Ok(response)
+    }
+
     async fn store_server_encrypted_blob(
         &self,
         request: Request<Streaming<Message>>,
@@ -295,7 +411,19 @@ impl<DB: DataStore> LockKeeperKeyServer<DB> {
         request: Request<Streaming<Message>>,
     ) -> Result<(Channel<Unauthenticated>, Response<MessageStream>), LockKeeperServerError> {
         debug!("Creating new unauthenticated channel.");
-        let (channel, rx) = Channel::new(request)?;
+        let config = self.config.current();
+        let limits = ChannelLimits {
+            receive_timeout: config.receive_timeout,
+            max_message_size: config.max_message_size,
+        };
+        let (mut channel, rx) = Channel::new(request, limits)?;
+        check_protocol_version(channel.metadata())?;
+
+        // Count the request against the server-wide concurrency limit before it
+        // does any work. The channel releases the permit when the request is
+        // finished.
+        let permit = self.concurrency.acquire(config.max_concurrent_requests)?;
+        channel.hold_permit(permit);
         let response = Response::new(ReceiverStream::new(rx));
 
         Ok((channel, response))
@@ -315,19 +443,20 @@ impl<DB: DataStore> LockKeeperKeyServer<DB> {
     ) -> Result<(Channel<Authenticated<StdRng>>, Response<MessageStream>), LockKeeperServerError>
     {
         debug!("Creating new authenticated channel.");
-        let (channel, response) = self.create_unauthenticated_channel(request).await?;
+        let (mut channel, response) = self.create_unauthenticated_channel(request).await?;
 
         // Upgrade channel to be authenticated
-        let session_id = channel
-            .metadata()
-            .session_id()
-            .ok_or(LockKeeperServerError::SessionIdNotFound)?;
         let context = self.context();
+        let session =
+            find_request_session(context.session_cache.as_ref(), channel.metadata()).await?;
 
-        let session = {
-            let session_cache = context.session_cache.lock().await;
-            session_cache.find_session(*session_id).await?
-        };
+        // Count the request against the limit of the session's account. The
+        // account name sent by the client isn't used since it can't be trusted.
+        let permit = self.concurrency.acquire_for_account(
+            session.account_id,
+            self.config.current().max_concurrent_requests_per_account,
+        )?;
+        channel.hold_permit(permit);
 
         let session_key = session.session_key(&context)?;
 
@@ -339,10 +468,114 @@ impl<DB: DataStore> LockKeeperKeyServer<DB> {
             .await?
             .ok_or(LockKeeperServerError::InvalidAccount)?;
 
-        let channel = channel.into_authenticated(account, session_key, context.rng.clone());
+        // Sessions created with a service credential are limited to the actions
+        // that credential was granted.
+        if let Some(credential_id) = session.service_credential_id {
+            let credential = context
+                .db
+                .find_service_credential(account.id(), credential_id)
+                .await?
+                .ok_or(LockKeeperServerError::InvalidServiceCredential)?;
+
+            let action = channel.metadata().action();
+            if !credential.allows(action) {
+                return Err(LockKeeperServerError::ActionNotPermitted(action));
+            }
+        }
+
+        let client_identity = self
+            .verify_client_certificate(channel.client_certificate(), channel.metadata(), &account)
+            .await?;
+
+        let channel = channel.into_authenticated(
+            account,
+            session_key,
+            StdRng::from_entropy(),
+            client_identity,
+        );
 
         Ok((channel, response))
     }
+
+    /// Checks the client certificate against the certificate bindings in the
+    /// database.
+    ///
+    /// A certificate identity that is bound to an account may only be used
+    /// for requests on behalf of that account, and an account with bindings
+    /// may only be used with a certificate bound to it. Returns the matched
+    /// identity so it can be recorded in audit events.
+    #[instrument(skip_all, err(Debug))]
+    async fn verify_client_certificate(
+        &self,
+        certificate: Option<&ClientCertificate>,
+        metadata: &RequestMetadata,
+        account: &Account,
+    ) -> Result<Option<String>, LockKeeperServerError> {
+        let mut client_identity = None;
+
+        if let Some(certificate) = certificate {
+            for identity in certificate.identities() {
+                let bound_account_id = match self.db.find_certificate_binding(identity).await? {
+                    Some(account_id) => account_id,
+                    None => continue,
+                };
+
+                if bound_account_id != account.id()
+                    || metadata.account_name() != &account.account_name
+                {
+                    return Err(LockKeeperServerError::CertificateAccountMismatch);
+                }
+
+                if client_identity.is_none() {
+                    client_identity = Some(identity.clone());
+                }
+            }
+        }
+
+        if client_identity.is_none()
+            && self
+                .db
+                .account_has_certificate_binding(account.id())
+                .await?
+        {
+            return Err(LockKeeperServerError::CertificateAccountMismatch);
+        }
+
+        Ok(client_identity)
+    }
+}
+
+/// Reject requests from clients whose protocol version this server doesn't
+/// support, before any of their messages are parsed.
+fn check_protocol_version(metadata: &RequestMetadata) -> Result<(), LockKeeperServerError> {
+    let version = metadata.protocol_version();
+    if (MIN_SUPPORTED_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+        Ok(())
+    } else {
+        Err(LockKeeperServerError::UnsupportedProtocolVersion(version))
+    }
+}
+
+/// Find the session that an authenticated request was made on and record the
+/// request against it.
+///
+/// Messages on an authenticated channel are only bound to the request they
+/// were sent in, so each request may only be made once per session. Otherwise
+/// a recorded request could be replayed in full.
+async fn find_request_session(
+    session_cache: &dyn SessionCache,
+    metadata: &RequestMetadata,
+) -> Result<Session, LockKeeperServerError> {
+    let session_id = metadata
+        .session_id()
+        .ok_or(LockKeeperServerError::SessionIdNotFound)?;
+
+    let session = session_cache.find_session(*session_id).await?;
+    session_cache
+        .record_request(*session_id, metadata.request_id(), metadata.action())
+        .await?;
+
+    Ok(session)
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -361,3 +594,162 @@ impl ReleaseInfo {
         Ok(toml::from_str(&toml_string)?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::session_cache::SessionCacheError;
+    use generic_array::GenericArray;
+    use lock_keeper::{
+        crypto::{Encrypted, MessageContext, MessageDirection, OpaqueSessionKey, RemoteStorageKey},
+        types::{database::account::AccountId, operations::ClientAction},
+    };
+    use std::{collections::HashSet, sync::Mutex, time::Duration};
+    use time::OffsetDateTime;
+    use tokio_stream::Stream;
+    use uuid::Uuid;
+
+    const LIMITS: ChannelLimits = ChannelLimits {
+        receive_timeout: Duration::from_secs(1),
+        max_message_size: 1024,
+    };
+
+    /// Session cache that holds a single session.
+    struct SingleSessionCache {
+        session_id: Uuid,
+        session_key: Encrypted<OpaqueSessionKey>,
+        requests: Mutex<HashSet<(Uuid, ClientAction)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl SessionCache for SingleSessionCache {
+        async fn create_session(
+            &self,
+            _account_id: AccountId,
+            _session_key: Encrypted<OpaqueSessionKey>,
+            _service_credential_id: Option<Uuid>,
+        ) -> Result<Uuid, SessionCacheError> {
+            Err(SessionCacheError::SessionExists)
+        }
+
+        async fn find_session(&self, session_id: Uuid) -> Result<Session, SessionCacheError> {
+            if session_id != self.session_id {
+                return Err(SessionCacheError::MissingSession);
+            }
+            Ok(Session {
+                session_id,
+                account_id: AccountId::from(1),
+                timestamp: OffsetDateTime::now_utc(),
+                session_key: self.session_key.clone(),
+                service_credential_id: None,
+            })
+        }
+
+        async fn delete_session(&self, _session_id: Uuid) -> Result<(), SessionCacheError> {
+            Ok(())
+        }
+
+        async fn record_request(
+            &self,
+            _session_id: Uuid,
+            request_id: Uuid,
+            action: ClientAction,
+        ) -> Result<(), SessionCacheError> {
+            if !self.requests.lock().unwrap().insert((request_id, action)) {
+                return Err(SessionCacheError::ReplayedRequest);
+            }
+            Ok(())
+        }
+
+        async fn count_active_sessions(&self) -> Result<u64, SessionCacheError> {
+            Ok(1)
+        }
+
+        async fn health_check(&self) -> Result<(), SessionCacheError> {
+            Ok(())
+        }
+    }
+
+    /// Create the request that a client makes when it sends `stream`.
+    fn request(
+        metadata: &RequestMetadata,
+        stream: Vec<Message>,
+    ) -> Request<impl Stream<Item = Result<Message, Status>>> {
+        let mut request = Request::new(tokio_stream::iter(stream.into_iter().map(Ok)));
+        let _ = request
+            .metadata_mut()
+            .insert(METADATA, metadata.try_into().unwrap());
+        request
+    }
+
+    #[tokio::test]
+    async fn replayed_stream_is_rejected() {
+        let mut rng = StdRng::seed_from_u64(1234);
+        let session_key = OpaqueSessionKey::try_from(GenericArray::from([42; 64])).unwrap();
+        let cache = SingleSessionCache {
+            session_id: Uuid::new_v4(),
+            session_key: RemoteStorageKey::generate(&mut rng)
+                .encrypt_session_key(&mut rng, session_key.clone())
+                .unwrap(),
+            requests: Default::default(),
+        };
+        let metadata = RequestMetadata::new(
+            &"alice".parse().unwrap(),
+            ClientAction::RemoteSignBytes,
+            Some(&cache.session_id),
+            Uuid::new_v4(),
+            MessageEncoding::Json,
+        );
+
+        // Every message the client sent in the request, in order
+        let stream: Vec<Message> = (0..3)
+            .map(|sequence_number| {
+                let context = MessageContext {
+                    metadata: &metadata,
+                    direction: MessageDirection::ClientToServer,
+                    sequence_number,
+                };
+                session_key
+                    .encrypt(
+                        &mut rng,
+                        Message {
+                            content: vec![1, 2, 3],
+                        },
+                        &context,
+                    )
+                    .unwrap()
+                    .try_into_message(metadata.message_encoding())
+                    .unwrap()
+            })
+            .collect();
+
+        let (channel, _) = Channel::new(request(&metadata, stream.clone()), LIMITS).unwrap();
+        let session = find_request_session(&cache, channel.metadata())
+            .await
+            .unwrap();
+        assert_eq!(session.session_id, cache.session_id);
+
+        // Sending the whole stream again on the same session is rejected before
+        // any of its messages are read.
+        let (replayed, _) = Channel::new(request(&metadata, stream), LIMITS).unwrap();
+        assert!(matches!(
+            find_request_session(&cache, replayed.metadata()).await,
+            Err(LockKeeperServerError::SessionCache(
+                SessionCacheError::ReplayedRequest
+            ))
+        ));
+
+        // Other requests on the session are still accepted
+        let other_request = RequestMetadata::new(
+            &"alice".parse().unwrap(),
+            ClientAction::RemoteSignBytes,
+            Some(&cache.session_id),
+            Uuid::new_v4(),
+            MessageEncoding::Json,
+        );
+        let (channel, _) = Channel::new(request(&other_request, Vec::new()), LIMITS).unwrap();
+        let _ = find_request_session(&cache, channel.metadata())
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,291 @@
+//! Signed archive files for audit events that have been removed from the
+//! database.
+//!
+//! An archive is a gzip-compressed JSON Lines file. The first line is an
+//! [`AuditArchiveHeader`] and every following line is one [`AuditEvent`] in
+//! the same form that it had in the database, ordered by ID. The header is
+//! signed with the server's [`AuditSigningKey`] and the signature covers the
+//! hash of every event, so an archive can't be modified without the change
+//! being detected by [`AuditArchive::read`].
+//!
+//! Archived events keep their place in the hash chain of their account. After
+//! they are imported back into a database, the chain verifies as if they had
+//! never been removed.
+
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use lock_keeper::{
+    crypto::{AuditPublicKey, AuditSignature, AuditSigningKey, CryptoError},
+    types::audit_event::AuditEvent,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use time::OffsetDateTime;
+
+/// Version of the archive format written by this server.
+pub const AUDIT_ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum AuditArchiveError {
+    #[error("An audit archive must contain at least one event")]
+    Empty,
+    #[error("Audit archive events are not ordered by ID")]
+    Unordered,
+    #[error("Unsupported audit archive version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Audit archive events do not match its header")]
+    HeaderMismatch,
+    #[error("Audit archive signature is invalid")]
+    InvalidSignature,
+
+    // Wrapped errors
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+/// First line of an archive file, describing and signing the events in it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditArchiveHeader {
+    pub version: u32,
+    /// Only whole seconds are signed.
+    pub created_at: OffsetDateTime,
+    pub event_count: u64,
+    pub first_audit_event_id: i64,
+    pub last_audit_event_id: i64,
+    pub signature: AuditSignature,
+}
+
+/// Audit events together with the signed header that covers them.
+#[derive(Debug)]
+pub struct AuditArchive {
+    pub header: AuditArchiveHeader,
+    pub events: Vec<AuditEvent>,
+}
+
+impl AuditArchive {
+    /// Create an archive of `events`, which must be ordered by ID, signed
+    /// with the server's [`AuditSigningKey`].
+    pub fn new(
+        signing_key: &AuditSigningKey,
+        events: Vec<AuditEvent>,
+        created_at: OffsetDateTime,
+    ) -> Result<Self, AuditArchiveError> {
+        let (first, last) = match (events.first(), events.last()) {
+            (Some(first), Some(last)) => (first.audit_event_id, last.audit_event_id),
+            _ => return Err(AuditArchiveError::Empty),
+        };
+        if events
+            .windows(2)
+            .any(|pair| pair[0].audit_event_id >= pair[1].audit_event_id)
+        {
+            return Err(AuditArchiveError::Unordered);
+        }
+
+        let created_at = created_at
+            .replace_nanosecond(0)
+            .map_err(|_| CryptoError::ConversionError)?;
+        let mut header = AuditArchiveHeader {
+            version: AUDIT_ARCHIVE_VERSION,
+            created_at,
+            event_count: events.len() as u64,
+            first_audit_event_id: first,
+            last_audit_event_id: last,
+            signature: AuditSignature::new(Vec::new()),
+        };
+        header.signature = signing_key.sign(&Self::message(&header, &events))?;
+
+        Ok(Self { header, events })
+    }
+
+    /// File name for this archive, based on the IDs of the events in it.
+    pub fn file_name(&self) -> String {
+        format!(
+            "audit-events-{}-{}.jsonl.gz",
+            self.header.first_audit_event_id, self.header.last_audit_event_id
+        )
+    }
+
+    /// Write the archive to a new file in `directory` and return its path.
+    ///
+    /// The archive is written to a temporary file and synced to disk before
+    /// it is renamed, so the returned path never refers to a partial archive.
+    pub fn write_to_directory(
+        &self,
+        directory: impl AsRef<Path>,
+    ) -> Result<PathBuf, AuditArchiveError> {
+        let directory = directory.as_ref();
+        fs::create_dir_all(directory)?;
+        let path = directory.join(self.file_name());
+        let temp_path = directory.join(format!(".{}.tmp", self.file_name()));
+
+        let file = File::create(&temp_path)?;
+        let mut writer = BufWriter::new(GzEncoder::new(file, Compression::default()));
+        serde_json::to_writer(&mut writer, &self.header)?;
+        writer.write_all(b"\n")?;
+        for event in &self.events {
+            serde_json::to_writer(&mut writer, event)?;
+            writer.write_all(b"\n")?;
+        }
+        let file = writer.into_inner().map_err(|e| e.into_error())?.finish()?;
+        file.sync_all()?;
+
+        fs::rename(&temp_path, &path)?;
+        File::open(directory)?.sync_all()?;
+
+        Ok(path)
+    }
+
+    /// Read an archive file and check its signature against the server's
+    /// [`AuditPublicKey`].
+    pub fn read(
+        path: impl AsRef<Path>,
+        public_key: &AuditPublicKey,
+    ) -> Result<Self, AuditArchiveError> {
+        let file = File::open(path)?;
+        let mut lines = BufReader::new(GzDecoder::new(file)).lines();
+
+        let header: AuditArchiveHeader = match lines.next() {
+            Some(line) => serde_json::from_str(&line?)?,
+            None => return Err(AuditArchiveError::Empty),
+        };
+        if header.version != AUDIT_ARCHIVE_VERSION {
+            return Err(AuditArchiveError::UnsupportedVersion(header.version));
+        }
+
+        let mut events: Vec<AuditEvent> = Vec::new();
+        for line in lines {
+            events.push(serde_json::from_str(&line?)?);
+        }
+
+        let ids_match = events.len() as u64 == header.event_count
+            && events.first().map(|event| event.audit_event_id)
+                == Some(header.first_audit_event_id)
+            && events.last().map(|event| event.audit_event_id) == Some(header.last_audit_event_id);
+        if !ids_match {
+            return Err(AuditArchiveError::HeaderMismatch);
+        }
+        public_key
+            .verify(&Self::message(&header, &events), &header.signature)
+            .map_err(|_| AuditArchiveError::InvalidSignature)?;
+
+        Ok(Self { header, events })
+    }
+
+    fn message(header: &AuditArchiveHeader, events: &[AuditEvent]) -> Vec<u8> {
+        let mut message = [
+            b"Lock Keeper audit archive".as_slice(),
+            &header.version.to_be_bytes(),
+            &header.created_at.unix_timestamp().to_be_bytes(),
+            &header.event_count.to_be_bytes(),
+            &header.first_audit_event_id.to_be_bytes(),
+            &header.last_audit_event_id.to_be_bytes(),
+        ]
+        .concat();
+        for event in events {
+            message.extend_from_slice(event.hash().as_bytes());
+        }
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lock_keeper::types::{
+        audit_event::{AuditEventDetails, AuditEventHash, EventStatus},
+        database::account::AccountId,
+        operations::ClientAction,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use uuid::Uuid;
+
+    fn events(count: i64) -> Vec<AuditEvent> {
+        let mut events: Vec<AuditEvent> = Vec::new();
+        for audit_event_id in 1..=count {
+            let event = AuditEvent {
+                audit_event_id,
+                account_id: AccountId(1),
+                request_id: Uuid::new_v4(),
+                key_id: None,
+                timestamp: OffsetDateTime::now_utc(),
+                client_action: ClientAction::Authenticate,
+                status: EventStatus::Successful,
+                details: AuditEventDetails {
+                    peer_address: Some("192.0.2.1".parse().unwrap()),
+                    ..Default::default()
+                },
+                previous_hash: events.last().map(AuditEvent::hash),
+            };
+            events.push(event);
+        }
+        events
+    }
+
+    fn hashes(events: &[AuditEvent]) -> Vec<AuditEventHash> {
+        events.iter().map(AuditEvent::hash).collect()
+    }
+
+    fn temp_directory() -> PathBuf {
+        std::env::temp_dir().join(format!("lock-keeper-audit-archive-{}", Uuid::new_v4()))
+    }
+
+    #[test]
+    fn archive_round_trips() {
+        let signing_key = AuditSigningKey::generate(&mut StdRng::from_entropy());
+        let directory = temp_directory();
+        let events = events(5);
+        let expected = hashes(&events);
+
+        let archive = AuditArchive::new(&signing_key, events, OffsetDateTime::now_utc()).unwrap();
+        let path = archive.write_to_directory(&directory).unwrap();
+        assert_eq!(path.file_name().unwrap(), "audit-events-1-5.jsonl.gz");
+
+        let read = AuditArchive::read(&path, &signing_key.public_key().unwrap()).unwrap();
+        assert_eq!(read.header, archive.header);
+        assert_eq!(hashes(&read.events), expected);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn modified_archive_is_rejected() {
+        let signing_key = AuditSigningKey::generate(&mut StdRng::from_entropy());
+        let directory = temp_directory();
+        let mut archive =
+            AuditArchive::new(&signing_key, events(3), OffsetDateTime::now_utc()).unwrap();
+        archive.events[1].status = EventStatus::Failed;
+        let path = archive.write_to_directory(&directory).unwrap();
+
+        assert!(matches!(
+            AuditArchive::read(path, &signing_key.public_key().unwrap()),
+            Err(AuditArchiveError::InvalidSignature)
+        ));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn archive_requires_ordered_events() {
+        let signing_key = AuditSigningKey::generate(&mut StdRng::from_entropy());
+        let mut events = events(3);
+        events.reverse();
+
+        assert!(matches!(
+            AuditArchive::new(&signing_key, events, OffsetDateTime::now_utc()),
+            Err(AuditArchiveError::Unordered)
+        ));
+        assert!(matches!(
+            AuditArchive::new(&signing_key, Vec::new(), OffsetDateTime::now_utc()),
+            Err(AuditArchiveError::Empty)
+        ));
+    }
+}
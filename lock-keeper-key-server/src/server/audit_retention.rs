@@ -0,0 +1,283 @@
+//! Background task that moves expired audit events out of the database.
+//!
+//! Expired events are written to an [`AuditArchive`] before they are deleted.
+//! For every account in an archive, a checkpoint is signed for the last
+//! archived event. It anchors the events that stay in the database, so their
+//! chain still verifies after the earlier events are gone.
+//!
+//! The archive is written before anything is deleted. If the server stops in
+//! between, the same events are archived again on the next run, so events can
+//! end up in more than one archive but are never lost.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use lock_keeper::{
+    crypto::AuditSigningKey,
+    types::{
+        audit_chain::AuditCheckpoint,
+        audit_event::{AuditEvent, AuditEventOptions, EventType},
+        database::account::AccountId,
+    },
+    LockKeeperError,
+};
+use time::OffsetDateTime;
+use tracing::{debug, error, info, instrument};
+
+use crate::{
+    config::{AuditRetentionConfig, SharedConfig},
+    server::{audit_archive::AuditArchive, database::DataStore},
+    LockKeeperServerError,
+};
+
+/// Maximum number of events in a single archive file.
+const ARCHIVE_BATCH_SIZE: u32 = 1000;
+
+/// How often to check whether audit retention has been turned on while it is
+/// off.
+const RETENTION_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Archives expired audit events every `check_interval` until the server shuts
+/// down.
+///
+/// The retention settings are read again before every run, so changes made
+/// by reloading the config take effect on the next run. While audit retention
+/// is not configured, no events are archived and the config is checked again
+/// every [`RETENTION_POLL_INTERVAL`].
+pub(crate) async fn run_audit_retention<DB: DataStore>(
+    config: SharedConfig,
+    db: Arc<DB>,
+) -> Result<(), LockKeeperServerError> {
+    loop {
+        let (retention, audit_key) = wait_for_retention(&config).await;
+
+        let now = OffsetDateTime::now_utc();
+        match archive_expired_events(db.as_ref(), &retention, &audit_key, now).await {
+            Ok(0) => debug!("No expired audit events to archive."),
+            Ok(count) => info!("Archived {} expired audit events.", count),
+            Err(e) => error!("Failed to archive expired audit events: {}", e),
+        }
+
+        tokio::time::sleep(retention.check_interval).await;
+    }
+}
+
+/// Waits until audit retention is configured and returns its settings.
+async fn wait_for_retention(config: &SharedConfig) -> (AuditRetentionConfig, AuditSigningKey) {
+    loop {
+        let current = config.current();
+        if let (Some(retention), Some(audit_key)) = (&current.audit_retention, &current.audit_key) {
+            return (retention.clone(), audit_key.clone());
+        }
+
+        debug!("Audit retention is not configured.");
+        tokio::time::sleep(RETENTION_POLL_INTERVAL).await;
+    }
+}
+
+/// Archive and delete the audit events that are older than the retention
+/// period at `now`. Returns the number of events archived.
+#[instrument(skip_all, err(Debug))]
+async fn archive_expired_events<DB: DataStore>(
+    db: &DB,
+    retention: &AuditRetentionConfig,
+    audit_key: &AuditSigningKey,
+    now: OffsetDateTime,
+) -> Result<u64, LockKeeperServerError> {
+    let cutoff = match time::Duration::try_from(retention.retention_period)
+        .ok()
+        .and_then(|retention_period| now.checked_sub(retention_period))
+    {
+        Some(cutoff) => cutoff,
+        None => return Ok(0),
+    };
+
+    // Only events with a lower ID than the first unexpired event are archived.
+    // Timestamps are not guaranteed to increase with IDs, and archiving a
+    // later event while keeping an earlier one would leave a gap in the chain.
+    let first_unexpired = AuditEventOptions {
+        after_date: Some(cutoff),
+        limit: Some(1),
+        ..Default::default()
+    };
+    let id_limit = db
+        .find_all_audit_events(EventType::All, first_unexpired)
+        .await?
+        .first()
+        .map(|event| event.audit_event_id);
+
+    let mut latest_event_ids = HashMap::new();
+    let mut options = AuditEventOptions {
+        before_date: Some(cutoff),
+        limit: Some(ARCHIVE_BATCH_SIZE),
+        ..Default::default()
+    };
+    let mut archived = 0;
+    loop {
+        let events = db
+            .find_all_audit_events(EventType::All, options.clone())
+            .await?;
+        match events.last() {
+            Some(last_event) => options.after_audit_event_id = Some(last_event.audit_event_id),
+            None => break,
+        }
+
+        let mut expired = Vec::new();
+        let mut reached_limit = false;
+        for event in events {
+            if id_limit.map_or(false, |id_limit| event.audit_event_id >= id_limit) {
+                reached_limit = true;
+                break;
+            }
+            // The latest event of an account is kept so that the next event
+            // can link to it.
+            if !is_latest_event(db, &mut latest_event_ids, &event).await? {
+                expired.push(event);
+            }
+        }
+
+        if !expired.is_empty() {
+            archived += archive_events(db, retention, audit_key, expired, now).await?;
+        }
+        if reached_limit {
+            break;
+        }
+    }
+
+    Ok(archived)
+}
+
+async fn is_latest_event<DB: DataStore>(
+    db: &DB,
+    latest_event_ids: &mut HashMap<AccountId, Option<i64>>,
+    event: &AuditEvent,
+) -> Result<bool, LockKeeperServerError> {
+    let latest_event_id = match latest_event_ids.get(&event.account_id) {
+        Some(latest_event_id) => *latest_event_id,
+        None => {
+            let latest_event_id = db
+                .find_latest_audit_event(event.account_id)
+                .await?
+                .map(|latest| latest.audit_event_id);
+            let _ = latest_event_ids.insert(event.account_id, latest_event_id);
+            latest_event_id
+        }
+    };
+
+    Ok(latest_event_id == Some(event.audit_event_id))
+}
+
+/// Write `events` to an archive file, anchor the remaining events of each
+/// account with a checkpoint, and delete the archived events.
+async fn archive_events<DB: DataStore>(
+    db: &DB,
+    retention: &AuditRetentionConfig,
+    audit_key: &AuditSigningKey,
+    events: Vec<AuditEvent>,
+    now: OffsetDateTime,
+) -> Result<u64, LockKeeperServerError> {
+    let archive = AuditArchive::new(audit_key, events, now)?;
+    let archive_directory = retention.archive_directory.clone();
+    let (archive, path) = tokio::task::spawn_blocking(move || {
+        let path = archive.write_to_directory(archive_directory)?;
+        Ok::<_, LockKeeperServerError>((archive, path))
+    })
+    .await
+    .map_err(std::io::Error::from)??;
+    info!(
+        "Wrote {} audit events to {}.",
+        archive.events.len(),
+        path.display()
+    );
+
+    let mut last_events: HashMap<AccountId, &AuditEvent> = HashMap::new();
+    for event in &archive.events {
+        let _ = last_events.insert(event.account_id, event);
+    }
+    for event in last_events.into_values() {
+        let checkpoint =
+            AuditCheckpoint::new(audit_key, event, now).map_err(LockKeeperError::from)?;
+        db.create_audit_checkpoint(&checkpoint).await?;
+    }
+
+    let audit_event_ids: Vec<i64> = archive
+        .events
+        .iter()
+        .map(|event| event.audit_event_id)
+        .collect();
+    Ok(db.delete_audit_events(&audit_event_ids).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, LoggingConfig};
+    use futures::poll;
+    use lock_keeper::crypto::RemoteStorageKey;
+    use opaque_ke::ServerSetup;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::path::PathBuf;
+    use tracing::Level;
+
+    fn retention() -> AuditRetentionConfig {
+        AuditRetentionConfig {
+            retention_period: Duration::from_secs(400 * 24 * 60 * 60),
+            archive_directory: PathBuf::from("audit-archive"),
+            check_interval: Duration::from_secs(60 * 60),
+        }
+    }
+
+    fn config(audit_retention: Option<AuditRetentionConfig>) -> Config {
+        let mut rng = StdRng::seed_from_u64(1234);
+        Config {
+            address: [127, 0, 0, 1].into(),
+            port: 1113,
+            readiness_address: None,
+            tls_config: None,
+            opaque_server_setup: ServerSetup::new(&mut rng),
+            remote_storage_key: RemoteStorageKey::generate(&mut rng),
+            logging: LoggingConfig {
+                stdout_log_level: Level::INFO,
+                stdout_log_format: Default::default(),
+                log_files: None,
+                otlp: None,
+            },
+            release_toml_path: PathBuf::from("boltlabs-release.toml"),
+            max_blob_size: 1024,
+            shutdown_timeout: Duration::from_secs(10),
+            receive_timeout: Duration::from_secs(30),
+            max_message_size: 1024,
+            max_concurrent_requests: 16,
+            max_concurrent_requests_per_account: 4,
+            audit_key: Some(AuditSigningKey::generate(&mut rng)),
+            audit_checkpoint_interval: Duration::from_secs(60),
+            audit_sinks: Vec::new(),
+            audit_retention,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retention_can_be_turned_on_and_off_by_reloading() {
+        let shared_config = SharedConfig::new(config(None));
+
+        let wait = wait_for_retention(&shared_config);
+        tokio::pin!(wait);
+        assert!(poll!(&mut wait).is_pending());
+        tokio::time::advance(RETENTION_POLL_INTERVAL).await;
+        assert!(poll!(&mut wait).is_pending());
+
+        // Turned on by reloading the config
+        shared_config.replace(config(Some(retention())));
+        tokio::time::advance(RETENTION_POLL_INTERVAL).await;
+        let (found_retention, _) = wait.await;
+        assert_eq!(found_retention, retention());
+
+        // Turned off and on again
+        shared_config.replace(config(None));
+        let wait = wait_for_retention(&shared_config);
+        tokio::pin!(wait);
+        assert!(poll!(&mut wait).is_pending());
+        shared_config.replace(config(Some(retention())));
+        tokio::time::advance(RETENTION_POLL_INTERVAL).await;
+        assert!(poll!(&mut wait).is_ready());
+    }
+}
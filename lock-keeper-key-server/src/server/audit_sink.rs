@@ -0,0 +1,436 @@
+//! Delivery of audit events to systems outside the key server's database.
+//!
+//! Every audit event stored by the key server is also published to the sinks
+//! listed in [`ConfigFile::audit_sinks`](crate::config::ConfigFile::audit_sinks).
+//! Each sink has its own bounded queue and background task, so a slow or
+//! unreachable sink never delays the operation that created the event. Failed
+//! deliveries are retried with exponential backoff. An event is dropped, with
+//! a log message, if the sink's queue is full or the sink still fails after
+//! the configured number of retries. The database remains the authoritative
+//! record of every event.
+//!
+//! Events are sent in the [`AuditEventRecord`] schema that is also used for
+//! exports.
+
+mod file;
+mod syslog;
+mod webhook;
+
+pub use file::FileSink;
+pub use syslog::SyslogSink;
+pub use webhook::WebhookSink;
+
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use hyper::StatusCode;
+use lock_keeper::types::{audit_event::AuditEvent, audit_export::AuditEventRecord};
+use thiserror::Error;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{error, warn};
+
+use crate::config::{AuditSinkConfig, AuditSinkDestination};
+
+/// Longest delay between two attempts to deliver the same event.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Error)]
+pub enum AuditSinkError {
+    #[error("Invalid audit sink configuration: {0}")]
+    InvalidConfig(String),
+    #[error("Webhook responded with status {0}")]
+    WebhookStatus(StatusCode),
+
+    // Wrapped errors
+    #[error(transparent)]
+    Http(#[from] hyper::http::Error),
+    #[error(transparent)]
+    Hyper(#[from] hyper::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+    #[error(transparent)]
+    TimeFormat(#[from] time::error::Format),
+}
+
+/// A destination for audit events.
+///
+/// Each sink is driven by a single background task, so `send` is never called
+/// concurrently and events are sent in the order they were created.
+#[async_trait]
+pub trait AuditSink: Send + 'static {
+    /// Description of the destination used in log messages.
+    fn name(&self) -> String;
+
+    /// Deliver a single event. If this fails, it is called again with the
+    /// same event until the retries run out.
+    async fn send(&mut self, record: &AuditEventRecord) -> Result<(), AuditSinkError>;
+}
+
+/// Fans out audit events to every configured [`AuditSink`].
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AuditSinks {
+    queues: Arc<Vec<SinkQueue>>,
+}
+
+#[derive(Debug)]
+struct SinkQueue {
+    name: String,
+    sender: mpsc::Sender<AuditEventRecord>,
+}
+
+impl AuditSinks {
+    /// Create the sinks in `configs` and start their delivery tasks. Must be
+    /// called from within a Tokio runtime.
+    pub(crate) fn from_config(configs: &[AuditSinkConfig]) -> Result<Self, AuditSinkError> {
+        let queues = configs
+            .iter()
+            .map(|config| {
+                if config.buffer_size == 0 {
+                    return Err(AuditSinkError::InvalidConfig(
+                        "buffer_size must be at least 1".to_string(),
+                    ));
+                }
+
+                let sink: Box<dyn AuditSink> = match &config.destination {
+                    AuditSinkDestination::File { path } => Box::new(FileSink::open(path)?),
+                    AuditSinkDestination::Syslog { socket, facility } => {
+                        Box::new(SyslogSink::new(socket, *facility)?)
+                    }
+                    AuditSinkDestination::Webhook { url } => Box::new(WebhookSink::new(url)?),
+                };
+                Ok(spawn_delivery(sink, config))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            queues: Arc::new(queues),
+        })
+    }
+
+    /// Returns `true` if no sinks are configured.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.queues.is_empty()
+    }
+
+    /// Queue `event` for delivery to every sink. This never waits for a sink.
+    pub(crate) fn publish(&self, event: &AuditEvent) {
+        if self.is_empty() {
+            return;
+        }
+
+        let record = match AuditEventRecord::try_from(event) {
+            Ok(record) => record,
+            Err(e) => {
+                error!(
+                    audit_event_id = event.audit_event_id,
+                    "Could not convert audit event for audit sinks: {}", e
+                );
+                return;
+            }
+        };
+
+        for queue in self.queues.iter() {
+            match queue.sender.try_send(record.clone()) {
+                Ok(()) => {}
+                Err(TrySendError::Full(record)) => warn!(
+                    sink = %queue.name,
+                    audit_event_id = record.audit_event_id,
+                    "Audit sink queue is full. Dropping event."
+                ),
+                Err(TrySendError::Closed(record)) => warn!(
+                    sink = %queue.name,
+                    audit_event_id = record.audit_event_id,
+                    "Audit sink has stopped. Dropping event."
+                ),
+            }
+        }
+    }
+}
+
+/// Start the task that delivers queued events to `sink`. The task stops once
+/// the returned queue is dropped.
+fn spawn_delivery(sink: Box<dyn AuditSink>, config: &AuditSinkConfig) -> SinkQueue {
+    let (sender, receiver) = mpsc::channel(config.buffer_size);
+    let name = sink.name();
+
+    let handle = tokio::spawn(deliver(
+        sink,
+        receiver,
+        config.max_retries,
+        config.retry_delay,
+    ));
+    // The task runs until the queue is closed, so we don't wait for it.
+    std::mem::drop(handle);
+
+    SinkQueue { name, sender }
+}
+
+async fn deliver(
+    mut sink: Box<dyn AuditSink>,
+    mut receiver: mpsc::Receiver<AuditEventRecord>,
+    max_retries: u32,
+    retry_delay: Duration,
+) {
+    while let Some(record) = receiver.recv().await {
+        let mut retries = 0;
+        let mut delay = retry_delay;
+
+        while let Err(e) = sink.send(&record).await {
+            if retries == max_retries {
+                error!(
+                    sink = %sink.name(),
+                    audit_event_id = record.audit_event_id,
+                    "Failed to deliver audit event after {} retries. Dropping event. Error: {}",
+                    retries,
+                    e
+                );
+                break;
+            }
+
+            warn!(
+                sink = %sink.name(),
+                audit_event_id = record.audit_event_id,
+                "Failed to deliver audit event. Retrying in {:?}. Error: {}",
+                delay,
+                e
+            );
+            tokio::time::sleep(delay).await;
+            retries += 1;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Response, Server,
+    };
+    use lock_keeper::types::{
+        audit_event::{AuditEventDetails, EventStatus, FailureCode},
+        database::account::AccountId,
+        operations::ClientAction,
+    };
+    use std::{
+        convert::Infallible,
+        net::SocketAddr,
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use time::OffsetDateTime;
+    use tokio::{net::UnixDatagram, sync::mpsc::UnboundedSender};
+    use uuid::Uuid;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn event(audit_event_id: i64, status: EventStatus) -> AuditEvent {
+        AuditEvent {
+            audit_event_id,
+            account_id: AccountId(1),
+            request_id: Uuid::new_v4(),
+            key_id: None,
+            timestamp: OffsetDateTime::now_utc(),
+            client_action: ClientAction::RemoteSignBytes,
+            status,
+            details: AuditEventDetails {
+                failure_code: (status == EventStatus::Failed).then_some(FailureCode::KeyNotFound),
+                ..Default::default()
+            },
+            previous_hash: None,
+        }
+    }
+
+    fn sink_config(destination: AuditSinkDestination) -> AuditSinkConfig {
+        AuditSinkConfig {
+            destination,
+            buffer_size: 16,
+            max_retries: 3,
+            retry_delay: Duration::from_millis(10),
+        }
+    }
+
+    fn temp_path(extension: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("lock-keeper-audit-{}.{extension}", Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn file_sink_appends_json_lines() {
+        let path = temp_path("jsonl");
+        std::fs::write(&path, "existing line\n").unwrap();
+
+        let sinks = AuditSinks::from_config(&[sink_config(AuditSinkDestination::File {
+            path: path.clone(),
+        })])
+        .unwrap();
+        sinks.publish(&event(1, EventStatus::Started));
+        sinks.publish(&event(2, EventStatus::Successful));
+
+        let lines = tokio::time::timeout(TIMEOUT, async {
+            loop {
+                let contents = tokio::fs::read_to_string(&path).await.unwrap();
+                if contents.lines().count() == 3 {
+                    break contents;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<_> = lines.lines().collect();
+        assert_eq!(lines[0], "existing line");
+        for (line, expected_id) in lines[1..].iter().zip([1, 2]) {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(record["audit_event_id"], expected_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn syslog_sink_sends_rfc5424_messages() {
+        let path = temp_path("sock");
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        let sinks = AuditSinks::from_config(&[sink_config(AuditSinkDestination::Syslog {
+            socket: path.clone(),
+            facility: 13,
+        })])
+        .unwrap();
+        sinks.publish(&event(1, EventStatus::Successful));
+        sinks.publish(&event(2, EventStatus::Failed));
+
+        let mut buf = vec![0; 4096];
+        let mut messages = Vec::new();
+        for _ in 0..2 {
+            let len = tokio::time::timeout(TIMEOUT, socket.recv(&mut buf))
+                .await
+                .unwrap()
+                .unwrap();
+            messages.push(String::from_utf8(buf[..len].to_vec()).unwrap());
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        // Facility 13 with severity informational (6) and warning (4)
+        assert!(messages[0].starts_with("<110>1 "));
+        assert!(messages[1].starts_with("<108>1 "));
+
+        let json = &messages[1][messages[1].find('{').unwrap()..];
+        let record: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(record["audit_event_id"], 2);
+        assert_eq!(record["failure_code"], "key-not-found");
+    }
+
+    /// Starts an HTTP server that fails the first `failures` requests and
+    /// sends the body of every successful request to `bodies`.
+    fn webhook_stub(failures: usize, bodies: UnboundedSender<Vec<u8>>) -> SocketAddr {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let make_service = make_service_fn(move |_| {
+            let requests = requests.clone();
+            let bodies = bodies.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let requests = requests.clone();
+                    let bodies = bodies.clone();
+                    async move {
+                        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                        if requests.fetch_add(1, Ordering::SeqCst) < failures {
+                            return Response::builder()
+                                .status(StatusCode::SERVICE_UNAVAILABLE)
+                                .body(Body::empty());
+                        }
+                        bodies.send(body.to_vec()).unwrap();
+                        Ok::<_, hyper::http::Error>(Response::new(Body::empty()))
+                    }
+                }))
+            }
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let address = server.local_addr();
+        std::mem::drop(tokio::spawn(server));
+        address
+    }
+
+    #[tokio::test]
+    async fn webhook_sink_retries_failed_requests() {
+        let (sender, mut bodies) = mpsc::unbounded_channel();
+        let address = webhook_stub(2, sender);
+
+        let sinks = AuditSinks::from_config(&[sink_config(AuditSinkDestination::Webhook {
+            url: format!("http://{address}/audit"),
+        })])
+        .unwrap();
+        sinks.publish(&event(1, EventStatus::Started));
+        sinks.publish(&event(2, EventStatus::Successful));
+
+        for expected_id in [1, 2] {
+            let body = tokio::time::timeout(TIMEOUT, bodies.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            let record: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(record["audit_event_id"], expected_id);
+        }
+    }
+
+    #[tokio::test]
+    async fn full_queue_drops_events_without_blocking() {
+        let (sender, mut bodies) = mpsc::unbounded_channel();
+        let address = webhook_stub(0, sender);
+
+        let config = AuditSinkConfig {
+            buffer_size: 1,
+            ..sink_config(AuditSinkDestination::Webhook {
+                url: format!("http://{address}/audit"),
+            })
+        };
+        let sinks = AuditSinks::from_config(&[config]).unwrap();
+        for id in 0..100 {
+            sinks.publish(&event(id, EventStatus::Started));
+        }
+
+        // At least the first event is delivered, but not all of them fit in
+        // the queue.
+        let body = tokio::time::timeout(TIMEOUT, bodies.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let record: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(record["audit_event_id"], 0);
+
+        drop(sinks);
+        let mut delivered = 1;
+        while let Ok(Some(_)) =
+            tokio::time::timeout(Duration::from_millis(200), bodies.recv()).await
+        {
+            delivered += 1;
+        }
+        assert!(delivered < 100);
+    }
+
+    #[tokio::test]
+    async fn invalid_config_is_rejected() {
+        let zero_buffer = AuditSinkConfig {
+            buffer_size: 0,
+            ..sink_config(AuditSinkDestination::File {
+                path: temp_path("jsonl"),
+            })
+        };
+        assert!(AuditSinks::from_config(&[zero_buffer]).is_err());
+
+        let bad_facility = sink_config(AuditSinkDestination::Syslog {
+            socket: temp_path("sock"),
+            facility: 24,
+        });
+        assert!(AuditSinks::from_config(&[bad_facility]).is_err());
+
+        let bad_url = sink_config(AuditSinkDestination::Webhook {
+            url: "not a url".to_string(),
+        });
+        assert!(AuditSinks::from_config(&[bad_url]).is_err());
+    }
+}
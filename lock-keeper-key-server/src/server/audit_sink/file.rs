@@ -0,0 +1,49 @@
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use lock_keeper::types::audit_export::AuditEventRecord;
+use tokio::{fs::File, io::AsyncWriteExt};
+
+use super::{AuditSink, AuditSinkError};
+
+/// Appends each event as a line of JSON to a file.
+///
+/// The file is created if it doesn't exist and is never truncated, so it can
+/// be rotated by moving it away and restarting the server.
+#[derive(Debug)]
+pub struct FileSink {
+    path: PathBuf,
+    file: File,
+}
+
+impl FileSink {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, AuditSinkError> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(Self {
+            path,
+            file: File::from_std(file),
+        })
+    }
+}
+
+#[async_trait]
+impl AuditSink for FileSink {
+    fn name(&self) -> String {
+        format!("file:{}", self.path.display())
+    }
+
+    async fn send(&mut self, record: &AuditEventRecord) -> Result<(), AuditSinkError> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        self.file.write_all(&line).await?;
+        self.file.flush().await?;
+
+        Ok(())
+    }
+}
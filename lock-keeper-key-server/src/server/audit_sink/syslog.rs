@@ -0,0 +1,78 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use lock_keeper::types::{audit_event::EventStatus, audit_export::AuditEventRecord};
+use time::{macros::format_description, OffsetDateTime};
+use tokio::net::UnixDatagram;
+
+use super::{AuditSink, AuditSinkError};
+
+/// Syslog severity for events of successful or in-progress operations.
+const SEVERITY_INFORMATIONAL: u8 = 6;
+/// Syslog severity for events of failed or aborted operations.
+const SEVERITY_WARNING: u8 = 4;
+
+/// Sends each event to a local syslog daemon over a Unix datagram socket.
+///
+/// Messages use the RFC 5424 format with the app name `lock-keeper` and the
+/// message ID `audit`. The message body is the event as a JSON object.
+#[derive(Debug)]
+pub struct SyslogSink {
+    path: PathBuf,
+    socket: UnixDatagram,
+    facility: u8,
+}
+
+impl SyslogSink {
+    /// Create a sink for the syslog socket at `path`. `facility` must be a
+    /// syslog facility code between 0 and 23.
+    pub fn new(path: impl AsRef<Path>, facility: u8) -> Result<Self, AuditSinkError> {
+        if facility > 23 {
+            return Err(AuditSinkError::InvalidConfig(format!(
+                "Invalid syslog facility {facility}"
+            )));
+        }
+
+        // The socket isn't connected so that messages keep being delivered
+        // after the syslog daemon restarts.
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            socket: UnixDatagram::unbound()?,
+            facility,
+        })
+    }
+
+    fn message(&self, record: &AuditEventRecord) -> Result<Vec<u8>, AuditSinkError> {
+        let severity = match record.status {
+            EventStatus::Started | EventStatus::Successful => SEVERITY_INFORMATIONAL,
+            EventStatus::Failed | EventStatus::Aborted => SEVERITY_WARNING,
+        };
+        let priority = self.facility * 8 + severity;
+        let timestamp = OffsetDateTime::now_utc().format(format_description!(
+            "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:6]Z"
+        ))?;
+
+        let mut message = format!(
+            "<{priority}>1 {timestamp} - lock-keeper {} audit - ",
+            std::process::id()
+        )
+        .into_bytes();
+        serde_json::to_writer(&mut message, record)?;
+
+        Ok(message)
+    }
+}
+
+#[async_trait]
+impl AuditSink for SyslogSink {
+    fn name(&self) -> String {
+        format!("syslog:{}", self.path.display())
+    }
+
+    async fn send(&mut self, record: &AuditEventRecord) -> Result<(), AuditSinkError> {
+        let message = self.message(record)?;
+        let _ = self.socket.send_to(&message, &self.path).await?;
+
+        Ok(())
+    }
+}
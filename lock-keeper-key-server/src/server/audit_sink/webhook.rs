@@ -0,0 +1,75 @@
+use async_trait::async_trait;
+use hyper::{client::HttpConnector, header, Body, Client, Method, Request, Uri};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use lock_keeper::types::audit_export::AuditEventRecord;
+use rustls::{Certificate, ClientConfig, RootCertStore};
+use tracing::debug;
+
+use super::{AuditSink, AuditSinkError};
+
+/// POSTs each event as a JSON object to an HTTP or HTTPS URL. Any response
+/// other than a 2xx status is treated as a failed delivery.
+///
+/// HTTPS URLs are verified against the platform's trusted root certificates.
+#[derive(Debug)]
+pub struct WebhookSink {
+    url: Uri,
+    client: Client<HttpsConnector<HttpConnector>>,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str) -> Result<Self, AuditSinkError> {
+        let url: Uri = url
+            .parse()
+            .map_err(|_| AuditSinkError::InvalidConfig(format!("Invalid webhook URL {url:?}")))?;
+        if url.scheme().is_none() || url.host().is_none() {
+            return Err(AuditSinkError::InvalidConfig(format!(
+                "Webhook URL {url} must be an absolute URL"
+            )));
+        }
+
+        let mut roots = RootCertStore::empty();
+        for certificate in rustls_native_certs::load_native_certs()? {
+            if let Err(e) = roots.add(&Certificate(certificate.0)) {
+                debug!("Ignoring invalid platform root certificate: {}", e);
+            }
+        }
+        let tls_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let connector = HttpsConnectorBuilder::new()
+            .with_tls_config(tls_config)
+            .https_or_http()
+            .enable_http1()
+            .build();
+
+        Ok(Self {
+            url,
+            client: Client::builder().build(connector),
+        })
+    }
+}
+
+#[async_trait]
+impl AuditSink for WebhookSink {
+    fn name(&self) -> String {
+        format!("webhook:{}", self.url)
+    }
+
+    async fn send(&mut self, record: &AuditEventRecord) -> Result<(), AuditSinkError> {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(self.url.clone())
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_vec(record)?))?;
+
+        let response = self.client.request(request).await?;
+        if !response.status().is_success() {
+            return Err(AuditSinkError::WebhookStatus(response.status()));
+        }
+
+        Ok(())
+    }
+}
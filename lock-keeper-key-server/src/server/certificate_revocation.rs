@@ -0,0 +1,474 @@
+//! Certificate revocation checking for mutual TLS clients.
+//!
+//! Certificate revocation lists (CRLs) are read from a file or a directory of
+//! files given in the server's [`TlsConfig`](crate::config::TlsConfig). Files
+//! may be PEM or DER encoded, and a PEM file may hold several CRLs. The files
+//! are read when the TLS config is built and checked in memory during
+//! handshakes. A background thread checks the modification time and size of
+//! the files every [`CRL_POLL_INTERVAL`] and loads them again when they
+//! change, so a leaked certificate can be revoked without restarting the
+//! server or reloading its config. If the changed files can't be loaded, the
+//! error is logged and the previous lists stay in use.
+//!
+//! CRLs are read from a location controlled by the server operator, like the
+//! rest of the server's configuration, so their signatures are not verified.
+
+use rustls::{
+    server::{ClientCertVerified, ClientCertVerifier},
+    Certificate, DistinguishedNames, Error as TlsError,
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock, Weak},
+    time::{Duration, SystemTime},
+};
+use tracing::{info, warn};
+use x509_parser::{
+    certificate::X509Certificate, pem::Pem, prelude::FromDer,
+    revocation_list::CertificateRevocationList,
+};
+
+use crate::LockKeeperServerError;
+
+/// How often the revocation list files are checked for changes.
+const CRL_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Wraps another [`ClientCertVerifier`] and additionally rejects client
+/// certificates that appear in the configured revocation lists.
+pub(crate) struct RevocationCheckingVerifier {
+    inner: Arc<dyn ClientCertVerifier>,
+    /// Replaced by the thread started in [`watch_revocation_lists`] when the
+    /// files change.
+    revocation_lists: Arc<RwLock<RevocationLists>>,
+}
+
+impl RevocationCheckingVerifier {
+    /// Load the revocation lists at `crl_path` and wrap `inner`. The lists
+    /// are loaded again whenever the files change, until the verifier is
+    /// dropped.
+    pub(crate) fn new(
+        inner: Arc<dyn ClientCertVerifier>,
+        crl_path: impl AsRef<Path>,
+    ) -> Result<Self, LockKeeperServerError> {
+        Self::with_poll_interval(inner, crl_path.as_ref(), CRL_POLL_INTERVAL)
+    }
+
+    fn with_poll_interval(
+        inner: Arc<dyn ClientCertVerifier>,
+        crl_path: &Path,
+        poll_interval: Duration,
+    ) -> Result<Self, LockKeeperServerError> {
+        // Look at the files before reading them, so that changes made while
+        // they are read are picked up by the next check.
+        let fingerprint = crl_fingerprint(crl_path);
+        let revocation_lists = Arc::new(RwLock::new(RevocationLists::load(crl_path)?));
+        watch_revocation_lists(
+            crl_path.to_path_buf(),
+            fingerprint,
+            Arc::downgrade(&revocation_lists),
+            poll_interval,
+        );
+
+        Ok(Self {
+            inner,
+            revocation_lists,
+        })
+    }
+
+    /// Check whether the certificate has been revoked.
+    fn is_revoked(&self, certificate: &X509Certificate) -> bool {
+        self.revocation_lists
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_revoked(certificate)
+    }
+}
+
+impl ClientCertVerifier for RevocationCheckingVerifier {
+    fn offer_client_auth(&self) -> bool {
+        self.inner.offer_client_auth()
+    }
+
+    fn client_auth_mandatory(&self) -> Option<bool> {
+        self.inner.client_auth_mandatory()
+    }
+
+    fn client_auth_root_subjects(&self) -> Option<DistinguishedNames> {
+        self.inner.client_auth_root_subjects()
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: SystemTime,
+    ) -> Result<ClientCertVerified, TlsError> {
+        let verified = self
+            .inner
+            .verify_client_cert(end_entity, intermediates, now)
+            .map_err(|e| {
+                warn!("Rejected client certificate: {}", e);
+                e
+            })?;
+
+        let (_, certificate) = X509Certificate::from_der(&end_entity.0)
+            .map_err(|_| TlsError::InvalidCertificateEncoding)?;
+
+        if self.is_revoked(&certificate) {
+            warn!(
+                subject = %certificate.subject(),
+                serial = %certificate.raw_serial_as_string(),
+                "Rejected revoked client certificate."
+            );
+            return Err(TlsError::InvalidCertificateData(
+                "Certificate has been revoked".to_string(),
+            ));
+        }
+
+        Ok(verified)
+    }
+}
+
+/// Identifies a certificate by its issuer and serial number, which together
+/// are unique.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RevokedCertificate {
+    issuer: Vec<u8>,
+    serial: Vec<u8>,
+}
+
+impl RevokedCertificate {
+    fn from_certificate(certificate: &X509Certificate) -> Self {
+        Self {
+            issuer: certificate.issuer().as_raw().to_vec(),
+            serial: certificate.raw_serial().to_vec(),
+        }
+    }
+}
+
+/// Revoked certificates loaded from disk.
+struct RevocationLists {
+    revoked: HashSet<RevokedCertificate>,
+}
+
+impl RevocationLists {
+    fn load(path: &Path) -> Result<Self, LockKeeperServerError> {
+        let files = crl_files(path).map_err(|e| LockKeeperServerError::FileIo(e, path.into()))?;
+        let revoked = read_revocation_lists(&files)?;
+        info!(
+            path = %path.display(),
+            revoked = revoked.len(),
+            "Loaded certificate revocation lists."
+        );
+
+        Ok(Self { revoked })
+    }
+
+    /// Check whether the certificate has been revoked.
+    fn is_revoked(&self, certificate: &X509Certificate) -> bool {
+        self.revoked
+            .contains(&RevokedCertificate::from_certificate(certificate))
+    }
+}
+
+/// Modification time and size of every CRL file. A change to any of them means
+/// the lists have to be loaded again.
+type CrlFingerprint = Vec<(PathBuf, Option<SystemTime>, u64)>;
+
+/// Returns the fingerprint of the CRL files at `path`, or `None` if they
+/// can't be read.
+fn crl_fingerprint(path: &Path) -> Option<CrlFingerprint> {
+    crl_files(path)
+        .and_then(|files| {
+            files
+                .into_iter()
+                .map(|file| {
+                    let metadata = std::fs::metadata(&file)?;
+                    Ok((file, metadata.modified().ok(), metadata.len()))
+                })
+                .collect()
+        })
+        .ok()
+}
+
+/// Start a thread that checks the CRL files at `path` every `poll_interval`
+/// and replaces `revocation_lists` when they change. The thread stops once
+/// `revocation_lists` has been dropped.
+fn watch_revocation_lists(
+    path: PathBuf,
+    mut fingerprint: Option<CrlFingerprint>,
+    revocation_lists: Weak<RwLock<RevocationLists>>,
+    poll_interval: Duration,
+) {
+    let _ = std::thread::spawn(move || loop {
+        std::thread::sleep(poll_interval);
+        let revocation_lists = match revocation_lists.upgrade() {
+            Some(revocation_lists) => revocation_lists,
+            None => return,
+        };
+
+        let new_fingerprint = crl_fingerprint(&path);
+        if new_fingerprint == fingerprint {
+            continue;
+        }
+        fingerprint = new_fingerprint;
+
+        match RevocationLists::load(&path) {
+            Ok(new_lists) => {
+                *revocation_lists
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner) = new_lists
+            }
+            Err(e) => warn!(
+                path = %path.display(),
+                "Failed to reload certificate revocation lists. Keeping the current lists. Error: {}",
+                e
+            ),
+        }
+    });
+}
+
+/// Returns the CRL file, or every file in the CRL directory.
+fn crl_files(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn read_revocation_lists(
+    files: &[PathBuf],
+) -> Result<HashSet<RevokedCertificate>, LockKeeperServerError> {
+    let mut revoked = HashSet::new();
+
+    for file in files {
+        let bytes =
+            std::fs::read(file).map_err(|e| LockKeeperServerError::FileIo(e, file.clone()))?;
+        let invalid_crl = || LockKeeperServerError::InvalidCertificateRevocationList(file.clone());
+
+        if bytes.starts_with(b"-----BEGIN") {
+            for pem in Pem::iter_from_buffer(&bytes) {
+                let pem = pem.map_err(|_| invalid_crl())?;
+                if pem.label != "X509 CRL" {
+                    continue;
+                }
+                add_revoked_certificates(&pem.contents, &mut revoked).ok_or_else(invalid_crl)?;
+            }
+        } else {
+            add_revoked_certificates(&bytes, &mut revoked).ok_or_else(invalid_crl)?;
+        }
+    }
+
+    Ok(revoked)
+}
+
+/// Parse a DER-encoded CRL and add its entries to `revoked`. Returns `None` if
+/// the CRL can't be parsed.
+fn add_revoked_certificates(der: &[u8], revoked: &mut HashSet<RevokedCertificate>) -> Option<()> {
+    let (_, crl) = CertificateRevocationList::from_der(der).ok()?;
+    let issuer = crl.issuer().as_raw().to_vec();
+
+    for entry in crl.iter_revoked_certificates() {
+        let _ = revoked.insert(RevokedCertificate {
+            issuer: issuer.clone(),
+            serial: entry.raw_serial().to_vec(),
+        });
+    }
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    /// Client certificate with serial number `0x1001` issued by `CN=Test CA`.
+    const CLIENT_CERTIFICATE: &str = "\
+        MIIBVTCB/aADAgECAgIQATAKBggqhkjOPQQDAjASMRAwDgYDVQQDDAdUZXN0IENB\
+        MCAXDTI2MTAxODIwMDk1MVoYDzIxMjYwOTI0MjAwOTUxWjARMQ8wDQYDVQQDDAZj\
+        bGllbnQwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAAQ4YX+kc3DnScpHUpbec+HB\
+        zgFbOtF+X0X3G9Y5lwXnNhnEBJl7LydpFtP2XuBknbYaG/Zo+AgNbTzuhfvT5u52\
+        o0IwQDAdBgNVHQ4EFgQUJApVdxz8gBB9ayIHZJzQTkQ51dAwHwYDVR0jBBgwFoAU\
+        JtX17xcoT2zHGg7jE5891gRtRwMwCgYIKoZIzj0EAwIDRwAwRAIgeYRXss7aEXKw\
+        DWbQUOR8wG00a934LsKIdJsHaGoRhD0CIAXYIQBxiPgEjHmIJE40JMGtPeOUmfxZ\
+        bVWODgIgKcDD";
+
+    /// CRL from `CN=Test CA` with no revoked certificates.
+    const EMPTY_CRL: &str = "-----BEGIN X509 CRL-----
+MIGrMFQCAQEwCgYIKoZIzj0EAwIwEjEQMA4GA1UEAwwHVGVzdCBDQRcNMjYxMDE4
+MjAwOTUxWhgPMjEyNjA5MjQyMDA5NTFaoA8wDTALBgNVHRQEBAICEAAwCgYIKoZI
+zj0EAwIDRwAwRAIgL3z4GT6Bv5Na0zXAozCZO4/O9dyNBx2ca45l1fbS1/ICIDg9
+0fmXr9K4ywxQD8KNjLe7vF52pg7MU7BFT9CEthqI
+-----END X509 CRL-----
+";
+
+    /// CRL from `CN=Test CA` revoking the client certificate.
+    const REVOKED_CRL: &str = "-----BEGIN X509 CRL-----
+MIHEMGsCAQEwCgYIKoZIzj0EAwIwEjEQMA4GA1UEAwwHVGVzdCBDQRcNMjYxMDE4
+MjAwOTUxWhgPMjEyNjA5MjQyMDA5NTFaMBUwEwICEAEXDTI2MTAxODIwMDk1MVqg
+DzANMAsGA1UdFAQEAgIQATAKBggqhkjOPQQDAgNJADBGAiEA5kao2NKaXQNQQK7F
+ZtftyEruGF5AY0IHrF1l5S27kdwCIQDUFxRVq9L9XDNAyci5F8JoGiFp3kj8jq4l
+izCi0u442w==
+-----END X509 CRL-----
+";
+
+    fn client_certificate_der() -> Vec<u8> {
+        base64::decode(CLIENT_CERTIFICATE).unwrap()
+    }
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!("lock-keeper-crl-{}", Uuid::new_v4()))
+    }
+
+    fn is_revoked(lists: &RevocationLists) -> bool {
+        let der = client_certificate_der();
+        let (_, certificate) = X509Certificate::from_der(&der).unwrap();
+        lists.is_revoked(&certificate)
+    }
+
+    #[test]
+    fn revoked_certificate_is_rejected() {
+        let path = temp_path();
+        std::fs::write(&path, REVOKED_CRL).unwrap();
+
+        let lists = RevocationLists::load(&path).unwrap();
+        assert!(is_revoked(&lists));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn unrevoked_certificate_is_accepted() {
+        let path = temp_path();
+        std::fs::write(&path, EMPTY_CRL).unwrap();
+
+        let lists = RevocationLists::load(&path).unwrap();
+        assert!(!is_revoked(&lists));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn der_encoded_crl_is_supported() {
+        let path = temp_path();
+        let pem = Pem::iter_from_buffer(REVOKED_CRL.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        std::fs::write(&path, pem.contents).unwrap();
+
+        let lists = RevocationLists::load(&path).unwrap();
+        assert!(is_revoked(&lists));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn directory_of_crls_is_supported() {
+        let path = temp_path();
+        std::fs::create_dir(&path).unwrap();
+        std::fs::write(path.join("empty.crl"), EMPTY_CRL).unwrap();
+
+        std::fs::write(path.join("revoked.crl"), REVOKED_CRL).unwrap();
+
+        let lists = RevocationLists::load(&path).unwrap();
+        assert!(is_revoked(&lists));
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn crl_is_not_read_during_checks() {
+        let path = temp_path();
+        std::fs::write(&path, REVOKED_CRL).unwrap();
+
+        let lists = RevocationLists::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // Changes only take effect when the lists are loaded again
+        assert!(is_revoked(&lists));
+    }
+
+    /// Accepts every client certificate, so that only revocation is checked.
+    struct AcceptAnyClient;
+
+    impl ClientCertVerifier for AcceptAnyClient {
+        fn client_auth_root_subjects(&self) -> Option<DistinguishedNames> {
+            Some(DistinguishedNames::new())
+        }
+
+        fn verify_client_cert(
+            &self,
+            _end_entity: &Certificate,
+            _intermediates: &[Certificate],
+            _now: SystemTime,
+        ) -> Result<ClientCertVerified, TlsError> {
+            Ok(ClientCertVerified::assertion())
+        }
+    }
+
+    /// Wait until the verifier's result for the client certificate is
+    /// `accepted`, or fail after a few seconds.
+    fn wait_for_result(verifier: &RevocationCheckingVerifier, accepted: bool) {
+        let certificate = Certificate(client_certificate_der());
+        for _ in 0..500 {
+            let result = verifier.verify_client_cert(&certificate, &[], SystemTime::now());
+            if result.is_ok() == accepted {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("Revocation lists were not reloaded");
+    }
+
+    #[test]
+    fn changed_crl_is_reloaded_without_config_reload() {
+        let path = temp_path();
+        std::fs::write(&path, EMPTY_CRL).unwrap();
+
+        let verifier = RevocationCheckingVerifier::with_poll_interval(
+            Arc::new(AcceptAnyClient),
+            &path,
+            Duration::from_millis(10),
+        )
+        .unwrap();
+        wait_for_result(&verifier, true);
+
+        std::fs::write(&path, REVOKED_CRL).unwrap();
+        wait_for_result(&verifier, false);
+
+        // An invalid file keeps the lists that were loaded last
+        std::fs::write(&path, "not a crl").unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        wait_for_result(&verifier, false);
+
+        std::fs::write(&path, EMPTY_CRL).unwrap();
+        wait_for_result(&verifier, true);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn invalid_crl_is_rejected_on_load() {
+        let path = temp_path();
+        std::fs::write(&path, "not a crl").unwrap();
+
+        assert!(matches!(
+            RevocationLists::load(&path),
+            Err(LockKeeperServerError::InvalidCertificateRevocationList(_))
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
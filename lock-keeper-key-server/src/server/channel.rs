@@ -1,15 +1,14 @@
+use opentelemetry::Context;
 use rand::{CryptoRng, RngCore};
-use std::{fmt::Debug, sync::Arc};
-use tokio::sync::{
-    mpsc::{self, Receiver, Sender},
-    Mutex,
-};
-use tokio_stream::StreamExt;
-use tonic::{Request, Status, Streaming};
+use std::{net::IpAddr, pin::Pin, time::Duration};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Code, Request, Status};
 
 use lock_keeper::{
     constants::METADATA,
-    crypto::{Encrypted, OpaqueSessionKey, StorageKey},
+    crypto::{Encrypted, MessageContext, MessageDirection, OpaqueSessionKey, StorageKey},
+    infrastructure::trace_context,
     rpc::Message,
     types::{
         database::account::{Account, AccountId, UserId},
@@ -18,10 +17,28 @@ use lock_keeper::{
     LockKeeperError,
 };
 
+use super::{
+    client_certificate::ClientCertificate, concurrency::RequestPermit, service::PeerAddress,
+};
+
 /// Number of buffer in our MPSC Channel. Determines how many messages
 /// may be queued in channel.
 const BUFFER_SIZE: usize = 2;
 
+/// Stream of messages sent by the client.
+type ClientStream = Pin<Box<dyn Stream<Item = Result<Message, Status>> + Send>>;
+
+/// Limits on the messages a client sends over a [`Channel`].
+#[derive(Clone, Copy, Debug)]
+pub struct ChannelLimits {
+    /// How long to wait for each message from the client.
+    pub receive_timeout: Duration,
+    /// Maximum size in bytes of a message from the client, checked before the
+    /// message is decoded. The connection also stops reading messages that
+    /// are larger before they are buffered.
+    pub max_message_size: usize,
+}
+
 /// Server-side implementation of a two-way channel between a client and the
 /// server used to communicate `Message` objects.
 ///
@@ -34,7 +51,10 @@ const BUFFER_SIZE: usize = 2;
 ///
 /// The `AUTH` generic parameter allows our channel to be generic over
 /// either _authenticated_ or _unauthenticated_ operations.
-#[derive(Debug)]
+///
+/// Every receive fails if the client doesn't send the next message within the
+/// receive timeout, or if the message is larger than the maximum message size.
+/// See [`ChannelLimits`].
 pub struct Channel<AUTH> {
     /// `Sender` end of an unidirectional channel. Allows us to send messages
     /// to the client. The server spawns the `sender` and `receiver` ends as a pair.
@@ -45,8 +65,18 @@ pub struct Channel<AUTH> {
     /// messages from the client. When the client made a gRPC call, it sent
     /// this receiving end. The client can send messages to it and we will
     /// receive them.
-    receiver: Streaming<Message>,
+    receiver: ClientStream,
+    limits: ChannelLimits,
+    /// Places of this request under the server's concurrency limits. They are
+    /// released when the channel is dropped at the end of the request.
+    permits: Vec<RequestPermit>,
     metadata: RequestMetadata,
+    /// Certificate presented by the client if mutual TLS is enabled.
+    client_certificate: Option<ClientCertificate>,
+    /// IP address of the client connection.
+    peer_address: Option<IpAddr>,
+    /// Trace context sent by the client. Empty if the client didn't send one.
+    trace_context: Context,
     auth: AUTH,
 }
 
@@ -56,14 +86,56 @@ impl<AUTH> Channel<AUTH> {
         &self.metadata
     }
 
+    /// Returns the certificate the client presented during the TLS handshake,
+    /// if any.
+    pub fn client_certificate(&self) -> Option<&ClientCertificate> {
+        self.client_certificate.as_ref()
+    }
+
+    /// Returns the IP address of the client connection, if known.
+    pub fn peer_address(&self) -> Option<IpAddr> {
+        self.peer_address
+    }
+
+    /// Keep `permit` until the channel is dropped.
+    pub(crate) fn hold_permit(&mut self, permit: RequestPermit) {
+        self.permits.push(permit);
+    }
+
+    /// Returns the trace context of the client span that made this request.
+    pub fn trace_context(&self) -> &Context {
+        &self.trace_context
+    }
+
     /// Send an error message across the channel.
     pub async fn send_error(&mut self, status: impl Into<Status>) -> Result<(), LockKeeperError> {
         let payload = Err(status.into());
         Ok(self.sender.send(payload).await?)
     }
 
+    /// Wait for the client to close the channel, but no longer than the
+    /// receive timeout.
     pub async fn closed(&mut self) {
-        self.sender.closed().await;
+        let _ = tokio::time::timeout(self.limits.receive_timeout, self.sender.closed()).await;
+    }
+
+    /// Wait for the next message from the client and check its size.
+    async fn receive_message(&mut self) -> Result<Message, LockKeeperError> {
+        let max = self.limits.max_message_size;
+        let message = tokio::time::timeout(self.limits.receive_timeout, self.receiver.next())
+            .await
+            .map_err(|_| LockKeeperError::ReceiveTimeout)?
+            .ok_or(LockKeeperError::NoMessageReceived)?
+            .map_err(|status| match status.code() {
+                // The connection stopped reading a message over the limit.
+                Code::OutOfRange => LockKeeperError::MessageTooLarge { max },
+                _ => LockKeeperError::TonicStatus(status),
+            })?;
+
+        if message.content.len() > max {
+            return Err(LockKeeperError::MessageTooLarge { max });
+        }
+        Ok(message)
     }
 }
 
@@ -74,7 +146,14 @@ impl<AUTH> Channel<AUTH> {
 pub struct Authenticated<RNG: CryptoRng + RngCore> {
     pub session_key: OpaqueSessionKey,
     pub account: Account,
-    pub rng: Arc<Mutex<RNG>>,
+    /// Random number generator of this request's task.
+    pub rng: RNG,
+    /// Client certificate identity that is bound to `account`, if any.
+    pub client_identity: Option<String>,
+    /// Number of messages sent to the client so far.
+    sent_messages: u64,
+    /// Number of messages received from the client so far.
+    received_messages: u64,
 }
 
 impl<RNG: CryptoRng + RngCore> Channel<Authenticated<RNG>> {
@@ -92,43 +171,55 @@ impl<RNG: CryptoRng + RngCore> Channel<Authenticated<RNG>> {
         &self.auth.account
     }
 
+    /// Returns the client certificate identity that matched a binding for
+    /// this account.
+    pub fn client_identity(&self) -> Option<&str> {
+        self.auth.client_identity.as_deref()
+    }
+
     pub fn set_storage_key(&mut self, storage_key: Encrypted<StorageKey>) {
         self.auth.account.storage_key = Some(storage_key);
     }
 
     /// Receive the next message on the channel and convert it to the type `R`.
     pub async fn receive<R: ConvertMessage>(&mut self) -> Result<R, LockKeeperError> {
-        match self.receiver.next().await {
-            Some(message) => {
-                let message = message?;
-                let encrypted_message: Encrypted<Message> =
-                    Encrypted::<Message>::try_from_message(message)?;
-                let message = encrypted_message.decrypt_message(&self.auth.session_key)?;
-                let result =
-                    R::from_message(message).map_err(|_| LockKeeperError::InvalidMessage)?;
-                Ok(result)
-            }
-            None => Err(LockKeeperError::NoMessageReceived),
-        }
+        let message = self.receive_message().await?;
+        let encrypted_message: Encrypted<Message> =
+            Encrypted::<Message>::try_from_message(message, self.metadata.message_encoding())?;
+        let context = MessageContext {
+            metadata: &self.metadata,
+            direction: MessageDirection::ClientToServer,
+            sequence_number: self.auth.received_messages,
+        };
+        let message = encrypted_message.decrypt_message(&self.auth.session_key, &context)?;
+        self.auth.received_messages += 1;
+        let result = R::from_message(message, self.metadata.message_encoding())
+            .map_err(|_| LockKeeperError::InvalidMessage)?;
+        Ok(result)
     }
 
     /// Send a message across the channel. This function accepts any type that
     /// can be converted to a `message` via the [`ConvertMessage`] trait.
     pub async fn send(&mut self, message: impl ConvertMessage) -> Result<(), LockKeeperError> {
-        let message = message.to_message()?;
-
-        let encrypted_message = {
-            let mut rng = self.auth.rng.lock().await;
-            self.auth
-                .session_key
-                .encrypt(&mut *rng, message)
-                .map_err(LockKeeperError::Crypto)?
-        }
-        .try_into_message()?;
+        let message = message.to_message(self.metadata.message_encoding())?;
+
+        let context = MessageContext {
+            metadata: &self.metadata,
+            direction: MessageDirection::ServerToClient,
+            sequence_number: self.auth.sent_messages,
+        };
+        let encrypted_message = self
+            .auth
+            .session_key
+            .encrypt(&mut self.auth.rng, message, &context)
+            .map_err(LockKeeperError::Crypto)?
+            .try_into_message(self.metadata.message_encoding())?;
 
         let payload = Ok(encrypted_message);
 
-        Ok(self.sender.send(payload).await?)
+        self.sender.send(payload).await?;
+        self.auth.sent_messages += 1;
+        Ok(())
     }
 }
 
@@ -138,9 +229,13 @@ impl<RNG: CryptoRng + RngCore> Channel<Authenticated<RNG>> {
 pub struct Unauthenticated;
 
 impl Channel<Unauthenticated> {
-    pub fn new(
-        request: Request<Streaming<Message>>,
-    ) -> Result<(Self, Receiver<Result<Message, Status>>), LockKeeperError> {
+    pub fn new<S>(
+        request: Request<S>,
+        limits: ChannelLimits,
+    ) -> Result<(Self, Receiver<Result<Message, Status>>), LockKeeperError>
+    where
+        S: Stream<Item = Result<Message, Status>> + Send + 'static,
+    {
         let (sender, remote_receiver) = mpsc::channel(BUFFER_SIZE);
 
         let metadata = request
@@ -148,12 +243,23 @@ impl Channel<Unauthenticated> {
             .get(METADATA)
             .ok_or(LockKeeperError::MetadataNotFound)?
             .try_into()?;
+        let client_certificate = request.extensions().get::<ClientCertificate>().cloned();
+        let peer_address = request
+            .extensions()
+            .get::<PeerAddress>()
+            .map(|peer_address| peer_address.0);
+        let trace_context = trace_context::extract(request.metadata());
 
         Ok((
             Self {
                 sender,
-                receiver: request.into_inner(),
+                receiver: Box::pin(request.into_inner()),
+                limits,
+                permits: Vec::new(),
                 metadata,
+                client_certificate,
+                peer_address,
+                trace_context,
                 auth: Unauthenticated,
             },
             remote_receiver,
@@ -164,37 +270,185 @@ impl Channel<Unauthenticated> {
         self,
         account: Account,
         session_key: OpaqueSessionKey,
-        rng: Arc<Mutex<RNG>>,
+        rng: RNG,
+        client_identity: Option<String>,
     ) -> Channel<Authenticated<RNG>> {
         Channel {
             sender: self.sender,
             receiver: self.receiver,
+            limits: self.limits,
+            permits: self.permits,
             metadata: self.metadata,
+            client_certificate: self.client_certificate,
+            peer_address: self.peer_address,
+            trace_context: self.trace_context,
             auth: Authenticated {
                 account,
                 session_key,
                 rng,
+                client_identity,
+                sent_messages: 0,
+                received_messages: 0,
             },
         }
     }
 
     /// Receive the next message on the channel and convert it to the type `R`.
     pub async fn receive<R: ConvertMessage>(&mut self) -> Result<R, LockKeeperError> {
-        match self.receiver.next().await {
-            Some(message) => {
-                let message = message?;
-                let result =
-                    R::from_message(message).map_err(|_| LockKeeperError::InvalidMessage)?;
-                Ok(result)
-            }
-            None => Err(LockKeeperError::NoMessageReceived),
-        }
+        let message = self.receive_message().await?;
+        let result = R::from_message(message, self.metadata.message_encoding())
+            .map_err(|_| LockKeeperError::InvalidMessage)?;
+        Ok(result)
     }
 
     /// Send a message across the channel. This function accepts any type that
     /// can be converted to a `Message`.
     pub async fn send(&mut self, message: impl ConvertMessage) -> Result<(), LockKeeperError> {
-        let payload = Ok(message.to_message()?);
+        let payload = Ok(message.to_message(self.metadata.message_encoding())?);
         Ok(self.sender.send(payload).await?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LockKeeperServerError;
+    use futures::poll;
+    use lock_keeper::types::{
+        audit_event::FailureCode,
+        operations::{ClientAction, MessageEncoding},
+    };
+    use tokio_stream::wrappers::ReceiverStream;
+    use uuid::Uuid;
+
+    const LIMITS: ChannelLimits = ChannelLimits {
+        receive_timeout: Duration::from_millis(50),
+        max_message_size: 64,
+    };
+
+    type Payload = Result<Message, Status>;
+
+    /// Create a server channel and the client's ends of it.
+    fn channel() -> (Sender<Payload>, Channel<Unauthenticated>, Receiver<Payload>) {
+        let metadata = RequestMetadata::new(
+            &"alice".parse().unwrap(),
+            ClientAction::Register,
+            None,
+            Uuid::new_v4(),
+            MessageEncoding::Json,
+        );
+        let (client_sender, client_stream) = mpsc::channel(BUFFER_SIZE);
+        let mut request = Request::new(ReceiverStream::new(client_stream));
+        let _ = request
+            .metadata_mut()
+            .insert(METADATA, (&metadata).try_into().unwrap());
+
+        let (channel, client_receiver) = Channel::new(request, LIMITS).unwrap();
+        (client_sender, channel, client_receiver)
+    }
+
+    fn message(size: usize) -> Message {
+        Message {
+            content: vec![b' '; size],
+        }
+    }
+
+    fn hello() -> Message {
+        "hello"
+            .to_string()
+            .to_message(MessageEncoding::Json)
+            .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn receive_times_out_if_client_stalls() {
+        let (_client_sender, mut channel, _client_receiver) = channel();
+
+        let receive = channel.receive::<String>();
+        tokio::pin!(receive);
+        assert!(poll!(&mut receive).is_pending());
+
+        tokio::time::advance(LIMITS.receive_timeout - Duration::from_millis(1)).await;
+        assert!(poll!(&mut receive).is_pending());
+
+        tokio::time::advance(Duration::from_millis(1)).await;
+        let error = receive.await.unwrap_err();
+        assert!(matches!(error, LockKeeperError::ReceiveTimeout));
+        assert_eq!(
+            LockKeeperServerError::from(error).failure_code(),
+            FailureCode::ReceiveTimeout
+        );
+    }
+
+    #[tokio::test]
+    async fn receive_fails_if_client_disconnects() {
+        let (client_sender, mut channel, _client_receiver) = channel();
+        drop(client_sender);
+
+        let error = channel.receive::<String>().await.unwrap_err();
+        assert!(matches!(error, LockKeeperError::NoMessageReceived));
+    }
+
+    #[tokio::test]
+    async fn oversized_messages_are_rejected_before_decoding() {
+        let (client_sender, mut channel, _client_receiver) = channel();
+
+        // A message at the limit is decoded, and this one isn't valid JSON.
+        client_sender.send(Ok(message(64))).await.unwrap();
+        let error = channel.receive::<String>().await.unwrap_err();
+        assert!(matches!(error, LockKeeperError::InvalidMessage));
+
+        client_sender.send(Ok(message(65))).await.unwrap();
+        let error = channel.receive::<String>().await.unwrap_err();
+        assert!(matches!(
+            error,
+            LockKeeperError::MessageTooLarge { max: 64 }
+        ));
+        assert_eq!(
+            LockKeeperServerError::from(error).failure_code(),
+            FailureCode::MessageTooLarge
+        );
+    }
+
+    #[tokio::test]
+    async fn messages_rejected_by_tonic_are_too_large() {
+        let (client_sender, mut channel, _client_receiver) = channel();
+
+        let status = Status::out_of_range("Error, message length too large");
+        client_sender.send(Err(status)).await.unwrap();
+        let error = channel.receive::<String>().await.unwrap_err();
+        assert!(matches!(
+            error,
+            LockKeeperError::MessageTooLarge { max: 64 }
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn each_receive_gets_its_own_timeout() {
+        let (client_sender, mut channel, _client_receiver) = channel();
+
+        // Send each message well within the timeout, but the whole exchange
+        // takes longer than a single timeout.
+        for _ in 0..3 {
+            let receive = channel.receive::<String>();
+            tokio::pin!(receive);
+            assert!(poll!(&mut receive).is_pending());
+
+            tokio::time::advance(LIMITS.receive_timeout / 2).await;
+            client_sender.send(Ok(hello())).await.unwrap();
+            assert_eq!(receive.await.unwrap(), "hello");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closed_does_not_wait_for_stalled_client() {
+        let (_client_sender, mut channel, _client_receiver) = channel();
+
+        let closed = channel.closed();
+        tokio::pin!(closed);
+        assert!(poll!(&mut closed).is_pending());
+
+        tokio::time::advance(LIMITS.receive_timeout).await;
+        assert!(poll!(&mut closed).is_ready());
+    }
+}
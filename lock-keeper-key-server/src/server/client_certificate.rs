@@ -0,0 +1,138 @@
+//! Identities presented by clients through mutual TLS.
+//!
+//! When client authentication is enabled, the end-entity certificate from
+//! each connection is parsed into a [`ClientCertificate`] and attached to
+//! every request made over that connection. Identities can be bound to an
+//! account in the database so a certificate may only be used on behalf of
+//! that account.
+
+use x509_parser::{certificate::X509Certificate, extensions::GeneralName, prelude::FromDer};
+
+use crate::LockKeeperServerError;
+
+/// Identities taken from a verified client certificate.
+///
+/// Each identity is prefixed with its source so that values from different
+/// fields can never collide:
+/// - `subject:<distinguished name>`, e.g. `subject:CN=client, O=Bolt Labs`
+/// - `dns:<name>` for DNS subject alternative names
+/// - `email:<address>` for RFC 822 subject alternative names
+/// - `uri:<uri>` for URI subject alternative names
+/// - `ip:<address>` for IP address subject alternative names
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientCertificate {
+    identities: Vec<String>,
+}
+
+impl ClientCertificate {
+    /// Parse the identities out of a DER-encoded certificate.
+    pub fn from_der(der: &[u8]) -> Result<Self, LockKeeperServerError> {
+        let (_, certificate) = X509Certificate::from_der(der)
+            .map_err(|_| LockKeeperServerError::InvalidClientCertificate)?;
+
+        let mut identities = vec![format!("subject:{}", certificate.subject())];
+
+        let alternative_names = certificate
+            .subject_alternative_name()
+            .map_err(|_| LockKeeperServerError::InvalidClientCertificate)?;
+        if let Some(alternative_names) = alternative_names {
+            for name in &alternative_names.value.general_names {
+                let identity = match name {
+                    GeneralName::DNSName(dns) => format!("dns:{dns}"),
+                    GeneralName::RFC822Name(email) => format!("email:{email}"),
+                    GeneralName::URI(uri) => format!("uri:{uri}"),
+                    GeneralName::IPAddress(bytes) => match ip_address(bytes) {
+                        Some(ip) => format!("ip:{ip}"),
+                        None => continue,
+                    },
+                    _ => continue,
+                };
+                identities.push(identity);
+            }
+        }
+
+        Ok(Self { identities })
+    }
+
+    /// All identities in the certificate, starting with the subject.
+    pub fn identities(&self) -> &[String] {
+        &self.identities
+    }
+
+    /// Returns `true` if `identity` has one of the prefixes used for the
+    /// identities of a certificate.
+    pub fn is_valid_identity(identity: &str) -> bool {
+        IDENTITY_PREFIXES.iter().any(|prefix| {
+            identity
+                .strip_prefix(prefix)
+                .map_or(false, |value| !value.is_empty())
+        })
+    }
+}
+
+const IDENTITY_PREFIXES: [&str; 5] = ["subject:", "dns:", "email:", "uri:", "ip:"];
+
+fn ip_address(bytes: &[u8]) -> Option<std::net::IpAddr> {
+    match bytes.len() {
+        4 => <[u8; 4]>::try_from(bytes).ok().map(Into::into),
+        16 => <[u8; 16]>::try_from(bytes).ok().map(Into::into),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed certificate with subject `CN=client, O=Bolt Labs` and one
+    /// subject alternative name of each supported type.
+    const CERTIFICATE: &str = "\
+        MIIB+TCCAZ+gAwIBAgIUb7JXHo3VXFfDz5yUFwJJw3dgYBIwCgYIKoZIzj0EAwIwJTEPMA0GA1UE\
+        AwwGY2xpZW50MRIwEAYDVQQKDAlCb2x0IExhYnMwIBcNMjYxMDE4MTk1OTE5WhgPMjEyNjA5MjQx\
+        OTU5MTlaMCUxDzANBgNVBAMMBmNsaWVudDESMBAGA1UECgwJQm9sdCBMYWJzMFkwEwYHKoZIzj0C\
+        AQYIKoZIzj0DAQcDQgAEZ9XrjPS/eC165R5u872vKWyB3qN3o1hJFo0NBCIPcLwP7BeLz9d0ykuT\
+        IDRlea3UiJE+TF5fBXsT0mM1jmHzM6OBqjCBpzAdBgNVHQ4EFgQU/NaJ0b+E9PPbrrdmKKsPsOom\
+        DjMwHwYDVR0jBBgwFoAU/NaJ0b+E9PPbrrdmKKsPsOomDjMwDwYDVR0TAQH/BAUwAwEB/zBUBgNV\
+        HREETTBLghJjbGllbnQuZXhhbXBsZS5jb22BEmNsaWVudEBleGFtcGxlLmNvbYYbc3BpZmZlOi8v\
+        ZXhhbXBsZS5jb20vY2xpZW50hwR/AAABMAoGCCqGSM49BAMCA0gAMEUCIQCnSZqj2LrjoNAt8a6O\
+        Ce1ToJmCv0o4WKsM4RtnqBLbMwIgKOK8fKqVngQ4Wyz05I8F5KUnHOIgVN/kn33Iimyr7Wk=";
+
+    #[test]
+    fn identities_include_subject_and_alternative_names() {
+        let der = base64::decode(CERTIFICATE).unwrap();
+        let certificate = ClientCertificate::from_der(&der).unwrap();
+
+        assert_eq!(
+            certificate.identities(),
+            [
+                "subject:CN=client, O=Bolt Labs",
+                "dns:client.example.com",
+                "email:client@example.com",
+                "uri:spiffe://example.com/client",
+                "ip:127.0.0.1",
+            ]
+        );
+    }
+
+    #[test]
+    fn identities_are_validated_by_prefix() {
+        let der = base64::decode(CERTIFICATE).unwrap();
+        let certificate = ClientCertificate::from_der(&der).unwrap();
+        assert!(certificate
+            .identities()
+            .iter()
+            .all(|identity| ClientCertificate::is_valid_identity(identity)));
+
+        assert!(!ClientCertificate::is_valid_identity("CN=client"));
+        assert!(!ClientCertificate::is_valid_identity("dns:"));
+        assert!(!ClientCertificate::is_valid_identity("name:client"));
+    }
+
+    #[test]
+    fn invalid_certificate_is_rejected() {
+        assert!(matches!(
+            ClientCertificate::from_der(&[0, 1, 2, 3]),
+            Err(LockKeeperServerError::InvalidClientCertificate)
+        ));
+    }
+}
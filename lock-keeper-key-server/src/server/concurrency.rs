@@ -0,0 +1,215 @@
+//! Concurrency limits for the key server.
+//!
+//! Every request holds a [`RequestPermit`] from the [`ConcurrencyLimiter`]
+//! while it is handled, and authenticated requests hold a second one for the
+//! account of their session. Requests beyond the server-wide limit, or beyond
+//! the limit for a single account, are rejected with
+//! [`LockKeeperServerError::RateLimited`] instead of being queued, so a burst
+//! of requests can't exhaust database connections. The limits are taken from
+//! the config of each request, so they can be changed by reloading the config.
+//!
+//! The account limit only applies once the session has been found, since the
+//! account name that clients send with a request can't be trusted.
+//! Unauthenticated requests only count against the server-wide limit.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use lock_keeper::types::database::account::AccountId;
+use tracing::warn;
+
+use crate::{server::metrics::Metrics, LockKeeperServerError};
+
+/// Counts the requests being handled, in total and per account.
+#[derive(Clone)]
+pub(crate) struct ConcurrencyLimiter {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    metrics: Metrics,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    requests: usize,
+    /// Number of requests per account. Accounts without requests are removed.
+    accounts: HashMap<AccountId, usize>,
+}
+
+impl ConcurrencyLimiter {
+    /// Create a limiter that records rejected requests in `metrics`.
+    pub(crate) fn new(metrics: Metrics) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                state: Mutex::default(),
+                metrics,
+            }),
+        }
+    }
+
+    /// Start handling a request. The request counts against `max_requests`
+    /// until the returned [`RequestPermit`] is dropped.
+    ///
+    /// Returns [`LockKeeperServerError::RateLimited`] if the limit has been
+    /// reached.
+    pub(crate) fn acquire(
+        &self,
+        max_requests: usize,
+    ) -> Result<RequestPermit, LockKeeperServerError> {
+        let mut state = self.inner.state();
+
+        if state.requests >= max_requests {
+            warn!("Rejecting request: server is at its concurrent request limit.");
+            self.inner.metrics.record_rejected_request("server");
+            return Err(LockKeeperServerError::RateLimited);
+        }
+        state.requests += 1;
+
+        Ok(RequestPermit {
+            limiter: self.inner.clone(),
+            account_id: None,
+        })
+    }
+
+    /// Start handling a request for the account of an authenticated session.
+    /// The request counts against `max_requests_per_account` until the
+    /// returned [`RequestPermit`] is dropped.
+    ///
+    /// Returns [`LockKeeperServerError::RateLimited`] if the limit has been
+    /// reached.
+    pub(crate) fn acquire_for_account(
+        &self,
+        account_id: AccountId,
+        max_requests_per_account: usize,
+    ) -> Result<RequestPermit, LockKeeperServerError> {
+        let mut state = self.inner.state();
+
+        let account_requests = state.accounts.get(&account_id).copied().unwrap_or(0);
+        if account_requests >= max_requests_per_account {
+            warn!("Rejecting request: account is at its concurrent request limit.");
+            self.inner.metrics.record_rejected_request("account");
+            return Err(LockKeeperServerError::RateLimited);
+        }
+        let _ = state.accounts.insert(account_id, account_requests + 1);
+
+        Ok(RequestPermit {
+            limiter: self.inner.clone(),
+            account_id: Some(account_id),
+        })
+    }
+}
+
+impl Inner {
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Held while a request is handled. Dropping it frees the request's place
+/// under the server-wide limit, or under the limit of its account.
+pub(crate) struct RequestPermit {
+    limiter: Arc<Inner>,
+    /// Set if this permit counts against the limit of an account instead of
+    /// the server-wide limit.
+    account_id: Option<AccountId>,
+}
+
+impl Drop for RequestPermit {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state();
+
+        match self.account_id {
+            None => state.requests -= 1,
+            Some(account_id) => {
+                if let Some(account_requests) = state.accounts.get_mut(&account_id) {
+                    *account_requests -= 1;
+                    if *account_requests == 0 {
+                        let _ = state.accounts.remove(&account_id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_REQUESTS: usize = 3;
+    const MAX_REQUESTS_PER_ACCOUNT: usize = 2;
+
+    fn limiter() -> ConcurrencyLimiter {
+        ConcurrencyLimiter::new(Metrics::new().unwrap())
+    }
+
+    fn is_rate_limited(result: Result<RequestPermit, LockKeeperServerError>) -> bool {
+        matches!(result, Err(LockKeeperServerError::RateLimited))
+    }
+
+    #[test]
+    fn requests_over_the_account_limit_are_rejected() {
+        let limiter = limiter();
+        let alice = AccountId(1);
+        let bob = AccountId(2);
+
+        let first = limiter
+            .acquire_for_account(alice, MAX_REQUESTS_PER_ACCOUNT)
+            .unwrap();
+        let _second = limiter
+            .acquire_for_account(alice, MAX_REQUESTS_PER_ACCOUNT)
+            .unwrap();
+        assert!(is_rate_limited(
+            limiter.acquire_for_account(alice, MAX_REQUESTS_PER_ACCOUNT)
+        ));
+
+        // Other accounts aren't affected
+        let _bob = limiter
+            .acquire_for_account(bob, MAX_REQUESTS_PER_ACCOUNT)
+            .unwrap();
+
+        drop(first);
+        let _third = limiter
+            .acquire_for_account(alice, MAX_REQUESTS_PER_ACCOUNT)
+            .unwrap();
+    }
+
+    #[test]
+    fn requests_over_the_server_limit_are_rejected() {
+        let limiter = limiter();
+        let permits = [(); MAX_REQUESTS].map(|_| limiter.acquire(MAX_REQUESTS).unwrap());
+
+        assert!(is_rate_limited(limiter.acquire(MAX_REQUESTS)));
+
+        // Account permits don't count against the server-wide limit
+        let _alice = limiter
+            .acquire_for_account(AccountId(1), MAX_REQUESTS_PER_ACCOUNT)
+            .unwrap();
+
+        drop(permits);
+        let _fourth = limiter.acquire(MAX_REQUESTS).unwrap();
+    }
+
+    #[test]
+    fn idle_accounts_are_forgotten() {
+        let limiter = limiter();
+        let alice = AccountId(1);
+
+        let request = limiter.acquire(MAX_REQUESTS).unwrap();
+        let permit = limiter.acquire_for_account(alice, 1).unwrap();
+        assert!(is_rate_limited(limiter.acquire_for_account(alice, 1)));
+        assert!(is_rate_limited(
+            limiter.acquire_for_account(AccountId(2), 0)
+        ));
+        drop(permit);
+        drop(request);
+
+        let state = limiter.inner.state();
+        assert_eq!(state.requests, 0);
+        assert!(state.accounts.is_empty());
+    }
+}
@@ -2,24 +2,56 @@ use std::sync::Arc;
 
 use lock_keeper::{
     crypto::KeyId,
-    types::{audit_event::EventStatus, database::account::AccountId, operations::ClientAction},
+    types::{
+        audit_chain::AuditCheckpoint,
+        audit_event::{AuditEventDetails, EventStatus, FailureCode},
+        database::account::AccountId,
+        operations::ClientAction,
+    },
+    LockKeeperError,
 };
 use rand::rngs::StdRng;
-use tokio::sync::Mutex;
-use tracing::instrument;
+use time::OffsetDateTime;
+use tracing::{debug, instrument};
 use uuid::Uuid;
 
 use crate::{Config, LockKeeperServerError};
 
-use super::{database::DataStore, session_cache::SessionCache};
+use super::{
+    audit_sink::AuditSinks,
+    channel::{Authenticated, Channel},
+    database::{DataStore, DataStoreTransaction},
+    metrics::Metrics,
+    session_cache::SessionCache,
+    shutdown::ShutdownCoordinator,
+};
 
 pub(crate) struct Context<DB: DataStore> {
     pub db: Arc<DB>,
     pub config: Arc<Config>,
-    pub rng: Arc<Mutex<StdRng>>,
+    /// Random number generator of this request's task.
+    pub rng: StdRng,
     pub key_id: Option<KeyId>,
     /// Our user session keys are held in this cache after authentication.
-    pub session_cache: Arc<Mutex<dyn SessionCache>>,
+    pub session_cache: Arc<dyn SessionCache>,
+    /// Client identity, peer address, and payload information recorded with
+    /// every audit event for the request. Operations fill in the payload
+    /// fields once they have received the client's data.
+    pub audit_details: AuditEventDetails,
+    /// Tracks the operation so that shutdown can wait for it to finish.
+    pub shutdown: ShutdownCoordinator,
+    /// External destinations that stored audit events are published to.
+    pub audit_sinks: AuditSinks,
+    /// Metrics that the request is recorded in.
+    pub metrics: Metrics,
+    /// Set once the `Successful` audit event has been committed together with
+    /// the operation's changes, so that neither it nor a `Failed` event is
+    /// written afterwards.
+    pub success_recorded: bool,
+    /// Account of an unauthenticated request once the operation has recorded
+    /// its `Started` audit event, so that an aborted request can be recorded
+    /// for the same account.
+    pub started_account_id: Option<AccountId>,
 }
 
 impl<DB: DataStore> Context<DB> {
@@ -30,10 +62,88 @@ impl<DB: DataStore> Context<DB> {
         request_id: Uuid,
         client_action: ClientAction,
         status: EventStatus,
+        failure_code: Option<FailureCode>,
     ) -> Result<(), LockKeeperServerError> {
-        Ok(self
+        let details = AuditEventDetails {
+            failure_code,
+            ..self.audit_details.clone()
+        };
+        let event = self
             .db
-            .create_audit_event(request_id, account_id, &self.key_id, client_action, status)
-            .await?)
+            .create_audit_event(
+                request_id,
+                account_id,
+                &self.key_id,
+                client_action,
+                status,
+                &details,
+            )
+            .await?;
+        self.audit_sinks.publish(&event);
+
+        Ok(())
+    }
+
+    /// Commit `transaction` together with the `Successful` audit event for the
+    /// request on `channel`. Operations should call this before sending their
+    /// final reply so that the client only sees success once the change and
+    /// its audit event are stored.
+    #[instrument(skip_all, err(Debug))]
+    pub(crate) async fn commit_with_audit_event(
+        &mut self,
+        mut transaction: DB::Transaction,
+        channel: &mut Channel<Authenticated<StdRng>>,
+    ) -> Result<(), LockKeeperServerError> {
+        transaction
+            .create_audit_event(
+                channel.metadata().request_id(),
+                channel.account_id(),
+                &self.key_id,
+                channel.metadata().action(),
+                EventStatus::Successful,
+                &self.audit_details,
+            )
+            .await?;
+        let events = transaction.commit().await?;
+        self.success_recorded = true;
+        for event in &events {
+            self.audit_sinks.publish(event);
+        }
+
+        Ok(())
+    }
+
+    /// Sign an [`AuditCheckpoint`] for the account's latest audit event if
+    /// the server has an audit key and the account's last checkpoint is older
+    /// than the configured interval.
+    #[instrument(skip(self), err(Debug))]
+    pub(crate) async fn checkpoint_audit_log_if_due(
+        &self,
+        account_id: AccountId,
+    ) -> Result<(), LockKeeperServerError> {
+        let signing_key = match &self.config.audit_key {
+            Some(signing_key) => signing_key,
+            None => return Ok(()),
+        };
+        let event = match self.db.find_latest_audit_event(account_id).await? {
+            Some(event) => event,
+            None => return Ok(()),
+        };
+
+        let now = OffsetDateTime::now_utc();
+        if let Some(latest) = self.db.find_latest_audit_checkpoint(account_id).await? {
+            if latest.audit_event_id == event.audit_event_id
+                || now - latest.timestamp < self.config.audit_checkpoint_interval
+            {
+                return Ok(());
+            }
+        }
+
+        debug!("Creating audit checkpoint.");
+        let checkpoint =
+            AuditCheckpoint::new(signing_key, &event, now).map_err(LockKeeperError::from)?;
+        self.db.create_audit_checkpoint(&checkpoint).await?;
+
+        Ok(())
     }
 }
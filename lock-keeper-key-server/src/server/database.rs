@@ -6,12 +6,14 @@
 use async_trait::async_trait;
 use lock_keeper::{
     config::opaque::OpaqueCipherSuite,
-    crypto::{Encrypted, KeyId, StorageKey},
+    crypto::{Encrypted, KeyId, ServiceCredentialPublicKey, StorageKey},
     types::{
-        audit_event::{AuditEvent, AuditEventOptions, EventStatus, EventType},
+        audit_chain::AuditCheckpoint,
+        audit_event::{AuditEvent, AuditEventDetails, AuditEventOptions, EventStatus, EventType},
         database::{
             account::{Account, AccountId, AccountName, UserId},
             secrets::StoredSecret,
+            service_credential::ServiceCredential,
         },
         operations::ClientAction,
     },
@@ -29,8 +31,10 @@ pub enum DatabaseError {
     InvalidRowCountFound,
     #[error("The provided audit event filtering options were not formatted correctly.")]
     InvalidAuditEventOptions,
-    #[error("Key ID exists but associated user ID or key type were incorrect.")]
+    #[error("Key ID exists but belongs to another account.")]
     IncorrectKeyMetadata,
+    #[error("Key ID exists but its secret type doesn't match the filter.")]
+    WrongSecretType,
     #[error("An error occurred within the database: {0}. See database logs.")]
     InternalDatabaseError(String),
 }
@@ -45,9 +49,31 @@ impl From<DatabaseError> for Status {
 ///
 /// This trait definition is not complete. New trait methods may be added
 /// in future server versions.
+///
+/// Implementations can be checked with the conformance tests in the
+/// `lock-keeper-tests` crate (`lock_keeper_tests::conformance`).
 #[async_trait]
 pub trait DataStore: Send + Sync + 'static {
-    /// Create a new [`AuditEvent`] for the given actor, action, and outcome
+    /// Unit of work returned by [`DataStore::begin_transaction`].
+    type Transaction: DataStoreTransaction;
+
+    /// Start a [`DataStoreTransaction`]. Changes made through the transaction
+    /// are only visible to other callers once it has been committed.
+    async fn begin_transaction(&self) -> Result<Self::Transaction, DatabaseError>;
+
+    /// Check that the database can currently serve requests, e.g. by running
+    /// a trivial query. This is called by readiness probes, so it must be
+    /// quick and must not modify any data.
+    async fn health_check(&self) -> Result<(), DatabaseError>;
+
+    /// Create a new [`AuditEvent`] for the given actor, action, and outcome,
+    /// and return it as stored. Every field of `details` must be returned
+    /// unchanged by [`DataStore::find_audit_events`].
+    ///
+    /// Audit events are hash-chained: the new event's `previous_hash` must be
+    /// the [`AuditEvent::hash`] of the latest event for the account, and
+    /// events created concurrently for the same account must not link to the
+    /// same predecessor.
     async fn create_audit_event(
         &self,
         request_id: Uuid,
@@ -55,10 +81,15 @@ pub trait DataStore: Send + Sync + 'static {
         key_id: &Option<KeyId>,
         action: ClientAction,
         status: EventStatus,
-    ) -> Result<(), DatabaseError>;
+        details: &AuditEventDetails,
+    ) -> Result<AuditEvent, DatabaseError>;
 
     /// Find [`AuditEvent`]s that correspond to the event type and provided
     /// filters
+    ///
+    /// Events must be returned in ascending order of `audit_event_id`, so that
+    /// `options.after_audit_event_id` can be used as a cursor. At most
+    /// `options.limit` events are returned if it is set.
     async fn find_audit_events(
         &self,
         account_id: AccountId,
@@ -66,6 +97,52 @@ pub trait DataStore: Send + Sync + 'static {
         options: AuditEventOptions,
     ) -> Result<Vec<AuditEvent>, DatabaseError>;
 
+    /// Same as [`DataStore::find_audit_events`], but searches the events of
+    /// every account. This is only used for administrative exports and must
+    /// never be called on behalf of a client.
+    async fn find_all_audit_events(
+        &self,
+        event_type: EventType,
+        options: AuditEventOptions,
+    ) -> Result<Vec<AuditEvent>, DatabaseError>;
+
+    /// Find the [`AuditEvent`] with the highest ID for the given account.
+    async fn find_latest_audit_event(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditEvent>, DatabaseError>;
+
+    /// Delete the [`AuditEvent`]s with the given IDs. This is only used by
+    /// the audit retention task once the events have been archived. Returns
+    /// the number of events deleted.
+    async fn delete_audit_events(&self, audit_event_ids: &[i64]) -> Result<u64, DatabaseError>;
+
+    /// Store [`AuditEvent`]s restored from an archive. Every field, including
+    /// `audit_event_id`, `timestamp`, and `previous_hash`, must be stored
+    /// unchanged. Events whose ID is already stored are skipped. Fails if the
+    /// account of an event doesn't exist. Returns the number of events stored.
+    async fn import_audit_events(&self, events: &[AuditEvent]) -> Result<u64, DatabaseError>;
+
+    /// Store an [`AuditCheckpoint`] signed by the key server.
+    async fn create_audit_checkpoint(
+        &self,
+        checkpoint: &AuditCheckpoint,
+    ) -> Result<(), DatabaseError>;
+
+    /// Find every [`AuditCheckpoint`] for the given account, ordered by the ID
+    /// of the audit event they cover.
+    async fn find_audit_checkpoints(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<AuditCheckpoint>, DatabaseError>;
+
+    /// Find the [`AuditCheckpoint`] covering the most recent audit event for
+    /// the given account.
+    async fn find_latest_audit_checkpoint(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditCheckpoint>, DatabaseError>;
+
     // Secret
     /// Add a [`StoredSecret`] to a [`Account`]'s list of arbitrary
     /// secrets
@@ -74,6 +151,10 @@ pub trait DataStore: Send + Sync + 'static {
     /// Get a [`Account`]'s [`StoredSecret`] based on its [`KeyId`].
     /// A [`StoredSecret`] will only be returned if it matches the given
     /// [`SecretFilter`].
+    ///
+    /// Returns [`DatabaseError::IncorrectKeyMetadata`] if the key belongs to
+    /// another account, and [`DatabaseError::WrongSecretType`] if it belongs
+    /// to this account but doesn't match the filter.
     async fn get_secret(
         &self,
         account_id: AccountId,
@@ -133,6 +214,89 @@ pub trait DataStore: Send + Sync + 'static {
 
     /// Returns `true` if the [`UserId`] already exists in the database.
     async fn user_id_exists(&self, user_id: &UserId) -> Result<bool, DatabaseError>;
+
+    // Service credentials
+    /// Register a [`ServiceCredential`] for the [`Account`] associated with the
+    /// given [`AccountId`]. The credential ID is generated by the database.
+    async fn create_service_credential(
+        &self,
+        account_id: AccountId,
+        public_key: &ServiceCredentialPublicKey,
+        allowed_actions: &[ClientAction],
+    ) -> Result<ServiceCredential, DatabaseError>;
+
+    /// Find a [`ServiceCredential`] by its ID. Only credentials registered to
+    /// the given [`AccountId`] are returned.
+    async fn find_service_credential(
+        &self,
+        account_id: AccountId,
+        credential_id: Uuid,
+    ) -> Result<Option<ServiceCredential>, DatabaseError>;
+
+    // Certificate bindings
+    /// Bind a client certificate identity to the [`Account`] associated with
+    /// the given [`AccountId`]. Requests presenting a certificate with this
+    /// identity will only be accepted for that account.
+    async fn create_certificate_binding(
+        &self,
+        identity: &str,
+        account_id: AccountId,
+    ) -> Result<(), DatabaseError>;
+
+    /// Find the [`AccountId`] that a client certificate identity is bound to.
+    async fn find_certificate_binding(
+        &self,
+        identity: &str,
+    ) -> Result<Option<AccountId>, DatabaseError>;
+
+    /// Returns `true` if any client certificate identity is bound to the given
+    /// [`AccountId`].
+    async fn account_has_certificate_binding(
+        &self,
+        account_id: AccountId,
+    ) -> Result<bool, DatabaseError>;
+}
+
+/// A set of changes to a [`DataStore`] that are applied atomically.
+///
+/// Either every change is committed or none are. Dropping a transaction
+/// without calling [`DataStoreTransaction::commit`] discards its changes.
+/// Implementations may report errors when a change is made or only when the
+/// transaction is committed.
+#[async_trait]
+pub trait DataStoreTransaction: Send + 'static {
+    /// Same as [`DataStore::create_audit_event`].
+    async fn create_audit_event(
+        &mut self,
+        request_id: Uuid,
+        account_id: AccountId,
+        key_id: &Option<KeyId>,
+        action: ClientAction,
+        status: EventStatus,
+        details: &AuditEventDetails,
+    ) -> Result<(), DatabaseError>;
+
+    /// Same as [`DataStore::add_secret`].
+    async fn add_secret(&mut self, secret: StoredSecret) -> Result<(), DatabaseError>;
+
+    /// Same as [`DataStore::delete_secret`].
+    async fn delete_secret(
+        &mut self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<(), DatabaseError>;
+
+    /// Same as [`DataStore::set_storage_key`].
+    async fn set_storage_key(
+        &mut self,
+        account_id: AccountId,
+        storage_key: Encrypted<StorageKey>,
+    ) -> Result<(), DatabaseError>;
+
+    /// Apply every change made through this transaction. Returns the audit
+    /// events created in the transaction, as stored, in the order they were
+    /// created.
+    async fn commit(self) -> Result<Vec<AuditEvent>, DatabaseError>;
 }
 
 /// Filters that can be used to influence database queries.
@@ -0,0 +1,184 @@
+//! Limit on the size of messages that clients send to the key server.
+//!
+//! `tonic` buffers every message in full before it is decoded, no matter how
+//! large the client says it is. [`limit_message_size`] reads the length prefix
+//! of each gRPC message in a request body and ends the body with an
+//! `OUT_OF_RANGE` [`Status`] as soon as a message is over the limit, before
+//! it is buffered. The [`Channel`](super::channel::Channel) of the operation
+//! reports it as [`LockKeeperError::MessageTooLarge`].
+//!
+//! [`LockKeeperError::MessageTooLarge`]: lock_keeper::LockKeeperError::MessageTooLarge
+
+use std::{
+    error::Error,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use hyper::{body::Bytes, Body, Request};
+use prost::encoding::{encoded_len_varint, key_len};
+use tokio_stream::Stream;
+use tonic::Status;
+
+/// Length of the prefix of every gRPC message: a compression flag followed by
+/// the length of the message as a big-endian `u32`.
+const GRPC_PREFIX_SIZE: usize = 5;
+
+/// Field number of `content` in the `Message` protobuf type.
+const MESSAGE_CONTENT_FIELD: u32 = 1;
+
+/// Size of the largest encoded `Message` whose content is at most
+/// `max_message_size` bytes: the key of the `content` field, the length of
+/// the content as a varint, and the content itself.
+fn max_encoded_message_size(max_message_size: usize) -> usize {
+    key_len(MESSAGE_CONTENT_FIELD) + encoded_len_varint(max_message_size as u64) + max_message_size
+}
+
+/// Ends the body of `request` with an error once it contains a message that
+/// can't fit in `max_message_size` bytes of content.
+pub(crate) fn limit_message_size(request: Request<Body>, max_message_size: usize) -> Request<Body> {
+    let max = max_encoded_message_size(max_message_size);
+    request.map(|body| {
+        Body::wrap_stream(LimitedBody {
+            body,
+            prefixes: PrefixReader::new(max),
+        })
+    })
+}
+
+/// Request body that is checked by a [`PrefixReader`] as it is read.
+struct LimitedBody {
+    body: Body,
+    prefixes: PrefixReader,
+}
+
+impl Stream for LimitedBody {
+    type Item = Result<Bytes, Box<dyn Error + Send + Sync>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let chunk = match ready!(Pin::new(&mut self.body).poll_next(cx)) {
+            Some(Ok(chunk)) => chunk,
+            Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+            None => return Poll::Ready(None),
+        };
+
+        let result = match self.prefixes.read(&chunk) {
+            Ok(()) => Ok(chunk),
+            Err(status) => Err(status.into()),
+        };
+        Poll::Ready(Some(result))
+    }
+}
+
+/// Follows the gRPC messages in a request body and checks the length in the
+/// prefix of each one.
+#[derive(Debug)]
+struct PrefixReader {
+    /// Largest message length that is accepted.
+    max: usize,
+    /// Prefix of the next message, which may be split across chunks.
+    prefix: [u8; GRPC_PREFIX_SIZE],
+    prefix_len: usize,
+    /// Bytes of the current message that haven't been read yet.
+    remaining: usize,
+}
+
+impl PrefixReader {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            prefix: [0; GRPC_PREFIX_SIZE],
+            prefix_len: 0,
+            remaining: 0,
+        }
+    }
+
+    /// Read the next chunk of the body. Returns an `OUT_OF_RANGE` status if
+    /// the chunk starts a message that is longer than `max`.
+    fn read(&mut self, mut chunk: &[u8]) -> Result<(), Status> {
+        while !chunk.is_empty() {
+            if self.remaining > 0 {
+                let skipped = self.remaining.min(chunk.len());
+                self.remaining -= skipped;
+                chunk = &chunk[skipped..];
+                continue;
+            }
+
+            let taken = (GRPC_PREFIX_SIZE - self.prefix_len).min(chunk.len());
+            self.prefix[self.prefix_len..self.prefix_len + taken].copy_from_slice(&chunk[..taken]);
+            self.prefix_len += taken;
+            chunk = &chunk[taken..];
+
+            if self.prefix_len == GRPC_PREFIX_SIZE {
+                self.prefix_len = 0;
+                let [_compression_flag, length @ ..] = self.prefix;
+                let length = u32::from_be_bytes(length) as usize;
+                if length > self.max {
+                    return Err(Status::out_of_range(format!(
+                        "Message of {length} bytes exceeds the limit of {} bytes",
+                        self.max
+                    )));
+                }
+                self.remaining = length;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body::HttpBody;
+    use lock_keeper::rpc::Message;
+    use prost::Message as _;
+    use tonic::Code;
+
+    /// A gRPC-framed `Message` with `size` bytes of content.
+    fn frame(size: usize) -> Vec<u8> {
+        let message = Message {
+            content: vec![b' '; size],
+        };
+        let mut frame = vec![0];
+        frame.extend((message.encoded_len() as u32).to_be_bytes());
+        message.encode(&mut frame).unwrap();
+        frame
+    }
+
+    #[test]
+    fn messages_up_to_the_limit_are_accepted() {
+        for max_message_size in [0, 64, 127, 128, 2 * 1024 * 1024] {
+            let mut reader = PrefixReader::new(max_encoded_message_size(max_message_size));
+            reader.read(&frame(max_message_size)).unwrap();
+            assert!(reader.read(&frame(max_message_size + 1)).is_err());
+        }
+    }
+
+    #[test]
+    fn prefixes_split_across_chunks_are_checked() {
+        let mut reader = PrefixReader::new(max_encoded_message_size(64));
+
+        // Several messages in one chunk, then a prefix split across chunks
+        let mut body = [frame(10), frame(64), frame(65)].concat();
+        let oversized = body.split_off(frame(10).len() + frame(64).len() + 2);
+        reader.read(&body).unwrap();
+
+        let status = reader.read(&oversized).unwrap_err();
+        assert_eq!(status.code(), Code::OutOfRange);
+    }
+
+    #[tokio::test]
+    async fn oversized_messages_end_the_body() {
+        let chunks: Vec<Result<_, std::io::Error>> = vec![Ok(frame(64)), Ok(frame(65))];
+        let request = Request::new(Body::wrap_stream(tokio_stream::iter(chunks)));
+        let mut body = limit_message_size(request, 64).into_body();
+
+        assert_eq!(body.data().await.unwrap().unwrap(), frame(64));
+
+        // tonic finds the status in the error of the body
+        let error = body.data().await.unwrap().unwrap_err();
+        let status = Status::from_error(Box::new(error));
+        assert_eq!(status.code(), Code::OutOfRange);
+    }
+}
@@ -0,0 +1,410 @@
+//! Prometheus metrics for the key server.
+//!
+//! Metrics are collected in a [`Metrics`] registry and can be served in the
+//! Prometheus text format with [`Metrics::serve`]. Every metric name starts
+//! with `lock_keeper_`:
+//!
+//! - `requests_total` and `request_duration_seconds`: finished client
+//!   requests, labeled by `action` and `status`. The status is the
+//!   [`EventStatus`] the request ended with.
+//! - `authentication_failures_total`: requests rejected because of the
+//!   client's credentials, labeled by `action`.
+//! - `active_sessions`: sessions in the session cache that haven't expired.
+//! - `database_call_duration_seconds` and
+//!   `session_cache_call_duration_seconds`: latency of every call to the
+//!   [`DataStore`](super::database::DataStore) and
+//!   [`SessionCache`], labeled by `method`.
+//! - `operation_tasks_spawned_total` and `operation_tasks_running`: tasks
+//!   spawned to run operations.
+//! - `rejected_requests_total`: requests rejected because of the concurrency
+//!   limits, labeled by the `limit` that was reached (`server` or `account`).
+
+mod database;
+mod session_cache;
+
+pub(crate) use database::MeteredDataStore;
+pub(crate) use session_cache::MeteredSessionCache;
+
+use std::{
+    convert::Infallible,
+    future::Future,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use hyper::{
+    header::{HeaderValue, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use lock_keeper::types::{
+    audit_event::{EventStatus, FailureCode},
+    operations::ClientAction,
+};
+use prometheus::{
+    core::Collector, Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    Opts, Registry, TextEncoder,
+};
+use tracing::{error, info};
+
+use crate::{server::session_cache::SessionCache, LockKeeperServerError};
+
+/// How often the number of active sessions is read from the session cache.
+const ACTIVE_SESSIONS_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Registry of the key server's metrics. Clones share the same metrics.
+#[derive(Clone)]
+pub struct Metrics {
+    inner: Arc<MetricsInner>,
+}
+
+struct MetricsInner {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    authentication_failures: IntCounterVec,
+    active_sessions: IntGauge,
+    database_call_duration: HistogramVec,
+    session_cache_call_duration: HistogramVec,
+    operation_tasks_spawned: IntCounter,
+    operation_tasks_running: IntGauge,
+    rejected_requests: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, LockKeeperServerError> {
+        let registry = Registry::new_custom(Some("lock_keeper".to_string()), None)?;
+
+        let inner = MetricsInner {
+            requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new("requests_total", "Finished client requests."),
+                    &["action", "status"],
+                )?,
+            )?,
+            request_duration: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "request_duration_seconds",
+                        "Time from receiving a client request until its operation finished.",
+                    ),
+                    &["action", "status"],
+                )?,
+            )?,
+            authentication_failures: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "authentication_failures_total",
+                        "Client requests rejected because of the client's credentials.",
+                    ),
+                    &["action"],
+                )?,
+            )?,
+            active_sessions: register(
+                &registry,
+                IntGauge::new("active_sessions", "Sessions that have not expired.")?,
+            )?,
+            database_call_duration: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "database_call_duration_seconds",
+                        "Duration of calls to the database.",
+                    ),
+                    &["method"],
+                )?,
+            )?,
+            session_cache_call_duration: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "session_cache_call_duration_seconds",
+                        "Duration of calls to the session cache.",
+                    ),
+                    &["method"],
+                )?,
+            )?,
+            operation_tasks_spawned: register(
+                &registry,
+                IntCounter::new(
+                    "operation_tasks_spawned_total",
+                    "Tasks spawned to run client operations.",
+                )?,
+            )?,
+            operation_tasks_running: register(
+                &registry,
+                IntGauge::new(
+                    "operation_tasks_running",
+                    "Tasks running client operations, including tasks waiting for the client to close the channel.",
+                )?,
+            )?,
+            rejected_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "rejected_requests_total",
+                        "Client requests rejected because too many requests were running.",
+                    ),
+                    &["limit"],
+                )?,
+            )?,
+            registry,
+        };
+
+        Ok(Self {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// Start recording a client request for `action`. This should be called
+    /// when the task running the operation is spawned, and the returned
+    /// [`RequestMetrics`] dropped when the task ends.
+    pub(crate) fn start_request(&self, action: ClientAction) -> RequestMetrics {
+        self.inner.operation_tasks_spawned.inc();
+        self.inner.operation_tasks_running.inc();
+
+        RequestMetrics {
+            metrics: self.clone(),
+            action,
+            start: Instant::now(),
+            finished: false,
+        }
+    }
+
+    /// Record a request that was rejected because the concurrency `limit` was
+    /// reached.
+    pub(crate) fn record_rejected_request(&self, limit: &'static str) {
+        self.inner
+            .rejected_requests
+            .with_label_values(&[limit])
+            .inc();
+    }
+
+    /// Run a call to the database and record its duration under `method`.
+    pub(crate) async fn time_database_call<F: Future>(
+        &self,
+        method: &'static str,
+        call: F,
+    ) -> F::Output {
+        let start = Instant::now();
+        let output = call.await;
+        self.inner
+            .database_call_duration
+            .with_label_values(&[method])
+            .observe(start.elapsed().as_secs_f64());
+        output
+    }
+
+    /// Run a call to the session cache and record its duration under
+    /// `method`.
+    pub(crate) async fn time_session_cache_call<F: Future>(
+        &self,
+        method: &'static str,
+        call: F,
+    ) -> F::Output {
+        let start = Instant::now();
+        let output = call.await;
+        self.inner
+            .session_cache_call_duration
+            .with_label_values(&[method])
+            .observe(start.elapsed().as_secs_f64());
+        output
+    }
+
+    /// Update the number of active sessions from the session cache every
+    /// [`ACTIVE_SESSIONS_INTERVAL`] until the server shuts down.
+    pub(crate) async fn record_active_sessions(
+        self,
+        session_cache: Arc<dyn SessionCache>,
+    ) -> Result<(), LockKeeperServerError> {
+        loop {
+            match session_cache.count_active_sessions().await {
+                Ok(count) => self.inner.active_sessions.set(count as i64),
+                Err(e) => error!("Failed to count active sessions: {}", e),
+            }
+
+            tokio::time::sleep(ACTIVE_SESSIONS_INTERVAL).await;
+        }
+    }
+
+    /// Encode every metric in the Prometheus text format.
+    pub fn encode(&self) -> Result<String, LockKeeperServerError> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.inner.registry.gather(), &mut buffer)?;
+
+        // The text encoder only writes UTF-8.
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+
+    /// Bind an HTTP listener to `address` that serves the metrics at
+    /// `/metrics`. The returned future runs the listener and must be polled,
+    /// usually by spawning it.
+    pub fn serve(
+        &self,
+        address: SocketAddr,
+    ) -> Result<impl Future<Output = Result<(), LockKeeperServerError>>, LockKeeperServerError>
+    {
+        let metrics = self.clone();
+        let make_service = make_service_fn(move |_| {
+            let metrics = metrics.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let response = metrics.respond(&request);
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+
+        let server = Server::try_bind(&address)?.serve(make_service);
+        info!(?address, "Serving metrics");
+
+        Ok(async move { Ok(server.await?) })
+    }
+
+    fn respond(&self, request: &Request<Body>) -> Response<Body> {
+        if request.method() != Method::GET || request.uri().path() != "/metrics" {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            return response;
+        }
+
+        match self.encode() {
+            Ok(text) => {
+                let mut response = Response::new(Body::from(text));
+                let _ = response.headers_mut().insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static(prometheus::TEXT_FORMAT),
+                );
+                response
+            }
+            Err(e) => {
+                error!("Failed to encode metrics: {}", e);
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                response
+            }
+        }
+    }
+}
+
+fn register<M: Collector + Clone + 'static>(
+    registry: &Registry,
+    metric: M,
+) -> Result<M, LockKeeperServerError> {
+    registry.register(Box::new(metric.clone()))?;
+    Ok(metric)
+}
+
+/// Records the outcome of a single client request.
+///
+/// A request that is dropped before [`RequestMetrics::finish`] is called,
+/// e.g. because its task panicked, is recorded as
+/// [`EventStatus::Aborted`].
+pub(crate) struct RequestMetrics {
+    metrics: Metrics,
+    action: ClientAction,
+    start: Instant,
+    finished: bool,
+}
+
+impl RequestMetrics {
+    /// Record that the request's operation finished with `status`. Only the
+    /// first call has an effect.
+    pub(crate) fn finish(&mut self, status: EventStatus, failure_code: Option<FailureCode>) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+
+        let inner = &self.metrics.inner;
+        let action = self.action.to_string();
+        let status = status.to_string();
+        inner.requests.with_label_values(&[&action, &status]).inc();
+        inner
+            .request_duration
+            .with_label_values(&[&action, &status])
+            .observe(self.start.elapsed().as_secs_f64());
+
+        if failure_code == Some(FailureCode::AuthenticationFailed) {
+            inner
+                .authentication_failures
+                .with_label_values(&[&action])
+                .inc();
+        }
+    }
+}
+
+impl Drop for RequestMetrics {
+    fn drop(&mut self) {
+        self.finish(EventStatus::Aborted, None);
+        self.metrics.inner.operation_tasks_running.dec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finished_requests_are_recorded() {
+        let metrics = Metrics::new().unwrap();
+
+        let mut request = metrics.start_request(ClientAction::Authenticate);
+        request.finish(EventStatus::Failed, Some(FailureCode::AuthenticationFailed));
+        // Later calls don't record the request again.
+        request.finish(EventStatus::Successful, None);
+        assert!(metrics
+            .encode()
+            .unwrap()
+            .contains("lock_keeper_operation_tasks_running 1"));
+        drop(request);
+
+        let text = metrics.encode().unwrap();
+        assert!(
+            text.contains(r#"lock_keeper_requests_total{action="Authenticate",status="Failed"} 1"#)
+        );
+        assert!(!text.contains(r#"status="Successful""#));
+        assert!(
+            text.contains(r#"lock_keeper_authentication_failures_total{action="Authenticate"} 1"#)
+        );
+        assert!(text.contains("lock_keeper_operation_tasks_spawned_total 1"));
+        assert!(text.contains("lock_keeper_operation_tasks_running 0"));
+    }
+
+    #[test]
+    fn dropped_request_is_recorded_as_aborted() {
+        let metrics = Metrics::new().unwrap();
+
+        drop(metrics.start_request(ClientAction::RetrieveSecret));
+
+        let text = metrics.encode().unwrap();
+        assert!(text
+            .contains(r#"lock_keeper_requests_total{action="RetrieveSecret",status="Aborted"} 1"#));
+        assert!(!text.contains("lock_keeper_authentication_failures_total{"));
+    }
+
+    #[tokio::test]
+    async fn call_durations_are_recorded_by_method() {
+        let metrics = Metrics::new().unwrap();
+
+        let answer = metrics
+            .time_database_call("find_account", async { 42 })
+            .await;
+        assert_eq!(answer, 42);
+        metrics
+            .time_session_cache_call("find_session", async {})
+            .await;
+
+        let text = metrics.encode().unwrap();
+        assert!(text.contains(
+            r#"lock_keeper_database_call_duration_seconds_count{method="find_account"} 1"#
+        ));
+        assert!(text.contains(
+            r#"lock_keeper_session_cache_call_duration_seconds_count{method="find_session"} 1"#
+        ));
+    }
+}
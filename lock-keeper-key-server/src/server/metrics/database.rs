@@ -0,0 +1,397 @@
+use async_trait::async_trait;
+use lock_keeper::{
+    config::opaque::OpaqueCipherSuite,
+    crypto::{Encrypted, KeyId, ServiceCredentialPublicKey, StorageKey},
+    types::{
+        audit_chain::AuditCheckpoint,
+        audit_event::{AuditEvent, AuditEventDetails, AuditEventOptions, EventStatus, EventType},
+        database::{
+            account::{Account, AccountId, AccountName, UserId},
+            secrets::StoredSecret,
+            service_credential::ServiceCredential,
+        },
+        operations::ClientAction,
+    },
+};
+use opaque_ke::ServerRegistration;
+use uuid::Uuid;
+
+use super::Metrics;
+use crate::server::database::{DataStore, DataStoreTransaction, DatabaseError, SecretFilter};
+
+/// [`DataStore`] that records the duration of every call to the wrapped
+/// store. Calls made through a transaction are recorded with a
+/// `transaction.` prefix.
+#[derive(Clone)]
+pub(crate) struct MeteredDataStore<DB> {
+    db: DB,
+    metrics: Metrics,
+}
+
+impl<DB: DataStore> MeteredDataStore<DB> {
+    pub(crate) fn new(db: DB, metrics: Metrics) -> Self {
+        Self { db, metrics }
+    }
+}
+
+#[async_trait]
+impl<DB: DataStore> DataStore for MeteredDataStore<DB> {
+    type Transaction = MeteredTransaction<DB::Transaction>;
+
+    async fn begin_transaction(&self) -> Result<Self::Transaction, DatabaseError> {
+        let transaction = self
+            .metrics
+            .time_database_call("begin_transaction", self.db.begin_transaction())
+            .await?;
+
+        Ok(MeteredTransaction {
+            transaction,
+            metrics: self.metrics.clone(),
+        })
+    }
+
+    async fn health_check(&self) -> Result<(), DatabaseError> {
+        self.metrics
+            .time_database_call("health_check", self.db.health_check())
+            .await
+    }
+
+    async fn create_audit_event(
+        &self,
+        request_id: Uuid,
+        account_id: AccountId,
+        key_id: &Option<KeyId>,
+        action: ClientAction,
+        status: EventStatus,
+        details: &AuditEventDetails,
+    ) -> Result<AuditEvent, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "create_audit_event",
+                self.db
+                    .create_audit_event(request_id, account_id, key_id, action, status, details),
+            )
+            .await
+    }
+
+    async fn find_audit_events(
+        &self,
+        account_id: AccountId,
+        event_type: EventType,
+        options: AuditEventOptions,
+    ) -> Result<Vec<AuditEvent>, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "find_audit_events",
+                self.db.find_audit_events(account_id, event_type, options),
+            )
+            .await
+    }
+
+    async fn find_all_audit_events(
+        &self,
+        event_type: EventType,
+        options: AuditEventOptions,
+    ) -> Result<Vec<AuditEvent>, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "find_all_audit_events",
+                self.db.find_all_audit_events(event_type, options),
+            )
+            .await
+    }
+
+    async fn find_latest_audit_event(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditEvent>, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "find_latest_audit_event",
+                self.db.find_latest_audit_event(account_id),
+            )
+            .await
+    }
+
+    async fn delete_audit_events(&self, audit_event_ids: &[i64]) -> Result<u64, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "delete_audit_events",
+                self.db.delete_audit_events(audit_event_ids),
+            )
+            .await
+    }
+
+    async fn import_audit_events(&self, events: &[AuditEvent]) -> Result<u64, DatabaseError> {
+        self.metrics
+            .time_database_call("import_audit_events", self.db.import_audit_events(events))
+            .await
+    }
+
+    async fn create_audit_checkpoint(
+        &self,
+        checkpoint: &AuditCheckpoint,
+    ) -> Result<(), DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "create_audit_checkpoint",
+                self.db.create_audit_checkpoint(checkpoint),
+            )
+            .await
+    }
+
+    async fn find_audit_checkpoints(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<AuditCheckpoint>, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "find_audit_checkpoints",
+                self.db.find_audit_checkpoints(account_id),
+            )
+            .await
+    }
+
+    async fn find_latest_audit_checkpoint(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditCheckpoint>, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "find_latest_audit_checkpoint",
+                self.db.find_latest_audit_checkpoint(account_id),
+            )
+            .await
+    }
+
+    async fn add_secret(&self, secret: StoredSecret) -> Result<(), DatabaseError> {
+        self.metrics
+            .time_database_call("add_secret", self.db.add_secret(secret))
+            .await
+    }
+
+    async fn get_secret(
+        &self,
+        account_id: AccountId,
+        key_id: &KeyId,
+        filter: SecretFilter,
+    ) -> Result<StoredSecret, DatabaseError> {
+        self.metrics
+            .time_database_call("get_secret", self.db.get_secret(account_id, key_id, filter))
+            .await
+    }
+
+    async fn get_server_encrypted_blob(
+        &self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<StoredSecret, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "get_server_encrypted_blob",
+                self.db.get_server_encrypted_blob(account_id, key_id),
+            )
+            .await
+    }
+
+    async fn delete_secret(
+        &self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<(), DatabaseError> {
+        self.metrics
+            .time_database_call("delete_secret", self.db.delete_secret(account_id, key_id))
+            .await
+    }
+
+    async fn create_account(
+        &self,
+        user_id: &UserId,
+        account_name: &AccountName,
+        server_registration: &ServerRegistration<OpaqueCipherSuite>,
+    ) -> Result<Account, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "create_account",
+                self.db
+                    .create_account(user_id, account_name, server_registration),
+            )
+            .await
+    }
+
+    async fn find_account_by_name(
+        &self,
+        account_name: &AccountName,
+    ) -> Result<Option<Account>, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "find_account_by_name",
+                self.db.find_account_by_name(account_name),
+            )
+            .await
+    }
+
+    async fn find_account(&self, account_id: AccountId) -> Result<Option<Account>, DatabaseError> {
+        self.metrics
+            .time_database_call("find_account", self.db.find_account(account_id))
+            .await
+    }
+
+    async fn delete_account(&self, account_id: AccountId) -> Result<(), DatabaseError> {
+        self.metrics
+            .time_database_call("delete_account", self.db.delete_account(account_id))
+            .await
+    }
+
+    async fn set_storage_key(
+        &self,
+        account_id: AccountId,
+        storage_key: Encrypted<StorageKey>,
+    ) -> Result<(), DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "set_storage_key",
+                self.db.set_storage_key(account_id, storage_key),
+            )
+            .await
+    }
+
+    async fn user_id_exists(&self, user_id: &UserId) -> Result<bool, DatabaseError> {
+        self.metrics
+            .time_database_call("user_id_exists", self.db.user_id_exists(user_id))
+            .await
+    }
+
+    async fn create_service_credential(
+        &self,
+        account_id: AccountId,
+        public_key: &ServiceCredentialPublicKey,
+        allowed_actions: &[ClientAction],
+    ) -> Result<ServiceCredential, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "create_service_credential",
+                self.db
+                    .create_service_credential(account_id, public_key, allowed_actions),
+            )
+            .await
+    }
+
+    async fn find_service_credential(
+        &self,
+        account_id: AccountId,
+        credential_id: Uuid,
+    ) -> Result<Option<ServiceCredential>, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "find_service_credential",
+                self.db.find_service_credential(account_id, credential_id),
+            )
+            .await
+    }
+
+    async fn create_certificate_binding(
+        &self,
+        identity: &str,
+        account_id: AccountId,
+    ) -> Result<(), DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "create_certificate_binding",
+                self.db.create_certificate_binding(identity, account_id),
+            )
+            .await
+    }
+
+    async fn find_certificate_binding(
+        &self,
+        identity: &str,
+    ) -> Result<Option<AccountId>, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "find_certificate_binding",
+                self.db.find_certificate_binding(identity),
+            )
+            .await
+    }
+
+    async fn account_has_certificate_binding(
+        &self,
+        account_id: AccountId,
+    ) -> Result<bool, DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "account_has_certificate_binding",
+                self.db.account_has_certificate_binding(account_id),
+            )
+            .await
+    }
+}
+
+/// [`DataStoreTransaction`] of a [`MeteredDataStore`].
+pub(crate) struct MeteredTransaction<T> {
+    transaction: T,
+    metrics: Metrics,
+}
+
+#[async_trait]
+impl<T: DataStoreTransaction> DataStoreTransaction for MeteredTransaction<T> {
+    async fn create_audit_event(
+        &mut self,
+        request_id: Uuid,
+        account_id: AccountId,
+        key_id: &Option<KeyId>,
+        action: ClientAction,
+        status: EventStatus,
+        details: &AuditEventDetails,
+    ) -> Result<(), DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "transaction.create_audit_event",
+                self.transaction
+                    .create_audit_event(request_id, account_id, key_id, action, status, details),
+            )
+            .await
+    }
+
+    async fn add_secret(&mut self, secret: StoredSecret) -> Result<(), DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "transaction.add_secret",
+                self.transaction.add_secret(secret),
+            )
+            .await
+    }
+
+    async fn delete_secret(
+        &mut self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<(), DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "transaction.delete_secret",
+                self.transaction.delete_secret(account_id, key_id),
+            )
+            .await
+    }
+
+    async fn set_storage_key(
+        &mut self,
+        account_id: AccountId,
+        storage_key: Encrypted<StorageKey>,
+    ) -> Result<(), DatabaseError> {
+        self.metrics
+            .time_database_call(
+                "transaction.set_storage_key",
+                self.transaction.set_storage_key(account_id, storage_key),
+            )
+            .await
+    }
+
+    async fn commit(self) -> Result<Vec<AuditEvent>, DatabaseError> {
+        self.metrics
+            .time_database_call("transaction.commit", self.transaction.commit())
+            .await
+    }
+}
@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use lock_keeper::{
+    crypto::{Encrypted, OpaqueSessionKey},
+    types::{database::account::AccountId, operations::ClientAction},
+};
+use uuid::Uuid;
+
+use super::Metrics;
+use crate::server::session_cache::{Session, SessionCache, SessionCacheError};
+
+/// [`SessionCache`] that records the duration of every call to the wrapped
+/// cache.
+pub(crate) struct MeteredSessionCache<S> {
+    session_cache: S,
+    metrics: Metrics,
+}
+
+impl<S: SessionCache> MeteredSessionCache<S> {
+    pub(crate) fn new(session_cache: S, metrics: Metrics) -> Self {
+        Self {
+            session_cache,
+            metrics,
+        }
+    }
+}
+
+#[async_trait]
+impl<S: SessionCache> SessionCache for MeteredSessionCache<S> {
+    async fn create_session(
+        &self,
+        account_id: AccountId,
+        session_key: Encrypted<OpaqueSessionKey>,
+        service_credential_id: Option<Uuid>,
+    ) -> Result<Uuid, SessionCacheError> {
+        self.metrics
+            .time_session_cache_call(
+                "create_session",
+                self.session_cache
+                    .create_session(account_id, session_key, service_credential_id),
+            )
+            .await
+    }
+
+    async fn find_session(&self, session_id: Uuid) -> Result<Session, SessionCacheError> {
+        self.metrics
+            .time_session_cache_call("find_session", self.session_cache.find_session(session_id))
+            .await
+    }
+
+    async fn delete_session(&self, session_id: Uuid) -> Result<(), SessionCacheError> {
+        self.metrics
+            .time_session_cache_call(
+                "delete_session",
+                self.session_cache.delete_session(session_id),
+            )
+            .await
+    }
+
+    async fn record_request(
+        &self,
+        session_id: Uuid,
+        request_id: Uuid,
+        action: ClientAction,
+    ) -> Result<(), SessionCacheError> {
+        self.metrics
+            .time_session_cache_call(
+                "record_request",
+                self.session_cache
+                    .record_request(session_id, request_id, action),
+            )
+            .await
+    }
+
+    async fn count_active_sessions(&self) -> Result<u64, SessionCacheError> {
+        self.metrics
+            .time_session_cache_call(
+                "count_active_sessions",
+                self.session_cache.count_active_sessions(),
+            )
+            .await
+    }
+
+    async fn health_check(&self) -> Result<(), SessionCacheError> {
+        self.metrics
+            .time_session_cache_call("health_check", self.session_cache.health_check())
+            .await
+    }
+}
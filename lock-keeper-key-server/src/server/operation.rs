@@ -1,8 +1,15 @@
 use async_trait::async_trait;
 
-use lock_keeper::{infrastructure::logging, types::audit_event::EventStatus};
+use lock_keeper::{
+    infrastructure::logging,
+    types::{
+        audit_event::{EventStatus, FailureCode},
+        database::account::AccountId,
+    },
+};
 use rand::rngs::StdRng;
-use tracing::{debug, error, info, instrument, Instrument};
+use tracing::{debug, error, info, instrument, Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::{
     server::{database::DataStore, Context},
@@ -35,8 +42,11 @@ pub(crate) trait Operation<AUTH: Send + 'static, DB: DataStore>:
 ///
 /// The spawned task processes the request through the logic defined by the
 /// `Operation::operation` method. Any errors returned are both logged and saved
-/// as an audit event.
-#[instrument(skip_all, err(Debug), fields(metadata, request_id))]
+/// as an audit event. If the server shuts down before the operation finishes,
+/// the operation is aborted and saved with an [`EventStatus::Aborted`] audit
+/// event. The outcome and duration of the request are recorded in the server's
+/// [`Metrics`](super::metrics::Metrics).
+#[instrument(skip_all, err(Debug), fields(request_id, action, account_id, metadata))]
 pub(crate) async fn handle_authenticated_request<
     DB: DataStore,
     O: Operation<Authenticated<StdRng>, DB>,
@@ -45,29 +55,77 @@ pub(crate) async fn handle_authenticated_request<
     mut context: Context<DB>,
     mut channel: Channel<Authenticated<StdRng>>,
 ) -> Result<(), LockKeeperServerError> {
-    logging::record_field("metadata", &channel.metadata());
+    // Continue the client's trace. The spawned task runs in this span too.
+    Span::current().set_parent(channel.trace_context().clone());
     logging::record_field("request_id", &channel.metadata().request_id());
+    logging::record_field("action", &channel.metadata().action());
+    logging::record_field("account_id", &channel.account_id());
+    logging::record_field("metadata", &channel.metadata());
     info!("Handling new client request.");
 
+    context.audit_details.client_identity = channel.client_identity().map(String::from);
+    context.audit_details.peer_address = channel.peer_address();
+    let guard = context.shutdown.track()?;
+    let mut request_metrics = context.metrics.start_request(channel.metadata().action());
+
     // Spawn a task to do the actual work. This way the gRPC call can return with
     // the receiving end of the channel. This task will use the writing end of
     // this same channel to send messages back to the client. The client and
     // server can go back and forth until the protocol is complete.
     let handle = tokio::spawn(
         async move {
-            audit_event(&mut channel, &context, EventStatus::Started).await;
+            audit_event(&mut channel, &context, EventStatus::Started, None).await;
 
-            match operation.operation(&mut channel, &mut context).await {
-                Ok(()) => {
+            let result = tokio::select! {
+                result = operation.operation(&mut channel, &mut context) => Some(result),
+                _ = guard.aborted() => None,
+            };
+
+            match result {
+                Some(Ok(())) => {
                     info!("Client request completed successfully!");
-                    audit_event(&mut channel, &context, EventStatus::Successful).await;
+                    request_metrics.finish(EventStatus::Successful, None);
+                    if !context.success_recorded {
+                        audit_event(&mut channel, &context, EventStatus::Successful, None).await;
+                    }
                 }
-                Err(e) => {
+                Some(Err(e)) => {
                     info!("Client request completed with an error!");
+                    let failure_code = e.failure_code();
+                    request_metrics.finish(EventStatus::Failed, Some(failure_code));
                     handle_error(&mut channel, e).await;
-                    audit_event(&mut channel, &context, EventStatus::Failed).await;
+                    // If the operation's changes were already committed with
+                    // a `Successful` event, only the reply to the client
+                    // failed, so the request isn't recorded as failed too.
+                    if !context.success_recorded {
+                        audit_event(
+                            &mut channel,
+                            &context,
+                            EventStatus::Failed,
+                            Some(failure_code),
+                        )
+                        .await;
+                    }
+                }
+                None => {
+                    info!("Client request aborted by server shutdown!");
+                    request_metrics.finish(EventStatus::Aborted, Some(FailureCode::ShuttingDown));
+                    handle_error(&mut channel, LockKeeperServerError::ShuttingDown).await;
+                    if !context.success_recorded {
+                        audit_event(
+                            &mut channel,
+                            &context,
+                            EventStatus::Aborted,
+                            Some(FailureCode::ShuttingDown),
+                        )
+                        .await;
+                    }
                 }
             }
+            checkpoint_audit_log(&context, channel.account_id()).await;
+            // The operation is finished. Waiting for the client to close the
+            // channel shouldn't hold up shutdown.
+            drop(guard);
             channel.closed().await;
         }
         .in_current_span(),
@@ -89,8 +147,10 @@ pub(crate) async fn handle_authenticated_request<
 /// messages from us for the lifetime of the protocol.
 ///
 /// The spawned task processes the request through the logic defined by the
-/// `Operation::operation` method. Any errors returned are logged.
-#[instrument(skip_all, err(Debug), fields(metadata, request_id))]
+/// `Operation::operation` method. Any errors returned are logged. The outcome
+/// and duration of the request are recorded in the server's
+/// [`Metrics`](super::metrics::Metrics).
+#[instrument(skip_all, err(Debug), fields(request_id, action, metadata))]
 pub(crate) async fn handle_unauthenticated_request<
     DB: DataStore,
     O: Operation<Unauthenticated, DB>,
@@ -99,25 +159,57 @@ pub(crate) async fn handle_unauthenticated_request<
     mut context: Context<DB>,
     mut channel: Channel<Unauthenticated>,
 ) -> Result<(), LockKeeperServerError> {
-    logging::record_field("metadata", &channel.metadata());
+    // Continue the client's trace. The spawned task runs in this span too.
+    Span::current().set_parent(channel.trace_context().clone());
     logging::record_field("request_id", &channel.metadata().request_id());
+    logging::record_field("action", &channel.metadata().action());
+    logging::record_field("metadata", &channel.metadata());
     info!("Handling new client request.");
 
+    context.audit_details.peer_address = channel.peer_address();
+    let guard = context.shutdown.track()?;
+    let mut request_metrics = context.metrics.start_request(channel.metadata().action());
+
     // Spawn a task to do the actual work. This way the gRPC call can return with
     // the receiving end of the channel. This task will use the writing end of
     // this same channel to send messages back to the client. The client and
     // server can go back and forth until the protocol is complete.
     let handle = tokio::spawn(
         async move {
-            match operation.operation(&mut channel, &mut context).await {
-                Ok(()) => {
+            let result = tokio::select! {
+                result = operation.operation(&mut channel, &mut context) => Some(result),
+                _ = guard.aborted() => None,
+            };
+
+            match result {
+                Some(Ok(())) => {
                     info!("This operation completed successfully!");
+                    request_metrics.finish(EventStatus::Successful, None);
                 }
-                Err(e) => {
+                Some(Err(e)) => {
                     info!("This operation completed with an error!");
+                    request_metrics.finish(EventStatus::Failed, Some(e.failure_code()));
                     handle_error(&mut channel, e).await;
                 }
+                None => {
+                    info!("This operation was aborted by server shutdown!");
+                    request_metrics.finish(EventStatus::Aborted, Some(FailureCode::ShuttingDown));
+                    handle_error(&mut channel, LockKeeperServerError::ShuttingDown).await;
+                    // Only requests that found their account have a `Started`
+                    // event to complete.
+                    if let Some(account_id) = context.started_account_id {
+                        unauthenticated_audit_event(
+                            &mut channel,
+                            &context,
+                            account_id,
+                            EventStatus::Aborted,
+                            Some(FailureCode::ShuttingDown),
+                        )
+                        .await;
+                    }
+                }
             }
+            drop(guard);
             channel.closed().await;
         }
         .in_current_span(),
@@ -143,6 +235,7 @@ async fn audit_event<DB: DataStore>(
     channel: &mut Channel<Authenticated<StdRng>>,
     context: &Context<DB>,
     status: EventStatus,
+    failure_code: Option<FailureCode>,
 ) {
     debug!("Creating audit event...");
     let account_id = channel.account_id();
@@ -150,10 +243,42 @@ async fn audit_event<DB: DataStore>(
     let request_id = channel.metadata().request_id();
 
     let result = context
-        .create_audit_event(account_id, request_id, client_action, status)
+        .create_audit_event(account_id, request_id, client_action, status, failure_code)
         .await;
 
     if let Err(e) = result {
         handle_error(channel, e).await;
     };
 }
+
+/// Log the given action of an unauthenticated request as an audit event for
+/// `account_id`. The error has already been sent to the client, so errors are
+/// only logged.
+#[instrument(skip(channel, context))]
+async fn unauthenticated_audit_event<DB: DataStore>(
+    channel: &mut Channel<Unauthenticated>,
+    context: &Context<DB>,
+    account_id: AccountId,
+    status: EventStatus,
+    failure_code: Option<FailureCode>,
+) {
+    debug!("Creating audit event...");
+    let client_action = channel.metadata().action();
+    let request_id = channel.metadata().request_id();
+
+    let result = context
+        .create_audit_event(account_id, request_id, client_action, status, failure_code)
+        .await;
+
+    if let Err(e) = result {
+        error!("Failed to create audit event: {}", e);
+    }
+}
+
+/// Checkpoint the audit log of the requesting account if one is due. The
+/// request has already finished, so errors are only logged.
+async fn checkpoint_audit_log<DB: DataStore>(context: &Context<DB>, account_id: AccountId) {
+    if let Err(e) = context.checkpoint_audit_log_if_due(account_id).await {
+        error!("Failed to checkpoint audit log: {}", e);
+    }
+}
@@ -0,0 +1,220 @@
+//! Readiness checks for load balancers and container orchestrators.
+//!
+//! The server is ready when the [`DataStore`] and [`SessionCache`] pass their
+//! `health_check` and the release TOML can be read. The result of every check
+//! is served by the `Readiness` RPC and, if `readiness_address` is
+//! configured, at `/readyz` over plain HTTP. Unlike the `Health` RPC, which
+//! only shows that the server is running, a server that isn't ready should not
+//! receive client traffic.
+
+use std::{convert::Infallible, fmt::Display, future::Future, net::SocketAddr, sync::Arc};
+
+use hyper::{
+    header::{HeaderValue, CONTENT_TYPE},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use lock_keeper::rpc::{ComponentStatus, ReadinessStatus};
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::{
+    config::SharedConfig,
+    server::{database::DataStore, session_cache::SessionCache, ReleaseInfo},
+    LockKeeperServerError,
+};
+
+/// How long a single component may take to respond before it is reported as
+/// not ready.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reported for a component whose check failed. The error itself is only
+/// logged, since readiness is served to unauthenticated callers and errors
+/// can contain connection details or file paths.
+const CHECK_FAILED: &str = "check failed";
+/// Reported for a component that didn't respond within [`CHECK_TIMEOUT`].
+const CHECK_TIMED_OUT: &str = "timed out";
+
+/// Checks whether the server's dependencies can serve requests.
+pub(crate) struct Readiness<DB> {
+    config: SharedConfig,
+    db: Arc<DB>,
+    session_cache: Arc<dyn SessionCache>,
+}
+
+impl<DB> Clone for Readiness<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            db: self.db.clone(),
+            session_cache: self.session_cache.clone(),
+        }
+    }
+}
+
+impl<DB: DataStore> Readiness<DB> {
+    pub(crate) fn new(
+        config: SharedConfig,
+        db: Arc<DB>,
+        session_cache: Arc<dyn SessionCache>,
+    ) -> Self {
+        Self {
+            config,
+            db,
+            session_cache,
+        }
+    }
+
+    /// Check every component. The server is ready if all of them are.
+    pub(crate) async fn check(&self) -> ReadinessStatus {
+        let release_toml_path = self.config.current().release_toml_path.clone();
+
+        let (database, session_cache, release_toml) = tokio::join!(
+            check_component("database", CHECK_TIMEOUT, self.db.health_check()),
+            check_component("session_cache", CHECK_TIMEOUT, async {
+                self.session_cache.health_check().await
+            }),
+            check_component("release_toml", CHECK_TIMEOUT, async {
+                ReleaseInfo::from_toml_file(release_toml_path).map(|_| ())
+            }),
+        );
+
+        let components = vec![database, session_cache, release_toml];
+        ReadinessStatus {
+            is_ready: components.iter().all(|component| component.is_ready),
+            components,
+        }
+    }
+
+    /// Bind an HTTP listener to `address` that serves the readiness status
+    /// at `/readyz`. The returned future runs the listener and must be
+    /// polled.
+    pub(crate) fn serve(
+        self,
+        address: SocketAddr,
+    ) -> Result<impl Future<Output = Result<(), LockKeeperServerError>>, LockKeeperServerError>
+    {
+        let make_service = make_service_fn(move |_| {
+            let readiness = self.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let readiness = readiness.clone();
+                    async move { Ok::<_, Infallible>(readiness.respond(&request).await) }
+                }))
+            }
+        });
+
+        let server = Server::try_bind(&address)?.serve(make_service);
+        info!(?address, "Serving readiness checks");
+
+        Ok(async move { Ok(server.await?) })
+    }
+
+    async fn respond(&self, request: &Request<Body>) -> Response<Body> {
+        if request.method() != Method::GET || request.uri().path() != "/readyz" {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            return response;
+        }
+
+        let status = self.check().await;
+        let mut response = Response::new(Body::from(format_status(&status)));
+        let _ = response.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; charset=utf-8"),
+        );
+        if !status.is_ready {
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        }
+        response
+    }
+}
+
+/// Run a single check, giving up after `timeout`.
+///
+/// Failures are logged in full but only reported as [`CHECK_FAILED`] or
+/// [`CHECK_TIMED_OUT`].
+async fn check_component<E: Display>(
+    name: &str,
+    timeout: Duration,
+    check: impl Future<Output = Result<(), E>>,
+) -> ComponentStatus {
+    let error = match tokio::time::timeout(timeout, check).await {
+        Ok(Ok(())) => None,
+        Ok(Err(error)) => {
+            warn!(component = name, %error, "Component is not ready.");
+            Some(CHECK_FAILED)
+        }
+        Err(_) => {
+            warn!(
+                component = name,
+                ?timeout,
+                "Component did not respond in time."
+            );
+            Some(CHECK_TIMED_OUT)
+        }
+    };
+
+    ComponentStatus {
+        name: name.to_string(),
+        is_ready: error.is_none(),
+        error: error.unwrap_or_default().to_string(),
+    }
+}
+
+/// One line per component, e.g. `database: ready`.
+fn format_status(status: &ReadinessStatus) -> String {
+    status
+        .components
+        .iter()
+        .map(|component| {
+            if component.is_ready {
+                format!("{}: ready\n", component.name)
+            } else {
+                format!("{}: not ready: {}\n", component.name, component.error)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn failed_checks_are_not_ready() {
+        let ready = check_component("database", CHECK_TIMEOUT, async { Ok::<_, String>(()) }).await;
+        assert!(ready.is_ready);
+        assert!(ready.error.is_empty());
+
+        let failed = check_component("session_cache", CHECK_TIMEOUT, async {
+            Err("connection refused".to_string())
+        })
+        .await;
+        // The error isn't passed on to callers
+        assert!(!failed.is_ready);
+        assert_eq!(failed.error, CHECK_FAILED);
+
+        let status = ReadinessStatus {
+            is_ready: false,
+            components: vec![ready, failed],
+        };
+        assert_eq!(
+            format_status(&status),
+            "database: ready\nsession_cache: not ready: check failed\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_checks_time_out() {
+        let status = check_component(
+            "database",
+            Duration::from_millis(10),
+            std::future::pending::<Result<(), LockKeeperServerError>>(),
+        )
+        .await;
+
+        assert!(!status.is_ready);
+        assert_eq!(status.error, CHECK_TIMED_OUT);
+    }
+}
@@ -1,68 +1,190 @@
 use crate::{
-    config::Config,
+    config::{Config, SharedConfig},
     error::LockKeeperServerError,
-    server::{database::DataStore, session_cache::SessionCache, LockKeeperKeyServer},
+    server::{
+        audit_retention::run_audit_retention,
+        client_certificate::ClientCertificate,
+        database::DataStore,
+        message_limit::limit_message_size,
+        metrics::{MeteredDataStore, MeteredSessionCache, Metrics},
+        readiness::Readiness,
+        session_cache::SessionCache,
+        shutdown::ShutdownCoordinator,
+        LockKeeperKeyServer,
+    },
 };
 
-use hyper::server::conn::Http;
+use hyper::{server::conn::Http, Body, Request};
 use lock_keeper::rpc::lock_keeper_rpc_server::LockKeeperRpcServer;
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
 use tokio::{
     net::{TcpListener, TcpStream},
-    signal,
-    sync::Mutex,
+    signal::{
+        self,
+        unix::{signal as unix_signal, SignalKind},
+    },
 };
 use tokio_rustls::TlsAcceptor;
 use tonic::transport::{server::Routes, Server};
 use tracing::{error, info};
 
+/// Re-reads the server configuration. It receives the config currently in use
+/// and returns the config to replace it with, usually built with
+/// [`Config::reload`].
+pub type ReloadConfig =
+    Box<dyn Fn(&Config) -> Result<Config, LockKeeperServerError> + Send + Sync + 'static>;
+
+/// IP address of the client connection, attached to every request made over
+/// that connection so that it can be recorded in audit events.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PeerAddress(pub(crate) IpAddr);
+
 /// Starts a full Lock Keeper server stack based on the given config.
+///
+/// If `reload_config` is provided, the config is reloaded whenever the server
+/// receives SIGHUP. New connections and requests use the reloaded config while
+/// existing ones keep running with the config they started with.
+///
+/// If audit retention is configured, expired audit events are archived in
+/// the background while the server runs.
+///
+/// Requests and calls to the database and session cache are recorded in
+/// `metrics`. Serving them is up to the caller, e.g. with [`Metrics::serve`].
+///
+/// If `readiness_address` is configured, readiness checks are served at
+/// `/readyz` on that address.
+///
+/// On SIGTERM or Ctrl-C the server stops accepting connections and requests,
+/// then waits up to the configured `shutdown_timeout` for running operations
+/// to finish before aborting them.
 pub async fn start_lock_keeper_server<DB: DataStore + Clone, S: SessionCache + 'static>(
     config: Config,
     db: DB,
     session_key_cache: S,
+    reload_config: Option<ReloadConfig>,
+    metrics: Metrics,
 ) -> Result<(), LockKeeperServerError> {
     info!("Starting Lock Keeper key server");
-    let db = Arc::new(db);
-    let session_key_cache = Arc::new(Mutex::new(session_key_cache));
+    let db = Arc::new(MeteredDataStore::new(db, metrics.clone()));
+    let session_key_cache: Arc<dyn SessionCache> =
+        Arc::new(MeteredSessionCache::new(session_key_cache, metrics.clone()));
+    let config = SharedConfig::new(config);
+    let shutdown = ShutdownCoordinator::default();
+    let readiness_server = config
+        .current()
+        .readiness_address
+        .map(|address| {
+            Readiness::new(config.clone(), db.clone(), session_key_cache.clone()).serve(address)
+        })
+        .transpose()?;
     // Collect the futures for the result of running each specified server
-    let server_future = start_service(&config, db, session_key_cache);
+    let server_future = start_service(
+        config.clone(),
+        db.clone(),
+        session_key_cache.clone(),
+        shutdown.clone(),
+        metrics.clone(),
+    );
+    let reload_future = reload_on_hangup(config.clone(), reload_config);
+    let retention_future = run_audit_retention(config.clone(), db);
+    let sessions_future = metrics.record_active_sessions(session_key_cache);
+    let readiness_future = async move {
+        match readiness_server {
+            Some(readiness_server) => readiness_server.await,
+            None => Ok(()),
+        }
+    };
 
     info!("Lock Keeper key server started");
 
     // Wait for the server to finish
     tokio::select! {
-        _ = signal::ctrl_c() => info!("Terminated by user"),
+        result = wait_for_termination() => result?,
         Err(e) = server_future => {
             error!("Error: {}", e);
         },
+        Err(e) = reload_future => {
+            error!("Error: {}", e);
+        },
+        Err(e) = retention_future => {
+            error!("Error: {}", e);
+        },
+        Err(e) = sessions_future => {
+            error!("Error: {}", e);
+        },
+        Err(e) = readiness_future => {
+            error!("Error: {}", e);
+        },
         else => {
             info!("Shutting down...")
         }
     }
+
+    shutdown.shutdown(config.current().shutdown_timeout).await;
+    Ok(())
+}
+
+/// Completes when the server receives SIGTERM or Ctrl-C.
+async fn wait_for_termination() -> Result<(), LockKeeperServerError> {
+    let mut terminate = unix_signal(SignalKind::terminate())?;
+    tokio::select! {
+        _ = signal::ctrl_c() => info!("Terminated by user"),
+        _ = terminate.recv() => info!("Received SIGTERM"),
+    }
+    Ok(())
+}
+
+/// Reloads the config every time the server receives SIGHUP. If reloading
+/// fails, the error is logged and the server keeps its current config.
+async fn reload_on_hangup(
+    config: SharedConfig,
+    reload_config: Option<ReloadConfig>,
+) -> Result<(), LockKeeperServerError> {
+    let reload_config = match reload_config {
+        Some(reload_config) => reload_config,
+        None => return Ok(()),
+    };
+
+    let mut hangup = unix_signal(SignalKind::hangup())?;
+    while hangup.recv().await.is_some() {
+        info!("Received SIGHUP. Reloading config.");
+        match reload_config(&config.current()) {
+            Ok(new_config) => {
+                config.replace(new_config);
+                info!("Config reloaded.");
+            }
+            Err(e) => error!(
+                "Failed to reload config. Keeping current config. Error: {}",
+                e
+            ),
+        }
+    }
+
     Ok(())
 }
 
 /// Starts a new thread that accepts connections and sends them through our
 /// service stack.
 async fn start_service<DB: DataStore + Clone>(
-    config: &Config,
+    config: SharedConfig,
     db: Arc<DB>,
-    session_key_cache: Arc<Mutex<dyn SessionCache>>,
+    session_key_cache: Arc<dyn SessionCache>,
+    shutdown: ShutdownCoordinator,
+    metrics: Metrics,
 ) -> Result<(), LockKeeperServerError> {
-    // Clone `Arc`s for the various resources we need in this server
-    let config = config.clone();
-    let db = db.clone();
-    let session_key_cache = session_key_cache.clone();
-
-    let tls_acceptor = config
-        .tls_config
-        .clone()
-        .map(|tls| TlsAcceptor::from(Arc::new(tls)));
-
-    let rpc_server = LockKeeperKeyServer::new(db, session_key_cache, config)?;
-    let addr = rpc_server.config.address;
-    let port = rpc_server.config.port;
+    let addr = config.current().address;
+    let port = config.current().port;
+
+    let rpc_server = LockKeeperKeyServer::with_shared_config(
+        db,
+        session_key_cache,
+        config.clone(),
+        shutdown.clone(),
+        metrics,
+    )?;
     info!(?addr, ?port, "Starting server with:");
 
     let svc = Server::builder()
@@ -77,7 +199,12 @@ async fn start_service<DB: DataStore + Clone>(
     // Spawn a task to accept connections
     let handle = tokio::spawn(async move {
         loop {
-            let (conn, client) = match listener.accept().await {
+            // Stop accepting connections once shutdown starts.
+            let incoming = tokio::select! {
+                incoming = listener.accept() => incoming,
+                _ = shutdown.shutting_down() => break,
+            };
+            let (conn, client) = match incoming {
                 Ok(incoming) => incoming,
                 Err(e) => {
                     error!("Error accepting connection: {}", e);
@@ -86,12 +213,17 @@ async fn start_service<DB: DataStore + Clone>(
             };
             info!(?client, "Accepted Connection from:");
             let http = http.clone();
-            let tls_acceptor = tls_acceptor.clone();
+            // Look up the acceptor for each connection so that reloaded certificates are
+            // used for new connections.
+            let tls_acceptor = config.tls_acceptor();
+            let max_message_size = config.current().max_message_size;
             let svc = svc.clone();
 
             // Spawn a task to handle each connection
             let handle = tokio::spawn(async move {
-                if let Err(e) = handle_connection(http, conn, tls_acceptor, svc).await {
+                if let Err(e) =
+                    handle_connection(http, conn, client, tls_acceptor, max_message_size, svc).await
+                {
                     // Log the error but don't bother returning it since it has nowhere to go.
                     error!("{}", e);
                 }
@@ -112,20 +244,52 @@ async fn start_service<DB: DataStore + Clone>(
 
 /// Processes an individual connection through our service stack including TLS
 /// and our `tonic` handler.
+///
+/// Request bodies end with an error as soon as the client starts a message
+/// that is larger than `max_message_size`, so that it isn't buffered.
 async fn handle_connection(
     http: Http,
     connection: TcpStream,
+    client: SocketAddr,
     tls_acceptor: Option<TlsAcceptor>,
+    max_message_size: usize,
     service: Routes,
 ) -> Result<(), LockKeeperServerError> {
-    let svc = tower::ServiceBuilder::new().service(service);
+    let peer_address = PeerAddress(client.ip());
 
     match tls_acceptor {
         Some(tls_acceptor) => {
             let conn = tls_acceptor.accept(connection).await?;
+
+            // Attach the client's certificate to every request on this connection so
+            // that it can be checked against the account making the request.
+            let (_, session) = conn.get_ref();
+            let client_certificate = session
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .map(|certificate| ClientCertificate::from_der(&certificate.0))
+                .transpose()?;
+
+            let svc = tower::ServiceBuilder::new()
+                .map_request(move |mut request: Request<Body>| {
+                    let _ = request.extensions_mut().insert(peer_address);
+                    if let Some(client_certificate) = &client_certificate {
+                        let _ = request.extensions_mut().insert(client_certificate.clone());
+                    }
+                    limit_message_size(request, max_message_size)
+                })
+                .service(service);
             http.serve_connection(conn, svc).await?;
         }
-        None => http.serve_connection(connection, svc).await?,
+        None => {
+            let svc = tower::ServiceBuilder::new()
+                .map_request(move |mut request: Request<Body>| {
+                    let _ = request.extensions_mut().insert(peer_address);
+                    limit_message_size(request, max_message_size)
+                })
+                .service(service);
+            http.serve_connection(connection, svc).await?
+        }
     }
 
     Ok(())
@@ -5,7 +5,7 @@ use crate::{
 use async_trait::async_trait;
 use lock_keeper::{
     crypto::{Encrypted, OpaqueSessionKey},
-    types::database::account::AccountId,
+    types::{database::account::AccountId, operations::ClientAction},
 };
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -20,6 +20,10 @@ pub struct Session {
     pub account_id: AccountId,
     pub timestamp: OffsetDateTime,
     pub session_key: Encrypted<OpaqueSessionKey>,
+    /// Set if this session was created by logging in with a service
+    /// credential. Such sessions are limited to the credential's allowed
+    /// actions.
+    pub service_credential_id: Option<Uuid>,
 }
 
 impl Session {
@@ -48,20 +52,29 @@ pub enum SessionCacheError {
     MissingSession,
     #[error("Session already exists for this user.")]
     SessionExists,
+    #[error("Request was already made on this session.")]
+    ReplayedRequest,
 }
 
 /// Cache holding our sessions, per user, after authentication. Maps
 /// [`AccountId`]s to [`Session`]s. Sessions should be tagged with a
 /// timestamp. A session is considered invalid after the expiration time has
 /// elapsed.
+///
+/// The server calls the cache from many requests at once without locking it,
+/// so implementations must synchronize their own state.
 #[async_trait]
 pub trait SessionCache: Send + Sync {
     /// Store a newly created session for the specified user. The previous
     /// session for that user should be overwritten.
+    ///
+    /// `service_credential_id` should be set if the session was established
+    /// with a service credential rather than the account password.
     async fn create_session(
         &self,
         account_id: AccountId,
         session_key: Encrypted<OpaqueSessionKey>,
+        service_credential_id: Option<Uuid>,
     ) -> Result<Uuid, SessionCacheError>;
 
     /// Get the session for the specified user, if one exists.
@@ -73,4 +86,27 @@ pub trait SessionCache: Send + Sync {
     /// user attempts to make a server call after expiring their session,
     /// they should need to authenticate again first.
     async fn delete_session(&self, session_id: Uuid) -> Result<(), SessionCacheError>;
+
+    /// Record that a request with the given ID and action was made on the
+    /// session. Returns [`SessionCacheError::ReplayedRequest`] if the same
+    /// request ID and action were already recorded for it.
+    ///
+    /// Messages on an authenticated channel are only bound to the request ID
+    /// and action, so this is what stops a recorded request from being
+    /// replayed in full on the same session. Recorded requests must be
+    /// removed along with their session.
+    async fn record_request(
+        &self,
+        session_id: Uuid,
+        request_id: Uuid,
+        action: ClientAction,
+    ) -> Result<(), SessionCacheError>;
+
+    /// Count the sessions that have not expired. Expired sessions that have
+    /// not been deleted yet must not be counted.
+    async fn count_active_sessions(&self) -> Result<u64, SessionCacheError>;
+
+    /// Check that the cache can currently serve requests. This is called by
+    /// readiness probes, so it must be quick and must not modify any sessions.
+    async fn health_check(&self) -> Result<(), SessionCacheError>;
 }
@@ -0,0 +1,182 @@
+//! Graceful shutdown for the key server.
+//!
+//! Every operation task spawned by the server is tracked by the
+//! [`ShutdownCoordinator`]. When shutdown starts, new requests are rejected
+//! and running operations are given until a deadline to finish. Operations
+//! still running after the deadline are cancelled, and authenticated
+//! operations record an [`Aborted`](lock_keeper::types::audit_event::EventStatus::Aborted)
+//! audit event so that every `Started` event has a matching end event.
+
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use tokio::sync::{mpsc, Mutex as AsyncMutex};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFuture};
+use tracing::{info, warn};
+
+use crate::LockKeeperServerError;
+
+/// How long cancelled operations get to record their audit events after the
+/// shutdown deadline passes.
+const ABORT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Tracks running operations and coordinates shutting them down.
+#[derive(Clone, Debug)]
+pub(crate) struct ShutdownCoordinator {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// Cancelled when shutdown starts. No new operations are accepted after
+    /// this.
+    shutting_down: CancellationToken,
+    /// Cancelled when the shutdown deadline passes. Operations that are still
+    /// running stop as soon as they see this.
+    abort: CancellationToken,
+    /// Each running operation holds a clone of this sender. Once shutdown
+    /// starts, the coordinator drops its own copy so `drained` only returns
+    /// `None` once every operation has finished.
+    operations: Mutex<Option<mpsc::Sender<()>>>,
+    drained: AsyncMutex<mpsc::Receiver<()>>,
+}
+
+impl Default for ShutdownCoordinator {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel(1);
+
+        Self {
+            inner: Arc::new(Inner {
+                shutting_down: CancellationToken::new(),
+                abort: CancellationToken::new(),
+                operations: Mutex::new(Some(sender)),
+                drained: AsyncMutex::new(receiver),
+            }),
+        }
+    }
+}
+
+impl ShutdownCoordinator {
+    /// Start tracking a new operation. The operation is tracked until the
+    /// returned [`OperationGuard`] is dropped.
+    ///
+    /// Returns [`LockKeeperServerError::ShuttingDown`] if shutdown has
+    /// started.
+    pub(crate) fn track(&self) -> Result<OperationGuard, LockKeeperServerError> {
+        let operations = self
+            .inner
+            .operations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        match operations.as_ref() {
+            Some(sender) if !self.is_shutting_down() => Ok(OperationGuard {
+                _sender: sender.clone(),
+                abort: self.inner.abort.clone(),
+            }),
+            _ => Err(LockKeeperServerError::ShuttingDown),
+        }
+    }
+
+    /// Returns `true` if shutdown has started.
+    pub(crate) fn is_shutting_down(&self) -> bool {
+        self.inner.shutting_down.is_cancelled()
+    }
+
+    /// Completes when shutdown starts.
+    pub(crate) fn shutting_down(&self) -> WaitForCancellationFuture<'_> {
+        self.inner.shutting_down.cancelled()
+    }
+
+    /// Stop accepting new operations and wait up to `timeout` for running
+    /// operations to finish. Any operations still running after that are
+    /// aborted.
+    pub(crate) async fn shutdown(&self, timeout: Duration) {
+        info!(?timeout, "Shutting down. Waiting for running operations.");
+        self.inner.shutting_down.cancel();
+        let _ = self
+            .inner
+            .operations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+
+        let mut drained = self.inner.drained.lock().await;
+        if tokio::time::timeout(timeout, drained.recv()).await.is_ok() {
+            info!("All operations finished.");
+            return;
+        }
+
+        warn!("Shutdown deadline passed. Aborting running operations.");
+        self.inner.abort.cancel();
+        if tokio::time::timeout(ABORT_GRACE_PERIOD, drained.recv())
+            .await
+            .is_err()
+        {
+            warn!("Some operations did not stop after being aborted.");
+        }
+    }
+}
+
+/// Held by a running operation. Dropping it marks the operation as finished.
+#[derive(Debug)]
+pub(crate) struct OperationGuard {
+    _sender: mpsc::Sender<()>,
+    abort: CancellationToken,
+}
+
+impl OperationGuard {
+    /// Completes when the operation should be aborted because the server is
+    /// shutting down.
+    pub(crate) fn aborted(&self) -> WaitForCancellationFuture<'_> {
+        self.abort.cancelled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn shutdown_waits_for_running_operations() {
+        let coordinator = ShutdownCoordinator::default();
+        let guard = coordinator.track().unwrap();
+
+        let operation = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(guard);
+        });
+
+        coordinator.shutdown(Duration::from_secs(10)).await;
+        assert!(operation.is_finished());
+    }
+
+    #[tokio::test]
+    async fn new_operations_are_rejected_after_shutdown() {
+        let coordinator = ShutdownCoordinator::default();
+        coordinator.shutdown(Duration::from_secs(1)).await;
+
+        assert!(coordinator.is_shutting_down());
+        assert!(matches!(
+            coordinator.track(),
+            Err(LockKeeperServerError::ShuttingDown)
+        ));
+    }
+
+    #[tokio::test]
+    async fn operations_are_aborted_after_deadline() {
+        let coordinator = ShutdownCoordinator::default();
+        let guard = coordinator.track().unwrap();
+
+        let operation = tokio::spawn(async move {
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(60)) => false,
+                _ = guard.aborted() => true,
+            }
+        });
+
+        coordinator.shutdown(Duration::from_millis(10)).await;
+        assert!(operation.await.unwrap());
+    }
+}
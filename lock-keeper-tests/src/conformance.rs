@@ -0,0 +1,67 @@
+//! Conformance tests for [`DataStore`] and [`SessionCache`] implementations.
+//!
+//! The key server only relies on the behavior described by these traits, so
+//! any backend that passes these tests can be used in place of the backends in
+//! this repository. To check your own backend, implement [`Backend`] for a
+//! type that creates it and pass it to [`run_conformance_tests`]:
+//!
+//! ```ignore
+//! let results = run_conformance_tests(&TestFilters::default(), MyBackend).await?;
+//! assert!(results.iter().all(|r| *r != TestResult::Failed));
+//! ```
+//!
+//! The tests cover account, secret, audit event, transaction and session
+//! invariants, including uniqueness constraints,
+//! [`DatabaseError::IncorrectKeyMetadata`], [`DatabaseError::WrongSecretType`]
+//! and [`DatabaseError::NoEntry`] errors, audit event time filters, and audit
+//! event hash chains. Tests run in
+//! parallel and create their own accounts, so a single data store instance is
+//! shared between them.
+//!
+//! [`DatabaseError::IncorrectKeyMetadata`]: lock_keeper_key_server::server::database::DatabaseError::IncorrectKeyMetadata
+//! [`DatabaseError::WrongSecretType`]: lock_keeper_key_server::server::database::DatabaseError::WrongSecretType
+//! [`DatabaseError::NoEntry`]: lock_keeper_key_server::server::database::DatabaseError::NoEntry
+
+use async_trait::async_trait;
+use lock_keeper_key_server::server::{database::DataStore, session_cache::SessionCache};
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    config::TestFilters,
+    error::Result,
+    test_suites::{database, database::TestDatabase, session_cache},
+    utils::TestResult,
+};
+
+/// Creates the [`DataStore`] and [`SessionCache`] under test.
+#[async_trait]
+pub trait Backend: Send + Sync + 'static {
+    type DataStore: DataStore + Clone;
+    type SessionCache: SessionCache;
+
+    /// Name used when reporting test results.
+    fn name(&self) -> &str;
+
+    /// Create the data store. It may already contain data from other runs.
+    async fn data_store(&self) -> Result<Self::DataStore>;
+
+    /// Create a session cache whose sessions expire after `expiration`. This
+    /// is called once per test so that tests can use different expiration
+    /// times.
+    async fn session_cache(&self, expiration: Duration) -> Result<Self::SessionCache>;
+}
+
+/// Run every [`DataStore`] and [`SessionCache`] test against the given
+/// backend. Use `filters` to only run tests whose names match.
+pub async fn run_conformance_tests<B: Backend>(
+    filters: &TestFilters,
+    backend: B,
+) -> Result<Vec<TestResult>> {
+    let backend = Arc::new(backend);
+
+    let db = TestDatabase::new(backend.data_store().await?);
+    let database_results = database::run_tests(filters, backend.name(), db).await?;
+    let session_cache_results = session_cache::run_tests(filters, backend).await?;
+
+    Ok([database_results, session_cache_results].concat())
+}
@@ -1,5 +1,6 @@
 use lock_keeper_postgres::PostgresError;
 use lock_keeper_session_cache_sql::Error as SessionCachePostgresError;
+use lock_keeper_sqlite::SqliteError;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, LockKeeperTestError>;
@@ -30,6 +31,10 @@ pub enum LockKeeperTestError {
     Io(#[from] std::io::Error),
     #[error("LockKeeperError: {0:?}")]
     LockKeeper(#[from] lock_keeper::LockKeeperError),
+    #[error("AuditChainError: {0:?}")]
+    AuditChain(#[from] lock_keeper::types::audit_chain::AuditChainError),
+    #[error("CryptoError: {0:?}")]
+    Crypto(#[from] lock_keeper::crypto::CryptoError),
     #[error("LockKeeperClientError: {0:?}")]
     LockKeeperClient(#[from] lock_keeper_client::LockKeeperClientError),
     #[error("LockKeeperServerError: {0:?}")]
@@ -42,6 +47,8 @@ pub enum LockKeeperTestError {
     Database(#[from] lock_keeper_key_server::server::database::DatabaseError),
     #[error("PostgresError error: {0:?}")]
     LockKeeperPostgres(#[from] PostgresError),
+    #[error("SqliteError error: {0:?}")]
+    LockKeeperSqlite(#[from] SqliteError),
     #[error("RandError: {0:?}")]
     Rand(#[from] rand::Error),
     #[error(transparent)]
@@ -0,0 +1,52 @@
+//! Test harness for the Lock Keeper workspace.
+//!
+//! Besides the test binary, this crate exposes the [`conformance`] module so
+//! that other [`DataStore`](lock_keeper_key_server::server::database::DataStore)
+//! and [`SessionCache`](lock_keeper_key_server::server::session_cache::SessionCache)
+//! implementations can be checked against the same tests as the backends in
+//! this repository.
+
+pub mod config;
+pub mod conformance;
+pub mod error;
+pub mod test_suites;
+pub mod utils;
+
+use crate::error::LockKeeperTestError;
+use clap::Parser;
+use std::{path::PathBuf, str::FromStr};
+
+#[derive(Debug, Parser)]
+pub struct Cli {
+    #[clap(long, default_value = "./dev/config/TestEnvironments.toml")]
+    pub environments: PathBuf,
+    #[clap(long = "filter")]
+    pub filters: Option<Vec<String>>,
+    #[clap(long, default_value = "all")]
+    pub test_type: TestType,
+    #[clap(long, short = 's')]
+    pub standard_only: bool,
+    /// Prints all errors even if the test was successful.
+    #[clap(long)]
+    pub print_errors: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestType {
+    All,
+    E2E,
+    Integration,
+}
+
+impl FromStr for TestType {
+    type Err = LockKeeperTestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(TestType::All),
+            "e2e" => Ok(TestType::E2E),
+            "integration" => Ok(TestType::Integration),
+            _ => Err(LockKeeperTestError::InvalidTestType(s.to_string())),
+        }
+    }
+}
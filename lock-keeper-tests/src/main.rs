@@ -1,55 +1,16 @@
-pub mod config;
-pub mod error;
-pub mod test_suites;
-pub mod utils;
-
-use crate::{
+use clap::Parser;
+use colored::Colorize;
+use lock_keeper_tests::{
+    config::Environments,
     error::LockKeeperTestError,
+    test_suites,
     utils::{report_test_results, TestResult},
+    Cli, TestType,
 };
-use clap::Parser;
-use colored::Colorize;
-use config::Environments;
-use std::{path::PathBuf, str::FromStr};
 use tracing_subscriber::{
     filter::Targets, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
 };
 
-#[derive(Debug, Parser)]
-pub struct Cli {
-    #[clap(long, default_value = "./dev/config/TestEnvironments.toml")]
-    pub environments: PathBuf,
-    #[clap(long = "filter")]
-    pub filters: Option<Vec<String>>,
-    #[clap(long, default_value = "all")]
-    pub test_type: TestType,
-    #[clap(long, short = 's')]
-    pub standard_only: bool,
-    /// Prints all errors even if the test was successful.
-    #[clap(long)]
-    pub print_errors: bool,
-}
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum TestType {
-    All,
-    E2E,
-    Integration,
-}
-
-impl FromStr for TestType {
-    type Err = LockKeeperTestError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "all" => Ok(TestType::All),
-            "e2e" => Ok(TestType::E2E),
-            "integration" => Ok(TestType::Integration),
-            _ => Err(LockKeeperTestError::InvalidTestType(s.to_string())),
-        }
-    }
-}
-
 #[tokio::main]
 pub async fn main() {
     // Run tests and print nice errors if any occur.
@@ -134,9 +95,7 @@ async fn run_integration_tests(
 ) -> Result<Vec<TestResult>, LockKeeperTestError> {
     let client_auth_results = test_suites::client_auth::run_tests(environments).await?;
     let config_file_results = test_suites::config_files::run_tests(&environments.filters).await?;
-    let database_results = test_suites::database::run_tests(&environments.filters).await?;
-    let session_cache_results =
-        test_suites::session_cache::run_tests(&environments.filters).await?;
+    let persistence_results = test_suites::persistence::run_tests(&environments.filters).await?;
 
     println!(
         "client auth tests: {}",
@@ -146,17 +105,15 @@ async fn run_integration_tests(
         "config file tests: {}",
         report_test_results(&config_file_results)
     );
-    println!("database tests: {}", report_test_results(&database_results));
     println!(
-        "session cache tests: {}",
-        report_test_results(&session_cache_results)
+        "persistence tests: {}",
+        report_test_results(&persistence_results)
     );
 
     Ok([
         config_file_results,
-        database_results,
+        persistence_results,
         client_auth_results,
-        session_cache_results,
     ]
     .concat())
 }
@@ -2,4 +2,5 @@ pub mod client_auth;
 pub mod config_files;
 pub mod database;
 pub mod end_to_end;
+pub mod persistence;
 pub mod session_cache;
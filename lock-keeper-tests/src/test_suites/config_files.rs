@@ -29,6 +29,8 @@ pub async fn run_tests(filters: &TestFilters) -> Result<Vec<TestResult>> {
         server_config_without_private_key_fails(),
         server_config_without_remote_storage_key_fails(),
         server_config_without_opaque_server_setup_fails(),
+        server_config_reload_applies_mutable_settings(),
+        server_config_reload_without_private_key_fails(),
     )?;
 
     println!("config file tests: {}", report_test_results(&results));
@@ -153,6 +155,43 @@ async fn server_config_without_opaque_server_setup_fails() -> Result<()> {
     Ok(())
 }
 
+async fn server_config_reload_applies_mutable_settings() -> Result<()> {
+    use lock_keeper_key_server::config::{Config as ServerConfig, ConfigFile};
+
+    let config_file = ConfigFile::from_str(SERVER_CONFIG_WITH_KEY)?;
+    let config = ServerConfig::from_config_file(config_file, None, None, None)?;
+
+    let mut config_file = ConfigFile::from_str(SERVER_CONFIG_WITH_KEY)?;
+    config_file.max_blob_size = 2048;
+    config_file.port = 2224;
+    config_file.logging.stdout_log_level = tracing::Level::DEBUG;
+
+    let reloaded = config.reload(config_file, None)?;
+    assert_eq!(reloaded.max_blob_size, 2048);
+    assert_eq!(reloaded.logging.stdout_log_level, tracing::Level::DEBUG);
+    assert!(reloaded.tls_config.is_some());
+    // Port can't change without a restart
+    assert_eq!(reloaded.port, config.port);
+
+    Ok(())
+}
+
+async fn server_config_reload_without_private_key_fails() -> Result<()> {
+    use lock_keeper_key_server::config::{Config as ServerConfig, ConfigFile};
+
+    let config_file = ConfigFile::from_str(SERVER_CONFIG_WITH_KEY)?;
+    let config = ServerConfig::from_config_file(config_file, None, None, None)?;
+
+    let config_file = ConfigFile::from_str(SERVER_CONFIG_NO_KEY)?;
+    let reloaded = config.reload(config_file, None);
+    assert!(matches!(
+        reloaded,
+        Err(LockKeeperServerError::PrivateKeyMissing)
+    ));
+
+    Ok(())
+}
+
 const CLIENT_CONFIG_NO_KEY: &str = r#"
 server_uri = "https://localhost:1114"
 ca_chain = "dev/test-pki/gen/ca/signing-ca.chain"
@@ -1,11 +1,15 @@
 //! Database integration tests
 
 pub mod audit_event;
+pub mod certificate_binding;
+pub mod health;
 pub mod secret;
+pub mod service_credential;
+pub mod transaction;
 pub mod user;
 
 use generic_array::{typenum::U64, GenericArray};
-use std::{ops::Deref, str::FromStr};
+use std::ops::Deref;
 
 use crate::{
     config::TestFilters,
@@ -23,25 +27,55 @@ use lock_keeper::{
     },
 };
 use lock_keeper_key_server::server::database::DataStore;
-use lock_keeper_postgres::{Config, ConfigFile as DatabaseConfigFile, PostgresDB, PostgresError};
 use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::utils::{server_registration, tagged};
 
-pub async fn run_tests(filters: &TestFilters) -> Result<Vec<TestResult>> {
-    println!("Running database tests");
-
-    let audit_event_results = audit_event::run_tests(filters).await?;
-    let user_results = user::run_tests(filters).await?;
-    let secret_results = secret::run_tests(filters).await?;
+/// Runs every database test against the given backend.
+pub async fn run_tests<DB: DataStore + Clone>(
+    filters: &TestFilters,
+    backend: &str,
+    db: TestDatabase<DB>,
+) -> Result<Vec<TestResult>> {
+    println!("Running database tests for backend: {backend}");
+
+    let audit_event_results = audit_event::run_tests(filters, db.clone()).await?;
+    let user_results = user::run_tests(filters, db.clone()).await?;
+    let secret_results = secret::run_tests(filters, db.clone()).await?;
+    let service_credential_results = service_credential::run_tests(filters, db.clone()).await?;
+    let certificate_binding_results = certificate_binding::run_tests(filters, db.clone()).await?;
+    let transaction_results = transaction::run_tests(filters, db.clone()).await?;
+    let health_results = health::run_tests(filters, db).await?;
 
     // Report results after all tests finish so results show up together
     println!(
-        "audit event tests: {}",
+        "{backend} audit event tests: {}",
         report_test_results(&audit_event_results)
     );
-    println!("user tests: {}", report_test_results(&user_results));
-    println!("secret tests: {}", report_test_results(&secret_results));
+    println!(
+        "{backend} user tests: {}",
+        report_test_results(&user_results)
+    );
+    println!(
+        "{backend} secret tests: {}",
+        report_test_results(&secret_results)
+    );
+    println!(
+        "{backend} service credential tests: {}",
+        report_test_results(&service_credential_results)
+    );
+    println!(
+        "{backend} certificate binding tests: {}",
+        report_test_results(&certificate_binding_results)
+    );
+    println!(
+        "{backend} transaction tests: {}",
+        report_test_results(&transaction_results)
+    );
+    println!(
+        "{backend} health tests: {}",
+        report_test_results(&health_results)
+    );
 
     println!();
 
@@ -49,47 +83,37 @@ pub async fn run_tests(filters: &TestFilters) -> Result<Vec<TestResult>> {
         .into_iter()
         .chain(user_results)
         .chain(secret_results)
+        .chain(service_credential_results)
+        .chain(certificate_binding_results)
+        .chain(transaction_results)
+        .chain(health_results)
         .collect();
 
     Ok(results)
 }
 
+/// Wraps a [`DataStore`] with helpers for creating test data. Tests are generic
+/// over the backend so that every backend is held to the same behavior.
 #[derive(Clone, Debug)]
-pub struct TestDatabase {
-    pub db: PostgresDB,
+pub struct TestDatabase<DB: DataStore> {
+    pub db: DB,
 }
 
-impl Deref for TestDatabase {
-    type Target = PostgresDB;
+impl<DB: DataStore> Deref for TestDatabase<DB> {
+    type Target = DB;
 
     fn deref(&self) -> &Self::Target {
         &self.db
     }
 }
 
-impl TestDatabase {
-    fn blob_test_data() -> Vec<u8> {
-        vec![42; 42]
+impl<DB: DataStore> TestDatabase<DB> {
+    pub fn new(db: DB) -> Self {
+        Self { db }
     }
 
-    pub async fn connect() -> Result<Self> {
-        let config_str = r#"
-            username = 'test' 
-            password = 'test_password'
-            address = 'localhost'
-            db_name = 'test'
-            min_connections = 2
-            max_connections = 5
-            connection_retries = 5
-            connection_retry_delay = "5s"
-            connection_timeout = "3s"
-            "#;
-
-        let config_file =
-            DatabaseConfigFile::from_str(config_str).map_err(PostgresError::ConfigError)?;
-        let config: Config = config_file.try_into().map_err(PostgresError::ConfigError)?;
-        let db = PostgresDB::connect(config).await.unwrap();
-        Ok(TestDatabase { db })
+    fn blob_test_data() -> Vec<u8> {
+        vec![42; 42]
     }
 
     /// Create a master key for testing using random bytes.
@@ -170,7 +194,7 @@ impl TestDatabase {
         let key_id = KeyId::generate(rng, &account.user_id)?;
         let encryption_key = RemoteStorageKey::generate(rng);
 
-        let blob = DataBlob::create(TestDatabase::blob_test_data(), &account.user_id, &key_id)?;
+        let blob = DataBlob::create(Self::blob_test_data(), &account.user_id, &key_id)?;
         let encrypted = encryption_key.encrypt_data_blob(rng, blob)?;
 
         let secret = StoredSecret::from_data_blob(key_id.clone(), account.id(), encrypted)?;
@@ -2,9 +2,13 @@
 
 use colored::Colorize;
 use lock_keeper::{
-    crypto::KeyId,
+    crypto::{AuditSigningKey, KeyId},
     types::{
-        audit_event::{AuditEvent, AuditEventOptions, EventStatus, EventType},
+        audit_chain::{verify_audit_chain, AuditCheckpoint},
+        audit_event::{
+            AuditEvent, AuditEventDetails, AuditEventOptions, EventStatus, EventType, FailureCode,
+            PayloadHash,
+        },
         database::account::Account,
         operations::ClientAction,
     },
@@ -24,10 +28,12 @@ use crate::{config::TestFilters, error::Result, run_parallel, utils::TestResult}
 
 use super::TestDatabase;
 
-pub async fn run_tests(filters: &TestFilters) -> Result<Vec<TestResult>> {
+pub async fn run_tests<DB: DataStore + Clone>(
+    filters: &TestFilters,
+    db: TestDatabase<DB>,
+) -> Result<Vec<TestResult>> {
     println!("{}", "Running audit event tests".cyan());
 
-    let db = TestDatabase::connect().await?;
     let result = run_parallel!(
         filters,
         event_type_filter_works(db.clone()),
@@ -35,7 +41,17 @@ pub async fn run_tests(filters: &TestFilters) -> Result<Vec<TestResult>> {
         after_date_filter_works(db.clone()),
         before_date_filter_works(db.clone()),
         request_id_filter_works(db.clone()),
+        date_range_filter_works(db.clone()),
+        audit_events_are_scoped_to_account(db.clone()),
         store_audit_event_identity(db.clone()),
+        store_audit_event_details(db.clone()),
+        detail_filters_work(db.clone()),
+        audit_events_are_chained(db.clone()),
+        concurrent_audit_events_are_chained(db.clone()),
+        audit_checkpoints_are_stored(db.clone()),
+        audit_events_are_paginated(db.clone()),
+        all_audit_events_span_accounts(db.clone()),
+        archived_audit_events_can_be_restored(db.clone()),
     )?;
 
     Ok(result)
@@ -46,7 +62,7 @@ const NUM_SAMPLE: usize = NUM_LOGS as usize / 2;
 const HOW_MANY_SECRETS: usize = 50;
 
 /// Tests that storing an event returns the same event back out.
-async fn store_audit_event_identity(db: TestDatabase) -> Result<()> {
+async fn store_audit_event_identity<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     // Create and store a single audit event.
 
     let action_list = ClientAction::iter().collect::<Vec<_>>();
@@ -85,10 +101,136 @@ async fn store_audit_event_identity(db: TestDatabase) -> Result<()> {
     Ok(())
 }
 
+/// Every detail of a stored event is returned unchanged, both when it is
+/// created and when it is found.
+async fn store_audit_event_details<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let request_id = Uuid::new_v4();
+    let details = AuditEventDetails {
+        client_identity: Some("dns:client.example.com".to_string()),
+        peer_address: Some("2001:db8::1".parse().unwrap()),
+        failure_code: Some(FailureCode::KeyNotFound),
+        payload_size: Some(1024),
+        payload_hash: Some(PayloadHash::of(b"bytes to sign")),
+    };
+
+    let event = db
+        .create_audit_event(
+            request_id,
+            account.id(),
+            &None,
+            ClientAction::RemoteSignBytes,
+            EventStatus::Failed,
+            &details,
+        )
+        .await?;
+    assert_eq!(event.details(), &details);
+
+    let options = AuditEventOptions {
+        request_id: Some(request_id),
+        ..Default::default()
+    };
+    let events = db
+        .find_audit_events(account.id(), EventType::All, options)
+        .await?;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].details(), &details);
+    assert_eq!(events[0].hash(), event.hash());
+
+    Ok(())
+}
+
+/// Events can be filtered by failure code, peer address and client identity.
+async fn detail_filters_work<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let identity = format!("dns:{}.example.com", Uuid::new_v4());
+    let all_details = [
+        AuditEventDetails {
+            peer_address: Some([10, 0, 0, 1].into()),
+            failure_code: Some(FailureCode::InvalidSession),
+            ..Default::default()
+        },
+        AuditEventDetails {
+            peer_address: Some([10, 0, 0, 2].into()),
+            failure_code: Some(FailureCode::PermissionDenied),
+            client_identity: Some(identity.clone()),
+            ..Default::default()
+        },
+        AuditEventDetails {
+            peer_address: Some([10, 0, 0, 2].into()),
+            ..Default::default()
+        },
+    ];
+    for details in &all_details {
+        let status = match details.failure_code {
+            Some(_) => EventStatus::Failed,
+            None => EventStatus::Successful,
+        };
+        db.create_audit_event(
+            Uuid::new_v4(),
+            account.id(),
+            &None,
+            ClientAction::Authenticate,
+            status,
+            details,
+        )
+        .await?;
+    }
+
+    let events = find_details(
+        &db,
+        &account,
+        AuditEventOptions {
+            failure_code: Some(FailureCode::InvalidSession),
+            ..Default::default()
+        },
+    )
+    .await?;
+    assert_eq!(events, all_details[..1]);
+
+    let mut events = find_details(
+        &db,
+        &account,
+        AuditEventOptions {
+            peer_address: Some([10, 0, 0, 2].into()),
+            ..Default::default()
+        },
+    )
+    .await?;
+    events.sort_by_key(|details| details.failure_code.is_none());
+    assert_eq!(events, all_details[1..]);
+
+    let events = find_details(
+        &db,
+        &account,
+        AuditEventOptions {
+            client_identity: Some(identity),
+            ..Default::default()
+        },
+    )
+    .await?;
+    assert_eq!(events, all_details[1..2]);
+
+    Ok(())
+}
+
+/// Details of the account's events that match `options`.
+async fn find_details<DB: DataStore>(
+    db: &TestDatabase<DB>,
+    account: &Account,
+    options: AuditEventOptions,
+) -> Result<Vec<AuditEventDetails>> {
+    let events = db
+        .find_audit_events(account.id(), EventType::All, options)
+        .await?;
+
+    Ok(events.into_iter().map(|event| event.details).collect())
+}
+
 /// Check that the DB test filters work by ensuring queries to the DB with
 /// specific [`EventType`]s return only the specified types of audit event log
 /// events.
-async fn event_type_filter_works(db: TestDatabase) -> Result<()> {
+async fn event_type_filter_works<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     let account = db.create_test_user().await?;
     let key_ids = db
         .create_random_arbitrary_secrets(HOW_MANY_SECRETS, &account)
@@ -125,7 +267,7 @@ async fn event_type_filter_works(db: TestDatabase) -> Result<()> {
     Ok(())
 }
 
-async fn key_id_filter_works(db: TestDatabase) -> Result<()> {
+async fn key_id_filter_works<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     let account = db.create_test_user().await?;
     let key_ids = db
         .create_random_arbitrary_secrets(HOW_MANY_SECRETS, &account)
@@ -151,7 +293,7 @@ async fn key_id_filter_works(db: TestDatabase) -> Result<()> {
     Ok(())
 }
 
-async fn after_date_filter_works(db: TestDatabase) -> Result<()> {
+async fn after_date_filter_works<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     // Add a user
     let account = db.create_test_user().await?;
 
@@ -178,7 +320,7 @@ async fn after_date_filter_works(db: TestDatabase) -> Result<()> {
     Ok(())
 }
 
-async fn before_date_filter_works(db: TestDatabase) -> Result<()> {
+async fn before_date_filter_works<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     // Add a user
     let account = db.create_test_user().await?;
 
@@ -205,7 +347,7 @@ async fn before_date_filter_works(db: TestDatabase) -> Result<()> {
     Ok(())
 }
 
-async fn request_id_filter_works(db: TestDatabase) -> Result<()> {
+async fn request_id_filter_works<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     let account = db.create_test_user().await?;
     let key_ids = db
         .create_random_arbitrary_secrets(HOW_MANY_SECRETS, &account)
@@ -232,12 +374,316 @@ async fn request_id_filter_works(db: TestDatabase) -> Result<()> {
     Ok(())
 }
 
+/// Only events between `after_date` and `before_date` are returned when both
+/// are set.
+async fn date_range_filter_works<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let key_ids = db
+        .create_random_arbitrary_secrets(HOW_MANY_SECRETS, &account)
+        .await?;
+
+    // Space the batches out so that timestamp precision differences between
+    // backends can't move an event across a boundary.
+    let pause = std::time::Duration::from_millis(10);
+    let _ = create_random_audit_events(&account, &key_ids, &db).await?;
+    tokio::time::sleep(pause).await;
+    let after_date = OffsetDateTime::now_utc();
+    tokio::time::sleep(pause).await;
+    let (_, expected_request_ids) = create_random_audit_events(&account, &key_ids, &db).await?;
+    tokio::time::sleep(pause).await;
+    let before_date = OffsetDateTime::now_utc();
+    tokio::time::sleep(pause).await;
+    let _ = create_random_audit_events(&account, &key_ids, &db).await?;
+
+    let options = AuditEventOptions {
+        after_date: Some(after_date),
+        before_date: Some(before_date),
+        ..Default::default()
+    };
+    let audit_events = db
+        .find_audit_events(account.id(), EventType::All, options)
+        .await?;
+
+    let mut actual_request_ids: Vec<Uuid> = audit_events.iter().map(|a| *a.request_id()).collect();
+    let mut expected_request_ids = expected_request_ids;
+    actual_request_ids.sort();
+    expected_request_ids.sort();
+    assert_eq!(actual_request_ids, expected_request_ids);
+
+    Ok(())
+}
+
+/// Events are only returned for the account that they belong to.
+async fn audit_events_are_scoped_to_account<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let other_account = db.create_test_user().await?;
+    let key_ids = db
+        .create_random_arbitrary_secrets(HOW_MANY_SECRETS, &account)
+        .await?;
+    let (_, request_ids) = create_random_audit_events(&account, &key_ids, &db).await?;
+
+    let audit_events = db
+        .find_audit_events(account.id(), EventType::All, Default::default())
+        .await?;
+    assert_eq!(audit_events.len(), NUM_LOGS as usize);
+
+    let options = AuditEventOptions {
+        request_id: Some(request_ids[0]),
+        ..Default::default()
+    };
+    let audit_events = db
+        .find_audit_events(other_account.id(), EventType::All, options)
+        .await?;
+    assert!(audit_events.is_empty());
+
+    Ok(())
+}
+
+/// Each stored event links to the hash of the previous event for its account.
+async fn audit_events_are_chained<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let public_key = AuditSigningKey::generate(&mut StdRng::from_entropy()).public_key()?;
+
+    assert!(db.find_latest_audit_event(account.id()).await?.is_none());
+
+    let key_ids = db
+        .create_random_arbitrary_secrets(HOW_MANY_SECRETS, &account)
+        .await?;
+    let _ = create_random_audit_events(&account, &key_ids, &db).await?;
+
+    let mut audit_events = db
+        .find_audit_events(account.id(), EventType::All, Default::default())
+        .await?;
+    audit_events.sort_by_key(|event| event.audit_event_id);
+    assert_eq!(audit_events.len(), NUM_LOGS as usize);
+    assert!(audit_events[0].previous_hash().is_none());
+    verify_audit_chain(&audit_events, &[], &public_key)?;
+
+    let latest = db.find_latest_audit_event(account.id()).await?.unwrap();
+    assert_eq!(latest.hash(), audit_events.last().unwrap().hash());
+
+    Ok(())
+}
+
+/// Events created at the same time for one account still form a single chain.
+async fn concurrent_audit_events_are_chained<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let public_key = AuditSigningKey::generate(&mut StdRng::from_entropy()).public_key()?;
+
+    let details = AuditEventDetails::default();
+    let requests = (0..NUM_LOGS).map(|_| {
+        db.create_audit_event(
+            Uuid::new_v4(),
+            account.id(),
+            &None,
+            ClientAction::Authenticate,
+            EventStatus::Successful,
+            &details,
+        )
+    });
+    for result in futures::future::join_all(requests).await {
+        result?;
+    }
+
+    let audit_events = db
+        .find_audit_events(account.id(), EventType::All, Default::default())
+        .await?;
+    assert_eq!(audit_events.len(), NUM_LOGS as usize);
+    verify_audit_chain(&audit_events, &[], &public_key)?;
+
+    Ok(())
+}
+
+/// Paging through events with a limit and cursor returns every event once, in
+/// order of their IDs.
+async fn audit_events_are_paginated<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    const PAGE_SIZE: u32 = 3;
+
+    let account = db.create_test_user().await?;
+    for _ in 0..NUM_LOGS {
+        db.create_audit_event(
+            Uuid::new_v4(),
+            account.id(),
+            &None,
+            ClientAction::Authenticate,
+            EventStatus::Successful,
+            &Default::default(),
+        )
+        .await?;
+    }
+
+    let all_events = db
+        .find_audit_events(account.id(), EventType::All, Default::default())
+        .await?;
+    assert_eq!(all_events.len(), NUM_LOGS as usize);
+    assert!(all_events
+        .windows(2)
+        .all(|pair| pair[0].audit_event_id < pair[1].audit_event_id));
+
+    let mut paged_events = Vec::new();
+    let mut options = AuditEventOptions {
+        limit: Some(PAGE_SIZE),
+        ..Default::default()
+    };
+    loop {
+        let page = db
+            .find_audit_events(account.id(), EventType::All, options.clone())
+            .await?;
+        assert!(page.len() <= PAGE_SIZE as usize);
+
+        match page.last() {
+            Some(last_event) => options.after_audit_event_id = Some(last_event.audit_event_id),
+            None => break,
+        }
+        paged_events.extend(page);
+    }
+    let ids = |events: &[AuditEvent]| -> Vec<i64> {
+        events.iter().map(|event| event.audit_event_id).collect()
+    };
+    assert_eq!(ids(&paged_events), ids(&all_events));
+
+    Ok(())
+}
+
+/// Searching every account returns matching events of all accounts, while
+/// searching one account does not.
+async fn all_audit_events_span_accounts<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let accounts = [db.create_test_user().await?, db.create_test_user().await?];
+    let request_id = Uuid::new_v4();
+    for account in &accounts {
+        db.create_audit_event(
+            request_id,
+            account.id(),
+            &None,
+            ClientAction::Authenticate,
+            EventStatus::Successful,
+            &Default::default(),
+        )
+        .await?;
+    }
+
+    let options = AuditEventOptions {
+        request_id: Some(request_id),
+        ..Default::default()
+    };
+    let events = db
+        .find_all_audit_events(EventType::All, options.clone())
+        .await?;
+    let account_ids: Vec<_> = events.iter().map(|event| event.account_id).collect();
+    assert_eq!(account_ids, [accounts[0].id(), accounts[1].id()]);
+
+    let events = db
+        .find_audit_events(accounts[0].id(), EventType::All, options)
+        .await?;
+    assert_eq!(events.len(), 1);
+
+    Ok(())
+}
+
+/// Checkpoints are returned in event order and the latest one can be found.
+async fn audit_checkpoints_are_stored<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let signing_key = AuditSigningKey::generate(&mut StdRng::from_entropy());
+    let public_key = signing_key.public_key()?;
+
+    assert!(db
+        .find_latest_audit_checkpoint(account.id())
+        .await?
+        .is_none());
+
+    let key_ids = db
+        .create_random_arbitrary_secrets(HOW_MANY_SECRETS, &account)
+        .await?;
+    let mut checkpoints = Vec::new();
+    for _ in 0..3 {
+        let _ = create_random_audit_events(&account, &key_ids, &db).await?;
+        let latest = db.find_latest_audit_event(account.id()).await?.unwrap();
+        let checkpoint = AuditCheckpoint::new(&signing_key, &latest, OffsetDateTime::now_utc())?;
+        db.create_audit_checkpoint(&checkpoint).await?;
+        checkpoints.push(checkpoint);
+    }
+
+    let stored_checkpoints = db.find_audit_checkpoints(account.id()).await?;
+    assert_eq!(stored_checkpoints, checkpoints);
+    let latest_checkpoint = db.find_latest_audit_checkpoint(account.id()).await?;
+    assert_eq!(latest_checkpoint.as_ref(), checkpoints.last());
+
+    let audit_events = db
+        .find_audit_events(account.id(), EventType::All, Default::default())
+        .await?;
+    verify_audit_chain(&audit_events, &stored_checkpoints, &public_key)?;
+
+    Ok(())
+}
+
+/// Deleted events can be imported again with their IDs, hashes and chain
+/// intact, and events that are already stored are skipped.
+async fn archived_audit_events_can_be_restored<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let signing_key = AuditSigningKey::generate(&mut StdRng::from_entropy());
+    let public_key = signing_key.public_key()?;
+    let key_ids = db
+        .create_random_arbitrary_secrets(HOW_MANY_SECRETS, &account)
+        .await?;
+    let _ = create_random_audit_events(&account, &key_ids, &db).await?;
+
+    let mut all_events = db
+        .find_audit_events(account.id(), EventType::All, Default::default())
+        .await?;
+    let remaining_events = all_events.split_off(NUM_SAMPLE);
+    let archived_events = all_events;
+
+    // Anchor the remaining events to the last archived one before deleting.
+    let checkpoint = AuditCheckpoint::new(
+        &signing_key,
+        archived_events.last().unwrap(),
+        OffsetDateTime::now_utc(),
+    )?;
+    db.create_audit_checkpoint(&checkpoint).await?;
+
+    let archived_ids: Vec<i64> = archived_events
+        .iter()
+        .map(|event| event.audit_event_id)
+        .collect();
+    let deleted = db.delete_audit_events(&archived_ids).await?;
+    assert_eq!(deleted, archived_ids.len() as u64);
+
+    let stored_events = db
+        .find_audit_events(account.id(), EventType::All, Default::default())
+        .await?;
+    assert_eq!(stored_events.len(), remaining_events.len());
+    let checkpoints = db.find_audit_checkpoints(account.id()).await?;
+    verify_audit_chain(&stored_events, &checkpoints, &public_key)?;
+
+    let imported = db.import_audit_events(&archived_events).await?;
+    assert_eq!(imported, archived_ids.len() as u64);
+    // Events that are already stored are skipped.
+    let imported = db.import_audit_events(&stored_events).await?;
+    assert_eq!(imported, 0);
+
+    let restored_events = db
+        .find_audit_events(account.id(), EventType::All, Default::default())
+        .await?;
+    let ids_and_hashes = |events: &[AuditEvent]| -> Vec<_> {
+        events
+            .iter()
+            .map(|event| (event.audit_event_id, event.hash()))
+            .collect()
+    };
+    let mut expected = ids_and_hashes(&archived_events);
+    expected.extend(ids_and_hashes(&remaining_events));
+    assert_eq!(ids_and_hashes(&restored_events), expected);
+    verify_audit_chain(&restored_events, &checkpoints, &public_key)?;
+
+    Ok(())
+}
+
 /// Create [NUM_LOGS] random audit events and store them in our database. Return
 /// the [KeyId]s and [Uuid]s (request IDs) assigned to these audit events.
-async fn create_random_audit_events(
+async fn create_random_audit_events<DB: DataStore>(
     account: &Account,
     key_ids: &Vec<KeyId>,
-    db: &TestDatabase,
+    db: &TestDatabase<DB>,
 ) -> Result<(Vec<KeyId>, Vec<Uuid>)> {
     let mut rng = StdRng::from_entropy();
 
@@ -261,12 +707,12 @@ async fn create_random_audit_events(
 ///
 /// The key IDs must already exist in database to make foreign key constraint
 /// happy. Returns the chosen (key_ids, request_ids, action) 3-tuples.
-async fn create_random_audit_event(
+async fn create_random_audit_event<DB: DataStore>(
     rng: &mut StdRng,
     action_list: &Vec<ClientAction>,
     key_ids: &Vec<KeyId>,
     account: &Account,
-    db: &TestDatabase,
+    db: &TestDatabase<DB>,
 ) -> Result<(KeyId, Uuid, ClientAction)> {
     let key_id = key_ids.choose(rng).unwrap();
     let action = action_list.choose(rng).unwrap();
@@ -278,6 +724,7 @@ async fn create_random_audit_event(
         &Some(key_id.clone()),
         *action,
         EventStatus::Started,
+        &Default::default(),
     )
     .await?;
 
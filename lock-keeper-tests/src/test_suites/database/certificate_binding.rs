@@ -0,0 +1,112 @@
+//! Integration tests for client certificate bindings in the database
+
+use colored::Colorize;
+use lock_keeper::types::{
+    audit_event::{AuditEventDetails, AuditEventOptions, EventStatus, EventType},
+    operations::ClientAction,
+};
+use lock_keeper_key_server::server::database::DataStore;
+use uuid::Uuid;
+
+use crate::{config::TestFilters, error::Result, run_parallel, utils::TestResult};
+
+use super::TestDatabase;
+
+pub async fn run_tests<DB: DataStore + Clone>(
+    filters: &TestFilters,
+    db: TestDatabase<DB>,
+) -> Result<Vec<TestResult>> {
+    println!("{}", "Running certificate binding tests".cyan());
+
+    let result = run_parallel!(
+        filters,
+        certificate_binding_is_findable(db.clone()),
+        identity_cannot_be_bound_twice(db.clone()),
+        unbound_identity_is_not_found(db.clone()),
+        audit_event_records_client_identity(db.clone()),
+    )?;
+
+    Ok(result)
+}
+
+/// Identities are unique across the whole table so each test generates its
+/// own.
+fn unique_identity() -> String {
+    format!("dns:{}.client.test", Uuid::new_v4())
+}
+
+async fn certificate_binding_is_findable<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let identity = unique_identity();
+
+    assert!(!db.account_has_certificate_binding(account.id()).await?);
+
+    db.create_certificate_binding(&identity, account.id())
+        .await?;
+
+    let bound_account_id = db.find_certificate_binding(&identity).await?;
+    assert_eq!(bound_account_id, Some(account.id()));
+    assert!(db.account_has_certificate_binding(account.id()).await?);
+
+    Ok(())
+}
+
+async fn identity_cannot_be_bound_twice<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let other_account = db.create_test_user().await?;
+    let identity = unique_identity();
+
+    db.create_certificate_binding(&identity, account.id())
+        .await?;
+    let result = db
+        .create_certificate_binding(&identity, other_account.id())
+        .await;
+    assert!(result.is_err());
+
+    let bound_account_id = db.find_certificate_binding(&identity).await?;
+    assert_eq!(bound_account_id, Some(account.id()));
+    assert!(
+        !db.account_has_certificate_binding(other_account.id())
+            .await?
+    );
+
+    Ok(())
+}
+
+async fn unbound_identity_is_not_found<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let bound_account_id = db.find_certificate_binding(&unique_identity()).await?;
+    assert!(bound_account_id.is_none());
+
+    Ok(())
+}
+
+async fn audit_event_records_client_identity<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let identity = unique_identity();
+    let request_id = Uuid::new_v4();
+
+    db.create_audit_event(
+        request_id,
+        account.id(),
+        &None,
+        ClientAction::GetUserId,
+        EventStatus::Successful,
+        &AuditEventDetails {
+            client_identity: Some(identity.clone()),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    let options = AuditEventOptions {
+        request_id: Some(request_id),
+        ..Default::default()
+    };
+    let events = db
+        .find_audit_events(account.id(), EventType::All, options)
+        .await?;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].client_identity(), Some(identity.as_str()));
+
+    Ok(())
+}
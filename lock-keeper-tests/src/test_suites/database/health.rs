@@ -0,0 +1,26 @@
+//! Integration tests for database health checks
+
+use colored::Colorize;
+use lock_keeper_key_server::server::database::DataStore;
+
+use crate::{config::TestFilters, error::Result, run_parallel, utils::TestResult};
+
+use super::TestDatabase;
+
+pub async fn run_tests<DB: DataStore + Clone>(
+    filters: &TestFilters,
+    db: TestDatabase<DB>,
+) -> Result<Vec<TestResult>> {
+    println!("{}", "Running database health tests".cyan());
+
+    let result = run_parallel!(filters, health_check_succeeds(db.clone()),)?;
+
+    Ok(result)
+}
+
+/// A reachable database passes its health check.
+async fn health_check_succeeds<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    db.health_check().await?;
+
+    Ok(())
+}
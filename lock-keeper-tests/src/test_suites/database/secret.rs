@@ -2,7 +2,8 @@
 
 use colored::Colorize;
 use lock_keeper::{
-    crypto::{DataBlob, Encrypted},
+    crypto::{DataBlob, Encrypted, KeyId},
+    types::database::secrets::{secret_types::ARBITRARY_SECRET, StoredSecret},
     LockKeeperError,
 };
 use lock_keeper_key_server::server::database::{DataStore, DatabaseError, SecretFilter};
@@ -13,21 +14,26 @@ use crate::{config::TestFilters, error::Result, run_parallel, utils::TestResult}
 use super::TestDatabase;
 use lock_keeper::types::database::secrets::secret_types::SERVER_ENCRYPTED_BLOB;
 
-pub async fn run_tests(filters: &TestFilters) -> Result<Vec<TestResult>> {
+pub async fn run_tests<DB: DataStore + Clone>(
+    filters: &TestFilters,
+    db: TestDatabase<DB>,
+) -> Result<Vec<TestResult>> {
     println!("{}", "Running secret tests".cyan());
 
-    let db = TestDatabase::connect().await?;
     let result = run_parallel!(
         filters,
         cannot_get_another_users_secrets(db.clone()),
         incorrect_key_type_specified(db.clone()),
-        store_data_blob_identity(db.clone())
+        store_data_blob_identity(db.clone()),
+        missing_secret_returns_no_entry(db.clone()),
+        key_id_is_unique(db.clone()),
+        deleted_secret_is_gone(db.clone()),
     )?;
 
     Ok(result)
 }
 
-async fn cannot_get_another_users_secrets(db: TestDatabase) -> Result<()> {
+async fn cannot_get_another_users_secrets<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     let mut rng = StdRng::from_entropy();
 
     // Add a user and get their storage key
@@ -78,7 +84,7 @@ async fn cannot_get_another_users_secrets(db: TestDatabase) -> Result<()> {
 
 /// Storing and retrieving an encrypted data blob returns the same stored
 /// secret.
-async fn store_data_blob_identity(db: TestDatabase) -> Result<()> {
+async fn store_data_blob_identity<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     let mut rng = StdRng::from_entropy();
 
     // Add a user and get their storage key
@@ -94,7 +100,7 @@ async fn store_data_blob_identity(db: TestDatabase) -> Result<()> {
     let blob: DataBlob = encrypted_blob.decrypt_data_blob(&remote_storage_key)?;
     assert_eq!(
         blob.blob_data(),
-        TestDatabase::blob_test_data(),
+        TestDatabase::<DB>::blob_test_data(),
         "Blob data matches after storing and retrieving."
     );
     assert_eq!(
@@ -114,7 +120,7 @@ async fn store_data_blob_identity(db: TestDatabase) -> Result<()> {
 }
 
 /// An error is returned if a wrong key type is specified.
-async fn incorrect_key_type_specified(db: TestDatabase) -> Result<()> {
+async fn incorrect_key_type_specified<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     let mut rng = StdRng::from_entropy();
 
     // Add a user and get their storage key
@@ -134,7 +140,91 @@ async fn incorrect_key_type_specified(db: TestDatabase) -> Result<()> {
         db.db
             .get_secret(account.id(), &key_id, SecretFilter::secret_type("Foo"))
             .await,
-        Err(DatabaseError::IncorrectKeyMetadata)
+        Err(DatabaseError::WrongSecretType)
+    ));
+
+    Ok(())
+}
+
+/// Looking up or deleting a key ID that was never stored returns `NoEntry`.
+async fn missing_secret_returns_no_entry<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let mut rng = StdRng::from_entropy();
+
+    let account = db.create_test_user().await?;
+    let key_id = KeyId::generate(&mut rng, &account.user_id)?;
+
+    assert!(matches!(
+        db.db
+            .get_secret(account.id(), &key_id, Default::default())
+            .await,
+        Err(DatabaseError::NoEntry)
+    ));
+    assert!(matches!(
+        db.db.get_server_encrypted_blob(account.id(), &key_id).await,
+        Err(DatabaseError::NoEntry)
+    ));
+    assert!(matches!(
+        db.db.delete_secret(account.id(), &key_id).await,
+        Err(DatabaseError::NoEntry)
+    ));
+
+    Ok(())
+}
+
+/// A key ID can only be stored once, even for a different account.
+async fn key_id_is_unique<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let mut rng = StdRng::from_entropy();
+
+    let account = db.create_test_user().await?;
+    let other_account = db.create_test_user().await?;
+    let (encrypted_storage_key, master_key) = db.create_test_storage_key(&account.user_id)?;
+    let storage_key = encrypted_storage_key.decrypt_storage_key(master_key, &account.user_id)?;
+    let key_id = db
+        .add_arbitrary_secret(&mut rng, &storage_key, &account)
+        .await?;
+
+    for account_id in [account.id(), other_account.id()] {
+        let duplicate = StoredSecret {
+            key_id: key_id.clone(),
+            account_id,
+            secret_type: ARBITRARY_SECRET.to_string(),
+            bytes: vec![42; 42],
+            retrieved: false,
+        };
+        assert!(db.db.add_secret(duplicate).await.is_err());
+    }
+
+    // The original secret is unchanged.
+    let stored_secret = db
+        .db
+        .get_secret(account.id(), &key_id, Default::default())
+        .await?;
+    assert_eq!(stored_secret.account_id, account.id());
+    assert_ne!(stored_secret.bytes, vec![42; 42]);
+
+    Ok(())
+}
+
+/// A deleted secret can't be retrieved. Secrets can only be deleted by the
+/// account that owns them.
+async fn deleted_secret_is_gone<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let mut rng = StdRng::from_entropy();
+
+    let account = db.create_test_user().await?;
+    let other_account = db.create_test_user().await?;
+    let key_id = db.import_signing_key(&mut rng, &account).await?;
+
+    assert!(matches!(
+        db.db.delete_secret(other_account.id(), &key_id).await,
+        Err(DatabaseError::NoEntry)
+    ));
+    db.db.delete_secret(account.id(), &key_id).await?;
+
+    assert!(matches!(
+        db.db
+            .get_secret(account.id(), &key_id, Default::default())
+            .await,
+        Err(DatabaseError::NoEntry)
     ));
 
     Ok(())
@@ -0,0 +1,87 @@
+//! Integration tests for service credentials in the database
+
+use colored::Colorize;
+use lock_keeper::{
+    crypto::{ServiceCredentialAlgorithm, ServiceCredentialKeyPair},
+    types::operations::ClientAction,
+};
+use lock_keeper_key_server::server::database::DataStore;
+use rand::{rngs::StdRng, SeedableRng};
+use uuid::Uuid;
+
+use crate::{config::TestFilters, error::Result, run_parallel, utils::TestResult};
+
+use super::TestDatabase;
+
+pub async fn run_tests<DB: DataStore + Clone>(
+    filters: &TestFilters,
+    db: TestDatabase<DB>,
+) -> Result<Vec<TestResult>> {
+    println!("{}", "Running service credential tests".cyan());
+
+    let result = run_parallel!(
+        filters,
+        service_credential_is_findable(db.clone()),
+        service_credential_is_scoped_to_account(db.clone()),
+        unknown_service_credential_is_not_found(db.clone()),
+    )?;
+
+    Ok(result)
+}
+
+async fn service_credential_is_findable<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let mut rng = StdRng::from_entropy();
+    let public_key =
+        ServiceCredentialKeyPair::generate(&mut rng, ServiceCredentialAlgorithm::Ed25519)
+            .public_key()?;
+    let allowed_actions = vec![ClientAction::RemoteSignBytes];
+
+    let credential = db
+        .create_service_credential(account.id(), &public_key, &allowed_actions)
+        .await?;
+
+    let found = db
+        .find_service_credential(account.id(), credential.credential_id)
+        .await?
+        .unwrap();
+    assert_eq!(found, credential);
+    assert_eq!(found.public_key, public_key);
+    assert_eq!(found.allowed_actions, allowed_actions);
+
+    Ok(())
+}
+
+async fn service_credential_is_scoped_to_account<DB: DataStore>(
+    db: TestDatabase<DB>,
+) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let other_account = db.create_test_user().await?;
+    let mut rng = StdRng::from_entropy();
+    let public_key = ServiceCredentialKeyPair::generate(&mut rng, ServiceCredentialAlgorithm::P256)
+        .public_key()?;
+
+    let credential = db
+        .create_service_credential(account.id(), &public_key, &[])
+        .await?;
+
+    let found = db
+        .find_service_credential(other_account.id(), credential.credential_id)
+        .await?;
+    assert!(found.is_none());
+
+    Ok(())
+}
+
+async fn unknown_service_credential_is_not_found<DB: DataStore>(
+    db: TestDatabase<DB>,
+) -> Result<()> {
+    let account = db.create_test_user().await?;
+
+    let found = db
+        .find_service_credential(account.id(), Uuid::new_v4())
+        .await?;
+    assert!(found.is_none());
+
+    Ok(())
+}
@@ -0,0 +1,197 @@
+//! Integration tests for database transactions
+
+use colored::Colorize;
+use lock_keeper::{
+    crypto::{DataBlob, KeyId, RemoteStorageKey},
+    types::{
+        audit_event::{AuditEventOptions, EventStatus, EventType},
+        database::{account::Account, secrets::StoredSecret},
+        operations::ClientAction,
+    },
+};
+use lock_keeper_key_server::server::database::{DataStore, DataStoreTransaction, DatabaseError};
+use rand::{rngs::StdRng, SeedableRng};
+use uuid::Uuid;
+
+use crate::{config::TestFilters, error::Result, run_parallel, utils::TestResult};
+
+use super::TestDatabase;
+
+pub async fn run_tests<DB: DataStore + Clone>(
+    filters: &TestFilters,
+    db: TestDatabase<DB>,
+) -> Result<Vec<TestResult>> {
+    println!("{}", "Running transaction tests".cyan());
+
+    let result = run_parallel!(
+        filters,
+        committed_changes_are_stored(db.clone()),
+        dropped_transaction_is_discarded(db.clone()),
+        failed_change_discards_transaction(db.clone()),
+        uncommitted_changes_are_not_visible(db.clone()),
+    )?;
+
+    Ok(result)
+}
+
+/// Create a new server-encrypted blob for the account without storing it.
+fn new_secret(rng: &mut StdRng, account: &Account) -> Result<StoredSecret> {
+    let key_id = KeyId::generate(rng, &account.user_id)?;
+    let blob = DataBlob::create(vec![42; 42], &account.user_id, &key_id)?;
+    let encrypted = RemoteStorageKey::generate(rng).encrypt_data_blob(rng, blob)?;
+
+    Ok(StoredSecret::from_data_blob(
+        key_id,
+        account.id(),
+        encrypted,
+    )?)
+}
+
+/// Number of audit events stored for the given request.
+async fn count_audit_events<DB: DataStore>(
+    db: &TestDatabase<DB>,
+    account: &Account,
+    request_id: Uuid,
+) -> Result<usize> {
+    let options = AuditEventOptions {
+        request_id: Some(request_id),
+        ..Default::default()
+    };
+    let events = db
+        .find_audit_events(account.id(), EventType::All, options)
+        .await?;
+
+    Ok(events.len())
+}
+
+/// A secret and its audit event are both stored once the transaction is
+/// committed, and the commit returns the stored event.
+async fn committed_changes_are_stored<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let mut rng = StdRng::from_entropy();
+    let account = db.create_test_user().await?;
+    let secret = new_secret(&mut rng, &account)?;
+    let key_id = secret.key_id.clone();
+    let request_id = Uuid::new_v4();
+
+    let mut transaction = db.begin_transaction().await?;
+    transaction.add_secret(secret).await?;
+    transaction
+        .create_audit_event(
+            request_id,
+            account.id(),
+            &Some(key_id.clone()),
+            ClientAction::StoreServerEncryptedBlob,
+            EventStatus::Successful,
+            &Default::default(),
+        )
+        .await?;
+    let events = transaction.commit().await?;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].request_id, request_id);
+    assert_eq!(events[0].key_id, Some(key_id.clone()));
+
+    let stored_secret = db.get_server_encrypted_blob(account.id(), &key_id).await?;
+    assert_eq!(stored_secret.key_id, key_id);
+    assert_eq!(count_audit_events(&db, &account, request_id).await?, 1);
+
+    Ok(())
+}
+
+/// Nothing is stored if the transaction is dropped without being committed.
+async fn dropped_transaction_is_discarded<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let mut rng = StdRng::from_entropy();
+    let account = db.create_test_user().await?;
+    let (storage_key, _) = db.create_test_storage_key(&account.user_id)?;
+    let secret = new_secret(&mut rng, &account)?;
+    let key_id = secret.key_id.clone();
+    let request_id = Uuid::new_v4();
+
+    let mut transaction = db.begin_transaction().await?;
+    transaction.add_secret(secret).await?;
+    transaction
+        .set_storage_key(account.id(), storage_key)
+        .await?;
+    transaction
+        .create_audit_event(
+            request_id,
+            account.id(),
+            &Some(key_id.clone()),
+            ClientAction::StoreServerEncryptedBlob,
+            EventStatus::Successful,
+            &Default::default(),
+        )
+        .await?;
+    drop(transaction);
+
+    assert!(matches!(
+        db.get_server_encrypted_blob(account.id(), &key_id).await,
+        Err(DatabaseError::NoEntry)
+    ));
+    let stored_account = db.find_account(account.id()).await?.unwrap();
+    assert!(stored_account.storage_key.is_none());
+    assert_eq!(count_audit_events(&db, &account, request_id).await?, 0);
+
+    Ok(())
+}
+
+/// If one change fails, none of the changes in the transaction are stored.
+/// Depending on the backend, the error is returned by the failing change or by
+/// the commit.
+async fn failed_change_discards_transaction<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let mut rng = StdRng::from_entropy();
+    let account = db.create_test_user().await?;
+    let secret = new_secret(&mut rng, &account)?;
+    let key_id = secret.key_id.clone();
+    let missing_key_id = KeyId::generate(&mut rng, &account.user_id)?;
+    let request_id = Uuid::new_v4();
+
+    let mut transaction = db.begin_transaction().await?;
+    transaction.add_secret(secret).await?;
+    transaction
+        .create_audit_event(
+            request_id,
+            account.id(),
+            &Some(missing_key_id.clone()),
+            ClientAction::DeleteKey,
+            EventStatus::Successful,
+            &Default::default(),
+        )
+        .await?;
+    let result = match transaction
+        .delete_secret(account.id(), &missing_key_id)
+        .await
+    {
+        Ok(()) => transaction.commit().await,
+        Err(e) => Err(e),
+    };
+    assert!(matches!(result, Err(DatabaseError::NoEntry)));
+
+    assert!(matches!(
+        db.get_server_encrypted_blob(account.id(), &key_id).await,
+        Err(DatabaseError::NoEntry)
+    ));
+    assert_eq!(count_audit_events(&db, &account, request_id).await?, 0);
+
+    Ok(())
+}
+
+/// Changes are not visible outside of the transaction until it is committed.
+async fn uncommitted_changes_are_not_visible<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let account = db.create_test_user().await?;
+    let (storage_key, _) = db.create_test_storage_key(&account.user_id)?;
+
+    let mut transaction = db.begin_transaction().await?;
+    transaction
+        .set_storage_key(account.id(), storage_key.clone())
+        .await?;
+
+    let stored_account = db.find_account(account.id()).await?.unwrap();
+    assert!(stored_account.storage_key.is_none());
+
+    transaction.commit().await?;
+
+    let stored_account = db.find_account(account.id()).await?.unwrap();
+    assert_eq!(stored_account.storage_key, Some(storage_key));
+
+    Ok(())
+}
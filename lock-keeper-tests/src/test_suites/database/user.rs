@@ -2,7 +2,7 @@
 
 use colored::Colorize;
 use lock_keeper::types::database::account::{AccountName, UserId};
-use lock_keeper_key_server::server::database::DataStore;
+use lock_keeper_key_server::server::database::{DataStore, DatabaseError};
 use rand::{rngs::StdRng, SeedableRng};
 
 use crate::{
@@ -14,23 +14,26 @@ use crate::{
 
 use super::TestDatabase;
 
-pub async fn run_tests(filters: &TestFilters) -> Result<Vec<TestResult>> {
+pub async fn run_tests<DB: DataStore + Clone>(
+    filters: &TestFilters,
+    db: TestDatabase<DB>,
+) -> Result<Vec<TestResult>> {
     println!("{}", "Running user tests".cyan());
 
-    let db = TestDatabase::connect().await?;
     let result = run_parallel!(
         filters,
         user_findable_by_account_name(db.clone()),
         user_findable_by_id(db.clone()),
         unique_indices_enforced(db.clone()),
         user_is_deleted(db.clone()),
-        storage_key_is_set(db.clone())
+        storage_key_is_set(db.clone()),
+        user_id_exists_works(db.clone()),
     )?;
 
     Ok(result)
 }
 
-async fn user_findable_by_account_name(db: TestDatabase) -> Result<()> {
+async fn user_findable_by_account_name<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     let account = db.create_test_user().await?;
 
     let user = db.find_account(account.id()).await?.unwrap();
@@ -39,7 +42,7 @@ async fn user_findable_by_account_name(db: TestDatabase) -> Result<()> {
     Ok(())
 }
 
-async fn user_findable_by_id(db: TestDatabase) -> Result<()> {
+async fn user_findable_by_id<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     let account = db.create_test_user().await?;
 
     let user = db.find_account(account.id()).await?.unwrap();
@@ -53,7 +56,7 @@ async fn user_findable_by_id(db: TestDatabase) -> Result<()> {
     Ok(())
 }
 
-async fn unique_indices_enforced(db: TestDatabase) -> Result<()> {
+async fn unique_indices_enforced<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     let mut rng = StdRng::from_entropy();
 
     // Add the "baseline" user.
@@ -88,7 +91,7 @@ async fn unique_indices_enforced(db: TestDatabase) -> Result<()> {
     Ok(())
 }
 
-async fn user_is_deleted(db: TestDatabase) -> Result<()> {
+async fn user_is_deleted<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     let account = db.create_test_user().await?;
 
     // Ensure that the user was created
@@ -104,13 +107,17 @@ async fn user_is_deleted(db: TestDatabase) -> Result<()> {
 
     // Ensure that an error is returned if the user is deleted again
     let result = db.delete_account(account.id()).await;
-    assert!(result.is_err());
+    assert!(matches!(result, Err(DatabaseError::NoEntry)));
+
+    // The account can no longer be found by name
+    let user = db.find_account_by_name(&account.account_name).await?;
+    assert!(user.is_none());
 
     Ok(())
 }
 
 /// Test that `set_storage_key` works correctly
-async fn storage_key_is_set(db: TestDatabase) -> Result<()> {
+async fn storage_key_is_set<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
     let account = db.create_test_user().await?;
 
     // Ensure that the user was created an no storage key is set
@@ -129,3 +136,15 @@ async fn storage_key_is_set(db: TestDatabase) -> Result<()> {
 
     Ok(())
 }
+
+async fn user_id_exists_works<DB: DataStore>(db: TestDatabase<DB>) -> Result<()> {
+    let mut rng = StdRng::from_entropy();
+
+    let account = db.create_test_user().await?;
+    assert!(db.user_id_exists(&account.user_id).await?);
+
+    let unknown_user_id = UserId::new(&mut rng)?;
+    assert!(!db.user_id_exists(&unknown_user_id).await?);
+
+    Ok(())
+}
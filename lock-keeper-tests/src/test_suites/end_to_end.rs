@@ -11,7 +11,7 @@ use colored::Colorize;
 use lock_keeper_client::Config;
 use test_cases::{
     authenticate, check_session, delete_key, export, generate, import, register, remote_generate,
-    remote_sign, retrieve,
+    remote_sign, retrieve, service_account,
 };
 
 pub async fn run_tests(environments: &Environments) -> Result<Vec<TestResult>> {
@@ -45,6 +45,7 @@ pub async fn run_tests_with_config(
     let import_results = import::run_tests(config, filters).await?;
     let remote_generate_results = remote_generate::run_tests(config, filters).await?;
     let remote_sign_results = remote_sign::run_tests(config, filters).await?;
+    let service_account_results = service_account::run_tests(config, filters).await?;
 
     println!("Results for environment: {}", environment_name.magenta());
     // Report results after all tests finish so results show up together
@@ -73,6 +74,10 @@ pub async fn run_tests_with_config(
         "remote sign tests: {}",
         report_test_results(&remote_sign_results)
     );
+    println!(
+        "service account tests: {}",
+        report_test_results(&service_account_results)
+    );
 
     println!();
 
@@ -87,6 +92,7 @@ pub async fn run_tests_with_config(
         .chain(import_results)
         .chain(remote_generate_results)
         .chain(remote_sign_results)
+        .chain(service_account_results)
         .collect();
 
     Ok(results)
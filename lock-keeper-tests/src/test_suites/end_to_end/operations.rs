@@ -1,15 +1,15 @@
-use crate::{test_suites::end_to_end::test_cases::TestState, LockKeeperTestError};
+use crate::test_suites::end_to_end::test_cases::TestState;
+use futures::TryStreamExt;
 use lock_keeper::{
     crypto::{Import, KeyId},
     types::{
-        audit_event::{AuditEventOptions, EventStatus, EventType},
+        audit_event::{AuditEvent, AuditEventOptions, EventStatus, EventType},
         operations::ClientAction,
     },
 };
 use lock_keeper_client::{LockKeeperClient, LockKeeperClientError, LockKeeperResponse};
 use rand::{prelude::StdRng, Rng, SeedableRng};
 use std::fmt::Display;
-use tonic::Status;
 use uuid::Uuid;
 
 /// Generate a fake key ID to test retrieve/export failure cases.
@@ -40,22 +40,6 @@ where
     );
 }
 
-/// Helper to compare status errors.
-pub(crate) fn compare_status_errors<T>(
-    res: LockKeeperResponse<T>,
-    expected: Status,
-) -> Result<(), LockKeeperTestError> {
-    let actual = match res.result.err().unwrap() {
-        LockKeeperClientError::TonicStatus(status) => Ok(status),
-        _ => Err(LockKeeperTestError::WrongErrorReturned),
-    }?;
-
-    assert_eq!(actual.code(), expected.code());
-    assert_eq!(actual.message(), expected.message());
-
-    Ok(())
-}
-
 /// Fetch relevant events based on request_id. Ensure that some event on the
 /// list matches the expected_status and expected_action. Note: we cannot rely
 /// on the ordering of the audit events.
@@ -77,10 +61,12 @@ pub(crate) async fn check_audit_events(
         request_id: Some(request_id),
         ..Default::default()
     };
-    let audit_event_log = lock_keeper_client
+    let audit_event_log: Vec<AuditEvent> = lock_keeper_client
         .retrieve_audit_event_log(EventType::All, options)
         .await
-        .result?;
+        .result?
+        .try_collect()
+        .await?;
 
     // Get all events that match given expected values.
     let matching_events = audit_event_log.iter().filter(|event| {
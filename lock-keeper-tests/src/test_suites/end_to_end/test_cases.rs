@@ -13,10 +13,7 @@ pub mod register;
 pub mod remote_generate;
 pub mod remote_sign;
 pub mod retrieve;
-
-pub(crate) const NO_ENTRY_FOUND: &str = "No such entry in table.";
-pub(crate) const WRONG_KEY_DATA: &str =
-    "Key ID exists but associated user ID or key type were incorrect.";
+pub mod service_account;
 
 pub(crate) struct TestState {
     pub(crate) account_name: AccountName,
@@ -1,7 +1,6 @@
 use colored::Colorize;
 use lock_keeper::types::{audit_event::EventStatus, operations::ClientAction};
-use lock_keeper_client::Config;
-use tonic::Status;
+use lock_keeper_client::{Config, LockKeeperClientError};
 
 use crate::{
     config::TestFilters,
@@ -9,7 +8,7 @@ use crate::{
     run_parallel,
     test_suites::end_to_end::{
         operations::{authenticate, check_audit_events, compare_errors, generate_fake_key_id},
-        test_cases::{init_test_state, NO_ENTRY_FOUND},
+        test_cases::init_test_state,
     },
     utils::TestResult,
 };
@@ -85,7 +84,7 @@ async fn cannot_delete_another_users_key(config: Config) -> Result<()> {
     let delete_result = different_client.delete_key(&key_id).await;
     let request_id = delete_result.metadata.clone().unwrap().request_id;
 
-    compare_errors(delete_result, Status::internal(NO_ENTRY_FOUND));
+    compare_errors(delete_result, LockKeeperClientError::KeyNotFound);
     check_audit_events(
         &different_state,
         EventStatus::Failed,
@@ -105,7 +104,7 @@ async fn cannot_delete_fake_key(config: Config) -> Result<()> {
     let fake_key_id = generate_fake_key_id(&client).await?;
     let delete_result = client.delete_key(&fake_key_id).await;
     let request_id = delete_result.metadata.clone().unwrap().request_id;
-    compare_errors(delete_result, Status::internal(NO_ENTRY_FOUND));
+    compare_errors(delete_result, LockKeeperClientError::KeyNotFound);
     check_audit_events(
         &state,
         EventStatus::Failed,
@@ -4,7 +4,6 @@ use lock_keeper::{
     types::{audit_event::EventStatus, operations::ClientAction},
 };
 use lock_keeper_client::{api::GenerateResult, Config, LockKeeperClientError};
-use tonic::Status;
 
 use crate::{
     config::TestFilters,
@@ -15,7 +14,7 @@ use crate::{
             authenticate, check_audit_events, compare_errors, generate_fake_key_id,
             import_signing_key,
         },
-        test_cases::{init_test_state, NO_ENTRY_FOUND, WRONG_KEY_DATA},
+        test_cases::init_test_state,
     },
     utils::TestResult,
 };
@@ -71,7 +70,7 @@ async fn cannot_export_fake_key(config: Config) -> Result<()> {
     let fake_key_id = generate_fake_key_id(&client).await?;
     let bytes_res = client.export_secret(&fake_key_id).await;
     let request_id = bytes_res.metadata.clone().unwrap().request_id;
-    compare_errors(bytes_res, Status::internal(NO_ENTRY_FOUND));
+    compare_errors(bytes_res, LockKeeperClientError::KeyNotFound);
     check_audit_events(
         &state,
         EventStatus::Failed,
@@ -91,7 +90,7 @@ async fn cannot_export_signing_key_as_secret(config: Config) -> Result<()> {
     let (key_id, _) = import_signing_key(&client).await.result?;
     let export_res = client.export_secret(&key_id).await;
     let request_id = export_res.metadata.clone().unwrap().request_id;
-    compare_errors(export_res, Status::internal(WRONG_KEY_DATA));
+    compare_errors(export_res, LockKeeperClientError::WrongSecretType);
     check_audit_events(
         &state,
         EventStatus::Failed,
@@ -157,7 +156,7 @@ async fn cannot_export_fake_signing_key(config: Config) -> Result<()> {
     let fake_key_id = generate_fake_key_id(&client).await?;
     let export_res = client.export_signing_key(&fake_key_id).await;
     let request_id = export_res.metadata.clone().unwrap().request_id;
-    compare_errors(export_res, Status::internal(NO_ENTRY_FOUND));
+    compare_errors(export_res, LockKeeperClientError::KeyNotFound);
     check_audit_events(
         &state,
         EventStatus::Failed,
@@ -180,7 +179,7 @@ async fn cannot_export_secret_as_signing_key(config: Config) -> Result<()> {
     } = client.generate_secret().await.result?;
     let export_res = client.export_signing_key(&key_id).await;
     let request_id = export_res.metadata.clone().unwrap().request_id;
-    compare_errors(export_res, Status::internal(WRONG_KEY_DATA));
+    compare_errors(export_res, LockKeeperClientError::WrongSecretType);
     check_audit_events(
         &state,
         EventStatus::Failed,
@@ -2,15 +2,14 @@ use colored::Colorize;
 use lock_keeper::types::{
     audit_event::EventStatus, database::account::AccountName, operations::ClientAction,
 };
-use lock_keeper_client::{client::Password, Config, LockKeeperClient};
+use lock_keeper_client::{client::Password, Config, LockKeeperClient, LockKeeperClientError};
 use std::str::FromStr;
-use tonic::Status;
 
 use crate::{
     config::TestFilters,
     error::Result,
     run_parallel,
-    test_suites::end_to_end::operations::{check_audit_events, compare_status_errors},
+    test_suites::end_to_end::operations::{check_audit_events, compare_errors},
     utils::{tagged, TestResult},
 };
 
@@ -61,10 +60,10 @@ async fn register_same_user_twice_fails(config: Config) -> Result<()> {
         .result?;
 
     let second_register = LockKeeperClient::register(&account_name, &password, &config).await;
-    compare_status_errors(
+    compare_errors(
         second_register,
-        Status::invalid_argument("Account already registered."),
-    )?;
+        LockKeeperClientError::AccountAlreadyRegistered,
+    );
 
     Ok(())
 }
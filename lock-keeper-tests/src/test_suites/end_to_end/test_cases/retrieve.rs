@@ -5,7 +5,6 @@ use lock_keeper::types::{
 };
 use lock_keeper_client::{api::GenerateResult, Config, LockKeeperClientError};
 use rand::Rng;
-use tonic::Status;
 
 use crate::{
     config::TestFilters,
@@ -13,7 +12,7 @@ use crate::{
     run_parallel,
     test_suites::end_to_end::{
         operations::{authenticate, check_audit_events, compare_errors, generate_fake_key_id},
-        test_cases::{init_test_state, NO_ENTRY_FOUND},
+        test_cases::init_test_state,
     },
     utils::TestResult,
 };
@@ -100,7 +99,7 @@ async fn cannot_retrieve_fake_key(config: Config) -> Result<()> {
         .retrieve_secret(&fake_key_id, RetrieveContext::LocalOnly)
         .await;
     let request_id = local_storage_res.metadata.clone().unwrap().request_id;
-    compare_errors(local_storage_res, Status::internal(NO_ENTRY_FOUND));
+    compare_errors(local_storage_res, LockKeeperClientError::KeyNotFound);
     check_audit_events(
         &state,
         EventStatus::Failed,
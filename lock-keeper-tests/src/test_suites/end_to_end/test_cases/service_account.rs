@@ -0,0 +1,195 @@
+use colored::Colorize;
+use lock_keeper::{
+    crypto::{ServiceCredentialAlgorithm, ServiceCredentialKeyPair, Signable, SignableBytes},
+    types::{audit_event::EventStatus, operations::ClientAction},
+};
+use lock_keeper_client::{
+    api::RemoteGenerateResult, Config, LockKeeperClient, LockKeeperClientError,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use uuid::Uuid;
+
+use crate::{
+    config::TestFilters,
+    error::Result,
+    run_parallel,
+    test_suites::end_to_end::{
+        operations::{authenticate, check_audit_events},
+        test_cases::{init_test_state, TestState},
+    },
+    utils::{self, TestResult, RNG_SEED},
+};
+
+pub async fn run_tests(config: &Config, filters: &TestFilters) -> Result<Vec<TestResult>> {
+    println!("{}", "Running service account tests".cyan());
+
+    let result = run_parallel!(
+        filters,
+        service_account_can_remote_sign(config.clone(), ServiceCredentialAlgorithm::Ed25519),
+        service_account_can_remote_sign(config.clone(), ServiceCredentialAlgorithm::P256),
+        service_account_cannot_exceed_scope(config.clone()),
+        service_credential_cannot_be_granted_client_side_actions(config.clone()),
+        service_account_login_fails_with_wrong_key(config.clone()),
+    )?;
+
+    Ok(result)
+}
+
+/// Register a service credential for the test account with the given allowed
+/// actions.
+async fn register_service_credential(
+    state: &TestState,
+    algorithm: ServiceCredentialAlgorithm,
+    allowed_actions: Vec<ClientAction>,
+) -> Result<(Uuid, ServiceCredentialKeyPair)> {
+    let client = authenticate(state).await.result?;
+
+    let mut rng = StdRng::from_entropy();
+    let key_pair = ServiceCredentialKeyPair::generate(&mut rng, algorithm);
+    let credential_id = client
+        .register_service_credential(key_pair.public_key()?, allowed_actions)
+        .await
+        .result?;
+
+    Ok((credential_id, key_pair))
+}
+
+async fn service_account_can_remote_sign(
+    config: Config,
+    algorithm: ServiceCredentialAlgorithm,
+) -> Result<()> {
+    let state = init_test_state(&config).await?;
+    let (credential_id, key_pair) = register_service_credential(
+        &state,
+        algorithm,
+        vec![
+            ClientAction::RemoteGenerateSigningKey,
+            ClientAction::RemoteSignBytes,
+        ],
+    )
+    .await?;
+
+    let response = LockKeeperClient::authenticated_service_client(
+        &state.account_name,
+        credential_id,
+        &key_pair,
+        &config,
+    )
+    .await;
+    let login_request_id = response.metadata.as_ref().unwrap().request_id;
+    let service_client = response.result?;
+
+    check_audit_events(
+        &state,
+        EventStatus::Successful,
+        ClientAction::AuthenticateServiceAccount,
+        login_request_id,
+        None,
+    )
+    .await?;
+
+    let RemoteGenerateResult { key_id, public_key } =
+        service_client.remote_generate().await.result?;
+
+    let mut rng = StdRng::from_seed(*RNG_SEED);
+    let data = SignableBytes(utils::random_bytes(&mut rng, 100));
+    let signature = service_client
+        .remote_sign_bytes(key_id, data.clone())
+        .await
+        .result?;
+    assert!(data.verify(&public_key, &signature).is_ok());
+
+    service_client.logout().await.result?;
+
+    Ok(())
+}
+
+async fn service_account_cannot_exceed_scope(config: Config) -> Result<()> {
+    let state = init_test_state(&config).await?;
+    let (credential_id, key_pair) = register_service_credential(
+        &state,
+        ServiceCredentialAlgorithm::Ed25519,
+        vec![ClientAction::StoreServerEncryptedBlob],
+    )
+    .await?;
+
+    let service_client = LockKeeperClient::authenticated_service_client(
+        &state.account_name,
+        credential_id,
+        &key_pair,
+        &config,
+    )
+    .await
+    .result?;
+
+    // Allowed by the credential
+    let _ = service_client
+        .store_server_encrypted_blob(vec![1, 2, 3])
+        .await
+        .result?;
+
+    // Not allowed by the credential
+    let res = service_client.remote_generate().await;
+    assert!(matches!(
+        res.result,
+        Err(LockKeeperClientError::ActionNotPermitted)
+    ));
+
+    // Never allowed for service accounts
+    let res = service_client.generate_secret().await;
+    assert!(res.result.is_err());
+
+    Ok(())
+}
+
+async fn service_credential_cannot_be_granted_client_side_actions(config: Config) -> Result<()> {
+    let state = init_test_state(&config).await?;
+
+    let res = register_service_credential(
+        &state,
+        ServiceCredentialAlgorithm::P256,
+        vec![ClientAction::RemoteSignBytes, ClientAction::RetrieveSecret],
+    )
+    .await;
+    assert!(res.is_err());
+
+    Ok(())
+}
+
+async fn service_account_login_fails_with_wrong_key(config: Config) -> Result<()> {
+    let state = init_test_state(&config).await?;
+    let (credential_id, _) = register_service_credential(
+        &state,
+        ServiceCredentialAlgorithm::Ed25519,
+        vec![ClientAction::RemoteSignBytes],
+    )
+    .await?;
+
+    let mut rng = StdRng::from_entropy();
+    let wrong_key_pair =
+        ServiceCredentialKeyPair::generate(&mut rng, ServiceCredentialAlgorithm::Ed25519);
+
+    let response = LockKeeperClient::authenticated_service_client(
+        &state.account_name,
+        credential_id,
+        &wrong_key_pair,
+        &config,
+    )
+    .await;
+    let request_id = response.metadata.as_ref().unwrap().request_id;
+    assert!(matches!(
+        response.result,
+        Err(LockKeeperClientError::InvalidServiceCredential)
+    ));
+
+    check_audit_events(
+        &state,
+        EventStatus::Failed,
+        ClientAction::AuthenticateServiceAccount,
+        request_id,
+        None,
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Runs the [conformance](crate::conformance) tests against every persistence
+//! backend in this repository.
+
+use async_trait::async_trait;
+#[cfg(feature = "in-memory")]
+use lock_keeper_in_memory::{InMemoryDB, InMemorySessionCache};
+use lock_keeper_postgres::{Config, ConfigFile as DatabaseConfigFile, PostgresDB, PostgresError};
+use lock_keeper_session_cache_sql::{config::Config as SessionConfig, PostgresSessionCache};
+use lock_keeper_sqlite::{SqliteDB, SqliteSessionCache};
+use std::{str::FromStr, time::Duration};
+
+use crate::{
+    config::TestFilters,
+    conformance::{run_conformance_tests, Backend},
+    error::Result,
+    utils::{sqlite_config, TestResult},
+};
+
+pub async fn run_tests(filters: &TestFilters) -> Result<Vec<TestResult>> {
+    println!("Running persistence tests");
+
+    let postgres_results = run_conformance_tests(filters, Postgres).await?;
+    let sqlite_results = run_conformance_tests(filters, Sqlite).await?;
+    #[cfg(feature = "in-memory")]
+    let in_memory_results = run_conformance_tests(filters, InMemory).await?;
+    #[cfg(not(feature = "in-memory"))]
+    let in_memory_results = Vec::new();
+
+    Ok([postgres_results, sqlite_results, in_memory_results].concat())
+}
+
+/// Postgres databases from the local test environment.
+struct Postgres;
+
+#[async_trait]
+impl Backend for Postgres {
+    type DataStore = PostgresDB;
+    type SessionCache = PostgresSessionCache;
+
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    async fn data_store(&self) -> Result<PostgresDB> {
+        let config_str = r#"
+            username = 'test'
+            password = 'test_password'
+            address = 'localhost'
+            db_name = 'test'
+            min_connections = 2
+            max_connections = 5
+            connection_retries = 5
+            connection_retry_delay = "5s"
+            connection_timeout = "3s"
+            "#;
+
+        let config_file =
+            DatabaseConfigFile::from_str(config_str).map_err(PostgresError::ConfigError)?;
+        let config: Config = config_file.try_into().map_err(PostgresError::ConfigError)?;
+        Ok(PostgresDB::connect(config).await?)
+    }
+
+    async fn session_cache(&self, expiration: Duration) -> Result<PostgresSessionCache> {
+        let expiration = expiration.as_secs();
+        let config_str = format!(
+            r#"
+            username = 'test'
+            password = 'test_password'
+            address = 'localhost:5432'
+            db_name = 'test'
+            min_connections = 2
+            max_connections = 5
+            connection_retries = 5
+            connection_retry_delay = "5s"
+            connection_timeout = "3s"
+            session_expiration = "{expiration}s"
+            "#,
+        );
+
+        let config = SessionConfig::from_str(&config_str)?;
+        Ok(PostgresSessionCache::connect(config).await?)
+    }
+}
+
+/// SQLite databases in new files in the temp directory.
+struct Sqlite;
+
+#[async_trait]
+impl Backend for Sqlite {
+    type DataStore = SqliteDB;
+    type SessionCache = SqliteSessionCache;
+
+    fn name(&self) -> &str {
+        "sqlite"
+    }
+
+    async fn data_store(&self) -> Result<SqliteDB> {
+        Ok(SqliteDB::connect(sqlite_config(Duration::from_secs(60))).await?)
+    }
+
+    async fn session_cache(&self, expiration: Duration) -> Result<SqliteSessionCache> {
+        let db = SqliteDB::connect(sqlite_config(expiration)).await?;
+        Ok(db.session_cache())
+    }
+}
+
+#[cfg(feature = "in-memory")]
+struct InMemory;
+
+#[cfg(feature = "in-memory")]
+#[async_trait]
+impl Backend for InMemory {
+    type DataStore = InMemoryDB;
+    type SessionCache = InMemorySessionCache;
+
+    fn name(&self) -> &str {
+        "in-memory"
+    }
+
+    async fn data_store(&self) -> Result<InMemoryDB> {
+        Ok(InMemoryDB::new())
+    }
+
+    async fn session_cache(&self, expiration: Duration) -> Result<InMemorySessionCache> {
+        Ok(InMemorySessionCache::new(expiration))
+    }
+}
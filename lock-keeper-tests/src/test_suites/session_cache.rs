@@ -1,18 +1,18 @@
 //! Session cache integration tests.
 
 use crate::{
-    config::TestFilters, error::Result, run_parallel, utils::report_test_results, TestResult,
+    config::TestFilters, conformance::Backend, error::Result, run_parallel,
+    utils::report_test_results, utils::TestResult,
 };
 use colored::Colorize;
 use generic_array::GenericArray;
 use lock_keeper::{
     crypto::{OpaqueSessionKey, RemoteStorageKey},
-    types::database::account::AccountId,
+    types::{database::account::AccountId, operations::ClientAction},
 };
 use lock_keeper_key_server::server::session_cache::{SessionCache, SessionCacheError};
-use lock_keeper_session_cache_sql::{config::Config as SessionConfig, PostgresSessionCache};
 use rand::{rngs::StdRng, RngCore, SeedableRng};
-use std::{str::FromStr, time::Duration};
+use std::{sync::Arc, time::Duration};
 use uuid::Uuid;
 
 const FILLER: u8 = 42;
@@ -24,45 +24,40 @@ struct TestState {
     account_id: AccountId,
 }
 
-pub async fn run_tests(filters: &TestFilters) -> Result<Vec<TestResult>> {
-    println!("{}", "Running session cache tests".cyan());
+/// Runs every session cache test against caches created by the given backend.
+pub async fn run_tests<B: Backend>(
+    filters: &TestFilters,
+    backend: Arc<B>,
+) -> Result<Vec<TestResult>> {
+    println!(
+        "{}",
+        format!(
+            "Running session cache tests for backend: {}",
+            backend.name()
+        )
+        .cyan()
+    );
 
     let results = run_parallel!(
         filters,
-        key_does_not_exist(),
-        get_key_back(),
-        overwrite_existing_key(),
-        key_expired(),
-        key_expired2(),
+        key_does_not_exist(backend.clone()),
+        get_key_back(backend.clone()),
+        multiple_sessions_per_account(backend.clone()),
+        deleted_session_is_missing(backend.clone()),
+        replayed_request_is_rejected(backend.clone()),
+        key_expired(backend.clone()),
+        key_expired2(backend.clone()),
+        active_sessions_are_counted(backend.clone()),
+        health_check_succeeds(backend.clone()),
     )?;
 
-    println!("session cache tests: {}", report_test_results(&results));
-
-    Ok(results)
-}
-
-/// Create a new test database with random characters appended to name.
-/// This is what you probably want to use.
-pub async fn new_cache(expiration: &str) -> Result<PostgresSessionCache> {
-    let config_str = format!(
-        r#"
-        username = 'test'
-        password = 'test_password'
-        address = 'localhost:5432'
-        db_name = 'test'
-        min_connections = 2
-        max_connections = 5
-        connection_retries = 5
-        connection_retry_delay = "5s"
-        connection_timeout = "3s"
-        session_expiration = "{expiration}"
-        "#,
+    println!(
+        "{} session cache tests: {}",
+        backend.name(),
+        report_test_results(&results)
     );
 
-    let config = SessionConfig::from_str(&config_str)?;
-    let cache = PostgresSessionCache::connect(config).await?;
-
-    Ok(cache)
+    Ok(results)
 }
 
 fn test_state(rng: &mut StdRng) -> Result<TestState> {
@@ -73,8 +68,8 @@ fn test_state(rng: &mut StdRng) -> Result<TestState> {
     })
 }
 
-async fn key_does_not_exist() -> Result<()> {
-    let cache = new_cache("60s").await?;
+async fn key_does_not_exist<B: Backend>(backend: Arc<B>) -> Result<()> {
+    let cache = backend.session_cache(Duration::from_secs(60)).await?;
     let session_id = get_temp_session_id();
 
     match cache.find_session(session_id).await {
@@ -90,9 +85,9 @@ async fn key_does_not_exist() -> Result<()> {
 }
 
 /// Ensure we can get the key back without encountering expiration error.
-async fn get_key_back() -> Result<()> {
+async fn get_key_back<B: Backend>(backend: Arc<B>) -> Result<()> {
     let mut rng = StdRng::seed_from_u64(SEED);
-    let cache = new_cache("60s").await?;
+    let cache = backend.session_cache(Duration::from_secs(60)).await?;
     let state = test_state(&mut rng)?;
 
     let encrypted_key = state
@@ -100,7 +95,7 @@ async fn get_key_back() -> Result<()> {
         .encrypt_session_key(&mut rng, state.session_key)?;
 
     let session_id = cache
-        .create_session(state.account_id, encrypted_key)
+        .create_session(state.account_id, encrypted_key, None)
         .await?;
 
     // We got a key back.
@@ -108,23 +103,93 @@ async fn get_key_back() -> Result<()> {
     Ok(())
 }
 
-/// Insert key twice and ensure code runs all the way.
-async fn overwrite_existing_key() -> Result<()> {
+/// An account can have several sessions at once. Each one gets its own ID.
+async fn multiple_sessions_per_account<B: Backend>(backend: Arc<B>) -> Result<()> {
     let mut rng = StdRng::seed_from_u64(SEED);
-    let cache = new_cache("60s").await?;
+    let cache = backend.session_cache(Duration::from_secs(60)).await?;
     let state = test_state(&mut rng)?;
 
     let encrypted_key = state
         .remote_key
         .encrypt_session_key(&mut rng, state.session_key)?;
-    cache
-        .create_session(state.account_id, encrypted_key)
+    let first_session_id = cache
+        .create_session(state.account_id, encrypted_key, None)
         .await?;
 
     let second_key = get_temp_session_key()?;
     let second_encrypted_key = state.remote_key.encrypt_session_key(&mut rng, second_key)?;
+    let second_session_id = cache
+        .create_session(state.account_id, second_encrypted_key, None)
+        .await?;
+
+    assert_ne!(first_session_id, second_session_id);
+    for session_id in [first_session_id, second_session_id] {
+        let session = cache.find_session(session_id).await?;
+        assert_eq!(session.session_id, session_id);
+        assert_eq!(session.account_id, state.account_id);
+    }
+
+    Ok(())
+}
+
+/// A deleted session can't be found, and deleting it again is not an error.
+async fn deleted_session_is_missing<B: Backend>(backend: Arc<B>) -> Result<()> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let cache = backend.session_cache(Duration::from_secs(60)).await?;
+    let state = test_state(&mut rng)?;
+
+    let encrypted_key = state
+        .remote_key
+        .encrypt_session_key(&mut rng, state.session_key)?;
+    let session_id = cache
+        .create_session(state.account_id, encrypted_key, None)
+        .await?;
+
+    cache.delete_session(session_id).await?;
+    assert!(matches!(
+        cache.find_session(session_id).await,
+        Err(SessionCacheError::MissingSession)
+    ));
+    cache.delete_session(session_id).await?;
+
+    Ok(())
+}
+
+/// A request can only be recorded once per session. The same request ID may
+/// still be used with another action or on another session.
+async fn replayed_request_is_rejected<B: Backend>(backend: Arc<B>) -> Result<()> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let cache = backend.session_cache(Duration::from_secs(60)).await?;
+    let state = test_state(&mut rng)?;
+    let request_id = Uuid::new_v4();
+
+    let mut session_ids = Vec::new();
+    for _ in 0..2 {
+        let encrypted_key = state
+            .remote_key
+            .encrypt_session_key(&mut rng, get_temp_session_key()?)?;
+        session_ids.push(
+            cache
+                .create_session(state.account_id, encrypted_key, None)
+                .await?,
+        );
+    }
+
+    cache
+        .record_request(session_ids[0], request_id, ClientAction::RetrieveStorageKey)
+        .await?;
+    cache
+        .record_request(session_ids[0], request_id, ClientAction::RetrieveSecret)
+        .await?;
+    assert!(matches!(
+        cache
+            .record_request(session_ids[0], request_id, ClientAction::RetrieveSecret)
+            .await,
+        Err(SessionCacheError::ReplayedRequest)
+    ));
+
     cache
-        .create_session(state.account_id, second_encrypted_key)
+        .record_request(session_ids[1], request_id, ClientAction::RetrieveSecret)
         .await?;
 
     Ok(())
@@ -132,17 +197,17 @@ async fn overwrite_existing_key() -> Result<()> {
 
 /// Test key expiration logic when enough time has passed that the key
 /// should be expired.
-async fn key_expired() -> Result<()> {
+async fn key_expired<B: Backend>(backend: Arc<B>) -> Result<()> {
     // Keys expire instantly
     let mut rng = StdRng::seed_from_u64(SEED);
-    let cache = new_cache("0s").await?;
+    let cache = backend.session_cache(Duration::from_secs(0)).await?;
     let state = test_state(&mut rng)?;
 
     let encrypted_key = state
         .remote_key
         .encrypt_session_key(&mut rng, state.session_key)?;
     let session_id = cache
-        .create_session(state.account_id, encrypted_key)
+        .create_session(state.account_id, encrypted_key, None)
         .await?;
 
     // Verify key is expired.
@@ -159,17 +224,17 @@ async fn key_expired() -> Result<()> {
 }
 
 /// Key expires after a short time.
-async fn key_expired2() -> Result<()> {
+async fn key_expired2<B: Backend>(backend: Arc<B>) -> Result<()> {
     // Handle a longer timeout.
     let mut rng = StdRng::seed_from_u64(SEED);
-    let cache = new_cache("1s").await?;
+    let cache = backend.session_cache(Duration::from_secs(1)).await?;
     let state = test_state(&mut rng)?;
 
     let encrypted_key = state
         .remote_key
         .encrypt_session_key(&mut rng, state.session_key)?;
     let session_id = cache
-        .create_session(state.account_id, encrypted_key)
+        .create_session(state.account_id, encrypted_key, None)
         .await?;
 
     // Sleep for a while so key expires.
@@ -188,6 +253,41 @@ async fn key_expired2() -> Result<()> {
     Ok(())
 }
 
+/// Only sessions that haven't expired are counted. Other tests may share the
+/// same cache, so only a lower bound is checked for active sessions.
+async fn active_sessions_are_counted<B: Backend>(backend: Arc<B>) -> Result<()> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    let state = test_state(&mut rng)?;
+
+    let cache = backend.session_cache(Duration::from_secs(60)).await?;
+    let encrypted_key = state
+        .remote_key
+        .encrypt_session_key(&mut rng, state.session_key)?;
+    let _ = cache
+        .create_session(state.account_id, encrypted_key, None)
+        .await?;
+    assert!(cache.count_active_sessions().await? >= 1);
+
+    // Every session is already expired for a cache with no expiration time.
+    let expired_cache = backend.session_cache(Duration::from_secs(0)).await?;
+    let encrypted_key = state
+        .remote_key
+        .encrypt_session_key(&mut rng, get_temp_session_key()?)?;
+    let _ = expired_cache
+        .create_session(state.account_id, encrypted_key, None)
+        .await?;
+    assert_eq!(expired_cache.count_active_sessions().await?, 0);
+
+    Ok(())
+}
+
+async fn health_check_succeeds<B: Backend>(backend: Arc<B>) -> Result<()> {
+    let cache = backend.session_cache(Duration::from_secs(60)).await?;
+    cache.health_check().await?;
+
+    Ok(())
+}
+
 fn get_temp_session_key() -> Result<OpaqueSessionKey> {
     let key = OpaqueSessionKey::try_from(GenericArray::from([FILLER; 64]))?;
     Ok(key)
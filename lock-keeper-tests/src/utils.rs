@@ -13,6 +13,7 @@ use colored::Colorize;
 use futures::Future;
 use lock_keeper::config::opaque::OpaqueCipherSuite;
 use lock_keeper_client::{Config as ClientConfig, LockKeeperClient};
+use lock_keeper_sqlite::Config as SqliteConfig;
 use opaque_ke::{
     ClientRegistration, ClientRegistrationFinishParameters, ServerRegistration, ServerSetup,
 };
@@ -23,6 +24,7 @@ pub const RNG_SEED: &[u8; 32] = b"we love deterministic testing!!!";
 /// Add random text to the end of a string
 /// # Example
 /// ```
+/// # use lock_keeper_tests::utils::tagged;
 /// let user = tagged("user");
 /// println!("{user}");
 /// // Prints something like "user_1h65k35"
@@ -38,6 +40,17 @@ pub fn tagged(text: impl AsRef<str>) -> String {
     format!("{text}_{tag}")
 }
 
+/// Config for a new SQLite database in a randomly named file in the temp
+/// directory. Each call gets an empty database.
+pub fn sqlite_config(session_expiration: Duration) -> SqliteConfig {
+    SqliteConfig {
+        path: std::env::temp_dir().join(format!("{}.db", tagged("lock-keeper-test"))),
+        max_connections: 5,
+        connection_timeout: Duration::from_secs(3),
+        session_expiration,
+    }
+}
+
 /// Locally simulates OPAQUE registration to get a valid
 /// `ServerRegistration` for remaining tests.
 pub fn server_registration() -> ServerRegistration<OpaqueCipherSuite> {
@@ -73,7 +86,7 @@ pub fn random_bytes(mut rng: impl Rng, len: usize) -> Vec<u8> {
 /// All test functions must return [`Result<()>`].
 /// Tests will run in parallel and report the names of any failing test.
 /// # Example
-/// ```no_run
+/// ```ignore
 /// run_parallel(test_1(), test_2(&db))
 /// ```
 #[macro_export]
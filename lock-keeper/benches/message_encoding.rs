@@ -0,0 +1,148 @@
+//! Compares the size and latency of the message encodings for the
+//! `store_server_encrypted_blob` and `remote_sign_bytes` operations.
+//!
+//! Each iteration encodes a message, encrypts it under a session key, converts
+//! it to the wire format and back, decrypts it, and decodes it again, like a
+//! message sent over an authenticated channel. Run with `cargo bench -p
+//! lock-keeper`; the size of each wire message is printed before the
+//! measurements.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use generic_array::GenericArray;
+use lock_keeper::{
+    crypto::{
+        Encrypted, KeyId, MessageContext, MessageDirection, OpaqueSessionKey, Signable,
+        SignableBytes, SigningKeyPair,
+    },
+    rpc::Message,
+    types::{
+        database::account::UserId,
+        operations::{
+            remote_sign_bytes, store_server_encrypted_blob, ClientAction, MessageEncoding,
+            RequestMetadata,
+        },
+    },
+};
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{de::DeserializeOwned, Serialize};
+use strum::IntoEnumIterator;
+use uuid::Uuid;
+
+/// Size of the blob sent by `store_server_encrypted_blob`.
+const BLOB_SIZE: usize = 16 * 1024;
+
+/// Encode, encrypt and convert `value` into the message sent over the wire.
+fn send(
+    rng: &mut StdRng,
+    session_key: &OpaqueSessionKey,
+    context: &MessageContext,
+    value: &impl Serialize,
+) -> Message {
+    let encoding = context.metadata.message_encoding();
+    let message = Message {
+        content: encoding.serialize(value).unwrap(),
+    };
+    session_key
+        .encrypt(rng, message, context)
+        .unwrap()
+        .try_into_message(encoding)
+        .unwrap()
+}
+
+/// Reverse of [`send`].
+fn receive<T: DeserializeOwned>(
+    session_key: &OpaqueSessionKey,
+    context: &MessageContext,
+    message: Message,
+) -> T {
+    let encoding = context.metadata.message_encoding();
+    let message = Encrypted::<Message>::try_from_message(message, encoding)
+        .unwrap()
+        .decrypt_message(session_key, context)
+        .unwrap();
+    encoding.deserialize(&message.content).unwrap()
+}
+
+fn bench_round_trip<T: Serialize + DeserializeOwned>(
+    c: &mut Criterion,
+    operation: &str,
+    action: ClientAction,
+    value: &T,
+) {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let session_key = OpaqueSessionKey::try_from(GenericArray::from([7; 64])).unwrap();
+
+    let mut group = c.benchmark_group(operation);
+    for encoding in MessageEncoding::iter() {
+        let metadata = RequestMetadata::new(
+            &"alice".parse().unwrap(),
+            action,
+            None,
+            Uuid::new_v4(),
+            encoding,
+        );
+        let context = MessageContext {
+            metadata: &metadata,
+            direction: MessageDirection::ClientToServer,
+            sequence_number: 0,
+        };
+        let size = send(&mut rng, &session_key, &context, value).content.len();
+        println!("{operation}/{encoding}: {size} bytes on the wire");
+
+        let _ = group.bench_with_input(
+            BenchmarkId::from_parameter(encoding),
+            &context,
+            |b, context| {
+                b.iter(|| {
+                    let message = send(&mut rng, &session_key, context, black_box(value));
+                    receive::<T>(&session_key, context, message)
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn store_server_encrypted_blob(c: &mut Criterion) {
+    let request = store_server_encrypted_blob::client::Request {
+        data_blob: vec![0xab; BLOB_SIZE],
+    };
+    bench_round_trip(
+        c,
+        "store_server_encrypted_blob",
+        ClientAction::StoreServerEncryptedBlob,
+        &request,
+    );
+}
+
+fn remote_sign_bytes(c: &mut Criterion) {
+    let mut rng = StdRng::from_seed([1; 32]);
+    let user_id = UserId::new(&mut rng).unwrap();
+    let key_id = KeyId::generate(&mut rng, &user_id).unwrap();
+    let data = SignableBytes(b"transaction to be signed by the key server".to_vec());
+
+    let request = remote_sign_bytes::client::RequestRemoteSign {
+        key_id: key_id.clone(),
+        data: data.clone(),
+    };
+    bench_round_trip(
+        c,
+        "remote_sign_bytes/request",
+        ClientAction::RemoteSignBytes,
+        &request,
+    );
+
+    let key_pair = SigningKeyPair::remote_generate(&mut rng, &user_id, &key_id);
+    let response = remote_sign_bytes::server::ReturnSignature {
+        signature: data.sign(&key_pair),
+    };
+    bench_round_trip(
+        c,
+        "remote_sign_bytes/response",
+        ClientAction::RemoteSignBytes,
+        &response,
+    );
+}
+
+criterion_group!(benches, store_server_encrypted_blob, remote_sign_bytes);
+criterion_main!(benches);
@@ -8,3 +8,16 @@ pub const ACTION: &str = "action";
 pub const KEY_ID: &str = "key_id";
 pub const METADATA: &str = "metadata";
 pub const USER_ID: &str = "user_id";
+
+/// Version of the client-server protocol spoken by this build. It is sent with
+/// every request in [`RequestMetadata`](crate::types::operations::RequestMetadata)
+/// and must be increased whenever a change breaks compatibility between
+/// clients and servers, e.g. a change to the messages of an operation.
+///
+/// Version 2 binds messages on authenticated channels to their request,
+/// direction and sequence number.
+pub const PROTOCOL_VERSION: u32 = 2;
+/// Oldest protocol version that servers from this build still accept.
+/// Version 0 stands for clients and servers from before protocol versions
+/// were introduced.
+pub const MIN_SUPPORTED_PROTOCOL_VERSION: u32 = 2;
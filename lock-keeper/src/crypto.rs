@@ -4,7 +4,13 @@
 //! transformations between them. Public functions here are mostly wrappers
 //! around multiple low-level cryptographic steps.
 
-use crate::{types::database::HexBytes, LockKeeperError};
+use crate::{
+    types::{
+        database::HexBytes,
+        operations::{MessageEncoding, RequestMetadata},
+    },
+    LockKeeperError,
+};
 use generic_array::{typenum::U64, GenericArray};
 use hkdf::{hmac::digest::Output, Hkdf};
 use k256::sha2::Sha512;
@@ -22,12 +28,14 @@ use zeroize::{Zeroize, ZeroizeOnDrop};
 use crate::types::database::account::UserId;
 
 mod arbitrary_secret;
+mod audit_key;
 mod cryptor;
 mod cryptor_key;
 mod data_blob;
 mod generic;
 pub mod seal_signing_private_key;
 pub mod secure_structs;
+mod service_credential;
 pub mod sharding;
 mod signing_key;
 mod signing_private_key;
@@ -35,11 +43,16 @@ mod storage_key;
 
 use crate::rpc::Message;
 pub use arbitrary_secret::Secret;
+pub use audit_key::{AuditPublicKey, AuditSignature, AuditSigningKey};
 pub use cryptor::{CryptorContext, Decryptor, Encryptor};
 pub use cryptor_key::CryptorKey;
 pub use data_blob::DataBlob;
 use generic::{AssociatedData, EncryptionKey};
 pub use generic::{CryptoError, Encrypted};
+pub use service_credential::{
+    ServiceAuthTranscript, ServiceCredentialAlgorithm, ServiceCredentialKeyPair,
+    ServiceCredentialPublicKey, ServiceCredentialSignature, ServiceKeyExchange,
+};
 pub use signing_key::{
     Import, Signable, SignableBytes, Signature, SigningKeyPair, SigningPublicKey,
 };
@@ -73,13 +86,15 @@ impl TryFrom<GenericArray<u8, U64>> for OpaqueSessionKey {
 
 impl OpaqueSessionKey {
     /// Encrypt the given [`Message`] under the [`OpaqueSessionKey`] using an
-    /// AEAD scheme.
+    /// AEAD scheme. The ciphertext is bound to the message's place in its
+    /// channel, given by `context`.
     pub fn encrypt(
         &self,
         rng: &mut (impl CryptoRng + RngCore),
         message: Message,
+        context: &MessageContext,
     ) -> Result<Encrypted<Message>, CryptoError> {
-        Encrypted::encrypt(rng, &self.0, message, &AssociatedData::new())
+        Encrypted::encrypt(rng, &self.0, message, &context.associated_data())
     }
 
     fn context(&self) -> &AssociatedData {
@@ -89,6 +104,27 @@ impl OpaqueSessionKey {
     pub(crate) fn domain_separator() -> &'static str {
         "OPAQUE-derived Lock Keeper session key"
     }
+
+    /// Derive a session key from the output of a Diffie-Hellman exchange.
+    ///
+    /// This is used by service accounts, which establish sessions without
+    /// running OPAQUE. The resulting key is bound to the given transcript.
+    pub(crate) fn derive_from_key_exchange(
+        shared_secret: &[u8],
+        transcript: &[u8],
+    ) -> Result<Self, CryptoError> {
+        let mut key_material = [0u8; 32];
+
+        Hkdf::<Sha3_256>::new(None, shared_secret)
+            .expand(transcript, &mut key_material)
+            .map_err(|e| {
+                error!("HKDF failed unexpectedly. {:?}", e);
+                CryptoError::KeyDerivationFailed(e)
+            })?;
+
+        let context = AssociatedData::new().with_str(Self::domain_separator());
+        Ok(Self(EncryptionKey::from_bytes(key_material, context)))
+    }
 }
 
 impl TryFrom<OpaqueSessionKey> for Vec<u8> {
@@ -239,27 +275,88 @@ impl MasterKey {
     }
 }
 
+/// Sender of a message on an authenticated channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageDirection {
+    ClientToServer,
+    ServerToClient,
+}
+
+impl MessageDirection {
+    fn domain_separator(self) -> &'static str {
+        match self {
+            MessageDirection::ClientToServer => "client to server",
+            MessageDirection::ServerToClient => "server to client",
+        }
+    }
+}
+
+/// Place of a message in an authenticated channel.
+///
+/// This is bound into the associated data of the message's ciphertext, so a
+/// message is only accepted by the request it was sent in, in the direction it
+/// was sent, and at its position in that direction. Replayed, reflected and
+/// reordered messages are rejected with
+/// [`LockKeeperError::MessageOutOfSequence`].
+///
+/// This doesn't stop every message of a request from being replayed together
+/// in a new request with the same metadata. The key server prevents that by
+/// accepting each request ID only once per session and action.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageContext<'a> {
+    pub metadata: &'a RequestMetadata,
+    pub direction: MessageDirection,
+    /// Number of messages sent in `direction` before this one.
+    pub sequence_number: u64,
+}
+
+impl MessageContext<'_> {
+    fn domain_separator() -> &'static str {
+        "Lock Keeper authenticated channel message"
+    }
+
+    fn associated_data(&self) -> AssociatedData {
+        AssociatedData::new()
+            .with_str(Self::domain_separator())
+            .with_bytes(self.metadata.request_id().into_bytes())
+            .with_bytes((self.metadata.action() as i64).to_be_bytes())
+            .with_str(self.direction.domain_separator())
+            .with_bytes(self.sequence_number.to_be_bytes())
+    }
+}
+
 impl Encrypted<Message> {
+    /// Decrypt a message received at the place in its channel given by
+    /// `context`.
     pub fn decrypt_message(
         self,
         session_key: &OpaqueSessionKey,
+        context: &MessageContext,
     ) -> Result<Message, LockKeeperError> {
+        // Check that the message was sent for this place in the channel.
+        if self.associated_data != context.associated_data() {
+            return Err(LockKeeperError::MessageOutOfSequence);
+        }
+
         let decrypted = self.decrypt_inner(&session_key.0)?;
         Ok(decrypted)
     }
 
     /// Translates an [`Encrypted<Message>`] to a [`Message`] in order to be
     /// sent through an authenticated channel.
-    pub fn try_into_message(self) -> Result<Message, LockKeeperError> {
-        let content = serde_json::to_vec(&self)?;
+    pub fn try_into_message(self, encoding: MessageEncoding) -> Result<Message, LockKeeperError> {
+        let content = encoding.serialize(&self)?;
 
         Ok(Message { content })
     }
 
     /// Translates a [`Message`] received through an authenticated channel to an
     /// [`Encrypted<Message>`].
-    pub fn try_from_message(message: Message) -> Result<Self, LockKeeperError> {
-        Ok(serde_json::from_slice(&message.content)?)
+    pub fn try_from_message(
+        message: Message,
+        encoding: MessageEncoding,
+    ) -> Result<Self, LockKeeperError> {
+        encoding.deserialize(&message.content)
     }
 }
 
@@ -382,9 +479,10 @@ mod test {
     use std::collections::HashSet;
 
     use crate::{
-        types::operations::{get_user_id, ConvertMessage},
+        types::operations::{get_user_id, ClientAction, ConvertMessage},
         LockKeeperError,
     };
+    use strum::IntoEnumIterator;
 
     use super::*;
     use rand::{rngs::StdRng, Rng, SeedableRng};
@@ -470,6 +568,16 @@ mod test {
             .all(|key_id| uniq.insert(key_id)))
     }
 
+    fn test_metadata(action: ClientAction) -> RequestMetadata {
+        RequestMetadata::new(
+            &"alice".parse().unwrap(),
+            action,
+            None,
+            uuid::Uuid::new_v4(),
+            MessageEncoding::default(),
+        )
+    }
+
     #[test]
     fn message_encryption_works() -> Result<(), LockKeeperError> {
         let mut rng = rand::thread_rng();
@@ -479,20 +587,31 @@ mod test {
         // Set up matching RNGs to check behavior of the utility function.
         let seed = b"not-random seed for convenience!";
         let mut rng = StdRng::from_seed(*seed);
-
-        // Encrypt a message
-        let message = get_user_id::server::Response {
-            user_id: user_id.clone(),
+        let metadata = test_metadata(ClientAction::GetUserId);
+        let context = MessageContext {
+            metadata: &metadata,
+            direction: MessageDirection::ServerToClient,
+            sequence_number: 0,
+        };
+
+        for encoding in MessageEncoding::iter() {
+            // Encrypt a message
+            let message = get_user_id::server::Response {
+                user_id: user_id.clone(),
+            }
+            .to_message(encoding);
+            let expected_message = get_user_id::server::Response {
+                user_id: user_id.clone(),
+            };
+            let encrypted_message = session_key.encrypt(&mut rng, message?, &context)?;
+
+            // Decrypt the message and check that it worked
+            let decrypted_message = get_user_id::server::Response::from_message(
+                encrypted_message.decrypt_message(&session_key, &context)?,
+                encoding,
+            )?;
+            assert_eq!(expected_message.user_id, decrypted_message.user_id);
         }
-        .to_message();
-        let expected_message = get_user_id::server::Response { user_id };
-        let encrypted_message = session_key.encrypt(&mut rng, message?)?;
-
-        // Decrypt the message and check that it worked
-        let decrypted_message = get_user_id::server::Response::from_message(
-            encrypted_message.decrypt_message(&session_key)?,
-        )?;
-        assert_eq!(expected_message.user_id, decrypted_message.user_id);
 
         Ok(())
     }
@@ -506,19 +625,102 @@ mod test {
         // Set up matching RNGs to check behavior of the utility function.
         let seed = b"not-random seed for convenience!";
         let mut rng = StdRng::from_seed(*seed);
+        let metadata = test_metadata(ClientAction::GetUserId);
+        let context = MessageContext {
+            metadata: &metadata,
+            direction: MessageDirection::ServerToClient,
+            sequence_number: 0,
+        };
+
+        for encoding in MessageEncoding::iter() {
+            // Encrypt a message
+            let message = get_user_id::server::Response {
+                user_id: user_id.clone(),
+            }
+            .to_message(encoding)?;
+            let encrypted_message = session_key.encrypt(&mut rng, message, &context)?;
+
+            let result_to_message = encrypted_message.clone().try_into_message(encoding);
+            assert!(result_to_message.is_ok());
+
+            let result_from_message =
+                Encrypted::<Message>::try_from_message(result_to_message.unwrap(), encoding);
+            assert!(result_from_message.is_ok());
+
+            assert_eq!(encrypted_message, result_from_message.unwrap());
+        }
 
-        // Encrypt a message
-        let message = get_user_id::server::Response { user_id }.to_message()?;
-        let encrypted_message = session_key.encrypt(&mut rng, message)?;
-
-        let result_to_message = encrypted_message.clone().try_into_message();
-        assert!(result_to_message.is_ok());
+        Ok(())
+    }
 
-        let result_from_message =
-            Encrypted::<Message>::try_from_message(result_to_message.unwrap());
-        assert!(result_from_message.is_ok());
+    #[test]
+    fn messages_are_bound_to_their_place_in_the_channel() -> Result<(), LockKeeperError> {
+        let mut rng = rand::thread_rng();
+        let session_key = create_test_session_key(&mut rng);
+        let metadata = test_metadata(ClientAction::RemoteSignBytes);
+        let context = MessageContext {
+            metadata: &metadata,
+            direction: MessageDirection::ClientToServer,
+            sequence_number: 1,
+        };
+        let encrypted_message = session_key.encrypt(&mut rng, vec![1, 2, 3].into(), &context)?;
+
+        // Replayed into another request
+        let other_request = test_metadata(ClientAction::RemoteSignBytes);
+        // Replayed into the same request with another action
+        let other_action = RequestMetadata::new(
+            &"alice".parse().unwrap(),
+            ClientAction::ImportSigningKey,
+            None,
+            metadata.request_id(),
+            MessageEncoding::default(),
+        );
+        let wrong_contexts = [
+            MessageContext {
+                metadata: &other_request,
+                ..context
+            },
+            MessageContext {
+                metadata: &other_action,
+                ..context
+            },
+            // Reflected back to the sender
+            MessageContext {
+                direction: MessageDirection::ServerToClient,
+                ..context
+            },
+            // Replayed or reordered within the request
+            MessageContext {
+                sequence_number: 0,
+                ..context
+            },
+            MessageContext {
+                sequence_number: 2,
+                ..context
+            },
+        ];
+        for wrong_context in wrong_contexts {
+            assert!(matches!(
+                encrypted_message
+                    .clone()
+                    .decrypt_message(&session_key, &wrong_context),
+                Err(LockKeeperError::MessageOutOfSequence)
+            ));
+        }
 
-        assert_eq!(encrypted_message, result_from_message.unwrap());
+        // Tampering with the associated data makes decryption fail.
+        let mut tampered = encrypted_message.clone();
+        let other_context = MessageContext {
+            sequence_number: 2,
+            ..context
+        };
+        tampered.associated_data = other_context.associated_data();
+        assert!(tampered
+            .decrypt_message(&session_key, &other_context)
+            .is_err());
+
+        let message = encrypted_message.decrypt_message(&session_key, &context)?;
+        assert_eq!(message.content, vec![1, 2, 3]);
 
         Ok(())
     }
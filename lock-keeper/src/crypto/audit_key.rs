@@ -0,0 +1,161 @@
+//! Key material for signing audit log checkpoints.
+//!
+//! The key server periodically signs the hash of the latest audit event for an
+//! account with its [`AuditSigningKey`]. Anyone holding the matching
+//! [`AuditPublicKey`] can then check that the audit log has not been truncated
+//! or rewritten since the checkpoint was made.
+
+use super::CryptoError;
+use crate::LockKeeperError;
+use rand::{CryptoRng, RngCore};
+use ring::signature::KeyPair;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::error;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Ed25519 key used by the key server to sign audit checkpoints.
+///
+/// This key never leaves the key server. Only its [`AuditPublicKey`] should be
+/// shared with the parties that verify audit logs.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct AuditSigningKey {
+    seed: [u8; 32],
+}
+
+impl std::fmt::Debug for AuditSigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditSigningKey").finish_non_exhaustive()
+    }
+}
+
+impl AuditSigningKey {
+    /// Generate a new signing key.
+    pub fn generate(rng: &mut (impl CryptoRng + RngCore)) -> Self {
+        let mut seed = [0_u8; 32];
+        rng.fill_bytes(&mut seed);
+        Self { seed }
+    }
+
+    /// Get the public key that verifies signatures made with this key.
+    pub fn public_key(&self) -> Result<AuditPublicKey, CryptoError> {
+        let key_pair = self.key_pair()?;
+        let bytes = key_pair
+            .public_key()
+            .as_ref()
+            .try_into()
+            .map_err(|_| CryptoError::ConversionError)?;
+
+        Ok(AuditPublicKey(bytes))
+    }
+
+    /// Sign the given message with this key.
+    pub fn sign(&self, message: &[u8]) -> Result<AuditSignature, CryptoError> {
+        let signature = self.key_pair()?.sign(message);
+        Ok(AuditSignature(signature.as_ref().to_vec()))
+    }
+
+    /// Serialize this key as its 32-byte Ed25519 seed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.seed.to_vec()
+    }
+
+    /// Parse a key serialized with [`AuditSigningKey::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+        let key = Self {
+            seed: bytes.try_into().map_err(|_| CryptoError::ConversionError)?,
+        };
+
+        // Make sure the seed produces a valid key pair.
+        let _ = key.key_pair()?;
+        Ok(key)
+    }
+
+    /// Returns the key found in the file at the given path.
+    pub fn read_from_file(path: impl AsRef<Path>) -> Result<Self, LockKeeperError> {
+        let bytes = std::fs::read(&path)
+            .map_err(|e| LockKeeperError::FileIo(e, path.as_ref().to_path_buf()))?;
+        Ok(Self::from_bytes(&bytes)?)
+    }
+
+    fn key_pair(&self) -> Result<ring::signature::Ed25519KeyPair, CryptoError> {
+        ring::signature::Ed25519KeyPair::from_seed_unchecked(&self.seed).map_err(|e| {
+            error!("{e}");
+            CryptoError::ConversionError
+        })
+    }
+}
+
+/// Public half of an [`AuditSigningKey`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditPublicKey([u8; 32]);
+
+impl AuditPublicKey {
+    /// Construct a public key from its raw 32-byte encoding.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+        Ok(Self(
+            bytes.try_into().map_err(|_| CryptoError::ConversionError)?,
+        ))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Verify an [`AuditSignature`] on the given message.
+    pub fn verify(&self, message: &[u8], signature: &AuditSignature) -> Result<(), CryptoError> {
+        ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, &self.0)
+            .verify(message, &signature.0)
+            .map_err(|_| CryptoError::VerificationFailed)
+    }
+}
+
+/// A signature produced by an [`AuditSigningKey`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditSignature(Vec<u8>);
+
+impl AuditSignature {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn signatures_verify() {
+        let mut rng = StdRng::from_entropy();
+        let key = AuditSigningKey::generate(&mut rng);
+        let public_key = key.public_key().unwrap();
+        let signature = key.sign(b"a message").unwrap();
+
+        public_key.verify(b"a message", &signature).unwrap();
+        assert!(public_key.verify(b"another message", &signature).is_err());
+
+        let other_key = AuditSigningKey::generate(&mut rng);
+        assert!(other_key
+            .public_key()
+            .unwrap()
+            .verify(b"a message", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn key_round_trips_through_bytes() {
+        let mut rng = StdRng::from_entropy();
+        let key = AuditSigningKey::generate(&mut rng);
+        let parsed = AuditSigningKey::from_bytes(&key.to_bytes()).unwrap();
+        assert_eq!(key.public_key().unwrap(), parsed.public_key().unwrap());
+
+        assert!(AuditSigningKey::from_bytes(&[7; 33]).is_err());
+        assert!(AuditPublicKey::from_bytes(&[7; 31]).is_err());
+    }
+}
@@ -0,0 +1,430 @@
+//! Key material for service-account credentials.
+//!
+//! Non-interactive clients (backend services) authenticate with an asymmetric
+//! [`ServiceCredentialKeyPair`] instead of an OPAQUE password. The public half
+//! of the key pair is registered with the key server by an authenticated
+//! account. To log in, the service signs a challenge-response transcript that
+//! also binds an ephemeral P-256 Diffie-Hellman exchange; the result of that
+//! exchange is the session key for the new session.
+//!
+//! Since service credentials never see the account password, they cannot
+//! derive the account's [`MasterKey`](super::MasterKey).
+
+use super::{generic::AssociatedData, CryptoError, OpaqueSessionKey};
+use crate::{types::database::account::AccountName, LockKeeperError};
+use p256::ecdsa::signature::{Signer, Verifier};
+use rand::{CryptoRng, RngCore};
+use ring::signature::KeyPair;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use strum::{Display, EnumString};
+use tracing::error;
+use uuid::Uuid;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Signature algorithms supported for service credentials.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Display, EnumString)]
+pub enum ServiceCredentialAlgorithm {
+    /// Ed25519 signatures as specified in RFC 8032.
+    Ed25519,
+    /// ECDSA over the NIST P-256 curve with SHA-256.
+    P256,
+}
+
+impl ServiceCredentialAlgorithm {
+    fn tag(&self) -> u8 {
+        match self {
+            ServiceCredentialAlgorithm::Ed25519 => 0,
+            ServiceCredentialAlgorithm::P256 => 1,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<Self, CryptoError> {
+        match tag {
+            0 => Ok(ServiceCredentialAlgorithm::Ed25519),
+            1 => Ok(ServiceCredentialAlgorithm::P256),
+            _ => Err(CryptoError::ConversionError),
+        }
+    }
+}
+
+/// Private key held by a service account.
+///
+/// This key should never be sent to the server. Only its
+/// [`ServiceCredentialPublicKey`] is registered.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct ServiceCredentialKeyPair {
+    #[zeroize(skip)]
+    algorithm: ServiceCredentialAlgorithm,
+    /// The Ed25519 seed or the P-256 secret scalar.
+    secret: [u8; 32],
+}
+
+impl std::fmt::Debug for ServiceCredentialKeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServiceCredentialKeyPair")
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ServiceCredentialKeyPair {
+    /// Generate a new key pair for the given algorithm.
+    pub fn generate(
+        rng: &mut (impl CryptoRng + RngCore),
+        algorithm: ServiceCredentialAlgorithm,
+    ) -> Self {
+        let secret = match algorithm {
+            ServiceCredentialAlgorithm::Ed25519 => {
+                let mut seed = [0_u8; 32];
+                rng.fill_bytes(&mut seed);
+                seed
+            }
+            ServiceCredentialAlgorithm::P256 => {
+                p256::ecdsa::SigningKey::random(rng).to_bytes().into()
+            }
+        };
+
+        Self { algorithm, secret }
+    }
+
+    pub fn algorithm(&self) -> ServiceCredentialAlgorithm {
+        self.algorithm
+    }
+
+    /// Get the public half of this key pair, to be registered with the server.
+    pub fn public_key(&self) -> Result<ServiceCredentialPublicKey, CryptoError> {
+        let bytes = match self.algorithm {
+            ServiceCredentialAlgorithm::Ed25519 => {
+                self.ed25519_key_pair()?.public_key().as_ref().to_vec()
+            }
+            ServiceCredentialAlgorithm::P256 => self
+                .p256_signing_key()?
+                .verifying_key()
+                .to_encoded_point(true)
+                .as_bytes()
+                .to_vec(),
+        };
+
+        Ok(ServiceCredentialPublicKey {
+            algorithm: self.algorithm,
+            bytes,
+        })
+    }
+
+    /// Sign the given message with this key pair.
+    pub fn sign(&self, message: &[u8]) -> Result<ServiceCredentialSignature, CryptoError> {
+        let bytes = match self.algorithm {
+            ServiceCredentialAlgorithm::Ed25519 => {
+                self.ed25519_key_pair()?.sign(message).as_ref().to_vec()
+            }
+            ServiceCredentialAlgorithm::P256 => {
+                let signature: p256::ecdsa::Signature =
+                    self.p256_signing_key()?.try_sign(message)?;
+                signature.to_bytes().to_vec()
+            }
+        };
+
+        Ok(ServiceCredentialSignature(bytes))
+    }
+
+    /// Serialize this key pair as an algorithm tag followed by the secret key
+    /// bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        std::iter::once(self.algorithm.tag())
+            .chain(self.secret)
+            .collect()
+    }
+
+    /// Parse a key pair serialized with [`ServiceCredentialKeyPair::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CryptoError> {
+        let (tag, secret) = bytes.split_first().ok_or(CryptoError::ConversionError)?;
+        let key_pair = Self {
+            algorithm: ServiceCredentialAlgorithm::from_tag(*tag)?,
+            secret: secret
+                .try_into()
+                .map_err(|_| CryptoError::ConversionError)?,
+        };
+
+        // Make sure the secret is valid for the given algorithm.
+        let _ = key_pair.public_key()?;
+        Ok(key_pair)
+    }
+
+    /// Returns the key pair found in the file at the given path.
+    pub fn read_from_file(path: impl AsRef<Path>) -> Result<Self, LockKeeperError> {
+        let bytes = std::fs::read(&path)
+            .map_err(|e| LockKeeperError::FileIo(e, path.as_ref().to_path_buf()))?;
+        Ok(Self::from_bytes(&bytes)?)
+    }
+
+    fn ed25519_key_pair(&self) -> Result<ring::signature::Ed25519KeyPair, CryptoError> {
+        ring::signature::Ed25519KeyPair::from_seed_unchecked(&self.secret).map_err(|e| {
+            error!("{e}");
+            CryptoError::ConversionError
+        })
+    }
+
+    fn p256_signing_key(&self) -> Result<p256::ecdsa::SigningKey, CryptoError> {
+        p256::ecdsa::SigningKey::from_slice(&self.secret).map_err(|e| {
+            error!("{e}");
+            CryptoError::ConversionError
+        })
+    }
+}
+
+/// Public half of a [`ServiceCredentialKeyPair`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceCredentialPublicKey {
+    algorithm: ServiceCredentialAlgorithm,
+    bytes: Vec<u8>,
+}
+
+impl ServiceCredentialPublicKey {
+    /// Construct a public key from its encoding. Ed25519 keys are the raw
+    /// 32-byte point; P-256 keys are SEC1-encoded.
+    pub fn new(algorithm: ServiceCredentialAlgorithm, bytes: Vec<u8>) -> Result<Self, CryptoError> {
+        let valid = match algorithm {
+            ServiceCredentialAlgorithm::Ed25519 => bytes.len() == 32,
+            ServiceCredentialAlgorithm::P256 => {
+                p256::ecdsa::VerifyingKey::from_sec1_bytes(&bytes).is_ok()
+            }
+        };
+
+        if !valid {
+            return Err(CryptoError::ConversionError);
+        }
+
+        Ok(Self { algorithm, bytes })
+    }
+
+    pub fn algorithm(&self) -> ServiceCredentialAlgorithm {
+        self.algorithm
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Verify a [`ServiceCredentialSignature`] on the given message.
+    pub fn verify(
+        &self,
+        message: &[u8],
+        signature: &ServiceCredentialSignature,
+    ) -> Result<(), CryptoError> {
+        match self.algorithm {
+            ServiceCredentialAlgorithm::Ed25519 => {
+                ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, &self.bytes)
+                    .verify(message, &signature.0)
+                    .map_err(|_| CryptoError::VerificationFailed)
+            }
+            ServiceCredentialAlgorithm::P256 => {
+                let verifying_key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&self.bytes)
+                    .map_err(|_| CryptoError::ConversionError)?;
+                let signature = p256::ecdsa::Signature::from_slice(&signature.0)
+                    .map_err(|_| CryptoError::VerificationFailed)?;
+                verifying_key.verify(message, &signature).map_err(|e| {
+                    error!("{e}");
+                    CryptoError::VerificationFailed
+                })
+            }
+        }
+    }
+}
+
+/// A signature produced by a [`ServiceCredentialKeyPair`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceCredentialSignature(Vec<u8>);
+
+/// The transcript of a service-account login. The client signs this
+/// transcript with its [`ServiceCredentialKeyPair`], and both parties bind the
+/// derived session key to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceAuthTranscript(AssociatedData);
+
+impl ServiceAuthTranscript {
+    pub fn new(
+        request_id: Uuid,
+        account_name: &AccountName,
+        credential_id: Uuid,
+        challenge: &[u8],
+        server_key_share: &[u8],
+        client_key_share: &[u8],
+    ) -> Self {
+        let fields: [&[u8]; 6] = [
+            request_id.as_bytes(),
+            account_name.as_bytes(),
+            credential_id.as_bytes(),
+            challenge,
+            server_key_share,
+            client_key_share,
+        ];
+
+        // Length-prefix each field so that the encoding is unambiguous.
+        let transcript = fields.iter().fold(
+            AssociatedData::new().with_str("Lock Keeper service account login"),
+            |transcript, field| {
+                transcript
+                    .with_bytes((field.len() as u32).to_be_bytes())
+                    .with_bytes(field.iter().copied())
+            },
+        );
+
+        Self(transcript)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        (&self.0).into()
+    }
+}
+
+/// One side of the ephemeral P-256 Diffie-Hellman exchange used to establish
+/// a session key for a service account.
+pub struct ServiceKeyExchange(p256::ecdh::EphemeralSecret);
+
+impl ServiceKeyExchange {
+    pub fn new(rng: &mut (impl CryptoRng + RngCore)) -> Self {
+        Self(p256::ecdh::EphemeralSecret::random(rng))
+    }
+
+    /// SEC1-encoded public key share to send to the other party.
+    pub fn key_share(&self) -> Vec<u8> {
+        self.0.public_key().to_sec1_bytes().to_vec()
+    }
+
+    /// Complete the exchange with the other party's key share and derive a
+    /// session key bound to the login transcript.
+    pub fn finish(
+        self,
+        peer_key_share: &[u8],
+        transcript: &ServiceAuthTranscript,
+    ) -> Result<OpaqueSessionKey, CryptoError> {
+        let peer_public_key = p256::PublicKey::from_sec1_bytes(peer_key_share)
+            .map_err(|_| CryptoError::ConversionError)?;
+        let shared_secret = self.0.diffie_hellman(&peer_public_key);
+
+        OpaqueSessionKey::derive_from_key_exchange(
+            shared_secret.raw_secret_bytes(),
+            transcript.as_bytes(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const ALGORITHMS: [ServiceCredentialAlgorithm; 2] = [
+        ServiceCredentialAlgorithm::Ed25519,
+        ServiceCredentialAlgorithm::P256,
+    ];
+
+    #[test]
+    fn signatures_verify() {
+        let mut rng = StdRng::from_entropy();
+        for algorithm in ALGORITHMS {
+            let key_pair = ServiceCredentialKeyPair::generate(&mut rng, algorithm);
+            let public_key = key_pair.public_key().unwrap();
+            let signature = key_pair.sign(b"a message").unwrap();
+
+            public_key.verify(b"a message", &signature).unwrap();
+            assert!(public_key.verify(b"another message", &signature).is_err());
+
+            let other_key = ServiceCredentialKeyPair::generate(&mut rng, algorithm);
+            assert!(other_key
+                .public_key()
+                .unwrap()
+                .verify(b"a message", &signature)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn key_pair_round_trips_through_bytes() {
+        let mut rng = StdRng::from_entropy();
+        for algorithm in ALGORITHMS {
+            let key_pair = ServiceCredentialKeyPair::generate(&mut rng, algorithm);
+            let parsed = ServiceCredentialKeyPair::from_bytes(&key_pair.to_bytes()).unwrap();
+            assert_eq!(key_pair.public_key().unwrap(), parsed.public_key().unwrap());
+        }
+
+        assert!(ServiceCredentialKeyPair::from_bytes(&[7; 33]).is_err());
+        assert!(ServiceCredentialKeyPair::from_bytes(&[]).is_err());
+    }
+
+    #[test]
+    fn public_key_construction_validates_encoding() {
+        let mut rng = StdRng::from_entropy();
+        let key_pair =
+            ServiceCredentialKeyPair::generate(&mut rng, ServiceCredentialAlgorithm::P256);
+        let public_key = key_pair.public_key().unwrap();
+
+        assert_eq!(
+            ServiceCredentialPublicKey::new(
+                ServiceCredentialAlgorithm::P256,
+                public_key.as_bytes().to_vec()
+            )
+            .unwrap(),
+            public_key
+        );
+        assert!(
+            ServiceCredentialPublicKey::new(ServiceCredentialAlgorithm::P256, vec![4; 65]).is_err()
+        );
+        assert!(
+            ServiceCredentialPublicKey::new(ServiceCredentialAlgorithm::Ed25519, vec![0; 31])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn key_exchange_derives_same_session_key() {
+        let mut rng = StdRng::from_entropy();
+        let account_name = AccountName::from("service");
+        let server = ServiceKeyExchange::new(&mut rng);
+        let client = ServiceKeyExchange::new(&mut rng);
+        let server_share = server.key_share();
+        let client_share = client.key_share();
+
+        let transcript = ServiceAuthTranscript::new(
+            Uuid::new_v4(),
+            &account_name,
+            Uuid::new_v4(),
+            &[1; 32],
+            &server_share,
+            &client_share,
+        );
+
+        let server_key = server.finish(&client_share, &transcript).unwrap();
+        let client_key = client.finish(&server_share, &transcript).unwrap();
+        assert_eq!(server_key, client_key);
+    }
+
+    #[test]
+    fn session_key_is_bound_to_transcript() {
+        let mut rng = StdRng::from_entropy();
+        let account_name = AccountName::from("service");
+        let server = ServiceKeyExchange::new(&mut rng);
+        let client = ServiceKeyExchange::new(&mut rng);
+        let server_share = server.key_share();
+        let client_share = client.key_share();
+        let request_id = Uuid::new_v4();
+        let credential_id = Uuid::new_v4();
+
+        let transcript = |challenge: &[u8]| {
+            ServiceAuthTranscript::new(
+                request_id,
+                &account_name,
+                credential_id,
+                challenge,
+                &server_share,
+                &client_share,
+            )
+        };
+
+        let server_key = server.finish(&client_share, &transcript(&[1; 32])).unwrap();
+        let client_key = client.finish(&server_share, &transcript(&[2; 32])).unwrap();
+        assert_ne!(server_key, client_key);
+    }
+}
@@ -1,11 +1,13 @@
 //! Error type for all errors returned to code outside of this crate.
 
+use prost::Message;
 use std::path::PathBuf;
+use strum::{Display, EnumIter, IntoEnumIterator};
 use thiserror::Error;
 use tokio::sync::mpsc::error::SendError;
 use tonic::Status;
 
-use crate::crypto::CryptoError;
+use crate::{crypto::CryptoError, rpc::ErrorDetails};
 
 #[derive(Debug, Error)]
 pub enum LockKeeperError {
@@ -29,8 +31,14 @@ pub enum LockKeeperError {
     // Channel errors
     #[error("Invalid message")]
     InvalidMessage,
+    #[error("Message was replayed or received out of order")]
+    MessageOutOfSequence,
     #[error("No message received")]
     NoMessageReceived,
+    #[error("Timed out waiting for the next message")]
+    ReceiveTimeout,
+    #[error("Message exceeds the limit of {max} bytes")]
+    MessageTooLarge { max: usize },
     #[error("Already authenticated")]
     AlreadyAuthenticated,
     #[error("This message should be send over an authenticated channel")]
@@ -46,6 +54,8 @@ pub enum LockKeeperError {
 
     // Wrapped errors
     #[error(transparent)]
+    Bincode(#[from] bincode::Error),
+    #[error(transparent)]
     Hex(#[from] hex::FromHexError),
     /// Generic kitchen sink IO error. Use [LockKeeperError::FileIo] if
     /// the IO error is specifically related to working with files.
@@ -59,6 +69,8 @@ pub enum LockKeeperError {
     OpaqueProtocol(opaque_ke::errors::ProtocolError),
     #[error(transparent)]
     SerdeJson(#[from] serde_json::Error),
+    #[error(transparent)]
+    TimeFormat(#[from] time::error::Format),
     #[error("tokio Sender error: {}", .0)]
     TokioSender(String),
     #[error(transparent)]
@@ -79,21 +91,52 @@ impl<T> From<SendError<T>> for LockKeeperError {
     }
 }
 
+impl LockKeeperError {
+    /// Code sent to the client when this error ends a request.
+    pub fn error_code(&self) -> ErrorCode {
+        match self {
+            LockKeeperError::InvalidMessage | LockKeeperError::MetadataNotFound => {
+                ErrorCode::InvalidRequest
+            }
+            LockKeeperError::MessageOutOfSequence => ErrorCode::MessageOutOfSequence,
+            LockKeeperError::UnknownSecretType(_) | LockKeeperError::InvalidSecretType => {
+                ErrorCode::WrongSecretType
+            }
+            LockKeeperError::NoMessageReceived => ErrorCode::NoMessageReceived,
+            LockKeeperError::ReceiveTimeout => ErrorCode::ReceiveTimeout,
+            LockKeeperError::MessageTooLarge { .. } => ErrorCode::MessageTooLarge,
+            LockKeeperError::OpaqueProtocol(_) => ErrorCode::AuthenticationFailed,
+            LockKeeperError::TonicStatus(status) => {
+                ErrorCode::from_status(status).unwrap_or(ErrorCode::Internal)
+            }
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
 impl From<LockKeeperError> for Status {
     fn from(error: LockKeeperError) -> Self {
-        match error {
+        let error_code = error.error_code();
+        let status = match error {
             // Errors that are safe to return to the client
             LockKeeperError::InvalidMessage
+            | LockKeeperError::MessageOutOfSequence
             | LockKeeperError::MetadataNotFound
             | LockKeeperError::UnknownSecretType(_)
             | LockKeeperError::InvalidSecretType => Status::invalid_argument(error.to_string()),
-            LockKeeperError::NoMessageReceived => Status::deadline_exceeded(error.to_string()),
+            LockKeeperError::NoMessageReceived | LockKeeperError::ReceiveTimeout => {
+                Status::deadline_exceeded(error.to_string())
+            }
+            LockKeeperError::MessageTooLarge { .. } => {
+                Status::resource_exhausted(error.to_string())
+            }
 
             // Errors that the client should not see
             LockKeeperError::FileIo(_, _)
             | LockKeeperError::InvalidClientAction
             | LockKeeperError::AlreadyAuthenticated
             | LockKeeperError::ShouldBeAuthenticated
+            | LockKeeperError::Bincode(_)
             | LockKeeperError::Crypto(_)
             | LockKeeperError::Hex(_)
             | LockKeeperError::Io(_)
@@ -102,9 +145,129 @@ impl From<LockKeeperError> for Status {
             | LockKeeperError::InvalidRemoteStorageKey
             | LockKeeperError::OpaqueProtocol(_)
             | LockKeeperError::SerdeJson(_)
+            | LockKeeperError::TimeFormat(_)
             | LockKeeperError::TokioSender(_)
             | LockKeeperError::TonicMetadata(_)
             | LockKeeperError::TonicStatus(_) => Status::internal("Internal server error"),
+        };
+        error_code.attach(status)
+    }
+}
+
+/// Stable reason that a request failed.
+///
+/// The key server attaches a code to every error [`Status`] it returns, so
+/// clients don't have to parse status messages. The discriminants are sent
+/// over the network: never change or reuse one, only add new codes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Display, EnumIter, Hash)]
+#[repr(u32)]
+pub enum ErrorCode {
+    /// Any other error. Details are only available in the server logs.
+    Internal = 1,
+    /// The request was malformed.
+    InvalidRequest = 2,
+    AccountAlreadyRegistered = 3,
+    /// The account does not exist.
+    InvalidAccount = 4,
+    /// The client's credentials were rejected.
+    AuthenticationFailed = 5,
+    InvalidServiceCredential = 6,
+    /// An action was requested for a service credential that service accounts
+    /// can't perform.
+    InvalidServiceCredentialScope = 7,
+    /// The session has expired. The client must authenticate again.
+    SessionExpired = 8,
+    /// The request didn't name a session the server knows of.
+    InvalidSession = 9,
+    /// The session is not allowed to perform the action.
+    ActionNotPermitted = 10,
+    /// The client certificate is not bound to the account.
+    CertificateAccountMismatch = 11,
+    /// The requested key does not exist for this account.
+    KeyNotFound = 12,
+    /// The requested secret has a different type than the operation expects.
+    WrongSecretType = 13,
+    /// The blob is larger than the server allows.
+    BlobTooLarge = 14,
+    StorageKeyAlreadySet = 15,
+    StorageKeyNotSet = 16,
+    /// The server doesn't support the client's protocol version.
+    UnsupportedProtocolVersion = 17,
+    /// The server is shutting down.
+    ShuttingDown = 18,
+    /// The server is handling too many requests. The request can be retried
+    /// later.
+    RateLimited = 19,
+    /// A message on an authenticated channel was replayed or reordered.
+    MessageOutOfSequence = 20,
+    /// The client stopped sending messages before the operation finished.
+    NoMessageReceived = 21,
+    /// The client took longer than the server allows to send the next message.
+    ReceiveTimeout = 22,
+    /// A message was larger than the server allows.
+    MessageTooLarge = 23,
+}
+
+impl ErrorCode {
+    /// Return `status` with this code in its binary details, replacing any
+    /// details it had.
+    pub fn attach(self, status: Status) -> Status {
+        let details = ErrorDetails { code: self as u32 }.encode_to_vec();
+        Status::with_details(status.code(), status.message(), details.into())
+    }
+
+    /// Read the code from the details of `status`. Returns `None` if the
+    /// status has no code, e.g. because it was returned by an older server, or
+    /// if the code is newer than this build.
+    pub fn from_status(status: &Status) -> Option<Self> {
+        let details = ErrorDetails::decode(status.details()).ok()?;
+        Self::try_from(details.code).ok()
+    }
+}
+
+impl TryFrom<u32> for ErrorCode {
+    type Error = u32;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        ErrorCode::iter()
+            .find(|code| *code as u32 == value)
+            .ok_or(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_codes_round_trip_through_status() {
+        for code in ErrorCode::iter() {
+            assert_eq!(code, ErrorCode::try_from(code as u32).unwrap());
+
+            let status = code.attach(Status::not_found("Not found"));
+            assert_eq!(status.code(), tonic::Code::NotFound);
+            assert_eq!(status.message(), "Not found");
+            assert_eq!(ErrorCode::from_status(&status), Some(code));
         }
+
+        // Statuses from servers without error codes, or with unknown codes
+        assert_eq!(ErrorCode::from_status(&Status::internal("error")), None);
+        let unknown = ErrorDetails { code: 9999 }.encode_to_vec();
+        let status = Status::with_details(tonic::Code::Internal, "error", unknown.into());
+        assert_eq!(ErrorCode::from_status(&status), None);
+    }
+
+    #[test]
+    fn lock_keeper_errors_carry_their_code() {
+        let status = Status::from(LockKeeperError::InvalidSecretType);
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(
+            ErrorCode::from_status(&status),
+            Some(ErrorCode::WrongSecretType)
+        );
+
+        let status = Status::from(LockKeeperError::InvalidPrivateKey);
+        assert_eq!(status.message(), "Internal server error");
+        assert_eq!(ErrorCode::from_status(&status), Some(ErrorCode::Internal));
     }
 }
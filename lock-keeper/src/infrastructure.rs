@@ -2,3 +2,4 @@
 pub mod logging;
 pub mod pem_utils;
 pub mod sensitive_info;
+pub mod trace_context;
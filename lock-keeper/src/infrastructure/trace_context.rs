@@ -0,0 +1,111 @@
+//! Propagation of OpenTelemetry trace context between the client and the
+//! server.
+//!
+//! The client adds the context of its current span to the gRPC metadata of
+//! every request as W3C Trace Context `traceparent` and `tracestate` entries,
+//! next to the serialized [`RequestMetadata`](crate::types::operations::RequestMetadata).
+//! The server reads them back and makes the span that handles the request a
+//! child of the client's span, so a single trace covers the whole operation.
+//!
+//! Both functions do nothing useful unless the process has an
+//! OpenTelemetry layer installed in its `tracing` subscriber.
+
+use opentelemetry::{
+    propagation::{Extractor, Injector, TextMapPropagator},
+    sdk::propagation::TraceContextPropagator,
+    Context,
+};
+use tonic::metadata::{KeyRef, MetadataKey, MetadataMap, MetadataValue};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Add the trace context of the current span to outgoing gRPC `metadata`.
+pub fn inject(metadata: &mut MetadataMap) {
+    inject_context(&Span::current().context(), metadata);
+}
+
+/// Read the trace context that the client added to incoming gRPC `metadata`.
+/// Returns an empty context if the client didn't send one.
+pub fn extract(metadata: &MetadataMap) -> Context {
+    TraceContextPropagator::new().extract(&MetadataExtractor(metadata))
+}
+
+fn inject_context(context: &Context, metadata: &mut MetadataMap) {
+    TraceContextPropagator::new().inject_context(context, &mut MetadataInjector(metadata));
+}
+
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+impl Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        // The propagator only produces valid ASCII keys and values.
+        if let (Ok(key), Ok(value)) = (
+            MetadataKey::from_bytes(key.as_bytes()),
+            MetadataValue::try_from(value.as_str()),
+        ) {
+            let _ = self.0.insert(key, value);
+        }
+    }
+}
+
+struct MetadataExtractor<'a>(&'a MetadataMap);
+
+impl Extractor for MetadataExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0
+            .keys()
+            .map(|key| match key {
+                KeyRef::Ascii(key) => key.as_str(),
+                KeyRef::Binary(key) => key.as_str(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{
+        SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+    };
+
+    #[test]
+    fn trace_context_round_trips_through_metadata() {
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        );
+        let context = Context::new().with_remote_span_context(span_context.clone());
+
+        let mut metadata = MetadataMap::new();
+        inject_context(&context, &mut metadata);
+        assert_eq!(
+            metadata.get("traceparent").unwrap(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+
+        let extracted = extract(&metadata);
+        let extracted = extracted.span().span_context().clone();
+        assert_eq!(extracted.trace_id(), span_context.trace_id());
+        assert_eq!(extracted.span_id(), span_context.span_id());
+        assert!(extracted.is_remote());
+    }
+
+    #[test]
+    fn missing_trace_context_is_empty() {
+        assert!(!extract(&MetadataMap::new()).has_active_span());
+
+        // Without an OpenTelemetry layer the current span has no trace context
+        // to add.
+        let mut metadata = MetadataMap::new();
+        inject(&mut metadata);
+        assert!(metadata.is_empty());
+    }
+}
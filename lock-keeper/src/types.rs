@@ -1,6 +1,8 @@
 //! Type definitions that are shared between crates but have little to no logic.
 
+pub mod audit_chain;
 pub mod audit_event;
+pub mod audit_export;
 pub mod database;
 pub mod operations;
 
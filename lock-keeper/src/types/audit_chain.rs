@@ -0,0 +1,334 @@
+//! Checkpoints and verification for hash-chained audit logs.
+//!
+//! Every [`AuditEvent`] stores the hash of the previous event for its account,
+//! so modifying or removing an event breaks the link from the event after it.
+//! The key server also periodically signs an [`AuditCheckpoint`] for the
+//! latest event of an account. Checkpoints detect changes that the chain alone
+//! cannot, such as removing the most recent events or rewriting the whole
+//! chain.
+
+use crate::{
+    crypto::{AuditPublicKey, AuditSignature, AuditSigningKey, CryptoError},
+    types::{
+        audit_event::{AuditEvent, AuditEventHash},
+        database::account::AccountId,
+    },
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use time::OffsetDateTime;
+
+/// A signed statement from the key server that `event_hash` was the hash of
+/// the audit event with ID `audit_event_id` at the given time.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditCheckpoint {
+    pub account_id: AccountId,
+    pub audit_event_id: i64,
+    pub event_hash: AuditEventHash,
+    /// Only whole seconds are signed, so that the timestamp is unaffected by
+    /// the precision of the database that stores it.
+    pub timestamp: OffsetDateTime,
+    pub signature: AuditSignature,
+}
+
+impl AuditCheckpoint {
+    /// Create a checkpoint for the given event, signed with the server's
+    /// [`AuditSigningKey`].
+    pub fn new(
+        signing_key: &AuditSigningKey,
+        event: &AuditEvent,
+        timestamp: OffsetDateTime,
+    ) -> Result<Self, CryptoError> {
+        let timestamp = timestamp
+            .replace_nanosecond(0)
+            .map_err(|_| CryptoError::ConversionError)?;
+        let event_hash = event.hash();
+        let message = Self::message(
+            event.account_id,
+            event.audit_event_id,
+            &event_hash,
+            timestamp,
+        );
+
+        Ok(Self {
+            account_id: event.account_id,
+            audit_event_id: event.audit_event_id,
+            event_hash,
+            timestamp,
+            signature: signing_key.sign(&message)?,
+        })
+    }
+
+    /// Check the signature on this checkpoint.
+    pub fn verify(&self, public_key: &AuditPublicKey) -> Result<(), CryptoError> {
+        let message = Self::message(
+            self.account_id,
+            self.audit_event_id,
+            &self.event_hash,
+            self.timestamp,
+        );
+        public_key.verify(&message, &self.signature)
+    }
+
+    fn message(
+        account_id: AccountId,
+        audit_event_id: i64,
+        event_hash: &AuditEventHash,
+        timestamp: OffsetDateTime,
+    ) -> Vec<u8> {
+        [
+            b"Lock Keeper audit checkpoint".as_slice(),
+            &i64::from(account_id).to_be_bytes(),
+            &audit_event_id.to_be_bytes(),
+            event_hash.as_bytes(),
+            &timestamp.unix_timestamp().to_be_bytes(),
+        ]
+        .concat()
+    }
+}
+
+/// Reasons that an audit log failed verification.
+#[derive(Debug, Error)]
+pub enum AuditChainError {
+    #[error("Audit events and checkpoints belong to more than one account")]
+    AccountMismatch,
+    #[error("Checkpoint for audit event {audit_event_id} has an invalid signature")]
+    InvalidCheckpointSignature { audit_event_id: i64 },
+    #[error("Audit event {audit_event_id} does not match its checkpoint")]
+    CheckpointMismatch { audit_event_id: i64 },
+    #[error("Audit event {audit_event_id} is covered by a checkpoint but is missing")]
+    MissingEvent { audit_event_id: i64 },
+    #[error("Audit events up to {audit_event_id} were checkpointed but are missing")]
+    Truncated { audit_event_id: i64 },
+    #[error("Audit event {audit_event_id} does not link to the event before it")]
+    ChainBroken { audit_event_id: i64 },
+    #[error("The event before audit event {audit_event_id} is missing")]
+    MissingPredecessor { audit_event_id: i64 },
+}
+
+/// Check that `events` form an unbroken hash chain that is consistent with
+/// every checkpoint.
+///
+/// `events` should be every audit event stored for a single account, in any
+/// order. Earlier events may be omitted if the first event is anchored by a
+/// checkpoint. Events stored before hash chaining was enabled have no
+/// `previous_hash` and are only protected by checkpoints.
+pub fn verify_audit_chain(
+    events: &[AuditEvent],
+    checkpoints: &[AuditCheckpoint],
+    public_key: &AuditPublicKey,
+) -> Result<(), AuditChainError> {
+    let mut events: Vec<&AuditEvent> = events.iter().collect();
+    events.sort_by_key(|event| event.audit_event_id);
+
+    let account_ids = events
+        .iter()
+        .map(|event| event.account_id)
+        .chain(checkpoints.iter().map(|checkpoint| checkpoint.account_id));
+    let mut account_ids = account_ids.peekable();
+    if let Some(first) = account_ids.peek().copied() {
+        if account_ids.any(|account_id| account_id != first) {
+            return Err(AuditChainError::AccountMismatch);
+        }
+    }
+
+    for checkpoint in checkpoints {
+        let audit_event_id = checkpoint.audit_event_id;
+        checkpoint
+            .verify(public_key)
+            .map_err(|_| AuditChainError::InvalidCheckpointSignature { audit_event_id })?;
+
+        match events.binary_search_by_key(&audit_event_id, |event| event.audit_event_id) {
+            Ok(index) => {
+                if events[index].hash() != checkpoint.event_hash {
+                    return Err(AuditChainError::CheckpointMismatch { audit_event_id });
+                }
+            }
+            // Checkpoints before the first event are only used as anchors.
+            Err(0) if !events.is_empty() => {}
+            Err(index) if index == events.len() => {
+                return Err(AuditChainError::Truncated { audit_event_id })
+            }
+            Err(_) => return Err(AuditChainError::MissingEvent { audit_event_id }),
+        }
+    }
+
+    let first = match events.first() {
+        Some(first) => first,
+        None => return Ok(()),
+    };
+    if let Some(previous_hash) = first.previous_hash {
+        let anchored = checkpoints.iter().any(|checkpoint| {
+            checkpoint.audit_event_id < first.audit_event_id
+                && checkpoint.event_hash == previous_hash
+        });
+        if !anchored {
+            return Err(AuditChainError::MissingPredecessor {
+                audit_event_id: first.audit_event_id,
+            });
+        }
+    }
+
+    for pair in events.windows(2) {
+        let (previous, event) = (pair[0], pair[1]);
+        let linked = match event.previous_hash {
+            Some(previous_hash) => previous_hash == previous.hash(),
+            // Unchained events can only precede the chained ones.
+            None => previous.previous_hash.is_none(),
+        };
+        if !linked {
+            return Err(AuditChainError::ChainBroken {
+                audit_event_id: event.audit_event_id,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{audit_event::EventStatus, operations::ClientAction};
+    use rand::{rngs::StdRng, SeedableRng};
+    use uuid::Uuid;
+
+    const ACCOUNT_ID: AccountId = AccountId(7);
+
+    /// Build a chain of `count` events with consecutive IDs starting at 1.
+    fn chain(count: i64) -> Vec<AuditEvent> {
+        let mut events: Vec<AuditEvent> = Vec::new();
+        for audit_event_id in 1..=count {
+            let event = AuditEvent {
+                audit_event_id,
+                account_id: ACCOUNT_ID,
+                request_id: Uuid::new_v4(),
+                key_id: None,
+                timestamp: OffsetDateTime::now_utc(),
+                client_action: ClientAction::Authenticate,
+                status: EventStatus::Successful,
+                details: Default::default(),
+                previous_hash: events.last().map(AuditEvent::hash),
+            };
+            events.push(event);
+        }
+        events
+    }
+
+    fn checkpoint(signing_key: &AuditSigningKey, event: &AuditEvent) -> AuditCheckpoint {
+        AuditCheckpoint::new(signing_key, event, OffsetDateTime::now_utc()).unwrap()
+    }
+
+    fn audit_key() -> (AuditSigningKey, AuditPublicKey) {
+        let signing_key = AuditSigningKey::generate(&mut StdRng::from_entropy());
+        let public_key = signing_key.public_key().unwrap();
+        (signing_key, public_key)
+    }
+
+    #[test]
+    fn valid_chain_verifies() {
+        let (signing_key, public_key) = audit_key();
+        let mut events = chain(5);
+        let checkpoints = vec![
+            checkpoint(&signing_key, &events[1]),
+            checkpoint(&signing_key, &events[4]),
+        ];
+
+        events.reverse();
+        verify_audit_chain(&events, &checkpoints, &public_key).unwrap();
+        verify_audit_chain(&[], &[], &public_key).unwrap();
+    }
+
+    #[test]
+    fn modified_event_is_detected() {
+        let (signing_key, public_key) = audit_key();
+        let mut events = chain(5);
+        events[2].status = EventStatus::Failed;
+
+        assert!(matches!(
+            verify_audit_chain(&events, &[], &public_key),
+            Err(AuditChainError::ChainBroken { audit_event_id: 4 })
+        ));
+
+        // The last event is only protected by a checkpoint.
+        let mut events = chain(5);
+        let checkpoints = vec![checkpoint(&signing_key, &events[4])];
+        events[4].client_action = ClientAction::DeleteKey;
+        assert!(matches!(
+            verify_audit_chain(&events, &checkpoints, &public_key),
+            Err(AuditChainError::CheckpointMismatch { audit_event_id: 5 })
+        ));
+    }
+
+    #[test]
+    fn gaps_are_detected() {
+        let (signing_key, public_key) = audit_key();
+        let mut events = chain(5);
+        let _ = events.remove(2);
+        assert!(matches!(
+            verify_audit_chain(&events, &[], &public_key),
+            Err(AuditChainError::ChainBroken { audit_event_id: 4 })
+        ));
+
+        let mut events = chain(5);
+        let _ = events.remove(0);
+        assert!(matches!(
+            verify_audit_chain(&events, &[], &public_key),
+            Err(AuditChainError::MissingPredecessor { audit_event_id: 2 })
+        ));
+
+        // A checkpoint can anchor the start of the chain.
+        let events = chain(5);
+        let checkpoints = vec![checkpoint(&signing_key, &events[0])];
+        verify_audit_chain(&events[1..], &checkpoints, &public_key).unwrap();
+    }
+
+    #[test]
+    fn truncation_is_detected() {
+        let (signing_key, public_key) = audit_key();
+        let events = chain(5);
+        let checkpoints = vec![checkpoint(&signing_key, &events[4])];
+
+        assert!(matches!(
+            verify_audit_chain(&events[..3], &checkpoints, &public_key),
+            Err(AuditChainError::Truncated { audit_event_id: 5 })
+        ));
+    }
+
+    #[test]
+    fn forged_checkpoints_are_detected() {
+        let (signing_key, public_key) = audit_key();
+        let events = chain(3);
+        let mut forged = checkpoint(&signing_key, &events[2]);
+        forged.audit_event_id = 2;
+
+        assert!(matches!(
+            verify_audit_chain(&events, &[forged], &public_key),
+            Err(AuditChainError::InvalidCheckpointSignature { audit_event_id: 2 })
+        ));
+
+        let (other_key, _) = audit_key();
+        let checkpoints = vec![checkpoint(&other_key, &events[2])];
+        assert!(matches!(
+            verify_audit_chain(&events, &checkpoints, &public_key),
+            Err(AuditChainError::InvalidCheckpointSignature { audit_event_id: 3 })
+        ));
+    }
+
+    #[test]
+    fn unchained_events_must_come_first() {
+        let (_, public_key) = audit_key();
+        let mut events = chain(4);
+        events[0].previous_hash = None;
+        events[1].previous_hash = None;
+        events[2].previous_hash = Some(events[1].hash());
+        events[3].previous_hash = Some(events[2].hash());
+        verify_audit_chain(&events, &[], &public_key).unwrap();
+
+        events[3].previous_hash = None;
+        assert!(matches!(
+            verify_audit_chain(&events, &[], &public_key),
+            Err(AuditChainError::ChainBroken { audit_event_id: 4 })
+        ));
+    }
+}
@@ -5,8 +5,13 @@
 use crate::{crypto::KeyId, types::operations::ClientAction};
 
 use serde::{Deserialize, Serialize};
-use std::fmt::{Debug, Display, Formatter};
-use strum::{Display, EnumString};
+use sha3::{Digest, Sha3_256};
+use std::{
+    array::TryFromSliceError,
+    fmt::{Debug, Display, Formatter},
+    net::IpAddr,
+};
+use strum::{Display, EnumIter, EnumString};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
@@ -19,6 +24,62 @@ pub enum EventStatus {
     Started,
     Successful,
     Failed,
+    /// The operation was cancelled because the server shut down before it
+    /// finished.
+    Aborted,
+}
+
+/// Non-sensitive category of the error that made an operation fail. Recorded
+/// with `Failed` and `Aborted` audit events.
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Display, EnumString, EnumIter,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum FailureCode {
+    /// The account does not exist.
+    InvalidAccount,
+    /// An account with the same name is already registered.
+    AccountAlreadyRegistered,
+    /// The client's credentials were rejected.
+    AuthenticationFailed,
+    /// The client's session is missing or has expired.
+    InvalidSession,
+    /// The client is not allowed to perform the action.
+    PermissionDenied,
+    /// The requested key does not exist for this account.
+    KeyNotFound,
+    /// The request was malformed or conflicts with the account's state.
+    InvalidRequest,
+    /// The request payload was larger than the server allows.
+    PayloadTooLarge,
+    /// The client stopped sending messages before the operation finished.
+    ClientDisconnected,
+    /// The client took too long to send the next message.
+    ReceiveTimeout,
+    /// A message from the client was larger than the server allows.
+    MessageTooLarge,
+    /// The server shut down before the operation finished.
+    ShuttingDown,
+    /// Any other error. Details are only available in the server logs.
+    Internal,
+}
+
+/// Context recorded with an [`AuditEvent`] beyond the action and its outcome.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEventDetails {
+    /// Identity from the client's TLS certificate, if it was bound to the
+    /// account.
+    pub client_identity: Option<String>,
+    /// IP address of the connection that made the request.
+    pub peer_address: Option<IpAddr>,
+    /// Reason that the operation failed.
+    pub failure_code: Option<FailureCode>,
+    /// Size in bytes of the data the action operated on, e.g. the bytes to
+    /// sign or the blob to store.
+    pub payload_size: Option<u64>,
+    /// Hash of the data the action operated on. Only recorded for data that
+    /// is not secret, such as bytes to sign.
+    pub payload_hash: Option<PayloadHash>,
 }
 
 /// A single entry that specifies the actor, action, outcome, and
@@ -36,6 +97,11 @@ pub struct AuditEvent {
     pub timestamp: OffsetDateTime,
     pub client_action: ClientAction,
     pub status: EventStatus,
+    pub details: AuditEventDetails,
+    /// Hash of the previous event for the same account. This is `None` for
+    /// the first event of an account and for events that were stored before
+    /// audit events were hash-chained.
+    pub previous_hash: Option<AuditEventHash>,
 }
 
 impl AuditEvent {
@@ -58,6 +124,144 @@ impl AuditEvent {
     pub fn status(&self) -> EventStatus {
         self.status
     }
+
+    pub fn details(&self) -> &AuditEventDetails {
+        &self.details
+    }
+
+    pub fn client_identity(&self) -> Option<&str> {
+        self.details.client_identity.as_deref()
+    }
+
+    pub fn peer_address(&self) -> Option<IpAddr> {
+        self.details.peer_address
+    }
+
+    pub fn failure_code(&self) -> Option<FailureCode> {
+        self.details.failure_code
+    }
+
+    pub fn previous_hash(&self) -> Option<&AuditEventHash> {
+        self.previous_hash.as_ref()
+    }
+
+    /// Hash of every field of this event, including the hash of the previous
+    /// event. The next event for the account stores this as its
+    /// `previous_hash`.
+    pub fn hash(&self) -> AuditEventHash {
+        let key_id = self
+            .key_id
+            .as_ref()
+            .map(KeyId::as_bytes)
+            .unwrap_or_default();
+        let details = &self.details;
+        let client_identity = details.client_identity.as_deref().unwrap_or_default();
+        let peer_address = details
+            .peer_address
+            .map(|address| address.to_string())
+            .unwrap_or_default();
+        let failure_code = details
+            .failure_code
+            .map(|code| code.to_string())
+            .unwrap_or_default();
+        let payload_size = details.payload_size.unwrap_or_default();
+        let payload_hash = details
+            .payload_hash
+            .as_ref()
+            .map(PayloadHash::as_bytes)
+            .unwrap_or_default();
+        let previous_hash = self
+            .previous_hash
+            .as_ref()
+            .map(AuditEventHash::as_bytes)
+            .unwrap_or_default();
+        let status = self.status.to_string();
+
+        let fields: [&[u8]; 20] = [
+            b"Lock Keeper audit event",
+            &self.audit_event_id.to_be_bytes(),
+            &i64::from(self.account_id).to_be_bytes(),
+            self.request_id.as_bytes(),
+            &[self.key_id.is_some() as u8],
+            key_id,
+            &self.timestamp.unix_timestamp_nanos().to_be_bytes(),
+            &(self.client_action as i64).to_be_bytes(),
+            status.as_bytes(),
+            &[details.client_identity.is_some() as u8],
+            client_identity.as_bytes(),
+            &[details.peer_address.is_some() as u8],
+            peer_address.as_bytes(),
+            &[details.failure_code.is_some() as u8],
+            failure_code.as_bytes(),
+            &[details.payload_size.is_some() as u8],
+            &payload_size.to_be_bytes(),
+            &[details.payload_hash.is_some() as u8],
+            payload_hash,
+            previous_hash,
+        ];
+
+        // Length-prefix each field so that the encoding is unambiguous.
+        let hasher = fields.iter().fold(Sha3_256::new(), |hasher, field| {
+            hasher
+                .chain_update((field.len() as u32).to_be_bytes())
+                .chain_update(field)
+        });
+
+        AuditEventHash(hasher.finalize().into())
+    }
+}
+
+/// Hash of an [`AuditEvent`], used to link each event to the one before it.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEventHash([u8; 32]);
+
+impl AuditEventHash {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl TryFrom<&[u8]> for AuditEventHash {
+    type Error = TryFromSliceError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Self(bytes.try_into()?))
+    }
+}
+
+impl Debug for AuditEventHash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AuditEventHash({})", hex::encode(self.0))
+    }
+}
+
+/// SHA3-256 hash of the data that an audited action operated on.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayloadHash([u8; 32]);
+
+impl PayloadHash {
+    /// Hash the given payload.
+    pub fn of(payload: &[u8]) -> Self {
+        Self(Sha3_256::digest(payload).into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl TryFrom<&[u8]> for PayloadHash {
+    type Error = TryFromSliceError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        Ok(Self(bytes.try_into()?))
+    }
+}
+
+impl Debug for PayloadHash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PayloadHash({})", hex::encode(self.0))
+    }
 }
 
 impl Display for AuditEvent {
@@ -68,7 +272,22 @@ impl Display for AuditEvent {
         }
         writeln!(f, "{}", self.date())?;
         writeln!(f, "{}", self.action())?;
-        writeln!(f, "{}", self.status())
+        if let Some(client_identity) = self.client_identity() {
+            writeln!(f, "Client identity: {client_identity}")?;
+        }
+        if let Some(peer_address) = self.peer_address() {
+            writeln!(f, "Peer address: {peer_address}")?;
+        }
+        if let Some(payload_size) = self.details.payload_size {
+            writeln!(f, "Payload size: {payload_size} bytes")?;
+        }
+        if let Some(payload_hash) = &self.details.payload_hash {
+            writeln!(f, "Payload hash: {}", hex::encode(payload_hash.as_bytes()))?;
+        }
+        match self.failure_code() {
+            Some(failure_code) => writeln!(f, "{} ({failure_code})", self.status()),
+            None => writeln!(f, "{}", self.status()),
+        }
     }
 }
 
@@ -101,14 +320,18 @@ const ALL_ACTIONS: &[ClientAction] = &[
     ClientAction::RetrieveStorageKey,
     ClientAction::StoreServerEncryptedBlob,
     ClientAction::CheckSession,
+    ClientAction::RegisterServiceCredential,
+    ClientAction::AuthenticateServiceAccount,
 ];
 
 const SYSTEM_ONLY_ACTIONS: &[ClientAction] = &[
     ClientAction::Authenticate,
+    ClientAction::AuthenticateServiceAccount,
     ClientAction::CreateStorageKey,
     ClientAction::GetUserId,
     ClientAction::Logout,
     ClientAction::Register,
+    ClientAction::RegisterServiceCredential,
     ClientAction::RetrieveAuditEvents,
     ClientAction::RetrieveStorageKey,
 ];
@@ -138,6 +361,10 @@ impl EventType {
 }
 
 /// Optional parameters to filter [`AuditEvent`]s by
+///
+/// Matching events are returned in order of their `audit_event_id`. Use
+/// `after_audit_event_id` and `limit` to page through long audit logs: pass
+/// the ID of the last event of one page as the cursor for the next.
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct AuditEventOptions {
     pub key_ids: Vec<KeyId>,
@@ -146,6 +373,13 @@ pub struct AuditEventOptions {
     #[serde(with = "time::serde::iso8601::option")]
     pub before_date: Option<OffsetDateTime>,
     pub request_id: Option<Uuid>,
+    pub failure_code: Option<FailureCode>,
+    pub peer_address: Option<IpAddr>,
+    pub client_identity: Option<String>,
+    /// Only return events with an `audit_event_id` greater than this.
+    pub after_audit_event_id: Option<i64>,
+    /// Maximum number of events to return.
+    pub limit: Option<u32>,
 }
 
 #[cfg(test)]
@@ -163,4 +397,55 @@ mod tests {
             assert!(ALL_ACTIONS.contains(&action))
         }
     }
+
+    #[test]
+    fn failure_codes_round_trip_through_strings() {
+        for code in FailureCode::iter() {
+            assert_eq!(code, code.to_string().parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn hash_covers_details() {
+        let request_id = Uuid::new_v4();
+        let timestamp = OffsetDateTime::now_utc();
+        let event = |details| AuditEvent {
+            audit_event_id: 1,
+            account_id: AccountId(1),
+            request_id,
+            key_id: None,
+            timestamp,
+            client_action: ClientAction::RemoteSignBytes,
+            status: EventStatus::Failed,
+            details,
+            previous_hash: None,
+        };
+        let original_hash = event(AuditEventDetails::default()).hash();
+
+        let changes = [
+            AuditEventDetails {
+                client_identity: Some("subject:CN=client".to_string()),
+                ..Default::default()
+            },
+            AuditEventDetails {
+                peer_address: Some([127, 0, 0, 1].into()),
+                ..Default::default()
+            },
+            AuditEventDetails {
+                failure_code: Some(FailureCode::Internal),
+                ..Default::default()
+            },
+            AuditEventDetails {
+                payload_size: Some(0),
+                ..Default::default()
+            },
+            AuditEventDetails {
+                payload_hash: Some(PayloadHash::of(b"")),
+                ..Default::default()
+            },
+        ];
+        for details in changes {
+            assert_ne!(event(details).hash(), original_hash);
+        }
+    }
 }
@@ -0,0 +1,278 @@
+//! Export of [`AuditEvent`]s for ingestion by other systems, e.g. a SIEM.
+//!
+//! Events are exported as JSON Lines (one JSON object per line) or CSV (with a
+//! header row). Both formats use the same flat schema, described by
+//! [`AuditEventRecord`]. Fields are only ever added to the end of the schema,
+//! so consumers can rely on the names and CSV column order below.
+//!
+//! | Field             | Description                                           |
+//! |-------------------|-------------------------------------------------------|
+//! | `audit_event_id`  | ID of the event, increasing over time                 |
+//! | `account_id`      | ID of the account that made the request               |
+//! | `request_id`      | UUID of the request the event belongs to              |
+//! | `timestamp`       | Time of the event in RFC 3339 format, in UTC          |
+//! | `client_action`   | Action the client requested, e.g. `RemoteSignBytes`   |
+//! | `status`          | `Started`, `Successful`, `Failed` or `Aborted`        |
+//! | `key_id`          | Hex-encoded ID of the key the action used             |
+//! | `client_identity` | Identity from the client's TLS certificate            |
+//! | `peer_address`    | IP address of the client connection                   |
+//! | `failure_code`    | Reason the action failed, e.g. `key-not-found`        |
+//! | `payload_size`    | Size in bytes of the data the action operated on      |
+//! | `payload_hash`    | Hex-encoded SHA3-256 hash of that data                |
+//! | `previous_hash`   | Hex-encoded hash of the account's previous event      |
+//!
+//! Fields after `status` are optional. They are `null` in JSON Lines and empty
+//! in CSV when missing.
+
+use crate::{
+    types::audit_event::{AuditEvent, EventStatus},
+    LockKeeperError,
+};
+use serde::Serialize;
+use std::io::Write;
+use strum::{Display, EnumString};
+use time::{format_description::well_known::Rfc3339, UtcOffset};
+use uuid::Uuid;
+
+/// File format for exported audit events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, EnumString)]
+pub enum AuditExportFormat {
+    /// One JSON object per line.
+    #[strum(serialize = "jsonl")]
+    JsonLines,
+    /// Comma-separated values with a header row.
+    #[strum(serialize = "csv")]
+    Csv,
+}
+
+/// A single exported audit event. See the [module documentation](self) for
+/// the meaning of each field.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AuditEventRecord {
+    pub audit_event_id: i64,
+    pub account_id: i64,
+    pub request_id: Uuid,
+    pub timestamp: String,
+    pub client_action: String,
+    pub status: EventStatus,
+    pub key_id: Option<String>,
+    pub client_identity: Option<String>,
+    pub peer_address: Option<String>,
+    pub failure_code: Option<String>,
+    pub payload_size: Option<u64>,
+    pub payload_hash: Option<String>,
+    pub previous_hash: Option<String>,
+}
+
+impl AuditEventRecord {
+    /// Names of the fields in CSV column order.
+    pub const FIELDS: [&'static str; 13] = [
+        "audit_event_id",
+        "account_id",
+        "request_id",
+        "timestamp",
+        "client_action",
+        "status",
+        "key_id",
+        "client_identity",
+        "peer_address",
+        "failure_code",
+        "payload_size",
+        "payload_hash",
+        "previous_hash",
+    ];
+
+    fn csv_values(&self) -> [String; 13] {
+        [
+            self.audit_event_id.to_string(),
+            self.account_id.to_string(),
+            self.request_id.to_string(),
+            self.timestamp.clone(),
+            self.client_action.clone(),
+            self.status.to_string(),
+            self.key_id.clone().unwrap_or_default(),
+            self.client_identity.clone().unwrap_or_default(),
+            self.peer_address.clone().unwrap_or_default(),
+            self.failure_code.clone().unwrap_or_default(),
+            self.payload_size
+                .map(|size| size.to_string())
+                .unwrap_or_default(),
+            self.payload_hash.clone().unwrap_or_default(),
+            self.previous_hash.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+impl TryFrom<&AuditEvent> for AuditEventRecord {
+    type Error = LockKeeperError;
+
+    fn try_from(event: &AuditEvent) -> Result<Self, Self::Error> {
+        let details = event.details();
+        let timestamp = event.timestamp.to_offset(UtcOffset::UTC).format(&Rfc3339)?;
+
+        Ok(Self {
+            audit_event_id: event.audit_event_id,
+            account_id: event.account_id.into(),
+            request_id: event.request_id,
+            timestamp,
+            client_action: event.client_action.to_string(),
+            status: event.status,
+            key_id: event
+                .key_id
+                .as_ref()
+                .map(|key_id| hex::encode(key_id.as_bytes())),
+            client_identity: details.client_identity.clone(),
+            peer_address: details.peer_address.map(|address| address.to_string()),
+            failure_code: details.failure_code.map(|code| code.to_string()),
+            payload_size: details.payload_size,
+            payload_hash: details
+                .payload_hash
+                .as_ref()
+                .map(|hash| hex::encode(hash.as_bytes())),
+            previous_hash: event
+                .previous_hash
+                .as_ref()
+                .map(|hash| hex::encode(hash.as_bytes())),
+        })
+    }
+}
+
+/// Writes [`AuditEvent`]s to `W` in an [`AuditExportFormat`].
+#[derive(Debug)]
+pub struct AuditEventWriter<W: Write> {
+    writer: W,
+    format: AuditExportFormat,
+    events_written: u64,
+}
+
+impl<W: Write> AuditEventWriter<W> {
+    /// Create a writer. The CSV header row is written immediately.
+    pub fn new(mut writer: W, format: AuditExportFormat) -> Result<Self, LockKeeperError> {
+        if format == AuditExportFormat::Csv {
+            writeln!(writer, "{}", AuditEventRecord::FIELDS.join(","))?;
+        }
+
+        Ok(Self {
+            writer,
+            format,
+            events_written: 0,
+        })
+    }
+
+    /// Write a single event.
+    pub fn write(&mut self, event: &AuditEvent) -> Result<(), LockKeeperError> {
+        let record = AuditEventRecord::try_from(event)?;
+        match self.format {
+            AuditExportFormat::JsonLines => {
+                serde_json::to_writer(&mut self.writer, &record)?;
+                writeln!(self.writer)?;
+            }
+            AuditExportFormat::Csv => {
+                let values = record.csv_values().map(|value| csv_escape(&value));
+                writeln!(self.writer, "{}", values.join(","))?;
+            }
+        }
+        self.events_written += 1;
+
+        Ok(())
+    }
+
+    /// Flush the underlying writer and return the number of events written.
+    pub fn finish(mut self) -> Result<u64, LockKeeperError> {
+        self.writer.flush()?;
+        Ok(self.events_written)
+    }
+}
+
+/// Quote a CSV value if it contains a separator, quote, or line break.
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        audit_event::{AuditEventDetails, FailureCode},
+        database::account::AccountId,
+        operations::ClientAction,
+    };
+    use time::macros::datetime;
+
+    fn event() -> AuditEvent {
+        AuditEvent {
+            audit_event_id: 7,
+            account_id: AccountId(3),
+            request_id: Uuid::nil(),
+            key_id: None,
+            timestamp: datetime!(2023-01-02 03:04:05 UTC),
+            client_action: ClientAction::RemoteSignBytes,
+            status: EventStatus::Failed,
+            details: AuditEventDetails {
+                client_identity: Some("subject:CN=client, O=\"Example\"".to_string()),
+                failure_code: Some(FailureCode::KeyNotFound),
+                ..Default::default()
+            },
+            previous_hash: None,
+        }
+    }
+
+    fn export(format: AuditExportFormat) -> String {
+        let mut writer = AuditEventWriter::new(Vec::new(), format).unwrap();
+        writer.write(&event()).unwrap();
+        String::from_utf8(writer.writer).unwrap()
+    }
+
+    #[test]
+    fn json_lines_uses_documented_field_names() {
+        let output = export(AuditExportFormat::JsonLines);
+        assert_eq!(output.lines().count(), 1);
+
+        let record: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&output).unwrap();
+        let mut names: Vec<_> = record.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        let mut expected = AuditEventRecord::FIELDS.to_vec();
+        expected.sort_unstable();
+        assert_eq!(names, expected);
+
+        assert_eq!(record["timestamp"], "2023-01-02T03:04:05Z");
+        assert_eq!(record["failure_code"], "key-not-found");
+        assert!(record["key_id"].is_null());
+    }
+
+    #[test]
+    fn csv_has_header_and_escapes_values() {
+        let output = export(AuditExportFormat::Csv);
+        let mut lines = output.lines();
+
+        assert_eq!(
+            lines.next(),
+            Some(AuditEventRecord::FIELDS.join(",").as_str())
+        );
+        assert_eq!(
+            lines.next(),
+            Some(
+                "7,3,00000000-0000-0000-0000-000000000000,2023-01-02T03:04:05Z,RemoteSignBytes,\
+                 Failed,,\"subject:CN=client, O=\"\"Example\"\"\",,key-not-found,,,"
+            )
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn formats_parse_from_strings() {
+        assert_eq!(
+            "jsonl".parse::<AuditExportFormat>().unwrap(),
+            AuditExportFormat::JsonLines
+        );
+        assert_eq!(
+            "csv".parse::<AuditExportFormat>().unwrap(),
+            AuditExportFormat::Csv
+        );
+    }
+}
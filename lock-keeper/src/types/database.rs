@@ -2,6 +2,7 @@
 
 pub mod account;
 pub mod secrets;
+pub mod service_credential;
 
 use std::fmt::Display;
 
@@ -135,7 +135,7 @@ impl TryFrom<HexBytes> for UserId {
 }
 
 /// Account name used as human-memorable identifier for a user during OPAQUE.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct AccountName(String);
 
 impl Display for AccountName {
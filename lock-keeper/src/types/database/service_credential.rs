@@ -0,0 +1,33 @@
+//! Database models for service-account credentials.
+
+use crate::{crypto::ServiceCredentialPublicKey, types::operations::ClientAction};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::account::AccountId;
+
+/// A public key registered to an [`Account`](super::account::Account) that a
+/// non-interactive client can use to log in, along with the set of actions
+/// sessions created with it may perform.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ServiceCredential {
+    pub credential_id: Uuid,
+    pub account_id: AccountId,
+    pub public_key: ServiceCredentialPublicKey,
+    pub allowed_actions: Vec<ClientAction>,
+}
+
+impl ServiceCredential {
+    /// Returns `true` if a session created with this credential may perform
+    /// the given action.
+    ///
+    /// [`ClientAction::CheckSession`], [`ClientAction::GetUserId`] and
+    /// [`ClientAction::Logout`] are always allowed since they are needed to
+    /// manage the session itself.
+    pub fn allows(&self, action: ClientAction) -> bool {
+        matches!(
+            action,
+            ClientAction::CheckSession | ClientAction::GetUserId | ClientAction::Logout
+        ) || (action.is_service_action() && self.allowed_actions.contains(&action))
+    }
+}
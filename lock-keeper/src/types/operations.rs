@@ -1,6 +1,7 @@
 //! Types related to server operations and the protocols they execute.
 
 pub mod authenticate;
+pub mod authenticate_service_account;
 pub mod create_storage_key;
 pub mod delete_key;
 pub mod generate;
@@ -8,6 +9,7 @@ pub mod get_user_id;
 pub mod import;
 pub mod logout;
 pub mod register;
+pub mod register_service_credential;
 pub mod remote_generate;
 pub mod remote_sign_bytes;
 pub mod retrieve_audit_events;
@@ -16,8 +18,8 @@ pub mod retrieve_server_encrypted_blob;
 pub mod retrieve_storage_key;
 pub mod store_server_encrypted_blob;
 
-use crate::{types::database::account::AccountName, LockKeeperError};
-use serde::{Deserialize, Serialize};
+use crate::{constants::PROTOCOL_VERSION, types::database::account::AccountName, LockKeeperError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use strum::{Display, EnumIter, EnumString};
 use tonic::metadata::{Ascii, MetadataValue};
 use uuid::Uuid;
@@ -50,6 +52,29 @@ pub enum ClientAction {
     StoreServerEncryptedBlob = 16,
     CheckSession = 17,
     DeleteKey = 18,
+    RegisterServiceCredential = 19,
+    AuthenticateServiceAccount = 20,
+}
+
+impl ClientAction {
+    /// Returns `true` if this action can be granted to a service credential.
+    ///
+    /// Service accounts cannot derive the account's master key, so they are
+    /// limited to operations that run entirely on the server.
+    pub fn is_service_action(&self) -> bool {
+        matches!(
+            self,
+            ClientAction::CheckSession
+                | ClientAction::DeleteKey
+                | ClientAction::GetUserId
+                | ClientAction::Logout
+                | ClientAction::RemoteGenerateSigningKey
+                | ClientAction::RemoteSignBytes
+                | ClientAction::RetrieveAuditEvents
+                | ClientAction::RetrieveServerEncryptedBlob
+                | ClientAction::StoreServerEncryptedBlob
+        )
+    }
 }
 
 impl TryFrom<i64> for ClientAction {
@@ -88,20 +113,72 @@ impl TryFrom<i64> for ClientAction {
             }
             x if x == ClientAction::CheckSession as i64 => Ok(ClientAction::CheckSession),
             x if x == ClientAction::DeleteKey as i64 => Ok(ClientAction::DeleteKey),
+            x if x == ClientAction::RegisterServiceCredential as i64 => {
+                Ok(ClientAction::RegisterServiceCredential)
+            }
+            x if x == ClientAction::AuthenticateServiceAccount as i64 => {
+                Ok(ClientAction::AuthenticateServiceAccount)
+            }
             // Return value of offending integer.
             _ => Err(v),
         }
     }
 }
 
+/// Format of the messages sent over an operation's channel.
+///
+/// The client picks an encoding for each connection from the ones the server
+/// supports and sends it in the [`RequestMetadata`] of every request. Every
+/// server supports [`MessageEncoding::Json`], so it is used with servers that
+/// don't list their encodings.
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    Display,
+    EnumIter,
+    EnumString,
+    Hash,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum MessageEncoding {
+    #[default]
+    Json,
+    /// Compact binary encoding. Byte arrays such as blobs and signatures are
+    /// stored as-is instead of as JSON arrays of numbers.
+    Bincode,
+}
+
+impl MessageEncoding {
+    pub fn serialize(self, value: &impl Serialize) -> Result<Vec<u8>, LockKeeperError> {
+        Ok(match self {
+            MessageEncoding::Json => serde_json::to_vec(value)?,
+            MessageEncoding::Bincode => bincode::serialize(value)?,
+        })
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, LockKeeperError> {
+        Ok(match self {
+            MessageEncoding::Json => serde_json::from_slice(bytes)?,
+            MessageEncoding::Bincode => bincode::deserialize(bytes)?,
+        })
+    }
+}
+
 /// Converts a serializable Rust type to and from the RPC [`Message`] type.
 pub trait ConvertMessage: Sized + for<'a> Deserialize<'a> + Serialize {
-    fn from_message(value: Message) -> Result<Self, LockKeeperError> {
-        Ok(serde_json::from_slice(&value.content)?)
+    fn from_message(value: Message, encoding: MessageEncoding) -> Result<Self, LockKeeperError> {
+        encoding.deserialize(&value.content)
     }
 
-    fn to_message(self) -> Result<Message, LockKeeperError> {
-        let content = serde_json::to_vec(&self)?;
+    fn to_message(self, encoding: MessageEncoding) -> Result<Message, LockKeeperError> {
+        let content = encoding.serialize(&self)?;
         Ok(Message { content })
     }
 }
@@ -119,6 +196,14 @@ pub struct RequestMetadata {
     action: ClientAction,
     session_id: Option<Uuid>,
     request_id: Uuid,
+    /// Protocol version of the client. Missing from clients that predate
+    /// protocol versions, which are treated as version 0.
+    #[serde(default)]
+    protocol_version: u32,
+    /// Encoding of the messages sent over the request's channel. Clients that
+    /// predate message encodings only send JSON.
+    #[serde(default)]
+    message_encoding: MessageEncoding,
 }
 
 impl RequestMetadata {
@@ -127,12 +212,15 @@ impl RequestMetadata {
         action: ClientAction,
         session_id: Option<&Uuid>,
         request_id: Uuid,
+        message_encoding: MessageEncoding,
     ) -> Self {
         Self {
             account_name: account_name.clone(),
             action,
             session_id: session_id.cloned(),
             request_id,
+            protocol_version: PROTOCOL_VERSION,
+            message_encoding,
         }
     }
 
@@ -151,6 +239,14 @@ impl RequestMetadata {
     pub fn session_id(&self) -> Option<&Uuid> {
         self.session_id.as_ref()
     }
+
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+
+    pub fn message_encoding(&self) -> MessageEncoding {
+        self.message_encoding
+    }
 }
 
 impl TryFrom<&RequestMetadata> for MetadataValue<Ascii> {
@@ -174,8 +270,29 @@ impl TryFrom<&MetadataValue<Ascii>> for RequestMetadata {
 
 #[cfg(test)]
 mod test {
-    use crate::types::operations::ClientAction;
+    use crate::{
+        config::opaque::OpaqueCipherSuite,
+        crypto::{KeyId, Signable, SignableBytes, SigningKeyPair},
+        types::{
+            database::account::UserId,
+            operations::{
+                authenticate, register, remote_sign_bytes, store_server_encrypted_blob,
+                ClientAction, ConvertMessage, MessageEncoding, RequestMetadata,
+            },
+        },
+    };
+    use opaque_ke::{
+        ClientLogin, ClientLoginFinishParameters, ClientRegistration,
+        ClientRegistrationFinishParameters, ServerLogin, ServerLoginStartParameters,
+        ServerRegistration, ServerSetup,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
     use strum::IntoEnumIterator;
+    use tonic::metadata::{Ascii, MetadataValue};
+
+    fn round_trip<T: ConvertMessage>(value: T, encoding: MessageEncoding) -> T {
+        T::from_message(value.to_message(encoding).unwrap(), encoding).unwrap()
+    }
 
     /// Ensure our conversion function covers all cases.
     #[test]
@@ -184,4 +301,162 @@ mod test {
             assert_eq!(action, ClientAction::try_from(action as i64).unwrap());
         }
     }
+
+    #[test]
+    fn protocol_version_is_sent_with_metadata() {
+        let metadata = RequestMetadata::new(
+            &"alice".parse().unwrap(),
+            ClientAction::Authenticate,
+            None,
+            uuid::Uuid::new_v4(),
+            MessageEncoding::Bincode,
+        );
+        let value = MetadataValue::<Ascii>::try_from(&metadata).unwrap();
+        let received = RequestMetadata::try_from(&value).unwrap();
+        assert_eq!(
+            received.protocol_version(),
+            crate::constants::PROTOCOL_VERSION
+        );
+        assert_eq!(received.message_encoding(), MessageEncoding::Bincode);
+
+        // Metadata from clients that predate protocol versions.
+        let value = MetadataValue::from_static(
+            r#"{"account_name":"alice","action":"Authenticate","session_id":null,"request_id":"67e55044-10b1-426f-9247-bb680e5fe0c8"}"#,
+        );
+        let received = RequestMetadata::try_from(&value).unwrap();
+        assert_eq!(received.protocol_version(), 0);
+        assert_eq!(received.message_encoding(), MessageEncoding::Json);
+    }
+
+    #[test]
+    fn opaque_messages_round_trip_with_every_encoding() {
+        let mut rng = StdRng::from_entropy();
+        let password = b"password";
+        let account_name: crate::types::database::account::AccountName = "alice".parse().unwrap();
+        let server_setup = ServerSetup::<OpaqueCipherSuite>::new(&mut rng);
+
+        for encoding in MessageEncoding::iter() {
+            // Registration
+            let client_start =
+                ClientRegistration::<OpaqueCipherSuite>::start(&mut rng, password).unwrap();
+            let request = round_trip(
+                register::client::RegisterStart {
+                    registration_request: client_start.message,
+                    account_name: account_name.clone(),
+                },
+                encoding,
+            );
+            assert_eq!(request.account_name, account_name);
+            let server_start = ServerRegistration::<OpaqueCipherSuite>::start(
+                &server_setup,
+                request.registration_request,
+                account_name.as_bytes(),
+            )
+            .unwrap();
+            let response = round_trip(
+                register::server::RegisterStart {
+                    registration_response: server_start.message,
+                },
+                encoding,
+            );
+            let client_finish = client_start
+                .state
+                .finish(
+                    &mut rng,
+                    password,
+                    response.registration_response,
+                    ClientRegistrationFinishParameters::default(),
+                )
+                .unwrap();
+            let upload = round_trip(
+                register::client::RegisterFinish {
+                    registration_upload: client_finish.message,
+                },
+                encoding,
+            );
+            let password_file = ServerRegistration::finish(upload.registration_upload);
+
+            // Authentication
+            let client_start = ClientLogin::<OpaqueCipherSuite>::start(&mut rng, password).unwrap();
+            let request = round_trip(
+                authenticate::client::AuthenticateStart {
+                    credential_request: client_start.message,
+                    account_name: account_name.clone(),
+                },
+                encoding,
+            );
+            let server_start = ServerLogin::start(
+                &mut rng,
+                &server_setup,
+                Some(password_file),
+                request.credential_request,
+                account_name.as_bytes(),
+                ServerLoginStartParameters::default(),
+            )
+            .unwrap();
+            let response = round_trip(
+                authenticate::server::AuthenticateStart {
+                    credential_response: server_start.message,
+                },
+                encoding,
+            );
+            let client_finish = client_start
+                .state
+                .finish(
+                    password,
+                    response.credential_response,
+                    ClientLoginFinishParameters::default(),
+                )
+                .unwrap();
+            let finalization = round_trip(
+                authenticate::client::AuthenticateFinish {
+                    credential_finalization: client_finish.message,
+                },
+                encoding,
+            );
+            let server_finish = server_start
+                .state
+                .finish(finalization.credential_finalization)
+                .unwrap();
+            assert_eq!(server_finish.session_key, client_finish.session_key);
+        }
+    }
+
+    #[test]
+    fn key_messages_round_trip_with_every_encoding() {
+        let mut rng = StdRng::from_entropy();
+        let user_id = UserId::new(&mut rng).unwrap();
+        let key_id = KeyId::generate(&mut rng, &user_id).unwrap();
+        let data = SignableBytes(b"some bytes".to_vec());
+        let key_pair = SigningKeyPair::remote_generate(&mut rng, &user_id, &key_id);
+
+        for encoding in MessageEncoding::iter() {
+            let request = round_trip(
+                store_server_encrypted_blob::client::Request {
+                    data_blob: vec![1, 2, 3],
+                },
+                encoding,
+            );
+            assert_eq!(request.data_blob, vec![1, 2, 3]);
+
+            let request = round_trip(
+                remote_sign_bytes::client::RequestRemoteSign {
+                    key_id: key_id.clone(),
+                    data: data.clone(),
+                },
+                encoding,
+            );
+            assert_eq!(request.key_id, key_id);
+            assert_eq!(request.data.0, data.0);
+
+            let response = round_trip(
+                remote_sign_bytes::server::ReturnSignature {
+                    signature: data.sign(&key_pair),
+                },
+                encoding,
+            );
+            data.verify(&key_pair.public_key(), &response.signature)
+                .unwrap();
+        }
+    }
 }
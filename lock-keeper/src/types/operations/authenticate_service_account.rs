@@ -0,0 +1,37 @@
+pub mod client {
+    use crate::{crypto::ServiceCredentialSignature, types::database::account::AccountName};
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    #[derive(Debug, Deserialize, Serialize)]
+    /// Identify the service credential that will be used to log in.
+    pub struct AuthenticateStart {
+        pub account_name: AccountName,
+        pub credential_id: Uuid,
+    }
+
+    #[derive(Debug, Deserialize, Serialize)]
+    /// Pass the client's key share and a signature over the login transcript.
+    pub struct AuthenticateFinish {
+        pub key_share: Vec<u8>,
+        pub signature: ServiceCredentialSignature,
+    }
+}
+
+pub mod server {
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    #[derive(Debug, Deserialize, Serialize)]
+    /// Return a fresh challenge and the server's key share.
+    pub struct AuthenticateStart {
+        pub challenge: [u8; 32],
+        pub key_share: Vec<u8>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize)]
+    /// Return the ID of the newly created session.
+    pub struct AuthenticateFinish {
+        pub session_id: Uuid,
+    }
+}
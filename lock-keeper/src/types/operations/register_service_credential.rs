@@ -0,0 +1,22 @@
+pub mod client {
+    use crate::{crypto::ServiceCredentialPublicKey, types::operations::ClientAction};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Deserialize, Serialize)]
+    /// Register a service credential that may perform the given actions.
+    pub struct Request {
+        pub public_key: ServiceCredentialPublicKey,
+        pub allowed_actions: Vec<ClientAction>,
+    }
+}
+
+pub mod server {
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    #[derive(Debug, Deserialize, Serialize)]
+    /// Return the ID the service should use to log in.
+    pub struct Response {
+        pub credential_id: Uuid,
+    }
+}
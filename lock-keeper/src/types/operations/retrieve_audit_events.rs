@@ -12,12 +12,22 @@ pub mod client {
 }
 
 pub mod server {
-    use crate::types::audit_event::AuditEvent;
+    use crate::types::{audit_chain::AuditCheckpoint, audit_event::AuditEvent};
     use serde::{Deserialize, Serialize};
 
     #[derive(Debug, Deserialize, Serialize)]
-    /// Return vector of audit events
+    /// Sent before the first [`Page`] of audit events
     pub struct Response {
-        pub summary_record: Vec<AuditEvent>,
+        /// Every checkpoint of the account's audit log, so that the client
+        /// can verify the returned events.
+        pub checkpoints: Vec<AuditCheckpoint>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize)]
+    /// One page of the requested audit events
+    pub struct Page {
+        pub audit_events: Vec<AuditEvent>,
+        /// Whether the server will send another page after this one.
+        pub has_more: bool,
     }
 }
@@ -0,0 +1,770 @@
+//! Implementation of the `DataStore` trait that keeps all data in memory.
+
+use crate::InMemoryTransaction;
+use async_trait::async_trait;
+use lock_keeper::{
+    config::opaque::OpaqueCipherSuite,
+    crypto::{Encrypted, KeyId, ServiceCredentialPublicKey, StorageKey},
+    types::{
+        audit_chain::AuditCheckpoint,
+        audit_event::{
+            AuditEvent, AuditEventDetails, AuditEventHash, AuditEventOptions, EventStatus,
+            EventType,
+        },
+        database::{
+            account::{Account, AccountId, AccountName, UserId},
+            secrets::{secret_types, StoredSecret},
+            service_credential::ServiceCredential,
+        },
+        operations::ClientAction,
+    },
+};
+use lock_keeper_key_server::server::database::{DataStore, DatabaseError, SecretFilter};
+use opaque_ke::ServerRegistration;
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+use time::OffsetDateTime;
+use tracing::{debug, info, instrument};
+use uuid::Uuid;
+
+/// Secret types that can be stored. Matches the `SecretTypes` table of the
+/// Postgres backend.
+const SECRET_TYPES: &[&str] = &[
+    secret_types::ARBITRARY_SECRET,
+    secret_types::REMOTE_SIGNING_KEY,
+    secret_types::SIGNING_KEY_PAIR,
+    secret_types::SERVER_ENCRYPTED_BLOB,
+];
+
+/// [`DataStore`] that keeps all data in memory.
+///
+/// Clones share the same underlying data.
+#[derive(Clone, Default)]
+pub struct InMemoryDB {
+    tables: Arc<RwLock<Tables>>,
+}
+
+/// All data held by an [`InMemoryDB`]. Every operation takes the lock once so
+/// that operations are atomic, like the queries of the Postgres backend.
+#[derive(Default)]
+pub(crate) struct Tables {
+    next_account_id: i64,
+    next_audit_event_id: i64,
+    accounts: Vec<AccountRow>,
+    secrets: Vec<SecretRow>,
+    audit_events: Vec<AuditEventRow>,
+    audit_checkpoints: Vec<AuditCheckpoint>,
+    service_credentials: Vec<ServiceCredential>,
+    certificate_bindings: HashMap<String, AccountId>,
+}
+
+struct AccountRow {
+    account_id: AccountId,
+    user_id: UserId,
+    account_name: AccountName,
+    storage_key: Option<Encrypted<StorageKey>>,
+    server_registration: ServerRegistration<OpaqueCipherSuite>,
+}
+
+pub(crate) struct SecretRow {
+    key_id: KeyId,
+    account_id: AccountId,
+    secret_type: String,
+    bytes: Vec<u8>,
+    retrieved: bool,
+}
+
+struct AuditEventRow {
+    audit_event_id: i64,
+    account_id: AccountId,
+    request_id: Uuid,
+    key_id: Option<KeyId>,
+    timestamp: OffsetDateTime,
+    client_action: ClientAction,
+    status: EventStatus,
+    details: AuditEventDetails,
+    previous_hash: Option<AuditEventHash>,
+}
+
+impl From<&AccountRow> for Account {
+    fn from(row: &AccountRow) -> Self {
+        Account {
+            account_id: row.account_id,
+            user_id: row.user_id.clone(),
+            account_name: row.account_name.clone(),
+            storage_key: row.storage_key.clone(),
+            server_registration: row.server_registration.clone(),
+        }
+    }
+}
+
+impl From<&SecretRow> for StoredSecret {
+    fn from(row: &SecretRow) -> Self {
+        StoredSecret {
+            key_id: row.key_id.clone(),
+            account_id: row.account_id,
+            secret_type: row.secret_type.clone(),
+            bytes: row.bytes.clone(),
+            retrieved: row.retrieved,
+        }
+    }
+}
+
+impl From<&AuditEventRow> for AuditEvent {
+    fn from(row: &AuditEventRow) -> Self {
+        AuditEvent {
+            audit_event_id: row.audit_event_id,
+            account_id: row.account_id,
+            request_id: row.request_id,
+            key_id: row.key_id.clone(),
+            timestamp: row.timestamp,
+            client_action: row.client_action,
+            status: row.status,
+            details: row.details.clone(),
+            previous_hash: row.previous_hash,
+        }
+    }
+}
+
+impl Debug for InMemoryDB {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let tables = self.read();
+        f.debug_struct("InMemoryDB")
+            .field("accounts", &tables.accounts.len())
+            .field("secrets", &tables.secrets.len())
+            .field("audit_events", &tables.audit_events.len())
+            .finish()
+    }
+}
+
+impl Tables {
+    fn account_exists(&self, account_id: AccountId) -> bool {
+        self.accounts.iter().any(|a| a.account_id == account_id)
+    }
+
+    /// Find audit events matching the filters. Events of every account are
+    /// searched if `account_id` is `None`.
+    fn find_audit_events(
+        &self,
+        account_id: Option<AccountId>,
+        event_type: EventType,
+        options: &AuditEventOptions,
+    ) -> Vec<AuditEvent> {
+        let actions = event_type.client_actions();
+        self.audit_events
+            .iter()
+            .filter(|e| account_id.map_or(true, |id| e.account_id == id))
+            .filter(|e| {
+                options
+                    .after_audit_event_id
+                    .map_or(true, |id| e.audit_event_id > id)
+            })
+            .filter(|e| options.after_date.map_or(true, |date| e.timestamp >= date))
+            .filter(|e| options.before_date.map_or(true, |date| e.timestamp <= date))
+            .filter(|e| {
+                options.key_ids.is_empty()
+                    || e.key_id
+                        .as_ref()
+                        .map_or(false, |key_id| options.key_ids.contains(key_id))
+            })
+            .filter(|e| options.request_id.map_or(true, |id| e.request_id == id))
+            .filter(|e| {
+                options
+                    .failure_code
+                    .map_or(true, |code| e.details.failure_code == Some(code))
+            })
+            .filter(|e| {
+                options
+                    .peer_address
+                    .map_or(true, |address| e.details.peer_address == Some(address))
+            })
+            .filter(|e| {
+                options.client_identity.as_ref().map_or(true, |identity| {
+                    e.details.client_identity.as_ref() == Some(identity)
+                })
+            })
+            .filter(|e| actions.contains(&e.client_action))
+            // Events are stored in order of their IDs.
+            .take(options.limit.map_or(usize::MAX, |limit| limit as usize))
+            .map(AuditEvent::from)
+            .collect()
+    }
+
+    /// Equivalent of a foreign key constraint on `account_id`.
+    fn check_account_exists(&self, account_id: AccountId) -> Result<(), DatabaseError> {
+        if self.account_exists(account_id) {
+            Ok(())
+        } else {
+            Err(constraint_violation("account does not exist"))
+        }
+    }
+
+    pub(crate) fn insert_audit_event(
+        &mut self,
+        request_id: Uuid,
+        account_id: AccountId,
+        key_id: &Option<KeyId>,
+        action: ClientAction,
+        status: EventStatus,
+        details: &AuditEventDetails,
+    ) -> Result<AuditEvent, DatabaseError> {
+        self.check_account_exists(account_id)?;
+
+        // Audit events are hash-chained. Holding the write lock means no other
+        // event can be added for the account in the meantime.
+        let previous_hash = self
+            .audit_events
+            .iter()
+            .rev()
+            .find(|e| e.account_id == account_id)
+            .map(|e| AuditEvent::from(e).hash());
+
+        self.next_audit_event_id += 1;
+        let row = AuditEventRow {
+            audit_event_id: self.next_audit_event_id,
+            account_id,
+            request_id,
+            key_id: key_id.clone(),
+            timestamp: OffsetDateTime::now_utc(),
+            client_action: action,
+            status,
+            details: details.clone(),
+            previous_hash,
+        };
+        let event = AuditEvent::from(&row);
+        self.audit_events.push(row);
+
+        Ok(event)
+    }
+
+    /// Deletes the audit events with the given IDs and returns how many were
+    /// deleted.
+    fn delete_audit_events(&mut self, audit_event_ids: &[i64]) -> u64 {
+        let count = self.audit_events.len();
+        self.audit_events
+            .retain(|e| !audit_event_ids.contains(&e.audit_event_id));
+        (count - self.audit_events.len()) as u64
+    }
+
+    /// Inserts archived audit events with their original IDs, skipping IDs
+    /// that are already stored. Nothing is inserted if an account is missing.
+    fn import_audit_events(&mut self, events: &[AuditEvent]) -> Result<u64, DatabaseError> {
+        for event in events {
+            self.check_account_exists(event.account_id)?;
+        }
+
+        let mut imported = 0;
+        for event in events {
+            // Keep the events ordered by ID.
+            let index = match self
+                .audit_events
+                .binary_search_by_key(&event.audit_event_id, |e| e.audit_event_id)
+            {
+                Ok(_) => continue,
+                Err(index) => index,
+            };
+            self.audit_events.insert(
+                index,
+                AuditEventRow {
+                    audit_event_id: event.audit_event_id,
+                    account_id: event.account_id,
+                    request_id: event.request_id,
+                    key_id: event.key_id.clone(),
+                    timestamp: event.timestamp,
+                    client_action: event.client_action,
+                    status: event.status,
+                    details: event.details.clone(),
+                    previous_hash: event.previous_hash,
+                },
+            );
+            self.next_audit_event_id = self.next_audit_event_id.max(event.audit_event_id);
+            imported += 1;
+        }
+
+        Ok(imported)
+    }
+
+    /// Removes the most recently inserted audit event.
+    pub(crate) fn pop_audit_event(&mut self) {
+        let _ = self.audit_events.pop();
+    }
+
+    pub(crate) fn insert_secret(&mut self, secret: StoredSecret) -> Result<(), DatabaseError> {
+        // The Postgres backend inserts nothing if the secret type is unknown.
+        if !SECRET_TYPES.contains(&secret.secret_type.as_str()) {
+            return Err(DatabaseError::InvalidRowCountFound);
+        }
+        self.check_account_exists(secret.account_id)?;
+        if self.secrets.iter().any(|s| s.key_id == secret.key_id) {
+            return Err(constraint_violation("key ID already exists"));
+        }
+
+        self.secrets.push(SecretRow {
+            key_id: secret.key_id,
+            account_id: secret.account_id,
+            secret_type: secret.secret_type,
+            bytes: secret.bytes,
+            retrieved: secret.retrieved,
+        });
+
+        Ok(())
+    }
+
+    /// Removes a secret without checking that it exists.
+    pub(crate) fn discard_secret(&mut self, key_id: &KeyId) {
+        self.secrets.retain(|s| s.key_id != *key_id);
+    }
+
+    /// Returns the removed rows so that they can be restored.
+    pub(crate) fn remove_secret(
+        &mut self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<Vec<SecretRow>, DatabaseError> {
+        let (removed, kept) = std::mem::take(&mut self.secrets)
+            .into_iter()
+            .partition(|s| s.key_id == *key_id && s.account_id == account_id);
+        self.secrets = kept;
+
+        if removed.is_empty() {
+            return Err(DatabaseError::NoEntry);
+        }
+
+        Ok(removed)
+    }
+
+    pub(crate) fn restore_secrets(&mut self, secrets: Vec<SecretRow>) {
+        self.secrets.extend(secrets);
+    }
+
+    /// Returns the previous storage key so that it can be restored. Like the
+    /// Postgres backend, setting the key for an unknown account does nothing.
+    pub(crate) fn update_storage_key(
+        &mut self,
+        account_id: AccountId,
+        storage_key: Option<Encrypted<StorageKey>>,
+    ) -> Option<Encrypted<StorageKey>> {
+        self.accounts
+            .iter_mut()
+            .find(|a| a.account_id == account_id)
+            .and_then(|account| std::mem::replace(&mut account.storage_key, storage_key))
+    }
+}
+
+fn constraint_violation(reason: &str) -> DatabaseError {
+    DatabaseError::InternalDatabaseError(format!("constraint violation: {reason}"))
+}
+
+impl InMemoryDB {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Tables> {
+        self.tables.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn write(&self) -> RwLockWriteGuard<'_, Tables> {
+        self.tables.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl DataStore for InMemoryDB {
+    type Transaction = InMemoryTransaction;
+
+    #[instrument(skip(self))]
+    async fn begin_transaction(&self) -> Result<InMemoryTransaction, DatabaseError> {
+        debug!("Beginning transaction.");
+        Ok(InMemoryTransaction::new(self.clone()))
+    }
+
+    async fn health_check(&self) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn create_audit_event(
+        &self,
+        request_id: Uuid,
+        account_id: AccountId,
+        key_id: &Option<KeyId>,
+        action: ClientAction,
+        status: EventStatus,
+        details: &AuditEventDetails,
+    ) -> Result<AuditEvent, DatabaseError> {
+        debug!("Storing new audit event.");
+        self.write()
+            .insert_audit_event(request_id, account_id, key_id, action, status, details)
+    }
+
+    #[instrument(skip(self))]
+    async fn find_audit_events(
+        &self,
+        account_id: AccountId,
+        event_type: EventType,
+        options: AuditEventOptions,
+    ) -> Result<Vec<AuditEvent>, DatabaseError> {
+        debug!("Finding audit event(s)");
+        Ok(self
+            .read()
+            .find_audit_events(Some(account_id), event_type, &options))
+    }
+
+    #[instrument(skip(self))]
+    async fn find_all_audit_events(
+        &self,
+        event_type: EventType,
+        options: AuditEventOptions,
+    ) -> Result<Vec<AuditEvent>, DatabaseError> {
+        debug!("Finding audit event(s) of every account");
+        Ok(self.read().find_audit_events(None, event_type, &options))
+    }
+
+    #[instrument(skip(self))]
+    async fn find_latest_audit_event(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditEvent>, DatabaseError> {
+        debug!("Finding latest audit event.");
+        let tables = self.read();
+
+        let event = tables
+            .audit_events
+            .iter()
+            .rev()
+            .find(|e| e.account_id == account_id)
+            .map(AuditEvent::from);
+
+        Ok(event)
+    }
+
+    #[instrument(skip_all, fields(count=audit_event_ids.len()))]
+    async fn delete_audit_events(&self, audit_event_ids: &[i64]) -> Result<u64, DatabaseError> {
+        debug!("Deleting audit events.");
+        Ok(self.write().delete_audit_events(audit_event_ids))
+    }
+
+    #[instrument(skip_all, fields(count=events.len()))]
+    async fn import_audit_events(&self, events: &[AuditEvent]) -> Result<u64, DatabaseError> {
+        debug!("Importing audit events.");
+        self.write().import_audit_events(events)
+    }
+
+    #[instrument(skip_all, fields(account_id=?checkpoint.account_id, audit_event_id=checkpoint.audit_event_id))]
+    async fn create_audit_checkpoint(
+        &self,
+        checkpoint: &AuditCheckpoint,
+    ) -> Result<(), DatabaseError> {
+        debug!("Storing new audit checkpoint.");
+        let mut tables = self.write();
+
+        tables.check_account_exists(checkpoint.account_id)?;
+        tables.audit_checkpoints.push(checkpoint.clone());
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn find_audit_checkpoints(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<AuditCheckpoint>, DatabaseError> {
+        debug!("Finding audit checkpoints.");
+        let tables = self.read();
+
+        let mut checkpoints: Vec<AuditCheckpoint> = tables
+            .audit_checkpoints
+            .iter()
+            .filter(|c| c.account_id == account_id)
+            .cloned()
+            .collect();
+        // The sort is stable, so checkpoints for the same event stay in the
+        // order they were created.
+        checkpoints.sort_by_key(|c| c.audit_event_id);
+
+        Ok(checkpoints)
+    }
+
+    #[instrument(skip(self))]
+    async fn find_latest_audit_checkpoint(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditCheckpoint>, DatabaseError> {
+        debug!("Finding latest audit checkpoint.");
+        let checkpoints = self.find_audit_checkpoints(account_id).await?;
+        Ok(checkpoints.into_iter().last())
+    }
+
+    #[instrument(skip_all, fields(account_id=?secret.account_id, key_id=?secret.key_id))]
+    async fn add_secret(&self, secret: StoredSecret) -> Result<(), DatabaseError> {
+        debug!("Adding user secret.");
+        self.write().insert_secret(secret)
+    }
+
+    /// Returns [`DatabaseError::IncorrectKeyMetadata`] if the key belongs to
+    /// another account, and [`DatabaseError::WrongSecretType`] if it doesn't
+    /// match the filter.
+    #[instrument(skip(self))]
+    async fn get_secret(
+        &self,
+        account_id: AccountId,
+        key_id: &KeyId,
+        filter: SecretFilter,
+    ) -> Result<StoredSecret, DatabaseError> {
+        debug!("Fetching user secret.");
+        let mut tables = self.write();
+
+        let secret = tables.secrets.iter_mut().find(|s| {
+            s.key_id == *key_id
+                && s.account_id == account_id
+                && filter
+                    .secret_type
+                    .as_ref()
+                    .map_or(true, |secret_type| s.secret_type == *secret_type)
+        });
+
+        if let Some(secret) = secret {
+            // Like the Postgres backend, the secret is returned as it was before
+            // being marked as retrieved.
+            let stored_secret = StoredSecret::from(&*secret);
+            secret.retrieved = true;
+            return Ok(stored_secret);
+        }
+
+        // Entry not found. Check if the key exists but the account or type were
+        // wrong.
+        match tables.secrets.iter().find(|s| s.key_id == *key_id) {
+            Some(s) if s.account_id == account_id => Err(DatabaseError::WrongSecretType),
+            Some(_) => Err(DatabaseError::IncorrectKeyMetadata),
+            None => Err(DatabaseError::NoEntry),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn get_server_encrypted_blob(
+        &self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<StoredSecret, DatabaseError> {
+        debug!("Fetching user secret.");
+        self.read()
+            .secrets
+            .iter()
+            .find(|s| {
+                s.key_id == *key_id
+                    && s.account_id == account_id
+                    && s.secret_type == secret_types::SERVER_ENCRYPTED_BLOB
+            })
+            .map(StoredSecret::from)
+            .ok_or(DatabaseError::NoEntry)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_secret(
+        &self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<(), DatabaseError> {
+        debug!("Deleting user secret.");
+        let _ = self.write().remove_secret(account_id, key_id)?;
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, server_registration))]
+    async fn create_account(
+        &self,
+        user_id: &UserId,
+        account_name: &AccountName,
+        server_registration: &ServerRegistration<OpaqueCipherSuite>,
+    ) -> Result<Account, DatabaseError> {
+        info!("Creating new user.");
+        let mut tables = self.write();
+
+        if tables.accounts.iter().any(|a| a.user_id == *user_id) {
+            return Err(constraint_violation("user ID already exists"));
+        }
+        if tables
+            .accounts
+            .iter()
+            .any(|a| a.account_name == *account_name)
+        {
+            return Err(constraint_violation("account name already exists"));
+        }
+
+        tables.next_account_id += 1;
+        let row = AccountRow {
+            account_id: tables.next_account_id.into(),
+            user_id: user_id.clone(),
+            account_name: account_name.clone(),
+            storage_key: None,
+            server_registration: server_registration.clone(),
+        };
+        let account = Account::from(&row);
+        tables.accounts.push(row);
+
+        Ok(account)
+    }
+
+    #[instrument(skip(self))]
+    async fn find_account_by_name(
+        &self,
+        account_name: &AccountName,
+    ) -> Result<Option<Account>, DatabaseError> {
+        debug!("Searching for user.");
+        Ok(self
+            .read()
+            .accounts
+            .iter()
+            .find(|a| a.account_name == *account_name)
+            .map(Account::from))
+    }
+
+    #[instrument(skip(self))]
+    async fn find_account(&self, account_id: AccountId) -> Result<Option<Account>, DatabaseError> {
+        debug!("Searching for user by ID");
+        Ok(self
+            .read()
+            .accounts
+            .iter()
+            .find(|a| a.account_id == account_id)
+            .map(Account::from))
+    }
+
+    /// Service credentials and certificate bindings are deleted with the
+    /// account. Like the Postgres backend, an account that still has secrets
+    /// or audit events can't be deleted.
+    #[instrument(skip(self))]
+    async fn delete_account(&self, account_id: AccountId) -> Result<(), DatabaseError> {
+        info!("Deleting user.");
+        let mut tables = self.write();
+
+        if !tables.account_exists(account_id) {
+            return Err(DatabaseError::NoEntry);
+        }
+        if tables.secrets.iter().any(|s| s.account_id == account_id)
+            || tables
+                .audit_events
+                .iter()
+                .any(|e| e.account_id == account_id)
+        {
+            return Err(constraint_violation("account is still referenced"));
+        }
+
+        tables.accounts.retain(|a| a.account_id != account_id);
+        tables
+            .service_credentials
+            .retain(|c| c.account_id != account_id);
+        tables
+            .certificate_bindings
+            .retain(|_, bound_account_id| *bound_account_id != account_id);
+        tables
+            .audit_checkpoints
+            .retain(|c| c.account_id != account_id);
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, storage_key))]
+    async fn set_storage_key(
+        &self,
+        account_id: AccountId,
+        storage_key: Encrypted<StorageKey>,
+    ) -> Result<(), DatabaseError> {
+        info!("Setting storage key");
+        let _ = self
+            .write()
+            .update_storage_key(account_id, Some(storage_key));
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn user_id_exists(&self, user_id: &UserId) -> Result<bool, DatabaseError> {
+        info!("Checking if user_id exists");
+        Ok(self.read().accounts.iter().any(|a| a.user_id == *user_id))
+    }
+
+    #[instrument(skip(self, public_key))]
+    async fn create_service_credential(
+        &self,
+        account_id: AccountId,
+        public_key: &ServiceCredentialPublicKey,
+        allowed_actions: &[ClientAction],
+    ) -> Result<ServiceCredential, DatabaseError> {
+        info!("Creating service credential.");
+        let mut tables = self.write();
+        tables.check_account_exists(account_id)?;
+
+        let credential = ServiceCredential {
+            credential_id: Uuid::new_v4(),
+            account_id,
+            public_key: public_key.clone(),
+            allowed_actions: allowed_actions.to_vec(),
+        };
+        tables.service_credentials.push(credential.clone());
+
+        Ok(credential)
+    }
+
+    #[instrument(skip(self))]
+    async fn find_service_credential(
+        &self,
+        account_id: AccountId,
+        credential_id: Uuid,
+    ) -> Result<Option<ServiceCredential>, DatabaseError> {
+        debug!("Searching for service credential.");
+        Ok(self
+            .read()
+            .service_credentials
+            .iter()
+            .find(|c| c.credential_id == credential_id && c.account_id == account_id)
+            .cloned())
+    }
+
+    #[instrument(skip(self))]
+    async fn create_certificate_binding(
+        &self,
+        identity: &str,
+        account_id: AccountId,
+    ) -> Result<(), DatabaseError> {
+        info!("Binding client certificate identity to account.");
+        let mut tables = self.write();
+        tables.check_account_exists(account_id)?;
+
+        if tables.certificate_bindings.contains_key(identity) {
+            return Err(constraint_violation("identity is already bound"));
+        }
+        let _ = tables
+            .certificate_bindings
+            .insert(identity.to_string(), account_id);
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn find_certificate_binding(
+        &self,
+        identity: &str,
+    ) -> Result<Option<AccountId>, DatabaseError> {
+        debug!("Searching for client certificate binding.");
+        Ok(self.read().certificate_bindings.get(identity).copied())
+    }
+
+    #[instrument(skip(self))]
+    async fn account_has_certificate_binding(
+        &self,
+        account_id: AccountId,
+    ) -> Result<bool, DatabaseError> {
+        debug!("Checking for client certificate bindings.");
+        Ok(self
+            .read()
+            .certificate_bindings
+            .values()
+            .any(|bound_account_id| *bound_account_id == account_id))
+    }
+}
@@ -0,0 +1,22 @@
+//! This crate is an in-memory implementation of the key server's
+//! [`DataStore`](lock_keeper_key_server::server::database::DataStore) and
+//! [`SessionCache`](lock_keeper_key_server::server::session_cache::SessionCache)
+//! traits.
+//!
+//! Nothing is persisted. All data is lost when the last handle to the store
+//! is dropped. This is useful for embedding a key server in tests or in
+//! another process without running Postgres. The implementations follow the
+//! semantics of the Postgres backends, including unique and foreign key
+//! constraints, so they can be checked with the same test suites.
+#![warn(unused_results)]
+#![warn(future_incompatible)]
+#![warn(unused)]
+#![forbid(rustdoc::broken_intra_doc_links)]
+
+mod database;
+mod session_cache;
+mod transaction;
+
+pub use database::InMemoryDB;
+pub use session_cache::InMemorySessionCache;
+pub use transaction::InMemoryTransaction;
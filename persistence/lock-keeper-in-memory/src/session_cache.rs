@@ -0,0 +1,285 @@
+//! Implementation of the `SessionCache` trait that keeps sessions in memory.
+
+use async_trait::async_trait;
+use lock_keeper::{
+    crypto::{Encrypted, OpaqueSessionKey},
+    types::{database::account::AccountId, operations::ClientAction},
+};
+use lock_keeper_key_server::server::session_cache::{Session, SessionCache, SessionCacheError};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+/// [`SessionCache`] that keeps sessions in memory. A session is considered
+/// invalid after the `expiration` time has elapsed.
+///
+/// Clones share the same underlying sessions.
+#[derive(Clone, Debug)]
+pub struct InMemorySessionCache {
+    expiration: Duration,
+    sessions: Arc<Mutex<HashMap<Uuid, CachedSession>>>,
+}
+
+/// A session along with the requests that have been made on it.
+#[derive(Debug)]
+struct CachedSession {
+    session: Session,
+    requests: HashSet<(Uuid, ClientAction)>,
+}
+
+impl InMemorySessionCache {
+    pub fn new(expiration: Duration) -> Self {
+        Self {
+            expiration,
+            sessions: Default::default(),
+        }
+    }
+
+    fn sessions(&self) -> MutexGuard<'_, HashMap<Uuid, CachedSession>> {
+        self.sessions.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Add a new session. An existing session with the same ID is left as it
+    /// is.
+    fn insert_session(&self, session: Session) -> Result<Uuid, SessionCacheError> {
+        let session_id = session.session_id;
+
+        match self.sessions().entry(session_id) {
+            Entry::Occupied(_) => Err(SessionCacheError::SessionExists),
+            Entry::Vacant(entry) => {
+                let _ = entry.insert(CachedSession {
+                    session,
+                    requests: HashSet::new(),
+                });
+                Ok(session_id)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SessionCache for InMemorySessionCache {
+    #[instrument(skip(self, session_key))]
+    async fn create_session(
+        &self,
+        account_id: AccountId,
+        session_key: Encrypted<OpaqueSessionKey>,
+        service_credential_id: Option<Uuid>,
+    ) -> Result<Uuid, SessionCacheError> {
+        info!("Creating session.");
+
+        let session = Session {
+            session_id: Uuid::new_v4(),
+            account_id,
+            timestamp: OffsetDateTime::now_utc(),
+            session_key,
+            service_credential_id,
+        };
+        self.insert_session(session)
+    }
+
+    /// Expired sessions are removed when they are found.
+    #[instrument(skip(self))]
+    async fn find_session(&self, session_id: Uuid) -> Result<Session, SessionCacheError> {
+        let mut sessions = self.sessions();
+
+        let session = &sessions
+            .get(&session_id)
+            .ok_or(SessionCacheError::MissingSession)?
+            .session;
+
+        let elapsed = OffsetDateTime::now_utc() - session.timestamp;
+        if elapsed >= self.expiration {
+            info!("Session key is expired.");
+            let _ = sessions.remove(&session_id);
+            return Err(SessionCacheError::ExpiredSession);
+        }
+
+        Ok(Session {
+            session_id: session.session_id,
+            account_id: session.account_id,
+            timestamp: session.timestamp,
+            session_key: session.session_key.clone(),
+            service_credential_id: session.service_credential_id,
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_session(&self, session_id: Uuid) -> Result<(), SessionCacheError> {
+        info!("Deleting session.");
+        let _ = self.sessions().remove(&session_id);
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn record_request(
+        &self,
+        session_id: Uuid,
+        request_id: Uuid,
+        action: ClientAction,
+    ) -> Result<(), SessionCacheError> {
+        let mut sessions = self.sessions();
+        let cached_session = sessions
+            .get_mut(&session_id)
+            .ok_or(SessionCacheError::MissingSession)?;
+
+        if !cached_session.requests.insert((request_id, action)) {
+            return Err(SessionCacheError::ReplayedRequest);
+        }
+
+        Ok(())
+    }
+
+    async fn count_active_sessions(&self) -> Result<u64, SessionCacheError> {
+        let now = OffsetDateTime::now_utc();
+        let count = self
+            .sessions()
+            .values()
+            .filter(|cached| now - cached.session.timestamp < self.expiration)
+            .count();
+
+        Ok(count as u64)
+    }
+
+    async fn health_check(&self) -> Result<(), SessionCacheError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use generic_array::GenericArray;
+    use lock_keeper::crypto::RemoteStorageKey;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn encrypted_session_key(rng: &mut StdRng) -> Encrypted<OpaqueSessionKey> {
+        let session_key = OpaqueSessionKey::try_from(GenericArray::from([42; 64])).unwrap();
+        RemoteStorageKey::generate(rng)
+            .encrypt_session_key(rng, session_key)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn session_is_found_until_deleted() {
+        let mut rng = StdRng::seed_from_u64(1234);
+        let cache = InMemorySessionCache::new(Duration::from_secs(60));
+        let account_id = AccountId::from(1);
+
+        let session_id = cache
+            .create_session(account_id, encrypted_session_key(&mut rng), None)
+            .await
+            .unwrap();
+        let session = cache.find_session(session_id).await.unwrap();
+        assert_eq!(session.account_id, account_id);
+
+        cache.delete_session(session_id).await.unwrap();
+        assert!(matches!(
+            cache.find_session(session_id).await,
+            Err(SessionCacheError::MissingSession)
+        ));
+    }
+
+    #[tokio::test]
+    async fn requests_are_recorded_per_session() {
+        let mut rng = StdRng::seed_from_u64(1234);
+        let cache = InMemorySessionCache::new(Duration::from_secs(60));
+        let request_id = Uuid::new_v4();
+
+        let session_id = cache
+            .create_session(AccountId::from(1), encrypted_session_key(&mut rng), None)
+            .await
+            .unwrap();
+        cache
+            .record_request(session_id, request_id, ClientAction::GenerateSecret)
+            .await
+            .unwrap();
+        assert!(matches!(
+            cache
+                .record_request(session_id, request_id, ClientAction::GenerateSecret)
+                .await,
+            Err(SessionCacheError::ReplayedRequest)
+        ));
+
+        // The same request ID may be used once on another session
+        let other_session_id = cache
+            .create_session(AccountId::from(1), encrypted_session_key(&mut rng), None)
+            .await
+            .unwrap();
+        cache
+            .record_request(other_session_id, request_id, ClientAction::GenerateSecret)
+            .await
+            .unwrap();
+
+        cache.delete_session(session_id).await.unwrap();
+        assert!(matches!(
+            cache
+                .record_request(session_id, request_id, ClientAction::GenerateSecret)
+                .await,
+            Err(SessionCacheError::MissingSession)
+        ));
+    }
+
+    #[tokio::test]
+    async fn existing_session_is_kept_on_collision() {
+        let mut rng = StdRng::seed_from_u64(1234);
+        let cache = InMemorySessionCache::new(Duration::from_secs(60));
+        let request_id = Uuid::new_v4();
+
+        let session_id = cache
+            .create_session(AccountId::from(1), encrypted_session_key(&mut rng), None)
+            .await
+            .unwrap();
+        cache
+            .record_request(session_id, request_id, ClientAction::GenerateSecret)
+            .await
+            .unwrap();
+
+        let colliding_session = Session {
+            session_id,
+            account_id: AccountId::from(2),
+            timestamp: OffsetDateTime::now_utc(),
+            session_key: encrypted_session_key(&mut rng),
+            service_credential_id: None,
+        };
+        assert!(matches!(
+            cache.insert_session(colliding_session),
+            Err(SessionCacheError::SessionExists)
+        ));
+
+        // Neither the session nor its recorded requests were replaced
+        let session = cache.find_session(session_id).await.unwrap();
+        assert_eq!(session.account_id, AccountId::from(1));
+        assert!(matches!(
+            cache
+                .record_request(session_id, request_id, ClientAction::GenerateSecret)
+                .await,
+            Err(SessionCacheError::ReplayedRequest)
+        ));
+    }
+
+    #[tokio::test]
+    async fn expired_session_is_removed() {
+        let mut rng = StdRng::seed_from_u64(1234);
+        let cache = InMemorySessionCache::new(Duration::ZERO);
+
+        let session_id = cache
+            .create_session(AccountId::from(1), encrypted_session_key(&mut rng), None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            cache.find_session(session_id).await,
+            Err(SessionCacheError::ExpiredSession)
+        ));
+        assert!(matches!(
+            cache.find_session(session_id).await,
+            Err(SessionCacheError::MissingSession)
+        ));
+    }
+}
@@ -0,0 +1,319 @@
+//! Implementation of the `DataStoreTransaction` trait for [`InMemoryDB`].
+
+use crate::{
+    database::{SecretRow, Tables},
+    InMemoryDB,
+};
+use async_trait::async_trait;
+use lock_keeper::{
+    crypto::{Encrypted, KeyId, StorageKey},
+    types::{
+        audit_event::{AuditEvent, AuditEventDetails, EventStatus},
+        database::{account::AccountId, secrets::StoredSecret},
+        operations::ClientAction,
+    },
+};
+use lock_keeper_key_server::server::database::{DataStoreTransaction, DatabaseError};
+use std::fmt::{Debug, Formatter};
+use tracing::{debug, info, instrument};
+use uuid::Uuid;
+
+/// [`DataStoreTransaction`] for an [`InMemoryDB`].
+///
+/// Changes are recorded and only checked and applied when the transaction is
+/// committed. The write lock is held while they are applied, and changes that
+/// were already applied are undone if a later one fails.
+pub struct InMemoryTransaction {
+    db: InMemoryDB,
+    changes: Vec<Change>,
+}
+
+/// A change recorded by an [`InMemoryTransaction`].
+enum Change {
+    CreateAuditEvent {
+        request_id: Uuid,
+        account_id: AccountId,
+        key_id: Option<KeyId>,
+        action: ClientAction,
+        status: EventStatus,
+        details: AuditEventDetails,
+    },
+    AddSecret(StoredSecret),
+    DeleteSecret {
+        account_id: AccountId,
+        key_id: KeyId,
+    },
+    SetStorageKey {
+        account_id: AccountId,
+        storage_key: Encrypted<StorageKey>,
+    },
+}
+
+/// Reverts a [`Change`] that has been applied.
+enum Undo {
+    RemoveAuditEvent,
+    RemoveSecret(KeyId),
+    RestoreSecrets(Vec<SecretRow>),
+    RestoreStorageKey {
+        account_id: AccountId,
+        storage_key: Option<Encrypted<StorageKey>>,
+    },
+}
+
+impl Change {
+    /// Apply the change. Audit events it creates are added to `events`.
+    fn apply(
+        self,
+        tables: &mut Tables,
+        events: &mut Vec<AuditEvent>,
+    ) -> Result<Undo, DatabaseError> {
+        match self {
+            Change::CreateAuditEvent {
+                request_id,
+                account_id,
+                key_id,
+                action,
+                status,
+                details,
+            } => {
+                let event = tables.insert_audit_event(
+                    request_id, account_id, &key_id, action, status, &details,
+                )?;
+                events.push(event);
+                Ok(Undo::RemoveAuditEvent)
+            }
+            Change::AddSecret(secret) => {
+                let key_id = secret.key_id.clone();
+                tables.insert_secret(secret)?;
+                Ok(Undo::RemoveSecret(key_id))
+            }
+            Change::DeleteSecret { account_id, key_id } => {
+                let removed = tables.remove_secret(account_id, &key_id)?;
+                Ok(Undo::RestoreSecrets(removed))
+            }
+            Change::SetStorageKey {
+                account_id,
+                storage_key,
+            } => {
+                let previous = tables.update_storage_key(account_id, Some(storage_key));
+                Ok(Undo::RestoreStorageKey {
+                    account_id,
+                    storage_key: previous,
+                })
+            }
+        }
+    }
+}
+
+impl Undo {
+    /// Undo entries must be applied in the reverse order of the changes.
+    fn apply(self, tables: &mut Tables) {
+        match self {
+            Undo::RemoveAuditEvent => tables.pop_audit_event(),
+            Undo::RemoveSecret(key_id) => tables.discard_secret(&key_id),
+            Undo::RestoreSecrets(secrets) => tables.restore_secrets(secrets),
+            Undo::RestoreStorageKey {
+                account_id,
+                storage_key,
+            } => {
+                let _ = tables.update_storage_key(account_id, storage_key);
+            }
+        }
+    }
+}
+
+impl InMemoryTransaction {
+    pub(crate) fn new(db: InMemoryDB) -> Self {
+        Self {
+            db,
+            changes: Vec::new(),
+        }
+    }
+}
+
+impl Debug for InMemoryTransaction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InMemoryTransaction")
+            .field("changes", &self.changes.len())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl DataStoreTransaction for InMemoryTransaction {
+    #[instrument(skip(self))]
+    async fn create_audit_event(
+        &mut self,
+        request_id: Uuid,
+        account_id: AccountId,
+        key_id: &Option<KeyId>,
+        action: ClientAction,
+        status: EventStatus,
+        details: &AuditEventDetails,
+    ) -> Result<(), DatabaseError> {
+        debug!("Storing new audit event in transaction.");
+        self.changes.push(Change::CreateAuditEvent {
+            request_id,
+            account_id,
+            key_id: key_id.clone(),
+            action,
+            status,
+            details: details.clone(),
+        });
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(account_id=?secret.account_id, key_id=?secret.key_id))]
+    async fn add_secret(&mut self, secret: StoredSecret) -> Result<(), DatabaseError> {
+        debug!("Adding user secret in transaction.");
+        self.changes.push(Change::AddSecret(secret));
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_secret(
+        &mut self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<(), DatabaseError> {
+        debug!("Deleting user secret in transaction.");
+        self.changes.push(Change::DeleteSecret {
+            account_id,
+            key_id: key_id.clone(),
+        });
+        Ok(())
+    }
+
+    #[instrument(skip(self, storage_key))]
+    async fn set_storage_key(
+        &mut self,
+        account_id: AccountId,
+        storage_key: Encrypted<StorageKey>,
+    ) -> Result<(), DatabaseError> {
+        info!("Setting storage key in transaction.");
+        self.changes.push(Change::SetStorageKey {
+            account_id,
+            storage_key,
+        });
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn commit(self) -> Result<Vec<AuditEvent>, DatabaseError> {
+        debug!("Committing transaction.");
+        let mut tables = self.db.write();
+
+        let mut applied = Vec::with_capacity(self.changes.len());
+        let mut events = Vec::new();
+        for change in self.changes {
+            match change.apply(&mut tables, &mut events) {
+                Ok(undo) => applied.push(undo),
+                Err(error) => {
+                    for undo in applied.into_iter().rev() {
+                        undo.apply(&mut tables);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lock_keeper::{
+        config::opaque::OpaqueCipherSuite,
+        types::database::{
+            account::{AccountName, UserId},
+            secrets::secret_types,
+        },
+    };
+    use lock_keeper_key_server::server::database::{DataStore, SecretFilter};
+    use opaque_ke::{
+        ClientRegistration, ClientRegistrationFinishParameters, ServerRegistration, ServerSetup,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    async fn create_account(db: &InMemoryDB, rng: &mut StdRng) -> AccountId {
+        let server_setup = ServerSetup::<OpaqueCipherSuite>::new(rng);
+        let registration_start =
+            ClientRegistration::<OpaqueCipherSuite>::start(rng, b"password").unwrap();
+        let registration_response =
+            ServerRegistration::start(&server_setup, registration_start.message, b"account")
+                .unwrap();
+        let registration_upload = registration_start
+            .state
+            .finish(
+                rng,
+                b"password",
+                registration_response.message,
+                ClientRegistrationFinishParameters::default(),
+            )
+            .unwrap()
+            .message;
+        let server_registration = ServerRegistration::finish(registration_upload);
+
+        db.create_account(
+            &UserId::new(rng).unwrap(),
+            &AccountName::from("account"),
+            &server_registration,
+        )
+        .await
+        .unwrap()
+        .account_id
+    }
+
+    fn secret(rng: &mut StdRng, account_id: AccountId) -> StoredSecret {
+        let user_id = UserId::new(rng).unwrap();
+        StoredSecret {
+            key_id: KeyId::generate(rng, &user_id).unwrap(),
+            account_id,
+            secret_type: secret_types::ARBITRARY_SECRET.to_string(),
+            bytes: vec![1, 2, 3],
+            retrieved: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_commit_undoes_applied_changes() {
+        let mut rng = StdRng::seed_from_u64(1234);
+        let db = InMemoryDB::new();
+        let account_id = create_account(&db, &mut rng).await;
+
+        let existing = secret(&mut rng, account_id);
+        let existing_key_id = existing.key_id.clone();
+        db.add_secret(existing).await.unwrap();
+
+        let added = secret(&mut rng, account_id);
+        let added_key_id = added.key_id.clone();
+        let missing_key_id = secret(&mut rng, account_id).key_id;
+
+        let mut transaction = db.begin_transaction().await.unwrap();
+        transaction.add_secret(added).await.unwrap();
+        transaction
+            .delete_secret(account_id, &existing_key_id)
+            .await
+            .unwrap();
+        transaction
+            .delete_secret(account_id, &missing_key_id)
+            .await
+            .unwrap();
+        assert!(matches!(
+            transaction.commit().await,
+            Err(DatabaseError::NoEntry)
+        ));
+
+        assert!(db
+            .get_secret(account_id, &existing_key_id, SecretFilter::default())
+            .await
+            .is_ok());
+        assert!(matches!(
+            db.get_secret(account_id, &added_key_id, SecretFilter::default())
+                .await,
+            Err(DatabaseError::NoEntry)
+        ));
+    }
+}
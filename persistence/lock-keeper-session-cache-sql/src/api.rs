@@ -2,7 +2,7 @@ use async_trait::async_trait;
 use lock_keeper::{
     crypto::{Encrypted, OpaqueSessionKey},
     infrastructure::logging,
-    types::database::account::AccountId,
+    types::{database::account::AccountId, operations::ClientAction},
 };
 use lock_keeper_key_server::server::session_cache::{Session, SessionCache, SessionCacheError};
 use sqlx::{postgres::PgPoolOptions, types::time::OffsetDateTime, PgPool};
@@ -76,8 +76,11 @@ impl SessionCache for PostgresSessionCache {
         &self,
         account_id: AccountId,
         session_key: Encrypted<OpaqueSessionKey>,
+        service_credential_id: Option<Uuid>,
     ) -> Result<Uuid, SessionCacheError> {
-        let session_id = self.create_session(account_id, session_key).await?;
+        let session_id = self
+            .create_session(account_id, session_key, service_credential_id)
+            .await?;
         Ok(session_id)
     }
 
@@ -92,6 +95,27 @@ impl SessionCache for PostgresSessionCache {
     async fn delete_session(&self, session_id: Uuid) -> Result<(), SessionCacheError> {
         Ok(self.delete_session(session_id).await?)
     }
+
+    /// Record that a request was made on the session. Recorded requests are
+    /// deleted along with the session.
+    async fn record_request(
+        &self,
+        session_id: Uuid,
+        request_id: Uuid,
+        action: ClientAction,
+    ) -> Result<(), SessionCacheError> {
+        Ok(self.record_request(session_id, request_id, action).await?)
+    }
+
+    /// Count the sessions that have not expired.
+    async fn count_active_sessions(&self) -> Result<u64, SessionCacheError> {
+        Ok(self.count_active_sessions().await?)
+    }
+
+    /// Check that the database holding the sessions can be queried.
+    async fn health_check(&self) -> Result<(), SessionCacheError> {
+        Ok(self.health_check().await?)
+    }
 }
 
 impl PostgresSessionCache {
@@ -102,17 +126,19 @@ impl PostgresSessionCache {
         &self,
         account_id: AccountId,
         session_key: Encrypted<OpaqueSessionKey>,
+        service_credential_id: Option<Uuid>,
     ) -> Result<Uuid, Error> {
         info!("Creating session.");
 
         let session_key = bincode::serialize(&session_key)?;
 
         let session_id = sqlx::query!(
-            "INSERT INTO Session (account_id, session_key) \
-             VALUES ($1, $2) \
+            "INSERT INTO Session (account_id, session_key, service_credential_id) \
+             VALUES ($1, $2, $3) \
              RETURNING session_id",
             account_id.0,
             session_key,
+            service_credential_id,
         )
         .fetch_one(&self.connection_pool)
         .await?
@@ -130,7 +156,7 @@ impl PostgresSessionCache {
     async fn find_session(&self, session_id: Uuid) -> Result<Session, Error> {
         let session_db = sqlx::query_as!(
             SessionDB,
-            "SELECT session_id, account_id, timestamp, session_key \
+            "SELECT session_id, account_id, timestamp, session_key, service_credential_id \
             FROM Session \
             WHERE session_id=$1",
             session_id,
@@ -164,4 +190,55 @@ impl PostgresSessionCache {
 
         Ok(())
     }
+
+    /// Record that a request was made on the session. Returns
+    /// [`Error::ReplayedRequest`] if it was already recorded.
+    #[instrument(skip(self), err(Debug))]
+    async fn record_request(
+        &self,
+        session_id: Uuid,
+        request_id: Uuid,
+        action: ClientAction,
+    ) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "INSERT INTO SessionRequests (session_id, request_id, client_action_id) \
+             VALUES ($1, $2, $3) \
+             ON CONFLICT DO NOTHING",
+            session_id,
+            request_id,
+            action as i64,
+        )
+        .execute(&self.connection_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::ReplayedRequest);
+        }
+
+        Ok(())
+    }
+
+    /// Count the sessions that have not expired.
+    #[instrument(skip(self), err(Debug))]
+    async fn count_active_sessions(&self) -> Result<u64, Error> {
+        let cutoff = OffsetDateTime::now_utc() - self.config.session_expiration;
+
+        let count = sqlx::query_scalar!(
+            "SELECT COUNT(1) as \"count!\" FROM Session WHERE timestamp > $1",
+            cutoff,
+        )
+        .fetch_one(&self.connection_pool)
+        .await?;
+
+        Ok(count as u64)
+    }
+
+    /// Check that the database holding the sessions can be queried.
+    #[instrument(skip(self), err(Debug))]
+    async fn health_check(&self) -> Result<(), Error> {
+        let _ = sqlx::query("SELECT 1")
+            .execute(&self.connection_pool)
+            .await?;
+        Ok(())
+    }
 }
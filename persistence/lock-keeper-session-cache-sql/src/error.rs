@@ -17,6 +17,8 @@ pub enum Error {
     Io(#[from] std::io::Error),
     #[error("No session for this user.")]
     MissingSession,
+    #[error("Request was already made on this session.")]
+    ReplayedRequest,
     #[error("sqlx error")]
     Sqlx(#[from] sqlx::Error),
     #[error(transparent)]
@@ -32,6 +34,7 @@ impl From<Error> for SessionCacheError {
         match error {
             Error::ExpiredSession => Self::ExpiredSession,
             Error::MissingSession => Self::MissingSession,
+            Error::ReplayedRequest => Self::ReplayedRequest,
             _ => Self::InternalCacheError,
         }
     }
@@ -9,6 +9,7 @@ pub(crate) struct SessionDB {
     pub(crate) account_id: i64,
     pub(crate) timestamp: OffsetDateTime,
     pub(crate) session_key: Vec<u8>,
+    pub(crate) service_credential_id: Option<Uuid>,
 }
 
 impl TryFrom<SessionDB> for Session {
@@ -23,6 +24,7 @@ impl TryFrom<SessionDB> for Session {
             account_id,
             timestamp: session.timestamp,
             session_key,
+            service_credential_id: session.service_credential_id,
         })
     }
 }
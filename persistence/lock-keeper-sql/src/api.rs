@@ -1,26 +1,30 @@
 //! Actual implementation of the `DataStore` trait for our postgres type.
 //! SQL queries are found here.
 use crate::{
-    types::{AccountDB, AuditEventDB, SecretDB},
-    Config, PostgresError,
+    types::{AccountDB, AuditCheckpointDB, AuditEventDB, SecretDB, ServiceCredentialDB},
+    Config, PostgresError, PostgresTransaction,
 };
 use async_trait::async_trait;
 use lock_keeper::{
     config::opaque::OpaqueCipherSuite,
-    crypto::{Encrypted, KeyId, StorageKey},
+    crypto::{Encrypted, KeyId, ServiceCredentialPublicKey, StorageKey},
     infrastructure::logging,
     types::{
-        audit_event::{AuditEvent, AuditEventOptions, EventStatus, EventType},
+        audit_chain::AuditCheckpoint,
+        audit_event::{AuditEvent, AuditEventDetails, AuditEventOptions, EventStatus, EventType},
         database::{
             account::{Account, AccountId, AccountName, UserId},
             secrets::{secret_types::SERVER_ENCRYPTED_BLOB, StoredSecret},
+            service_credential::ServiceCredential,
         },
         operations::ClientAction,
     },
 };
 use lock_keeper_key_server::server::database::{DataStore, DatabaseError, SecretFilter};
 use opaque_ke::ServerRegistration;
-use sqlx::{postgres::PgPoolOptions, Encode, PgPool, Postgres, QueryBuilder, Type};
+use sqlx::{
+    postgres::PgPoolOptions, Encode, PgConnection, PgExecutor, PgPool, Postgres, QueryBuilder, Type,
+};
 use std::{
     fmt::{Debug, Formatter},
     sync::Arc,
@@ -29,6 +33,9 @@ use time::OffsetDateTime;
 use tracing::{debug, error, info, instrument};
 use uuid::Uuid;
 
+/// Number of audit events inserted per query when importing an archive.
+const IMPORT_BATCH_SIZE: usize = 1000;
+
 #[derive(Clone)]
 pub struct PostgresDB {
     config: Arc<Config>,
@@ -39,6 +46,16 @@ pub struct PostgresDB {
 /// Trait implementation just calls out to the equivalent methods implemented directly on [`PostgresDB`].
 #[async_trait]
 impl DataStore for PostgresDB {
+    type Transaction = PostgresTransaction;
+
+    async fn begin_transaction(&self) -> Result<PostgresTransaction, DatabaseError> {
+        Ok(self.begin_transaction_impl().await?)
+    }
+
+    async fn health_check(&self) -> Result<(), DatabaseError> {
+        Ok(self.health_check_impl().await?)
+    }
+
     async fn create_audit_event(
         &self,
         request_id: Uuid,
@@ -46,9 +63,10 @@ impl DataStore for PostgresDB {
         key_id: &Option<KeyId>,
         action: ClientAction,
         status: EventStatus,
-    ) -> Result<(), DatabaseError> {
+        details: &AuditEventDetails,
+    ) -> Result<AuditEvent, DatabaseError> {
         Ok(self
-            .create_audit_event_impl(request_id, account_id, key_id, action, status)
+            .create_audit_event_impl(request_id, account_id, key_id, action, status, details)
             .await?)
     }
 
@@ -59,10 +77,56 @@ impl DataStore for PostgresDB {
         options: AuditEventOptions,
     ) -> Result<Vec<AuditEvent>, DatabaseError> {
         Ok(self
-            .find_audit_events_impl(account_id, event_type, options)
+            .find_audit_events_impl(Some(account_id), event_type, options)
             .await?)
     }
 
+    async fn find_all_audit_events(
+        &self,
+        event_type: EventType,
+        options: AuditEventOptions,
+    ) -> Result<Vec<AuditEvent>, DatabaseError> {
+        Ok(self
+            .find_audit_events_impl(None, event_type, options)
+            .await?)
+    }
+
+    async fn find_latest_audit_event(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditEvent>, DatabaseError> {
+        Ok(self.find_latest_audit_event_impl(account_id).await?)
+    }
+
+    async fn delete_audit_events(&self, audit_event_ids: &[i64]) -> Result<u64, DatabaseError> {
+        Ok(self.delete_audit_events_impl(audit_event_ids).await?)
+    }
+
+    async fn import_audit_events(&self, events: &[AuditEvent]) -> Result<u64, DatabaseError> {
+        Ok(self.import_audit_events_impl(events).await?)
+    }
+
+    async fn create_audit_checkpoint(
+        &self,
+        checkpoint: &AuditCheckpoint,
+    ) -> Result<(), DatabaseError> {
+        Ok(self.create_audit_checkpoint_impl(checkpoint).await?)
+    }
+
+    async fn find_audit_checkpoints(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<AuditCheckpoint>, DatabaseError> {
+        Ok(self.find_audit_checkpoints_impl(account_id).await?)
+    }
+
+    async fn find_latest_audit_checkpoint(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditCheckpoint>, DatabaseError> {
+        Ok(self.find_latest_audit_checkpoint_impl(account_id).await?)
+    }
+
     async fn add_secret(&self, secret: StoredSecret) -> Result<(), DatabaseError> {
         Ok(self.add_secret_impl(secret).await?)
     }
@@ -131,6 +195,53 @@ impl DataStore for PostgresDB {
     async fn user_id_exists(&self, user_id: &UserId) -> Result<bool, DatabaseError> {
         Ok(self.user_id_exists_impl(user_id).await?)
     }
+
+    async fn create_service_credential(
+        &self,
+        account_id: AccountId,
+        public_key: &ServiceCredentialPublicKey,
+        allowed_actions: &[ClientAction],
+    ) -> Result<ServiceCredential, DatabaseError> {
+        Ok(self
+            .create_service_credential_impl(account_id, public_key, allowed_actions)
+            .await?)
+    }
+
+    async fn find_service_credential(
+        &self,
+        account_id: AccountId,
+        credential_id: Uuid,
+    ) -> Result<Option<ServiceCredential>, DatabaseError> {
+        Ok(self
+            .find_service_credential_impl(account_id, credential_id)
+            .await?)
+    }
+
+    async fn create_certificate_binding(
+        &self,
+        identity: &str,
+        account_id: AccountId,
+    ) -> Result<(), DatabaseError> {
+        Ok(self
+            .create_certificate_binding_impl(identity, account_id)
+            .await?)
+    }
+
+    async fn find_certificate_binding(
+        &self,
+        identity: &str,
+    ) -> Result<Option<AccountId>, DatabaseError> {
+        Ok(self.find_certificate_binding_impl(identity).await?)
+    }
+
+    async fn account_has_certificate_binding(
+        &self,
+        account_id: AccountId,
+    ) -> Result<bool, DatabaseError> {
+        Ok(self
+            .account_has_certificate_binding_impl(account_id)
+            .await?)
+    }
 }
 
 impl Debug for PostgresDB {
@@ -187,6 +298,23 @@ impl PostgresDB {
         &self.config.db_name
     }
 
+    #[instrument(skip(self), err(Debug))]
+    pub(crate) async fn begin_transaction_impl(
+        &self,
+    ) -> Result<PostgresTransaction, PostgresError> {
+        debug!("Beginning transaction.");
+        let transaction = self.connection_pool.begin().await?;
+        Ok(PostgresTransaction::new(transaction))
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    pub(crate) async fn health_check_impl(&self) -> Result<(), PostgresError> {
+        let _ = sqlx::query("SELECT 1")
+            .execute(&self.connection_pool)
+            .await?;
+        Ok(())
+    }
+
     #[instrument(skip(self), err(Debug))]
     pub(crate) async fn create_audit_event_impl(
         &self,
@@ -195,54 +323,60 @@ impl PostgresDB {
         key_id: &Option<KeyId>,
         action: ClientAction,
         status: EventStatus,
-    ) -> Result<(), PostgresError> {
+        details: &AuditEventDetails,
+    ) -> Result<AuditEvent, PostgresError> {
         debug!("Storing new audit event.");
-
-        let timestamp = OffsetDateTime::now_utc();
-        let key_id = key_id.as_ref().map(|k| k.as_bytes());
-
-        let rows_affected = sqlx::query!(
-            "INSERT INTO AuditEvents (account_id, key_id, request_id, client_action_id, event_status, timestamp) \
-             VALUES ($1, $2, $3, $4, $5, $6)",
-            account_id.0,
-            key_id,
+        let mut transaction = self.connection_pool.begin().await?;
+        let event = insert_audit_event(
+            &mut transaction,
             request_id,
-            action as i64,
-            status.to_string(),
-            timestamp,
+            account_id,
+            key_id,
+            action,
+            status,
+            details,
         )
-        .execute(&self.connection_pool)
-        .await?.rows_affected();
-
-        // Only one row should ever be affected by our insert. Something has gone
-        // wrong...
-        if rows_affected != 1 {
-            error!("Unexpected number of rows affected: {}", rows_affected);
-            return Err(PostgresError::InvalidRowCountFound);
-        }
+        .await?;
+        transaction.commit().await?;
 
-        Ok(())
+        Ok(event)
     }
 
     /// Create a dynamic query to fetch audit events specified by the caller.
+    /// Events of every account are searched if `account_id` is `None`.
     #[instrument(skip_all, err(Debug), fields(account_id=?account_id, event_type=?event_type, options=?options))]
     async fn find_audit_events_impl(
         &self,
-        account_id: AccountId,
+        account_id: Option<AccountId>,
         event_type: EventType,
         options: AuditEventOptions,
     ) -> Result<Vec<AuditEvent>, PostgresError> {
         debug!("Finding audit event(s)");
 
         let mut query = QueryBuilder::new(
-            "SELECT audit_event_id, key_id, request_id, account_id, client_action_id, event_status, timestamp \
+            "SELECT audit_event_id, key_id, request_id, account_id, client_action_id, event_status, timestamp, client_identity, peer_address, failure_code, payload_size, payload_hash, previous_hash \
              FROM AuditEvents \
              WHERE ",
         );
 
         // Ensure account name matches, otherwise a client could fetch anyone's audit
         // events if they guess the request_id.
-        let _ = query.push(" account_id=").push_bind(account_id.0);
+        match account_id {
+            Some(account_id) => {
+                let _ = query.push(" account_id=").push_bind(account_id.0);
+            }
+            // Only administrative exports search every account.
+            None => {
+                let _ = query.push(" TRUE");
+            }
+        }
+
+        // Add the pagination cursor if present.
+        if let Some(after_audit_event_id) = options.after_audit_event_id {
+            let _ = query
+                .push(" AND audit_event_id > ")
+                .push_bind(after_audit_event_id);
+        }
 
         // Add filtering based on after_date if present.
         if let Some(after_date) = options.after_date {
@@ -266,6 +400,24 @@ impl PostgresDB {
             let _ = query.push(" AND request_id=").push_bind(request_id);
         }
 
+        if let Some(failure_code) = options.failure_code {
+            let _ = query
+                .push(" AND failure_code=")
+                .push_bind(failure_code.to_string());
+        }
+
+        if let Some(peer_address) = options.peer_address {
+            let _ = query
+                .push(" AND peer_address=")
+                .push_bind(peer_address.to_string());
+        }
+
+        if let Some(client_identity) = options.client_identity {
+            let _ = query
+                .push(" AND client_identity=")
+                .push_bind(client_identity);
+        }
+
         // Add filtering based on actions if the user specific event types.
         if !matches!(event_type, EventType::All) {
             // We use an IN operator to match multiple values based on the value of
@@ -278,6 +430,12 @@ impl PostgresDB {
             append_value_list(&mut query, actions)?;
         }
 
+        // Order by ID so that the last event of a page is the cursor for the next.
+        let _ = query.push(" ORDER BY audit_event_id");
+        if let Some(limit) = options.limit {
+            let _ = query.push(" LIMIT ").push_bind(i64::from(limit));
+        }
+
         debug!("Dynamically generated query: {}", query.sql());
 
         let matches: Vec<AuditEventDB> = query
@@ -291,25 +449,98 @@ impl PostgresDB {
         results
     }
 
-    #[instrument(skip_all, err(Debug), fields(account_id, key_id, secret_type))]
-    pub(crate) async fn add_secret_impl(&self, secret: StoredSecret) -> Result<(), PostgresError> {
-        logging::record_field("account_id", &secret.account_id);
-        logging::record_field("key_id", &secret.key_id);
-        logging::record_field("secret_type", &secret.secret_type);
-        debug!("Adding user secret.");
+    #[instrument(skip(self), err(Debug))]
+    pub(crate) async fn find_latest_audit_event_impl(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditEvent>, PostgresError> {
+        debug!("Finding latest audit event.");
+        latest_audit_event(&self.connection_pool, account_id).await
+    }
 
-        let secret_db: SecretDB = SecretDB::from(secret);
+    #[instrument(skip_all, err(Debug), fields(count=audit_event_ids.len()))]
+    pub(crate) async fn delete_audit_events_impl(
+        &self,
+        audit_event_ids: &[i64],
+    ) -> Result<u64, PostgresError> {
+        debug!("Deleting audit events.");
 
         let rows_affected = sqlx::query!(
-            "INSERT INTO Secrets (key_id, account_id, secret, secret_type_id, retrieved) \
-             SELECT $1, $2, $3, SecretTypes.secret_type_id, $4 \
-             FROM SecretTypes \
-             WHERE SecretTypes.secret_type=$5",
-            secret_db.key_id,
-            secret_db.account_id,
-            secret_db.secret,
-            secret_db.retrieved,
-            secret_db.secret_type,
+            "DELETE FROM AuditEvents WHERE audit_event_id = ANY($1)",
+            audit_event_ids
+        )
+        .execute(&self.connection_pool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    /// Events are inserted in batches, all within one transaction.
+    #[instrument(skip_all, err(Debug), fields(count=events.len()))]
+    pub(crate) async fn import_audit_events_impl(
+        &self,
+        events: &[AuditEvent],
+    ) -> Result<u64, PostgresError> {
+        debug!("Importing audit events.");
+
+        let events: Vec<AuditEventDB> = events
+            .iter()
+            .map(AuditEventDB::try_from)
+            .collect::<Result<_, _>>()?;
+
+        let mut transaction = self.connection_pool.begin().await?;
+        let mut rows_affected = 0;
+        // Postgres allows at most 65535 bind parameters per query.
+        for batch in events.chunks(IMPORT_BATCH_SIZE) {
+            let mut query = QueryBuilder::new(
+                "INSERT INTO AuditEvents (audit_event_id, account_id, key_id, request_id, client_action_id, event_status, timestamp, client_identity, peer_address, failure_code, payload_size, payload_hash, previous_hash) \
+                 OVERRIDING SYSTEM VALUE ",
+            );
+            let _ = query.push_values(batch, |mut row, event| {
+                let _ = row
+                    .push_bind(event.audit_event_id)
+                    .push_bind(event.account_id)
+                    .push_bind(event.key_id.as_deref())
+                    .push_bind(event.request_id)
+                    .push_bind(event.client_action_id)
+                    .push_bind(event.event_status.as_str())
+                    .push_bind(event.timestamp)
+                    .push_bind(event.client_identity.as_deref())
+                    .push_bind(event.peer_address.as_deref())
+                    .push_bind(event.failure_code.as_deref())
+                    .push_bind(event.payload_size)
+                    .push_bind(event.payload_hash.as_deref())
+                    .push_bind(event.previous_hash.as_deref());
+            });
+            let _ = query.push(" ON CONFLICT (audit_event_id) DO NOTHING");
+
+            rows_affected += query
+                .build()
+                .execute(&mut transaction)
+                .await?
+                .rows_affected();
+        }
+        transaction.commit().await?;
+
+        Ok(rows_affected)
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_id=?checkpoint.account_id, audit_event_id=checkpoint.audit_event_id))]
+    pub(crate) async fn create_audit_checkpoint_impl(
+        &self,
+        checkpoint: &AuditCheckpoint,
+    ) -> Result<(), PostgresError> {
+        debug!("Storing new audit checkpoint.");
+
+        let rows_affected = sqlx::query!(
+            "INSERT INTO AuditCheckpoints (account_id, audit_event_id, event_hash, timestamp, signature) \
+             VALUES ($1, $2, $3, $4, $5)",
+            checkpoint.account_id.0,
+            checkpoint.audit_event_id,
+            checkpoint.event_hash.as_bytes(),
+            checkpoint.timestamp,
+            checkpoint.signature.as_bytes(),
         )
         .execute(&self.connection_pool)
         .await?
@@ -325,8 +556,65 @@ impl PostgresDB {
         Ok(())
     }
 
+    #[instrument(skip(self), err(Debug))]
+    pub(crate) async fn find_audit_checkpoints_impl(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<AuditCheckpoint>, PostgresError> {
+        debug!("Finding audit checkpoints.");
+
+        let checkpoints = sqlx::query_as!(
+            AuditCheckpointDB,
+            "SELECT account_id, audit_event_id, event_hash, timestamp, signature \
+             FROM AuditCheckpoints \
+             WHERE account_id=$1 \
+             ORDER BY audit_event_id, checkpoint_id",
+            account_id.0
+        )
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        checkpoints
+            .into_iter()
+            .map(AuditCheckpoint::try_from)
+            .collect()
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    pub(crate) async fn find_latest_audit_checkpoint_impl(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditCheckpoint>, PostgresError> {
+        debug!("Finding latest audit checkpoint.");
+
+        let checkpoint = sqlx::query_as!(
+            AuditCheckpointDB,
+            "SELECT account_id, audit_event_id, event_hash, timestamp, signature \
+             FROM AuditCheckpoints \
+             WHERE account_id=$1 \
+             ORDER BY audit_event_id DESC, checkpoint_id DESC \
+             LIMIT 1",
+            account_id.0
+        )
+        .fetch_optional(&self.connection_pool)
+        .await?;
+
+        checkpoint.map(AuditCheckpoint::try_from).transpose()
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_id, key_id, secret_type))]
+    pub(crate) async fn add_secret_impl(&self, secret: StoredSecret) -> Result<(), PostgresError> {
+        logging::record_field("account_id", &secret.account_id);
+        logging::record_field("key_id", &secret.key_id);
+        logging::record_field("secret_type", &secret.secret_type);
+        debug!("Adding user secret.");
+
+        insert_secret(&self.connection_pool, secret).await
+    }
+
     /// This function verifies the user_id and key type matches. Otherwise will
-    /// return a IncorrectKeyMetadata error.
+    /// return a IncorrectKeyMetadata error if the key belongs to another
+    /// account, or a WrongSecretType error if the key type was wrong.
     #[instrument(skip_all, err(Debug), fields(account_id=?account_id, key_id=?key_id, filter=?filter))]
     pub(crate) async fn get_secret_impl(
         &self,
@@ -358,22 +646,22 @@ impl PostgresDB {
 
         match secret_db {
             None => {
-                // Entry not found. Check if the key exists but the user_id was wrong!
-                // The "count!" syntax allows us to tell sqlx that the result of this expression
-                // will not be null, and return type ((which is an anonymous record) of this
-                // `query!` should have a record field called "count".
-                let key_found = sqlx::query!(
-                    r#"SELECT COUNT(1) as "count!" FROM Secrets WHERE key_id=$1"#,
+                // Entry not found. Check if the key exists but the user_id or
+                // secret_type was wrong!
+                let owners = sqlx::query_scalar!(
+                    "SELECT account_id FROM Secrets WHERE key_id=$1",
                     key_id.as_bytes()
                 )
-                .fetch_one(&self.connection_pool)
+                .fetch_all(&self.connection_pool)
                 .await?;
 
-                match key_found.count {
+                match owners.as_slice() {
                     // The key doesn't even exist.
-                    0 => Err(PostgresError::NoEntry),
-                    // The key exists but the secret_type or user_id were incorrect.
-                    1 => Err(PostgresError::IncorrectKeyMetadata),
+                    [] => Err(PostgresError::NoEntry),
+                    // The key exists but the secret_type was incorrect.
+                    [owner] if *owner == account_id.0 => Err(PostgresError::WrongSecretType),
+                    // The key exists but the user_id was incorrect.
+                    [_] => Err(PostgresError::IncorrectKeyMetadata),
                     _ => Err(PostgresError::InvalidRowCountFound),
                 }
             }
@@ -421,21 +709,7 @@ impl PostgresDB {
     ) -> Result<(), PostgresError> {
         debug!("Deleting user secret.");
 
-        let rows_affected = sqlx::query!(
-            r#"DELETE FROM Secrets
-            WHERE account_id=$1 AND key_id=$2"#,
-            account_id.0,
-            key_id.as_bytes()
-        )
-        .execute(&self.connection_pool)
-        .await?
-        .rows_affected();
-
-        if rows_affected == 0 {
-            return Err(PostgresError::NoEntry);
-        }
-
-        Ok(())
+        remove_secret(&self.connection_pool, account_id, key_id).await
     }
 
     #[instrument(skip_all, err(Debug), fields(user_id=?user_id, account_name=?account_name))]
@@ -542,17 +816,7 @@ impl PostgresDB {
     ) -> Result<(), PostgresError> {
         info!("Setting storage key");
 
-        let storage_key = bincode::serialize(&storage_key)?;
-
-        let _ = sqlx::query!(
-            "UPDATE Accounts SET storage_key=$1 WHERE account_id=$2",
-            storage_key,
-            account_id.0,
-        )
-        .execute(&self.connection_pool)
-        .await?;
-
-        Ok(())
+        update_storage_key(&self.connection_pool, account_id, storage_key).await
     }
 
     #[instrument(skip_all, err(Debug), fields(user_id=?user_id))]
@@ -576,6 +840,281 @@ impl PostgresDB {
         };
         Ok(user_id_exists)
     }
+
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id, credential_id))]
+    pub(crate) async fn create_service_credential_impl(
+        &self,
+        account_id: AccountId,
+        public_key: &ServiceCredentialPublicKey,
+        allowed_actions: &[ClientAction],
+    ) -> Result<ServiceCredential, PostgresError> {
+        info!("Creating service credential.");
+
+        let serialized = bincode::serialize(public_key)?;
+        let action_ids: Vec<i64> = allowed_actions.iter().map(|a| *a as i64).collect();
+
+        let credential_id = sqlx::query!(
+            "INSERT INTO ServiceCredentials (account_id, public_key, allowed_actions) \
+             VALUES ($1, $2, $3) \
+             RETURNING credential_id",
+            account_id.0,
+            serialized,
+            &action_ids,
+        )
+        .fetch_one(&self.connection_pool)
+        .await?
+        .credential_id;
+
+        logging::record_field("credential_id", &credential_id);
+
+        Ok(ServiceCredential {
+            credential_id,
+            account_id,
+            public_key: public_key.clone(),
+            allowed_actions: allowed_actions.to_vec(),
+        })
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id, credential_id=?credential_id))]
+    pub(crate) async fn find_service_credential_impl(
+        &self,
+        account_id: AccountId,
+        credential_id: Uuid,
+    ) -> Result<Option<ServiceCredential>, PostgresError> {
+        debug!("Searching for service credential.");
+
+        let credential_db = sqlx::query_as!(
+            ServiceCredentialDB,
+            "SELECT credential_id, account_id, public_key, allowed_actions \
+            FROM ServiceCredentials \
+            WHERE credential_id=$1 AND account_id=$2",
+            credential_id,
+            account_id.0
+        )
+        .fetch_optional(&self.connection_pool)
+        .await?;
+
+        let credential = credential_db.map(ServiceCredential::try_from).transpose()?;
+        Ok(credential)
+    }
+
+    #[instrument(skip_all, err(Debug), fields(identity=?identity, account_id=?account_id))]
+    pub(crate) async fn create_certificate_binding_impl(
+        &self,
+        identity: &str,
+        account_id: AccountId,
+    ) -> Result<(), PostgresError> {
+        info!("Binding client certificate identity to account.");
+
+        let rows_affected = sqlx::query!(
+            "INSERT INTO CertificateBindings (identity, account_id) \
+             VALUES ($1, $2)",
+            identity,
+            account_id.0
+        )
+        .execute(&self.connection_pool)
+        .await?
+        .rows_affected();
+
+        // Only one row should ever be affected by our insert. Something has gone
+        // wrong...
+        if rows_affected != 1 {
+            error!("Unexpected number of rows affected: {}", rows_affected);
+            return Err(PostgresError::InvalidRowCountFound);
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip_all, err(Debug), fields(identity=?identity))]
+    pub(crate) async fn find_certificate_binding_impl(
+        &self,
+        identity: &str,
+    ) -> Result<Option<AccountId>, PostgresError> {
+        debug!("Searching for client certificate binding.");
+
+        let account_id = sqlx::query!(
+            "SELECT account_id FROM CertificateBindings WHERE identity=$1",
+            identity
+        )
+        .fetch_optional(&self.connection_pool)
+        .await?
+        .map(|row| AccountId::from(row.account_id));
+
+        Ok(account_id)
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id))]
+    pub(crate) async fn account_has_certificate_binding_impl(
+        &self,
+        account_id: AccountId,
+    ) -> Result<bool, PostgresError> {
+        debug!("Checking for client certificate bindings.");
+
+        let exists = sqlx::query!(
+            "SELECT EXISTS(SELECT 1 FROM CertificateBindings WHERE account_id=$1)",
+            account_id.0
+        )
+        .fetch_one(&self.connection_pool)
+        .await?
+        .exists;
+
+        Ok(exists.unwrap_or(false))
+    }
+}
+
+// The queries below modify data and can also be run as part of a
+// [`PostgresTransaction`](crate::PostgresTransaction), so they take an
+// executor or connection rather than using the connection pool directly.
+
+/// Audit events are hash-chained, so this locks the account row until the
+/// surrounding transaction ends. Concurrent events for the same account are
+/// stored one at a time and never link to the same predecessor.
+pub(crate) async fn insert_audit_event(
+    connection: &mut PgConnection,
+    request_id: Uuid,
+    account_id: AccountId,
+    key_id: &Option<KeyId>,
+    action: ClientAction,
+    status: EventStatus,
+    details: &AuditEventDetails,
+) -> Result<AuditEvent, PostgresError> {
+    let _ = sqlx::query!(
+        "SELECT account_id FROM Accounts WHERE account_id=$1 FOR NO KEY UPDATE",
+        account_id.0
+    )
+    .fetch_optional(&mut *connection)
+    .await?;
+
+    let previous_hash = latest_audit_event(&mut *connection, account_id)
+        .await?
+        .map(|event| event.hash().as_bytes().to_vec());
+    let timestamp = OffsetDateTime::now_utc();
+    let key_id = key_id.as_ref().map(|k| k.as_bytes());
+    let payload_size = details
+        .payload_size
+        .map(i64::try_from)
+        .transpose()
+        .map_err(|e| {
+            PostgresError::AuditEventConversion(format!("Payload size is too large: {e}"))
+        })?;
+
+    // The stored event is returned so that callers get its ID and the
+    // timestamp as stored.
+    let event = sqlx::query_as!(
+        AuditEventDB,
+        "INSERT INTO AuditEvents (account_id, key_id, request_id, client_action_id, event_status, timestamp, client_identity, peer_address, failure_code, payload_size, payload_hash, previous_hash) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) \
+         RETURNING audit_event_id, key_id, request_id, account_id, client_action_id, event_status, timestamp, client_identity, peer_address, failure_code, payload_size, payload_hash, previous_hash",
+        account_id.0,
+        key_id,
+        request_id,
+        action as i64,
+        status.to_string(),
+        timestamp,
+        details.client_identity,
+        details.peer_address.map(|address| address.to_string()),
+        details.failure_code.map(|code| code.to_string()),
+        payload_size,
+        details.payload_hash.as_ref().map(|hash| hash.as_bytes()),
+        previous_hash,
+    )
+    .fetch_one(connection)
+    .await?;
+
+    event.try_into()
+}
+
+async fn latest_audit_event(
+    executor: impl PgExecutor<'_>,
+    account_id: AccountId,
+) -> Result<Option<AuditEvent>, PostgresError> {
+    let event = sqlx::query_as!(
+        AuditEventDB,
+        "SELECT audit_event_id, key_id, request_id, account_id, client_action_id, event_status, timestamp, client_identity, peer_address, failure_code, payload_size, payload_hash, previous_hash \
+         FROM AuditEvents \
+         WHERE account_id=$1 \
+         ORDER BY audit_event_id DESC \
+         LIMIT 1",
+        account_id.0
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    event.map(AuditEvent::try_from).transpose()
+}
+
+pub(crate) async fn insert_secret(
+    executor: impl PgExecutor<'_>,
+    secret: StoredSecret,
+) -> Result<(), PostgresError> {
+    let secret_db: SecretDB = SecretDB::from(secret);
+
+    let rows_affected = sqlx::query!(
+        "INSERT INTO Secrets (key_id, account_id, secret, secret_type_id, retrieved) \
+         SELECT $1, $2, $3, SecretTypes.secret_type_id, $4 \
+         FROM SecretTypes \
+         WHERE SecretTypes.secret_type=$5",
+        secret_db.key_id,
+        secret_db.account_id,
+        secret_db.secret,
+        secret_db.retrieved,
+        secret_db.secret_type,
+    )
+    .execute(executor)
+    .await?
+    .rows_affected();
+
+    // Only one row should ever be affected by our insert. Something has gone
+    // wrong...
+    if rows_affected != 1 {
+        error!("Unexpected number of rows affected: {}", rows_affected);
+        return Err(PostgresError::InvalidRowCountFound);
+    }
+
+    Ok(())
+}
+
+/// Returns a NoEntry error if no secret matches both the account_id and
+/// key_id.
+pub(crate) async fn remove_secret(
+    executor: impl PgExecutor<'_>,
+    account_id: AccountId,
+    key_id: &KeyId,
+) -> Result<(), PostgresError> {
+    let rows_affected = sqlx::query!(
+        r#"DELETE FROM Secrets
+            WHERE account_id=$1 AND key_id=$2"#,
+        account_id.0,
+        key_id.as_bytes()
+    )
+    .execute(executor)
+    .await?
+    .rows_affected();
+
+    if rows_affected == 0 {
+        return Err(PostgresError::NoEntry);
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn update_storage_key(
+    executor: impl PgExecutor<'_>,
+    account_id: AccountId,
+    storage_key: Encrypted<StorageKey>,
+) -> Result<(), PostgresError> {
+    let storage_key = bincode::serialize(&storage_key)?;
+
+    let _ = sqlx::query!(
+        "UPDATE Accounts SET storage_key=$1 WHERE account_id=$2",
+        storage_key,
+        account_id.0,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
 }
 
 /// Create a SQL query list of the form (val1, val2, ...). Error is returned if
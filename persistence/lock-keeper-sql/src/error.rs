@@ -16,10 +16,14 @@ pub enum PostgresError {
     NoEntry,
     #[error("AuditEventDB to AuditEvent conversion failed: {0}")]
     AuditEventConversion(String),
+    #[error("ServiceCredentialDB to ServiceCredential conversion failed: {0}")]
+    ServiceCredentialConversion(String),
     #[error("Unexpected number of rows returned.")]
     InvalidRowCountFound,
-    #[error("Key ID exists but associated user ID or key type were incorrect.")]
+    #[error("Key ID exists but belongs to another account.")]
     IncorrectKeyMetadata,
+    #[error("Key ID exists but its secret type doesn't match the filter.")]
+    WrongSecretType,
     #[error("Empty iterator for append_value_list function.")]
     InvalidAuditEventOptions,
     #[error("Config file error.")]
@@ -45,6 +49,7 @@ impl From<PostgresError> for DatabaseError {
             PostgresError::NoEntry => Self::NoEntry,
             PostgresError::InvalidAuditEventOptions => Self::InvalidAuditEventOptions,
             PostgresError::IncorrectKeyMetadata => Self::IncorrectKeyMetadata,
+            PostgresError::WrongSecretType => Self::WrongSecretType,
             _ => Self::InternalDatabaseError(error.to_string()),
         }
     }
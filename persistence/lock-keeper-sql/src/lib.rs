@@ -40,8 +40,10 @@
 mod api;
 mod config;
 mod error;
+mod transaction;
 mod types;
 
 pub use api::PostgresDB;
 pub use config::{Config, ConfigFile};
 pub use error::PostgresError;
+pub use transaction::PostgresTransaction;
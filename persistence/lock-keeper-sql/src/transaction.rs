@@ -0,0 +1,102 @@
+//! Implementation of the `DataStoreTransaction` trait using a Postgres
+//! transaction.
+use crate::{
+    api::{insert_audit_event, insert_secret, remove_secret, update_storage_key},
+    PostgresError,
+};
+use async_trait::async_trait;
+use lock_keeper::{
+    crypto::{Encrypted, KeyId, StorageKey},
+    types::{
+        audit_event::{AuditEvent, AuditEventDetails, EventStatus},
+        database::{account::AccountId, secrets::StoredSecret},
+        operations::ClientAction,
+    },
+};
+use lock_keeper_key_server::server::database::{DataStoreTransaction, DatabaseError};
+use sqlx::{Postgres, Transaction};
+use tracing::{debug, info, instrument};
+use uuid::Uuid;
+
+/// A [`DataStoreTransaction`] for [`PostgresDB`](crate::PostgresDB).
+///
+/// sqlx rolls the transaction back if it is dropped without being committed.
+#[derive(Debug)]
+pub struct PostgresTransaction {
+    transaction: Transaction<'static, Postgres>,
+    /// Audit events created in the transaction, returned on commit.
+    audit_events: Vec<AuditEvent>,
+}
+
+impl PostgresTransaction {
+    pub(crate) fn new(transaction: Transaction<'static, Postgres>) -> Self {
+        Self {
+            transaction,
+            audit_events: Vec::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl DataStoreTransaction for PostgresTransaction {
+    #[instrument(skip(self))]
+    async fn create_audit_event(
+        &mut self,
+        request_id: Uuid,
+        account_id: AccountId,
+        key_id: &Option<KeyId>,
+        action: ClientAction,
+        status: EventStatus,
+        details: &AuditEventDetails,
+    ) -> Result<(), DatabaseError> {
+        debug!("Storing new audit event in transaction.");
+        let event = insert_audit_event(
+            &mut self.transaction,
+            request_id,
+            account_id,
+            key_id,
+            action,
+            status,
+            details,
+        )
+        .await?;
+        self.audit_events.push(event);
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(account_id=?secret.account_id, key_id=?secret.key_id))]
+    async fn add_secret(&mut self, secret: StoredSecret) -> Result<(), DatabaseError> {
+        debug!("Adding user secret in transaction.");
+        Ok(insert_secret(&mut self.transaction, secret).await?)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_secret(
+        &mut self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<(), DatabaseError> {
+        debug!("Deleting user secret in transaction.");
+        Ok(remove_secret(&mut self.transaction, account_id, key_id).await?)
+    }
+
+    #[instrument(skip(self, storage_key))]
+    async fn set_storage_key(
+        &mut self,
+        account_id: AccountId,
+        storage_key: Encrypted<StorageKey>,
+    ) -> Result<(), DatabaseError> {
+        info!("Setting storage key in transaction.");
+        Ok(update_storage_key(&mut self.transaction, account_id, storage_key).await?)
+    }
+
+    #[instrument(skip_all)]
+    async fn commit(self) -> Result<Vec<AuditEvent>, DatabaseError> {
+        debug!("Committing transaction.");
+        self.transaction
+            .commit()
+            .await
+            .map_err(PostgresError::from)?;
+        Ok(self.audit_events)
+    }
+}
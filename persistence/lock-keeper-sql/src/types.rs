@@ -1,11 +1,15 @@
 use crate::error::PostgresError;
 use lock_keeper::{
-    crypto::KeyId,
+    crypto::{AuditSignature, KeyId},
     types::{
-        audit_event::{AuditEvent, EventStatus},
+        audit_chain::AuditCheckpoint,
+        audit_event::{
+            AuditEvent, AuditEventDetails, AuditEventHash, EventStatus, FailureCode, PayloadHash,
+        },
         database::{
             account::{Account, AccountName, UserId},
             secrets::StoredSecret,
+            service_credential::ServiceCredential,
         },
         operations::ClientAction,
     },
@@ -51,6 +55,31 @@ pub(crate) struct AuditEventDB {
     pub(crate) client_action_id: i64,
     pub(crate) event_status: String,
     pub(crate) timestamp: OffsetDateTime,
+    pub(crate) client_identity: Option<String>,
+    pub(crate) peer_address: Option<String>,
+    pub(crate) failure_code: Option<String>,
+    pub(crate) payload_size: Option<i64>,
+    pub(crate) payload_hash: Option<Vec<u8>>,
+    pub(crate) previous_hash: Option<Vec<u8>>,
+}
+
+/// Mapping of our [AuditCheckpoint] type as it looks in the table. sqlx can
+/// use this to map selected rows to this rust type.
+pub(crate) struct AuditCheckpointDB {
+    pub(crate) account_id: i64,
+    pub(crate) audit_event_id: i64,
+    pub(crate) event_hash: Vec<u8>,
+    pub(crate) timestamp: OffsetDateTime,
+    pub(crate) signature: Vec<u8>,
+}
+
+/// Mapping of our [ServiceCredential] type as it looks in the table. sqlx can
+/// use this to map selected rows to this rust type.
+pub(crate) struct ServiceCredentialDB {
+    pub(crate) credential_id: Uuid,
+    pub(crate) account_id: i64,
+    pub(crate) public_key: Vec<u8>,
+    pub(crate) allowed_actions: Vec<i64>,
 }
 
 impl TryFrom<SecretDB> for StoredSecret {
@@ -121,6 +150,41 @@ impl TryFrom<AuditEventDB> for AuditEvent {
         let status = EventStatus::from_str(&event.event_status).map_err(|e| {
             PostgresError::AuditEventConversion(format!("EventStatus conversion failed {e}"))
         })?;
+        let peer_address = event
+            .peer_address
+            .map(|address| address.parse())
+            .transpose()
+            .map_err(|e| {
+                PostgresError::AuditEventConversion(format!("Peer address conversion failed: {e}"))
+            })?;
+        let failure_code = event
+            .failure_code
+            .map(|code| FailureCode::from_str(&code))
+            .transpose()
+            .map_err(|e| {
+                PostgresError::AuditEventConversion(format!("FailureCode conversion failed {e}"))
+            })?;
+        let payload_size = event
+            .payload_size
+            .map(u64::try_from)
+            .transpose()
+            .map_err(|e| {
+                PostgresError::AuditEventConversion(format!("Payload size conversion failed: {e}"))
+            })?;
+        let payload_hash = event
+            .payload_hash
+            .map(|hash| PayloadHash::try_from(hash.as_slice()))
+            .transpose()
+            .map_err(|e| {
+                PostgresError::AuditEventConversion(format!("Payload hash conversion failed: {e}"))
+            })?;
+        let previous_hash = event
+            .previous_hash
+            .map(|hash| AuditEventHash::try_from(hash.as_slice()))
+            .transpose()
+            .map_err(|e| {
+                PostgresError::AuditEventConversion(format!("Previous hash conversion failed: {e}"))
+            })?;
 
         let event = AuditEvent {
             audit_event_id: event.audit_event_id,
@@ -130,8 +194,97 @@ impl TryFrom<AuditEventDB> for AuditEvent {
             timestamp: event.timestamp,
             client_action,
             status,
+            details: AuditEventDetails {
+                client_identity: event.client_identity,
+                peer_address,
+                failure_code,
+                payload_size,
+                payload_hash,
+            },
+            previous_hash,
         };
 
         Ok(event)
     }
 }
+
+/// Used to import archived audit events with all of their original fields.
+impl TryFrom<&AuditEvent> for AuditEventDB {
+    type Error = PostgresError;
+
+    fn try_from(event: &AuditEvent) -> Result<Self, Self::Error> {
+        let payload_size = event
+            .details
+            .payload_size
+            .map(i64::try_from)
+            .transpose()
+            .map_err(|e| {
+                PostgresError::AuditEventConversion(format!("Payload size is too large: {e}"))
+            })?;
+
+        Ok(AuditEventDB {
+            audit_event_id: event.audit_event_id,
+            account_id: event.account_id.into(),
+            key_id: event.key_id.as_ref().map(|k| k.as_bytes().to_vec()),
+            request_id: event.request_id,
+            client_action_id: event.client_action as i64,
+            event_status: event.status.to_string(),
+            timestamp: event.timestamp,
+            client_identity: event.details.client_identity.clone(),
+            peer_address: event
+                .details
+                .peer_address
+                .map(|address| address.to_string()),
+            failure_code: event.details.failure_code.map(|code| code.to_string()),
+            payload_size,
+            payload_hash: event
+                .details
+                .payload_hash
+                .as_ref()
+                .map(|hash| hash.as_bytes().to_vec()),
+            previous_hash: event
+                .previous_hash
+                .as_ref()
+                .map(|hash| hash.as_bytes().to_vec()),
+        })
+    }
+}
+
+impl TryFrom<AuditCheckpointDB> for AuditCheckpoint {
+    type Error = PostgresError;
+
+    fn try_from(checkpoint: AuditCheckpointDB) -> Result<Self, Self::Error> {
+        Ok(AuditCheckpoint {
+            account_id: checkpoint.account_id.into(),
+            audit_event_id: checkpoint.audit_event_id,
+            event_hash: checkpoint.event_hash.as_slice().try_into()?,
+            timestamp: checkpoint.timestamp,
+            signature: AuditSignature::new(checkpoint.signature),
+        })
+    }
+}
+
+impl TryFrom<ServiceCredentialDB> for ServiceCredential {
+    type Error = PostgresError;
+
+    fn try_from(credential: ServiceCredentialDB) -> Result<Self, Self::Error> {
+        let allowed_actions = credential
+            .allowed_actions
+            .into_iter()
+            .map(|action| {
+                ClientAction::try_from(action).map_err(|i| {
+                    PostgresError::ServiceCredentialConversion(format!(
+                        "ClientAction conversion failed. Unknown integer {i}"
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ServiceCredential {
+            credential_id: credential.credential_id,
+            account_id: credential.account_id.into(),
+            public_key: bincode::deserialize(&credential.public_key)?,
+            allowed_actions,
+        })
+    }
+}
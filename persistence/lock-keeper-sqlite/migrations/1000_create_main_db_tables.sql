@@ -0,0 +1,78 @@
+-- SQLite equivalent of `persistence/migrations/1000_create_main_db_tables.sql`
+
+-- Maps client actions to unique ID
+CREATE TABLE IF NOT EXISTS ClientActionsTypes (
+    client_action_id INTEGER PRIMARY KEY NOT NULL,
+    client_action TEXT UNIQUE NOT NULL
+);
+
+-- These can be found in lock-keeper/src/types/operations.rs
+INSERT OR IGNORE INTO ClientActionsTypes (client_action_id, client_action)
+VALUES
+    (0, 'Authenticate'),
+    (1, 'CreateStorageKey'),
+    (2, 'ExportSecret'),
+    (3, 'ExportSigningKey'),
+    (4, 'GenerateSecret'),
+    (5, 'GetUserId'),
+    (6, 'ImportSigningKey'),
+    (7, 'Logout'),
+    (8, 'Register'),
+    (9, 'RemoteGenerateSigningKey'),
+    (10, 'RemoteSignBytes'),
+    (11, 'RetrieveSecret'),
+    (12, 'RetrieveAuditEvents'),
+    (13, 'RetrieveSigningKey'),
+    (14, 'RetrieveStorageKey'),
+    (15, 'RetrieveServerEncryptedBlob'),
+    (16, 'StoreSererEncryptedBlob'),
+    (17, 'CheckSession');
+
+-- Maps secret types to unique ID
+CREATE TABLE IF NOT EXISTS SecretTypes (
+    secret_type_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    secret_type TEXT UNIQUE NOT NULL
+);
+
+-- These can be found in /lock-keeper/src/types/database/secrets.rs
+INSERT OR IGNORE INTO SecretTypes (secret_type)
+VALUES
+    ('arbitrary_secret'),
+    ('remote_signing_key'),
+    ('signing_key_pair'),
+    ('server_encrypted_blob');
+
+CREATE TABLE IF NOT EXISTS Accounts
+(
+    account_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id BLOB UNIQUE NOT NULL,
+    account_name TEXT UNIQUE NOT NULL,
+    storage_key BLOB,
+    server_registration BLOB NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS Secrets
+(
+    secret_id INTEGER PRIMARY KEY AUTOINCREMENT,
+
+    key_id BLOB UNIQUE NOT NULL,
+    account_id INTEGER NOT NULL,
+    secret BLOB NOT NULL,
+    secret_type_id INTEGER NOT NULL,
+    retrieved BOOLEAN NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES Accounts(account_id)
+);
+
+CREATE TABLE IF NOT EXISTS AuditEvents
+(
+    audit_event_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    key_id BLOB,
+    request_id BLOB NOT NULL,
+    client_action_id INTEGER NOT NULL,
+    event_status TEXT NOT NULL,
+    -- Unix timestamp in nanoseconds. Stored as an integer so that date filters
+    -- compare correctly.
+    timestamp INTEGER NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES Accounts(account_id)
+);
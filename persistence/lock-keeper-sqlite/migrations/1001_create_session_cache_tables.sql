@@ -0,0 +1,11 @@
+-- SQLite equivalent of `persistence/migrations/1001_create_session_cache_tables.sql`
+
+CREATE TABLE IF NOT EXISTS Session
+(
+    session_id BLOB NOT NULL,
+    account_id INTEGER NOT NULL,
+    -- Unix timestamp in nanoseconds
+    timestamp INTEGER NOT NULL,
+    session_key BLOB NOT NULL,
+    PRIMARY KEY (session_id)
+);
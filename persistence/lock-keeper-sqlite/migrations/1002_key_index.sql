@@ -0,0 +1,2 @@
+CREATE INDEX IF NOT EXISTS idx_secrets_key_account
+    ON Secrets (key_id ASC, account_id ASC);
@@ -0,0 +1,21 @@
+-- These can be found in lock-keeper/src/types/operations.rs
+INSERT OR IGNORE INTO ClientActionsTypes (client_action_id, client_action)
+VALUES
+    (18, 'DeleteKey'),
+    (19, 'RegisterServiceCredential'),
+    (20, 'AuthenticateServiceAccount');
+
+-- Public keys that service accounts use to log in without a password
+CREATE TABLE IF NOT EXISTS ServiceCredentials
+(
+    credential_id BLOB NOT NULL,
+    account_id INTEGER NOT NULL,
+    public_key BLOB NOT NULL,
+    -- bincode-encoded list of IDs from ClientActionsTypes
+    allowed_actions BLOB NOT NULL,
+    PRIMARY KEY (credential_id),
+    FOREIGN KEY (account_id) REFERENCES Accounts(account_id) ON DELETE CASCADE
+);
+
+-- Sessions created with a service credential are limited to its allowed actions
+ALTER TABLE Session ADD COLUMN service_credential_id BLOB;
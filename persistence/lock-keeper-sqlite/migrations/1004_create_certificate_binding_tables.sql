@@ -0,0 +1,14 @@
+-- Maps identities from mutual TLS client certificates to the account allowed to use them
+CREATE TABLE IF NOT EXISTS CertificateBindings
+(
+    -- Certificate subject or subject alternative name, e.g. `dns:client.example.com`
+    identity TEXT NOT NULL,
+    account_id INTEGER NOT NULL,
+    PRIMARY KEY (identity),
+    FOREIGN KEY (account_id) REFERENCES Accounts(account_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS certificate_bindings_account_id ON CertificateBindings (account_id);
+
+-- Client certificate identity that was matched for the request, if any
+ALTER TABLE AuditEvents ADD COLUMN client_identity TEXT;
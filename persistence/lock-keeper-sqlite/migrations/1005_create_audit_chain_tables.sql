@@ -0,0 +1,21 @@
+-- Hash of the previous audit event for the same account. NULL for the first event of an account and for
+-- events stored before audit events were hash-chained.
+ALTER TABLE AuditEvents ADD COLUMN previous_hash BLOB;
+
+CREATE INDEX IF NOT EXISTS audit_events_account_id ON AuditEvents (account_id, audit_event_id);
+
+-- Audit event hashes signed by the key server's audit key
+CREATE TABLE IF NOT EXISTS AuditCheckpoints
+(
+    checkpoint_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    -- Not a foreign key, so that a checkpoint still detects the removal of the event it covers
+    audit_event_id INTEGER NOT NULL,
+    event_hash BLOB NOT NULL,
+    -- Nanoseconds since the Unix epoch
+    timestamp INTEGER NOT NULL,
+    signature BLOB NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES Accounts(account_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS audit_checkpoints_account_id ON AuditCheckpoints (account_id, audit_event_id);
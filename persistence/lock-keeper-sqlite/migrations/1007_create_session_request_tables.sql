@@ -0,0 +1,11 @@
+-- SQLite equivalent of `persistence/migrations/1007_create_session_request_tables.sql`
+
+CREATE TABLE IF NOT EXISTS SessionRequests
+(
+    session_id BLOB NOT NULL,
+    request_id BLOB NOT NULL,
+    -- ID from ClientActionsTypes
+    client_action_id INTEGER NOT NULL,
+    PRIMARY KEY (session_id, request_id, client_action_id),
+    FOREIGN KEY (session_id) REFERENCES Session(session_id) ON DELETE CASCADE
+);
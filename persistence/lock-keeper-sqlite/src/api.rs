@@ -0,0 +1,1260 @@
+//! Actual implementation of the `DataStore` trait for our SQLite type.
+//! SQL queries are found here.
+use crate::{
+    transaction::WriteTransaction,
+    types::{
+        timestamp_to_db, AccountDB, AuditCheckpointDB, AuditEventDB, SecretDB, ServiceCredentialDB,
+    },
+    Config, SqliteError, SqliteSessionCache, SqliteTransaction,
+};
+use async_trait::async_trait;
+use lock_keeper::{
+    config::opaque::OpaqueCipherSuite,
+    crypto::{Encrypted, KeyId, ServiceCredentialPublicKey, StorageKey},
+    infrastructure::logging,
+    types::{
+        audit_chain::AuditCheckpoint,
+        audit_event::{AuditEvent, AuditEventDetails, AuditEventOptions, EventStatus, EventType},
+        database::{
+            account::{Account, AccountId, AccountName, UserId},
+            secrets::{secret_types::SERVER_ENCRYPTED_BLOB, StoredSecret},
+            service_credential::ServiceCredential,
+        },
+        operations::ClientAction,
+    },
+};
+use lock_keeper_key_server::server::database::{DataStore, DatabaseError, SecretFilter};
+use opaque_ke::ServerRegistration;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+    Encode, QueryBuilder, Sqlite, SqliteConnection, SqliteExecutor, SqlitePool, Type,
+};
+use std::{
+    fmt::{Debug, Formatter},
+    sync::Arc,
+};
+use time::OffsetDateTime;
+use tracing::{debug, error, info, instrument};
+use uuid::Uuid;
+
+/// Number of rows written per query by bulk audit event operations. This keeps
+/// queries below SQLite's limit on bind parameters.
+const BATCH_SIZE: usize = 1000;
+
+#[derive(Clone)]
+pub struct SqliteDB {
+    config: Arc<Config>,
+    /// SqlitePool is already implemented in terms of an Arc. No need to wrap
+    /// it.
+    connection_pool: SqlitePool,
+}
+
+/// Trait implementation just calls out to the equivalent methods implemented directly on [`SqliteDB`].
+#[async_trait]
+impl DataStore for SqliteDB {
+    type Transaction = SqliteTransaction;
+
+    async fn begin_transaction(&self) -> Result<SqliteTransaction, DatabaseError> {
+        Ok(self.begin_transaction_impl().await?)
+    }
+
+    async fn health_check(&self) -> Result<(), DatabaseError> {
+        Ok(self.health_check_impl().await?)
+    }
+
+    async fn create_audit_event(
+        &self,
+        request_id: Uuid,
+        account_id: AccountId,
+        key_id: &Option<KeyId>,
+        action: ClientAction,
+        status: EventStatus,
+        details: &AuditEventDetails,
+    ) -> Result<AuditEvent, DatabaseError> {
+        Ok(self
+            .create_audit_event_impl(request_id, account_id, key_id, action, status, details)
+            .await?)
+    }
+
+    async fn find_audit_events(
+        &self,
+        account_id: AccountId,
+        event_type: EventType,
+        options: AuditEventOptions,
+    ) -> Result<Vec<AuditEvent>, DatabaseError> {
+        Ok(self
+            .find_audit_events_impl(Some(account_id), event_type, options)
+            .await?)
+    }
+
+    async fn find_all_audit_events(
+        &self,
+        event_type: EventType,
+        options: AuditEventOptions,
+    ) -> Result<Vec<AuditEvent>, DatabaseError> {
+        Ok(self
+            .find_audit_events_impl(None, event_type, options)
+            .await?)
+    }
+
+    async fn find_latest_audit_event(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditEvent>, DatabaseError> {
+        Ok(self.find_latest_audit_event_impl(account_id).await?)
+    }
+
+    async fn delete_audit_events(&self, audit_event_ids: &[i64]) -> Result<u64, DatabaseError> {
+        Ok(self.delete_audit_events_impl(audit_event_ids).await?)
+    }
+
+    async fn import_audit_events(&self, events: &[AuditEvent]) -> Result<u64, DatabaseError> {
+        Ok(self.import_audit_events_impl(events).await?)
+    }
+
+    async fn create_audit_checkpoint(
+        &self,
+        checkpoint: &AuditCheckpoint,
+    ) -> Result<(), DatabaseError> {
+        Ok(self.create_audit_checkpoint_impl(checkpoint).await?)
+    }
+
+    async fn find_audit_checkpoints(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<AuditCheckpoint>, DatabaseError> {
+        Ok(self.find_audit_checkpoints_impl(account_id).await?)
+    }
+
+    async fn find_latest_audit_checkpoint(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditCheckpoint>, DatabaseError> {
+        Ok(self.find_latest_audit_checkpoint_impl(account_id).await?)
+    }
+
+    async fn add_secret(&self, secret: StoredSecret) -> Result<(), DatabaseError> {
+        Ok(self.add_secret_impl(secret).await?)
+    }
+
+    async fn get_secret(
+        &self,
+        account_id: AccountId,
+        key_id: &KeyId,
+        filter: SecretFilter,
+    ) -> Result<StoredSecret, DatabaseError> {
+        Ok(self.get_secret_impl(account_id, key_id, filter).await?)
+    }
+
+    async fn get_server_encrypted_blob(
+        &self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<StoredSecret, DatabaseError> {
+        Ok(self
+            .get_server_encrypted_blob_impl(account_id, key_id)
+            .await?)
+    }
+
+    async fn delete_secret(
+        &self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<(), DatabaseError> {
+        Ok(self.delete_secret_impl(account_id, key_id).await?)
+    }
+
+    async fn create_account(
+        &self,
+        user_id: &UserId,
+        account_name: &AccountName,
+        server_registration: &ServerRegistration<OpaqueCipherSuite>,
+    ) -> Result<Account, DatabaseError> {
+        Ok(self
+            .create_account_impl(user_id, account_name, server_registration)
+            .await?)
+    }
+
+    async fn find_account_by_name(
+        &self,
+        account_name: &AccountName,
+    ) -> Result<Option<Account>, DatabaseError> {
+        Ok(self.find_account_by_name_impl(account_name).await?)
+    }
+
+    async fn find_account(&self, account_id: AccountId) -> Result<Option<Account>, DatabaseError> {
+        Ok(self.find_account_impl(account_id).await?)
+    }
+
+    async fn delete_account(&self, account_id: AccountId) -> Result<(), DatabaseError> {
+        Ok(self.delete_account_impl(account_id).await?)
+    }
+
+    async fn set_storage_key(
+        &self,
+        account_id: AccountId,
+        storage_key: Encrypted<StorageKey>,
+    ) -> Result<(), DatabaseError> {
+        Ok(self.set_storage_key_impl(account_id, storage_key).await?)
+    }
+
+    async fn user_id_exists(&self, user_id: &UserId) -> Result<bool, DatabaseError> {
+        Ok(self.user_id_exists_impl(user_id).await?)
+    }
+
+    async fn create_service_credential(
+        &self,
+        account_id: AccountId,
+        public_key: &ServiceCredentialPublicKey,
+        allowed_actions: &[ClientAction],
+    ) -> Result<ServiceCredential, DatabaseError> {
+        Ok(self
+            .create_service_credential_impl(account_id, public_key, allowed_actions)
+            .await?)
+    }
+
+    async fn find_service_credential(
+        &self,
+        account_id: AccountId,
+        credential_id: Uuid,
+    ) -> Result<Option<ServiceCredential>, DatabaseError> {
+        Ok(self
+            .find_service_credential_impl(account_id, credential_id)
+            .await?)
+    }
+
+    async fn create_certificate_binding(
+        &self,
+        identity: &str,
+        account_id: AccountId,
+    ) -> Result<(), DatabaseError> {
+        Ok(self
+            .create_certificate_binding_impl(identity, account_id)
+            .await?)
+    }
+
+    async fn find_certificate_binding(
+        &self,
+        identity: &str,
+    ) -> Result<Option<AccountId>, DatabaseError> {
+        Ok(self.find_certificate_binding_impl(identity).await?)
+    }
+
+    async fn account_has_certificate_binding(
+        &self,
+        account_id: AccountId,
+    ) -> Result<bool, DatabaseError> {
+        Ok(self
+            .account_has_certificate_binding_impl(account_id)
+            .await?)
+    }
+}
+
+impl Debug for SqliteDB {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SqliteDB")
+            .field("path", &self.config.path)
+            .finish()
+    }
+}
+
+impl SqliteDB {
+    /// Open the database file, creating it if it doesn't exist, and run any
+    /// pending migrations.
+    #[instrument(err(Debug))]
+    pub async fn connect(config: Config) -> Result<Self, SqliteError> {
+        info!("Connecting to database");
+
+        let options = SqliteConnectOptions::new()
+            .filename(&config.path)
+            .create_if_missing(true)
+            .foreign_keys(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(config.connection_timeout);
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(config.max_connections)
+            .acquire_timeout(config.connection_timeout)
+            .connect_with(options)
+            .await?;
+
+        info!("Running database migrations");
+        sqlx::migrate!("./migrations").run(&pool).await?;
+
+        Ok(SqliteDB {
+            config: Arc::new(config),
+            connection_pool: pool,
+        })
+    }
+
+    /// Path to the database file.
+    pub fn path(&self) -> &std::path::Path {
+        &self.config.path
+    }
+
+    /// Create a [`SqliteSessionCache`] that stores sessions in this database.
+    pub fn session_cache(&self) -> SqliteSessionCache {
+        SqliteSessionCache::new(self.connection_pool.clone(), self.config.session_expiration)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    pub(crate) async fn begin_transaction_impl(&self) -> Result<SqliteTransaction, SqliteError> {
+        debug!("Beginning transaction.");
+        let transaction = WriteTransaction::begin(&self.connection_pool).await?;
+        Ok(SqliteTransaction::new(transaction))
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    pub(crate) async fn health_check_impl(&self) -> Result<(), SqliteError> {
+        let _ = sqlx::query("SELECT 1")
+            .execute(&self.connection_pool)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    pub(crate) async fn create_audit_event_impl(
+        &self,
+        request_id: Uuid,
+        account_id: AccountId,
+        key_id: &Option<KeyId>,
+        action: ClientAction,
+        status: EventStatus,
+        details: &AuditEventDetails,
+    ) -> Result<AuditEvent, SqliteError> {
+        debug!("Storing new audit event.");
+
+        let mut transaction = WriteTransaction::begin(&self.connection_pool).await?;
+        let event = insert_audit_event(
+            &mut transaction,
+            request_id,
+            account_id,
+            key_id,
+            action,
+            status,
+            details,
+        )
+        .await?;
+        transaction.commit().await?;
+
+        Ok(event)
+    }
+
+    /// Create a dynamic query to fetch audit events specified by the caller.
+    /// Events of every account are searched if `account_id` is `None`.
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id, event_type=?event_type, options=?options))]
+    async fn find_audit_events_impl(
+        &self,
+        account_id: Option<AccountId>,
+        event_type: EventType,
+        options: AuditEventOptions,
+    ) -> Result<Vec<AuditEvent>, SqliteError> {
+        debug!("Finding audit event(s)");
+
+        let mut query = QueryBuilder::new(
+            "SELECT audit_event_id, key_id, request_id, account_id, client_action_id, event_status, timestamp, client_identity, peer_address, failure_code, payload_size, payload_hash, previous_hash \
+             FROM AuditEvents \
+             WHERE ",
+        );
+
+        // Ensure account name matches, otherwise a client could fetch anyone's audit
+        // events if they guess the request_id.
+        match account_id {
+            Some(account_id) => {
+                let _ = query.push(" account_id=").push_bind(account_id.0);
+            }
+            // Only administrative exports search every account.
+            None => {
+                let _ = query.push(" TRUE");
+            }
+        }
+
+        // Add the pagination cursor if present.
+        if let Some(after_audit_event_id) = options.after_audit_event_id {
+            let _ = query
+                .push(" AND audit_event_id > ")
+                .push_bind(after_audit_event_id);
+        }
+
+        // Add filtering based on after_date if present.
+        if let Some(after_date) = options.after_date {
+            let _ = query
+                .push(" AND timestamp >= ")
+                .push_bind(timestamp_to_db(after_date)?);
+        }
+
+        // Add filtering based on before_date if present.
+        if let Some(before_date) = options.before_date {
+            let _ = query
+                .push(" AND timestamp <= ")
+                .push_bind(timestamp_to_db(before_date)?);
+        }
+
+        // Add filtering based on key_ids if present.
+        if !options.key_ids.is_empty() {
+            let _ = query.push(" AND key_id IN ");
+            let key_id_bytes = options.key_ids.iter().map(|k| k.as_bytes().to_vec());
+            append_value_list(&mut query, key_id_bytes)?;
+        }
+
+        if let Some(request_id) = options.request_id {
+            let _ = query.push(" AND request_id=").push_bind(request_id);
+        }
+
+        if let Some(failure_code) = options.failure_code {
+            let _ = query
+                .push(" AND failure_code=")
+                .push_bind(failure_code.to_string());
+        }
+
+        if let Some(peer_address) = options.peer_address {
+            let _ = query
+                .push(" AND peer_address=")
+                .push_bind(peer_address.to_string());
+        }
+
+        if let Some(client_identity) = options.client_identity {
+            let _ = query
+                .push(" AND client_identity=")
+                .push_bind(client_identity);
+        }
+
+        // Add filtering based on actions if the user specific event types.
+        if !matches!(event_type, EventType::All) {
+            // We use an IN operator to match multiple values based on the value of
+            // `event_type`.
+            let _ = query.push(" AND client_action_id IN ");
+            // Turn the actions into their integer value for faster searching.
+            let actions = event_type.client_actions();
+            let actions = actions.iter().map(|a| *a as i64);
+
+            append_value_list(&mut query, actions)?;
+        }
+
+        // Order by ID so that the last event of a page is the cursor for the next.
+        let _ = query.push(" ORDER BY audit_event_id");
+        if let Some(limit) = options.limit {
+            let _ = query.push(" LIMIT ").push_bind(i64::from(limit));
+        }
+
+        debug!("Dynamically generated query: {}", query.sql());
+
+        let matches: Vec<AuditEventDB> = query
+            .build_query_as::<AuditEventDB>()
+            .fetch_all(&self.connection_pool)
+            .await?;
+        debug!("Matching entries from query: {}", matches.len());
+
+        // Iterator will stop and the first error is returned if our conversion fails.
+        let results: Result<Vec<_>, _> = matches.into_iter().map(AuditEvent::try_from).collect();
+        results
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    pub(crate) async fn find_latest_audit_event_impl(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditEvent>, SqliteError> {
+        debug!("Finding latest audit event.");
+        audit_event_before(&self.connection_pool, account_id, i64::MAX).await
+    }
+
+    /// IDs are deleted in batches, all within one transaction.
+    #[instrument(skip_all, err(Debug), fields(count=audit_event_ids.len()))]
+    pub(crate) async fn delete_audit_events_impl(
+        &self,
+        audit_event_ids: &[i64],
+    ) -> Result<u64, SqliteError> {
+        debug!("Deleting audit events.");
+
+        let mut transaction = WriteTransaction::begin(&self.connection_pool).await?;
+        let mut rows_affected = 0;
+        for batch in audit_event_ids.chunks(BATCH_SIZE) {
+            let mut query = QueryBuilder::new("DELETE FROM AuditEvents WHERE audit_event_id IN ");
+            append_value_list(&mut query, batch.iter().copied())?;
+
+            rows_affected += query
+                .build()
+                .execute(&mut *transaction)
+                .await?
+                .rows_affected();
+        }
+        transaction.commit().await?;
+
+        Ok(rows_affected)
+    }
+
+    /// Events are inserted in batches, all within one transaction.
+    #[instrument(skip_all, err(Debug), fields(count=events.len()))]
+    pub(crate) async fn import_audit_events_impl(
+        &self,
+        events: &[AuditEvent],
+    ) -> Result<u64, SqliteError> {
+        debug!("Importing audit events.");
+
+        let events: Vec<AuditEventDB> = events
+            .iter()
+            .map(AuditEventDB::try_from)
+            .collect::<Result<_, _>>()?;
+
+        let mut transaction = WriteTransaction::begin(&self.connection_pool).await?;
+        let mut rows_affected = 0;
+        for batch in events.chunks(BATCH_SIZE) {
+            let mut query = QueryBuilder::new(
+                "INSERT OR IGNORE INTO AuditEvents (audit_event_id, account_id, key_id, request_id, client_action_id, event_status, timestamp, client_identity, peer_address, failure_code, payload_size, payload_hash, previous_hash) ",
+            );
+            let _ = query.push_values(batch, |mut row, event| {
+                let _ = row
+                    .push_bind(event.audit_event_id)
+                    .push_bind(event.account_id)
+                    .push_bind(event.key_id.as_deref())
+                    .push_bind(event.request_id)
+                    .push_bind(event.client_action_id)
+                    .push_bind(event.event_status.as_str())
+                    .push_bind(event.timestamp)
+                    .push_bind(event.client_identity.as_deref())
+                    .push_bind(event.peer_address.as_deref())
+                    .push_bind(event.failure_code.as_deref())
+                    .push_bind(event.payload_size)
+                    .push_bind(event.payload_hash.as_deref())
+                    .push_bind(event.previous_hash.as_deref());
+            });
+
+            rows_affected += query
+                .build()
+                .execute(&mut *transaction)
+                .await?
+                .rows_affected();
+        }
+        transaction.commit().await?;
+
+        Ok(rows_affected)
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_id=?checkpoint.account_id, audit_event_id=checkpoint.audit_event_id))]
+    pub(crate) async fn create_audit_checkpoint_impl(
+        &self,
+        checkpoint: &AuditCheckpoint,
+    ) -> Result<(), SqliteError> {
+        debug!("Storing new audit checkpoint.");
+
+        let rows_affected = sqlx::query(
+            "INSERT INTO AuditCheckpoints (account_id, audit_event_id, event_hash, timestamp, signature) \
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(checkpoint.account_id.0)
+        .bind(checkpoint.audit_event_id)
+        .bind(checkpoint.event_hash.as_bytes())
+        .bind(timestamp_to_db(checkpoint.timestamp)?)
+        .bind(checkpoint.signature.as_bytes())
+        .execute(&self.connection_pool)
+        .await?
+        .rows_affected();
+
+        // Only one row should ever be affected by our insert. Something has gone
+        // wrong...
+        if rows_affected != 1 {
+            error!("Unexpected number of rows affected: {}", rows_affected);
+            return Err(SqliteError::InvalidRowCountFound);
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    pub(crate) async fn find_audit_checkpoints_impl(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Vec<AuditCheckpoint>, SqliteError> {
+        debug!("Finding audit checkpoints.");
+
+        let checkpoints: Vec<AuditCheckpointDB> = sqlx::query_as(
+            "SELECT account_id, audit_event_id, event_hash, timestamp, signature \
+             FROM AuditCheckpoints \
+             WHERE account_id=? \
+             ORDER BY audit_event_id, checkpoint_id",
+        )
+        .bind(account_id.0)
+        .fetch_all(&self.connection_pool)
+        .await?;
+
+        checkpoints
+            .into_iter()
+            .map(AuditCheckpoint::try_from)
+            .collect()
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    pub(crate) async fn find_latest_audit_checkpoint_impl(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<AuditCheckpoint>, SqliteError> {
+        debug!("Finding latest audit checkpoint.");
+
+        let checkpoint: Option<AuditCheckpointDB> = sqlx::query_as(
+            "SELECT account_id, audit_event_id, event_hash, timestamp, signature \
+             FROM AuditCheckpoints \
+             WHERE account_id=? \
+             ORDER BY audit_event_id DESC, checkpoint_id DESC \
+             LIMIT 1",
+        )
+        .bind(account_id.0)
+        .fetch_optional(&self.connection_pool)
+        .await?;
+
+        checkpoint.map(AuditCheckpoint::try_from).transpose()
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_id, key_id, secret_type))]
+    pub(crate) async fn add_secret_impl(&self, secret: StoredSecret) -> Result<(), SqliteError> {
+        logging::record_field("account_id", &secret.account_id);
+        logging::record_field("key_id", &secret.key_id);
+        logging::record_field("secret_type", &secret.secret_type);
+        debug!("Adding user secret.");
+
+        insert_secret(&self.connection_pool, secret).await
+    }
+
+    /// This function verifies the user_id and key type matches. Otherwise will
+    /// return a IncorrectKeyMetadata error if the key belongs to another
+    /// account, or a WrongSecretType error if the key type was wrong.
+    ///
+    /// The returned secret has the `retrieved` value from before this call.
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id, key_id=?key_id, filter=?filter))]
+    pub(crate) async fn get_secret_impl(
+        &self,
+        account_id: AccountId,
+        key_id: &KeyId,
+        filter: SecretFilter,
+    ) -> Result<StoredSecret, SqliteError> {
+        debug!("Fetching user secret.");
+
+        // SQLite can't return the old value from an UPDATE, so the read and the
+        // update of Secrets.retrieved happen in one transaction.
+        let mut transaction = WriteTransaction::begin(&self.connection_pool).await?;
+
+        // Join tables to map secret_type to the corresponding secret_type_id.
+        let secret_db: Option<SecretDB> = sqlx::query_as(
+            "SELECT S.key_id, S.account_id, ST.secret_type, S.secret, S.retrieved \
+             FROM Secrets S INNER JOIN SecretTypes ST \
+                ON S.secret_type_id=ST.secret_type_id \
+             WHERE S.key_id=?1 AND S.account_id=?2 AND (?3 IS NULL OR ST.secret_type=?3)",
+        )
+        .bind(key_id.as_bytes())
+        .bind(account_id.0)
+        .bind(filter.secret_type)
+        .fetch_optional(&mut *transaction)
+        .await?;
+
+        match secret_db {
+            None => {
+                // Entry not found. Check if the key exists but the user_id or
+                // secret_type was wrong!
+                let owners: Vec<i64> =
+                    sqlx::query_scalar("SELECT account_id FROM Secrets WHERE key_id=?")
+                        .bind(key_id.as_bytes())
+                        .fetch_all(&mut *transaction)
+                        .await?;
+
+                match owners.as_slice() {
+                    // The key doesn't even exist.
+                    [] => Err(SqliteError::NoEntry),
+                    // The key exists but the secret_type was incorrect.
+                    [owner] if *owner == account_id.0 => Err(SqliteError::WrongSecretType),
+                    // The key exists but the user_id was incorrect.
+                    [_] => Err(SqliteError::IncorrectKeyMetadata),
+                    _ => Err(SqliteError::InvalidRowCountFound),
+                }
+            }
+            Some(secret_db) => {
+                let _ = sqlx::query(
+                    "UPDATE Secrets SET retrieved=TRUE WHERE key_id=? AND account_id=?",
+                )
+                .bind(key_id.as_bytes())
+                .bind(account_id.0)
+                .execute(&mut *transaction)
+                .await?;
+                transaction.commit().await?;
+
+                Ok(secret_db.try_into()?)
+            }
+        }
+    }
+
+    /// This function provides a simplified query for server encrypted blobs.
+    #[instrument(skip_all, err(Debug), fields(key_id=?key_id))]
+    pub(crate) async fn get_server_encrypted_blob_impl(
+        &self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<StoredSecret, SqliteError> {
+        debug!("Fetching user secret.");
+
+        // Join tables to map secret_type to the corresponding secret_type_id.
+        let secret_db: Option<SecretDB> = sqlx::query_as(
+            "SELECT S.key_id, S.account_id, ST.secret_type, S.secret, S.retrieved \
+             FROM Secrets S INNER JOIN SecretTypes ST \
+                ON S.secret_type_id=ST.secret_type_id AND ST.secret_type=? \
+             WHERE S.key_id=? AND S.account_id=?",
+        )
+        .bind(SERVER_ENCRYPTED_BLOB)
+        .bind(key_id.as_bytes())
+        .bind(account_id.0)
+        .fetch_optional(&self.connection_pool)
+        .await?;
+
+        match secret_db {
+            None => Err(SqliteError::NoEntry),
+            Some(secret_db) => Ok(secret_db.try_into()?),
+        }
+    }
+
+    /// This function verifies the account_id and key_id match. Otherwise will
+    /// return a NoEntry error.
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id, key_id=?key_id))]
+    pub(crate) async fn delete_secret_impl(
+        &self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<(), SqliteError> {
+        debug!("Deleting user secret.");
+
+        remove_secret(&self.connection_pool, account_id, key_id).await
+    }
+
+    #[instrument(skip_all, err(Debug), fields(user_id=?user_id, account_name=?account_name))]
+    pub(crate) async fn create_account_impl(
+        &self,
+        user_id: &UserId,
+        account_name: &AccountName,
+        server_registration: &ServerRegistration<OpaqueCipherSuite>,
+    ) -> Result<Account, SqliteError> {
+        info!("Creating new user.");
+        let serialized = bincode::serialize(server_registration)?;
+
+        // `RETURNING` isn't used because sqlx hands back the returned row before
+        // the statement finishes, and the insert is only committed when it
+        // does. Another connection could then fail to find the new account.
+        let account_id = sqlx::query(
+            "INSERT INTO Accounts (user_id, account_name, server_registration) \
+             VALUES (?, ?, ?)",
+        )
+        .bind(&user_id.as_ref()[..])
+        .bind(account_name.as_ref())
+        .bind(serialized)
+        .execute(&self.connection_pool)
+        .await?
+        .last_insert_rowid();
+
+        Ok(Account {
+            account_id: account_id.into(),
+            user_id: user_id.clone(),
+            account_name: account_name.clone(),
+            storage_key: None,
+            server_registration: server_registration.clone(),
+        })
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_name=?account_name))]
+    pub(crate) async fn find_account_by_name_impl(
+        &self,
+        account_name: &AccountName,
+    ) -> Result<Option<Account>, SqliteError> {
+        debug!("Searching for user.");
+        let account_db: Option<AccountDB> = sqlx::query_as(
+            "SELECT account_id, user_id, account_name, storage_key, server_registration \
+            FROM Accounts \
+            WHERE account_name=?",
+        )
+        .bind(account_name.as_ref())
+        .fetch_optional(&self.connection_pool)
+        .await?;
+
+        let account = account_db.map(Account::try_from).transpose()?;
+        Ok(account)
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id))]
+    pub(crate) async fn find_account_impl(
+        &self,
+        account_id: AccountId,
+    ) -> Result<Option<Account>, SqliteError> {
+        debug!("Searching for user by ID");
+
+        let account_db: Option<AccountDB> = sqlx::query_as(
+            "SELECT account_id, user_id, account_name, storage_key, server_registration \
+            FROM Accounts \
+            WHERE account_id=?",
+        )
+        .bind(account_id.0)
+        .fetch_optional(&self.connection_pool)
+        .await?;
+
+        let account = account_db.map(Account::try_from).transpose()?;
+        Ok(account)
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id))]
+    pub(crate) async fn delete_account_impl(
+        &self,
+        account_id: AccountId,
+    ) -> Result<(), SqliteError> {
+        info!("Deleting user.");
+
+        let rows_affected = sqlx::query("DELETE FROM Accounts WHERE account_id=?")
+            .bind(account_id.0)
+            .execute(&self.connection_pool)
+            .await?
+            .rows_affected();
+
+        // No row was deleted.
+        match rows_affected {
+            0 => Err(SqliteError::NoEntry),
+            1 => Ok(()),
+            _ => Err(SqliteError::InvalidRowCountFound),
+        }
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id))]
+    pub(crate) async fn set_storage_key_impl(
+        &self,
+        account_id: AccountId,
+        storage_key: Encrypted<StorageKey>,
+    ) -> Result<(), SqliteError> {
+        info!("Setting storage key");
+
+        update_storage_key(&self.connection_pool, account_id, storage_key).await
+    }
+
+    #[instrument(skip_all, err(Debug), fields(user_id=?user_id))]
+    pub(crate) async fn user_id_exists_impl(&self, user_id: &UserId) -> Result<bool, SqliteError> {
+        info!("Checking if user_id exists");
+
+        let count: i64 = sqlx::query_scalar("SELECT count(*) FROM Accounts WHERE user_id=?")
+            .bind(&user_id.as_ref()[..])
+            .fetch_one(&self.connection_pool)
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id, credential_id))]
+    pub(crate) async fn create_service_credential_impl(
+        &self,
+        account_id: AccountId,
+        public_key: &ServiceCredentialPublicKey,
+        allowed_actions: &[ClientAction],
+    ) -> Result<ServiceCredential, SqliteError> {
+        info!("Creating service credential.");
+
+        let credential_id = Uuid::new_v4();
+        logging::record_field("credential_id", &credential_id);
+
+        let serialized = bincode::serialize(public_key)?;
+        let action_ids: Vec<i64> = allowed_actions.iter().map(|a| *a as i64).collect();
+        let action_ids = bincode::serialize(&action_ids)?;
+
+        let _ = sqlx::query(
+            "INSERT INTO ServiceCredentials (credential_id, account_id, public_key, allowed_actions) \
+             VALUES (?, ?, ?, ?)",
+        )
+        .bind(credential_id)
+        .bind(account_id.0)
+        .bind(serialized)
+        .bind(action_ids)
+        .execute(&self.connection_pool)
+        .await?;
+
+        Ok(ServiceCredential {
+            credential_id,
+            account_id,
+            public_key: public_key.clone(),
+            allowed_actions: allowed_actions.to_vec(),
+        })
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id, credential_id=?credential_id))]
+    pub(crate) async fn find_service_credential_impl(
+        &self,
+        account_id: AccountId,
+        credential_id: Uuid,
+    ) -> Result<Option<ServiceCredential>, SqliteError> {
+        debug!("Searching for service credential.");
+
+        let credential_db: Option<ServiceCredentialDB> = sqlx::query_as(
+            "SELECT credential_id, account_id, public_key, allowed_actions \
+            FROM ServiceCredentials \
+            WHERE credential_id=? AND account_id=?",
+        )
+        .bind(credential_id)
+        .bind(account_id.0)
+        .fetch_optional(&self.connection_pool)
+        .await?;
+
+        let credential = credential_db.map(ServiceCredential::try_from).transpose()?;
+        Ok(credential)
+    }
+
+    #[instrument(skip_all, err(Debug), fields(identity=?identity, account_id=?account_id))]
+    pub(crate) async fn create_certificate_binding_impl(
+        &self,
+        identity: &str,
+        account_id: AccountId,
+    ) -> Result<(), SqliteError> {
+        info!("Binding client certificate identity to account.");
+
+        let rows_affected =
+            sqlx::query("INSERT INTO CertificateBindings (identity, account_id) VALUES (?, ?)")
+                .bind(identity)
+                .bind(account_id.0)
+                .execute(&self.connection_pool)
+                .await?
+                .rows_affected();
+
+        // Only one row should ever be affected by our insert. Something has gone
+        // wrong...
+        if rows_affected != 1 {
+            error!("Unexpected number of rows affected: {}", rows_affected);
+            return Err(SqliteError::InvalidRowCountFound);
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip_all, err(Debug), fields(identity=?identity))]
+    pub(crate) async fn find_certificate_binding_impl(
+        &self,
+        identity: &str,
+    ) -> Result<Option<AccountId>, SqliteError> {
+        debug!("Searching for client certificate binding.");
+
+        let account_id: Option<i64> =
+            sqlx::query_scalar("SELECT account_id FROM CertificateBindings WHERE identity=?")
+                .bind(identity)
+                .fetch_optional(&self.connection_pool)
+                .await?;
+
+        Ok(account_id.map(AccountId::from))
+    }
+
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id))]
+    pub(crate) async fn account_has_certificate_binding_impl(
+        &self,
+        account_id: AccountId,
+    ) -> Result<bool, SqliteError> {
+        debug!("Checking for client certificate bindings.");
+
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM CertificateBindings WHERE account_id=?)",
+        )
+        .bind(account_id.0)
+        .fetch_one(&self.connection_pool)
+        .await?;
+
+        Ok(exists)
+    }
+}
+
+// The queries below modify data and can also be run as part of a
+// [`SqliteTransaction`](crate::SqliteTransaction), so they take an executor or
+// connection rather than using the connection pool directly.
+
+/// Audit events are hash-chained. The event is inserted before its
+/// predecessor is looked up so that the transaction holds the database write
+/// lock while the chain is extended. SQLite only allows one writer, so
+/// concurrent events for the same account never link to the same predecessor.
+pub(crate) async fn insert_audit_event(
+    connection: &mut SqliteConnection,
+    request_id: Uuid,
+    account_id: AccountId,
+    key_id: &Option<KeyId>,
+    action: ClientAction,
+    status: EventStatus,
+    details: &AuditEventDetails,
+) -> Result<AuditEvent, SqliteError> {
+    let timestamp = timestamp_to_db(OffsetDateTime::now_utc())?;
+    let key_id = key_id.as_ref().map(|k| k.as_bytes());
+    let payload_size = details
+        .payload_size
+        .map(i64::try_from)
+        .transpose()
+        .map_err(|e| {
+            SqliteError::AuditEventConversion(format!("Payload size is too large: {e}"))
+        })?;
+
+    let result = sqlx::query(
+        "INSERT INTO AuditEvents (account_id, key_id, request_id, client_action_id, event_status, timestamp, client_identity, peer_address, failure_code, payload_size, payload_hash) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(account_id.0)
+    .bind(key_id)
+    .bind(request_id)
+    .bind(action as i64)
+    .bind(status.to_string())
+    .bind(timestamp)
+    .bind(details.client_identity.as_deref())
+    .bind(details.peer_address.map(|address| address.to_string()))
+    .bind(details.failure_code.map(|code| code.to_string()))
+    .bind(payload_size)
+    .bind(details.payload_hash.as_ref().map(|hash| hash.as_bytes()))
+    .execute(&mut *connection)
+    .await?;
+
+    // Only one row should ever be affected by our insert. Something has gone
+    // wrong...
+    if result.rows_affected() != 1 {
+        error!(
+            "Unexpected number of rows affected: {}",
+            result.rows_affected()
+        );
+        return Err(SqliteError::InvalidRowCountFound);
+    }
+
+    let audit_event_id = result.last_insert_rowid();
+    let previous_hash = audit_event_before(&mut *connection, account_id, audit_event_id)
+        .await?
+        .map(|event| event.hash().as_bytes().to_vec());
+
+    let _ = sqlx::query("UPDATE AuditEvents SET previous_hash=? WHERE audit_event_id=?")
+        .bind(previous_hash)
+        .bind(audit_event_id)
+        .execute(&mut *connection)
+        .await?;
+
+    // Read the event back so that it is returned exactly as stored.
+    let event: AuditEventDB = sqlx::query_as(
+        "SELECT audit_event_id, key_id, request_id, account_id, client_action_id, event_status, timestamp, client_identity, peer_address, failure_code, payload_size, payload_hash, previous_hash \
+         FROM AuditEvents \
+         WHERE audit_event_id=?",
+    )
+    .bind(audit_event_id)
+    .fetch_one(connection)
+    .await?;
+
+    event.try_into()
+}
+
+/// Find the latest audit event for the account with an ID lower than
+/// `audit_event_id`.
+async fn audit_event_before(
+    executor: impl SqliteExecutor<'_>,
+    account_id: AccountId,
+    audit_event_id: i64,
+) -> Result<Option<AuditEvent>, SqliteError> {
+    let event: Option<AuditEventDB> = sqlx::query_as(
+        "SELECT audit_event_id, key_id, request_id, account_id, client_action_id, event_status, timestamp, client_identity, peer_address, failure_code, payload_size, payload_hash, previous_hash \
+         FROM AuditEvents \
+         WHERE account_id=? AND audit_event_id<? \
+         ORDER BY audit_event_id DESC \
+         LIMIT 1",
+    )
+    .bind(account_id.0)
+    .bind(audit_event_id)
+    .fetch_optional(executor)
+    .await?;
+
+    event.map(AuditEvent::try_from).transpose()
+}
+
+pub(crate) async fn insert_secret(
+    executor: impl SqliteExecutor<'_>,
+    secret: StoredSecret,
+) -> Result<(), SqliteError> {
+    let secret_db: SecretDB = SecretDB::from(secret);
+
+    let rows_affected = sqlx::query(
+        "INSERT INTO Secrets (key_id, account_id, secret, secret_type_id, retrieved) \
+         SELECT ?, ?, ?, SecretTypes.secret_type_id, ? \
+         FROM SecretTypes \
+         WHERE SecretTypes.secret_type=?",
+    )
+    .bind(secret_db.key_id)
+    .bind(secret_db.account_id)
+    .bind(secret_db.secret)
+    .bind(secret_db.retrieved)
+    .bind(secret_db.secret_type)
+    .execute(executor)
+    .await?
+    .rows_affected();
+
+    // Only one row should ever be affected by our insert. Something has gone
+    // wrong...
+    if rows_affected != 1 {
+        error!("Unexpected number of rows affected: {}", rows_affected);
+        return Err(SqliteError::InvalidRowCountFound);
+    }
+
+    Ok(())
+}
+
+/// Returns a NoEntry error if no secret matches both the account_id and
+/// key_id.
+pub(crate) async fn remove_secret(
+    executor: impl SqliteExecutor<'_>,
+    account_id: AccountId,
+    key_id: &KeyId,
+) -> Result<(), SqliteError> {
+    let rows_affected = sqlx::query("DELETE FROM Secrets WHERE account_id=? AND key_id=?")
+        .bind(account_id.0)
+        .bind(key_id.as_bytes())
+        .execute(executor)
+        .await?
+        .rows_affected();
+
+    if rows_affected == 0 {
+        return Err(SqliteError::NoEntry);
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn update_storage_key(
+    executor: impl SqliteExecutor<'_>,
+    account_id: AccountId,
+    storage_key: Encrypted<StorageKey>,
+) -> Result<(), SqliteError> {
+    let storage_key = bincode::serialize(&storage_key)?;
+
+    let _ = sqlx::query("UPDATE Accounts SET storage_key=? WHERE account_id=?")
+        .bind(storage_key)
+        .bind(account_id.0)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// Create a SQL query list of the form (val1, val2, ...). Error is returned if
+/// the iterator is empty
+#[allow(unused_results)]
+fn append_value_list<'a, I: 'a + Encode<'a, Sqlite> + Send + Type<Sqlite>>(
+    query: &mut QueryBuilder<'a, Sqlite>,
+    values: impl Iterator<Item = I> + Clone,
+) -> Result<(), SqliteError> {
+    if values.clone().count() == 0 {
+        return Err(SqliteError::InvalidAuditEventOptions);
+    }
+    query.push("(");
+    let mut separated = query.separated(", ");
+    for v in values {
+        separated.push_bind(v);
+    }
+    separated.push_unseparated(")");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{path::PathBuf, time::Duration};
+
+    fn test_config(path: PathBuf) -> Config {
+        Config {
+            path,
+            max_connections: 2,
+            connection_timeout: Duration::from_secs(5),
+            session_expiration: Duration::from_secs(60),
+        }
+    }
+
+    #[tokio::test]
+    async fn dropped_write_transaction_is_rolled_back() {
+        let path = std::env::temp_dir().join(format!("lock-keeper-{}.db", Uuid::new_v4()));
+        let db = SqliteDB::connect(test_config(path.clone())).await.unwrap();
+        let count_accounts = || async {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM Accounts")
+                .fetch_one(&db.connection_pool)
+                .await
+                .unwrap()
+        };
+
+        let mut transaction = WriteTransaction::begin(&db.connection_pool).await.unwrap();
+        let _ = sqlx::query(
+            "INSERT INTO Accounts (user_id, account_name, server_registration) \
+             VALUES (?, ?, ?)",
+        )
+        .bind(vec![0_u8; 32])
+        .bind("account")
+        .bind(vec![0_u8; 8])
+        .execute(&mut *transaction)
+        .await
+        .unwrap();
+        drop(transaction);
+
+        // The write lock was released, so another write transaction can begin.
+        let transaction = WriteTransaction::begin(&db.connection_pool).await.unwrap();
+        transaction.commit().await.unwrap();
+        assert_eq!(count_accounts().await, 0);
+
+        db.connection_pool.close().await;
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn audit_event_timestamps_survive_reconnect() {
+        let path = std::env::temp_dir().join(format!("lock-keeper-{}.db", Uuid::new_v4()));
+        let db = SqliteDB::connect(test_config(path.clone())).await.unwrap();
+
+        // The account row only needs to satisfy the foreign key.
+        let account_id = sqlx::query(
+            "INSERT INTO Accounts (user_id, account_name, server_registration) \
+             VALUES (?, ?, ?)",
+        )
+        .bind(vec![0_u8; 32])
+        .bind("account")
+        .bind(vec![0_u8; 8])
+        .execute(&db.connection_pool)
+        .await
+        .unwrap()
+        .last_insert_rowid();
+        let account_id = AccountId::from(account_id);
+
+        let before = OffsetDateTime::now_utc();
+        let event = db
+            .create_audit_event_impl(
+                Uuid::new_v4(),
+                account_id,
+                &None,
+                ClientAction::Authenticate,
+                EventStatus::Started,
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+        db.connection_pool.close().await;
+
+        // Reconnecting must not re-run migrations that were already applied.
+        let db = SqliteDB::connect(test_config(path.clone())).await.unwrap();
+        let events = db
+            .find_audit_events_impl(
+                Some(account_id),
+                EventType::All,
+                AuditEventOptions {
+                    after_date: Some(before),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert!(events[0].timestamp >= before);
+        // The returned event is the one that was stored.
+        assert_eq!(events[0].hash(), event.hash());
+
+        let events = db
+            .find_audit_events_impl(
+                Some(account_id),
+                EventType::All,
+                AuditEventOptions {
+                    before_date: Some(before),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+
+        db.connection_pool.close().await;
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,63 @@
+//! Config for the SQLite database.
+
+use crate::error::ConfigError;
+use serde::{Deserialize, Serialize};
+use std::{fs::read_to_string, path::Path, path::PathBuf, str::FromStr, time::Duration};
+
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub struct Config {
+    /// Path to the database file. The file is created if it doesn't exist.
+    pub path: PathBuf,
+    pub max_connections: u32,
+    /// How long to wait for a connection from the pool. This is also used as
+    /// the busy timeout when another connection holds a write lock.
+    #[serde(with = "humantime_serde")]
+    pub connection_timeout: Duration,
+    /// A session is considered invalid after this time has elapsed.
+    #[serde(with = "humantime_serde")]
+    pub session_expiration: Duration,
+}
+
+impl Config {
+    pub fn from_file(config_path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let config_string = read_to_string(&config_path).map_err(|e| {
+            ConfigError::ConfigFileReadFailure(e, config_path.as_ref().to_path_buf())
+        })?;
+        Self::from_str(&config_string)
+    }
+}
+
+impl FromStr for Config {
+    type Err = ConfigError;
+
+    fn from_str(config_string: &str) -> Result<Self, Self::Err> {
+        Ok(toml::from_str(config_string)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_string() {
+        let config_str = r#"
+            path = "dev/lock-keeper.db"
+            max_connections = 5
+            connection_timeout = "3s"
+            session_expiration = "60s"
+            "#;
+
+        let config = Config::from_str(config_str).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                path: PathBuf::from("dev/lock-keeper.db"),
+                max_connections: 5,
+                connection_timeout: Duration::from_secs(3),
+                session_expiration: Duration::from_secs(60),
+            }
+        );
+    }
+}
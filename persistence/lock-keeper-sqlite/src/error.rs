@@ -0,0 +1,71 @@
+use lock_keeper_key_server::server::{database::DatabaseError, session_cache::SessionCacheError};
+use std::{array::TryFromSliceError, path::PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SqliteError {
+    #[error("sqlx error")]
+    Sqlx(#[from] sqlx::Error),
+    #[error("Failed to run database migrations.")]
+    Migrate(#[from] sqlx::migrate::MigrateError),
+    #[error("Could not serialize/deserialize data to/from databases.")]
+    Serialization(#[from] bincode::Error),
+    #[error("Slice size mismatch.")]
+    WrongSliceSize(#[from] TryFromSliceError),
+    #[error("No such entry in table.")]
+    NoEntry,
+    #[error("AuditEventDB to AuditEvent conversion failed: {0}")]
+    AuditEventConversion(String),
+    #[error("ServiceCredentialDB to ServiceCredential conversion failed: {0}")]
+    ServiceCredentialConversion(String),
+    #[error("Timestamp is out of range for the database.")]
+    TimestampOutOfRange,
+    #[error("Unexpected number of rows returned.")]
+    InvalidRowCountFound,
+    #[error("Key ID exists but belongs to another account.")]
+    IncorrectKeyMetadata,
+    #[error("Key ID exists but its secret type doesn't match the filter.")]
+    WrongSecretType,
+    #[error("Empty iterator for append_value_list function.")]
+    InvalidAuditEventOptions,
+    #[error("Session has expired.")]
+    ExpiredSession,
+    #[error("No session for this user.")]
+    MissingSession,
+    #[error("Request was already made on this session.")]
+    ReplayedRequest,
+    #[error("Config file error.")]
+    ConfigError(#[from] ConfigError),
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Could not read config file {1}. Error: {0}.")]
+    ConfigFileReadFailure(std::io::Error, PathBuf),
+    #[error("Failed to read TOML file contents: {0}")]
+    TomlReadFailure(#[from] toml::de::Error),
+}
+
+impl From<SqliteError> for DatabaseError {
+    fn from(error: SqliteError) -> Self {
+        match error {
+            SqliteError::InvalidRowCountFound => Self::InvalidRowCountFound,
+            SqliteError::NoEntry => Self::NoEntry,
+            SqliteError::InvalidAuditEventOptions => Self::InvalidAuditEventOptions,
+            SqliteError::IncorrectKeyMetadata => Self::IncorrectKeyMetadata,
+            SqliteError::WrongSecretType => Self::WrongSecretType,
+            _ => Self::InternalDatabaseError(error.to_string()),
+        }
+    }
+}
+
+impl From<SqliteError> for SessionCacheError {
+    fn from(error: SqliteError) -> Self {
+        match error {
+            SqliteError::ExpiredSession => Self::ExpiredSession,
+            SqliteError::MissingSession => Self::MissingSession,
+            SqliteError::ReplayedRequest => Self::ReplayedRequest,
+            _ => Self::InternalCacheError,
+        }
+    }
+}
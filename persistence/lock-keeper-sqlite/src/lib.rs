@@ -0,0 +1,30 @@
+//! This crate is an implementation of the key server's
+//! [`DataStore`](lock_keeper_key_server::server::database::DataStore) and
+//! [`SessionCache`](lock_keeper_key_server::server::session_cache::SessionCache)
+//! traits backed by a single SQLite database file.
+//!
+//! It is intended for single-node deployments that don't want to run a
+//! separate Postgres server. The schema mirrors the Postgres migrations and
+//! lives in the `migrations` folder of this crate. Migrations are embedded in
+//! the binary and run automatically by [`SqliteDB::connect`].
+//!
+//! Unlike the Postgres crate, queries here are built at runtime with
+//! `sqlx::query` and the `QueryBuilder` API, so no database or `sqlx-data.json`
+//! is needed to compile this crate.
+#![warn(unused_results)]
+#![warn(future_incompatible)]
+#![warn(unused)]
+#![forbid(rustdoc::broken_intra_doc_links)]
+
+mod api;
+mod config;
+mod error;
+mod session_cache;
+mod transaction;
+mod types;
+
+pub use api::SqliteDB;
+pub use config::Config;
+pub use error::{ConfigError, SqliteError};
+pub use session_cache::SqliteSessionCache;
+pub use transaction::SqliteTransaction;
@@ -0,0 +1,203 @@
+//! Implementation of the `SessionCache` trait that stores sessions in the
+//! SQLite database.
+
+use async_trait::async_trait;
+use lock_keeper::{
+    crypto::{Encrypted, OpaqueSessionKey},
+    infrastructure::logging,
+    types::{database::account::AccountId, operations::ClientAction},
+};
+use lock_keeper_key_server::server::session_cache::{Session, SessionCache, SessionCacheError};
+use sqlx::SqlitePool;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+use crate::{
+    types::{timestamp_to_db, SessionDB},
+    SqliteError,
+};
+
+/// Cache holding sessions, per user, after authentication. Sessions are
+/// tagged with a timestamp. A session is considered invalid after the
+/// `expiration` time has elapsed.
+///
+/// Created with [`SqliteDB::session_cache`](crate::SqliteDB::session_cache)
+/// and shares its connection pool.
+#[derive(Clone, Debug)]
+pub struct SqliteSessionCache {
+    expiration: Duration,
+    /// SqlitePool is already implemented in terms of an Arc. No need to wrap
+    /// it.
+    connection_pool: SqlitePool,
+}
+
+impl SqliteSessionCache {
+    pub(crate) fn new(connection_pool: SqlitePool, expiration: Duration) -> Self {
+        Self {
+            expiration,
+            connection_pool,
+        }
+    }
+}
+
+#[async_trait]
+impl SessionCache for SqliteSessionCache {
+    async fn create_session(
+        &self,
+        account_id: AccountId,
+        session_key: Encrypted<OpaqueSessionKey>,
+        service_credential_id: Option<Uuid>,
+    ) -> Result<Uuid, SessionCacheError> {
+        Ok(self
+            .create_session_impl(account_id, session_key, service_credential_id)
+            .await?)
+    }
+
+    async fn find_session(&self, session_id: Uuid) -> Result<Session, SessionCacheError> {
+        Ok(self.find_session_impl(session_id).await?)
+    }
+
+    async fn delete_session(&self, session_id: Uuid) -> Result<(), SessionCacheError> {
+        Ok(self.delete_session_impl(session_id).await?)
+    }
+
+    async fn record_request(
+        &self,
+        session_id: Uuid,
+        request_id: Uuid,
+        action: ClientAction,
+    ) -> Result<(), SessionCacheError> {
+        Ok(self
+            .record_request_impl(session_id, request_id, action)
+            .await?)
+    }
+
+    async fn count_active_sessions(&self) -> Result<u64, SessionCacheError> {
+        Ok(self.count_active_sessions_impl().await?)
+    }
+
+    async fn health_check(&self) -> Result<(), SessionCacheError> {
+        Ok(self.health_check_impl().await?)
+    }
+}
+
+impl SqliteSessionCache {
+    #[instrument(skip_all, err(Debug), fields(account_id=?account_id, session_id))]
+    async fn create_session_impl(
+        &self,
+        account_id: AccountId,
+        session_key: Encrypted<OpaqueSessionKey>,
+        service_credential_id: Option<Uuid>,
+    ) -> Result<Uuid, SqliteError> {
+        info!("Creating session.");
+
+        let session_id = Uuid::new_v4();
+        logging::record_field("session_id", &session_id);
+
+        let session_key = bincode::serialize(&session_key)?;
+        let timestamp = timestamp_to_db(OffsetDateTime::now_utc())?;
+
+        let _ = sqlx::query(
+            "INSERT INTO Session (session_id, account_id, timestamp, session_key, service_credential_id) \
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(session_id)
+        .bind(account_id.0)
+        .bind(timestamp)
+        .bind(session_key)
+        .bind(service_credential_id)
+        .execute(&self.connection_pool)
+        .await?;
+
+        Ok(session_id)
+    }
+
+    /// Get the session with the given ID, if one exists.
+    /// This function checks if the session has expired and returns an error
+    /// instead.
+    #[instrument(skip(self), err(Debug))]
+    async fn find_session_impl(&self, session_id: Uuid) -> Result<Session, SqliteError> {
+        let session_db: Option<SessionDB> = sqlx::query_as(
+            "SELECT session_id, account_id, timestamp, session_key, service_credential_id \
+            FROM Session \
+            WHERE session_id=?",
+        )
+        .bind(session_id)
+        .fetch_optional(&self.connection_pool)
+        .await?;
+
+        let session: Session = match session_db {
+            Some(session_db) => session_db.try_into()?,
+            None => return Err(SqliteError::MissingSession),
+        };
+
+        let elapsed = OffsetDateTime::now_utc() - session.timestamp;
+        if elapsed >= self.expiration {
+            info!("Session key is expired.");
+            self.delete_session_impl(session_id).await?;
+            return Err(SqliteError::ExpiredSession);
+        }
+
+        Ok(session)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn delete_session_impl(&self, session_id: Uuid) -> Result<(), SqliteError> {
+        info!("Deleting session.");
+
+        let _ = sqlx::query("DELETE FROM Session WHERE session_id=?")
+            .bind(session_id)
+            .execute(&self.connection_pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Record that a request was made on the session. Returns
+    /// [`SqliteError::ReplayedRequest`] if it was already recorded.
+    #[instrument(skip(self), err(Debug))]
+    async fn record_request_impl(
+        &self,
+        session_id: Uuid,
+        request_id: Uuid,
+        action: ClientAction,
+    ) -> Result<(), SqliteError> {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO SessionRequests (session_id, request_id, client_action_id) \
+             VALUES (?, ?, ?)",
+        )
+        .bind(session_id)
+        .bind(request_id)
+        .bind(action as i64)
+        .execute(&self.connection_pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(SqliteError::ReplayedRequest);
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn count_active_sessions_impl(&self) -> Result<u64, SqliteError> {
+        let cutoff = timestamp_to_db(OffsetDateTime::now_utc() - self.expiration)?;
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(1) FROM Session WHERE timestamp > ?")
+            .bind(cutoff)
+            .fetch_one(&self.connection_pool)
+            .await?;
+
+        Ok(count as u64)
+    }
+
+    #[instrument(skip(self), err(Debug))]
+    async fn health_check_impl(&self) -> Result<(), SqliteError> {
+        let _ = sqlx::query("SELECT 1")
+            .execute(&self.connection_pool)
+            .await?;
+        Ok(())
+    }
+}
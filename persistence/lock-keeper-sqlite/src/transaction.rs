@@ -0,0 +1,165 @@
+//! Implementation of the `DataStoreTransaction` trait using a Postgres
+//! transaction.
+use crate::{
+    api::{insert_audit_event, insert_secret, remove_secret, update_storage_key},
+    SqliteError,
+};
+use async_trait::async_trait;
+use lock_keeper::{
+    crypto::{Encrypted, KeyId, StorageKey},
+    types::{
+        audit_event::{AuditEvent, AuditEventDetails, EventStatus},
+        database::{account::AccountId, secrets::StoredSecret},
+        operations::ClientAction,
+    },
+};
+use lock_keeper_key_server::server::database::{DataStoreTransaction, DatabaseError};
+use sqlx::{pool::PoolConnection, Sqlite, SqliteConnection, SqlitePool};
+use std::ops::{Deref, DerefMut};
+use tracing::{debug, info, instrument};
+use uuid::Uuid;
+
+/// A [`DataStoreTransaction`] for [`SqliteDB`](crate::SqliteDB).
+///
+/// The transaction is rolled back if it is dropped without being committed.
+#[derive(Debug)]
+pub struct SqliteTransaction {
+    transaction: WriteTransaction,
+    /// Audit events created in the transaction, returned on commit.
+    audit_events: Vec<AuditEvent>,
+}
+
+impl SqliteTransaction {
+    pub(crate) fn new(transaction: WriteTransaction) -> Self {
+        Self {
+            transaction,
+            audit_events: Vec::new(),
+        }
+    }
+}
+
+/// A transaction that holds the database write lock from the start.
+///
+/// SQLite transactions started with a plain `BEGIN` only take the write lock
+/// at their first write. If another connection commits in between, that write
+/// fails with `SQLITE_BUSY_SNAPSHOT` instead of waiting for the busy timeout.
+/// sqlx always begins transactions that way, so this begins them with
+/// `BEGIN IMMEDIATE` on a pooled connection instead.
+///
+/// If the transaction is dropped without being committed, its connection is
+/// closed instead of being returned to the pool, which rolls it back.
+#[derive(Debug)]
+pub(crate) struct WriteTransaction {
+    /// Only `None` while the transaction is being dropped.
+    connection: Option<PoolConnection<Sqlite>>,
+    committed: bool,
+}
+
+impl WriteTransaction {
+    pub(crate) async fn begin(pool: &SqlitePool) -> Result<Self, SqliteError> {
+        let mut connection = pool.acquire().await?;
+        let _ = sqlx::query("BEGIN IMMEDIATE")
+            .execute(&mut connection)
+            .await?;
+
+        Ok(Self {
+            connection: Some(connection),
+            committed: false,
+        })
+    }
+
+    pub(crate) async fn commit(mut self) -> Result<(), SqliteError> {
+        let _ = sqlx::query("COMMIT").execute(&mut *self).await?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Deref for WriteTransaction {
+    type Target = SqliteConnection;
+
+    fn deref(&self) -> &SqliteConnection {
+        self.connection
+            .as_ref()
+            .expect("connection is only taken on drop")
+    }
+}
+
+impl DerefMut for WriteTransaction {
+    fn deref_mut(&mut self) -> &mut SqliteConnection {
+        self.connection
+            .as_mut()
+            .expect("connection is only taken on drop")
+    }
+}
+
+impl Drop for WriteTransaction {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            if !self.committed {
+                drop(connection.detach());
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DataStoreTransaction for SqliteTransaction {
+    #[instrument(skip(self))]
+    async fn create_audit_event(
+        &mut self,
+        request_id: Uuid,
+        account_id: AccountId,
+        key_id: &Option<KeyId>,
+        action: ClientAction,
+        status: EventStatus,
+        details: &AuditEventDetails,
+    ) -> Result<(), DatabaseError> {
+        debug!("Storing new audit event in transaction.");
+        let event = insert_audit_event(
+            &mut self.transaction,
+            request_id,
+            account_id,
+            key_id,
+            action,
+            status,
+            details,
+        )
+        .await?;
+        self.audit_events.push(event);
+        Ok(())
+    }
+
+    #[instrument(skip_all, fields(account_id=?secret.account_id, key_id=?secret.key_id))]
+    async fn add_secret(&mut self, secret: StoredSecret) -> Result<(), DatabaseError> {
+        debug!("Adding user secret in transaction.");
+        Ok(insert_secret(&mut *self.transaction, secret).await?)
+    }
+
+    #[instrument(skip(self))]
+    async fn delete_secret(
+        &mut self,
+        account_id: AccountId,
+        key_id: &KeyId,
+    ) -> Result<(), DatabaseError> {
+        debug!("Deleting user secret in transaction.");
+        Ok(remove_secret(&mut *self.transaction, account_id, key_id).await?)
+    }
+
+    #[instrument(skip(self, storage_key))]
+    async fn set_storage_key(
+        &mut self,
+        account_id: AccountId,
+        storage_key: Encrypted<StorageKey>,
+    ) -> Result<(), DatabaseError> {
+        info!("Setting storage key in transaction.");
+        Ok(update_storage_key(&mut *self.transaction, account_id, storage_key).await?)
+    }
+
+    #[instrument(skip_all)]
+    async fn commit(self) -> Result<Vec<AuditEvent>, DatabaseError> {
+        debug!("Committing transaction.");
+        self.transaction.commit().await?;
+        Ok(self.audit_events)
+    }
+}
@@ -0,0 +1,327 @@
+use crate::error::SqliteError;
+use lock_keeper::{
+    crypto::{AuditSignature, KeyId},
+    types::{
+        audit_chain::AuditCheckpoint,
+        audit_event::{
+            AuditEvent, AuditEventDetails, AuditEventHash, EventStatus, FailureCode, PayloadHash,
+        },
+        database::{
+            account::{Account, AccountName, UserId},
+            secrets::StoredSecret,
+            service_credential::ServiceCredential,
+        },
+        operations::ClientAction,
+    },
+};
+use lock_keeper_key_server::server::session_cache::Session;
+use std::str::FromStr;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Timestamps are stored as Unix timestamps in nanoseconds so that they sort
+/// and compare correctly in SQLite.
+pub(crate) fn timestamp_to_db(timestamp: OffsetDateTime) -> Result<i64, SqliteError> {
+    i64::try_from(timestamp.unix_timestamp_nanos()).map_err(|_| SqliteError::TimestampOutOfRange)
+}
+
+pub(crate) fn timestamp_from_db(timestamp: i64) -> Result<OffsetDateTime, SqliteError> {
+    OffsetDateTime::from_unix_timestamp_nanos(timestamp.into())
+        .map_err(|_| SqliteError::TimestampOutOfRange)
+}
+
+/// Mapping of our [Account] type as it looks in the table. sqlx can use this to
+/// map selected rows to this rust type.
+#[derive(sqlx::FromRow)]
+pub(crate) struct AccountDB {
+    pub(crate) account_id: i64,
+    pub(crate) user_id: Vec<u8>,
+    pub(crate) account_name: String,
+    pub(crate) storage_key: Option<Vec<u8>>,
+    pub(crate) server_registration: Vec<u8>,
+}
+
+/// Mapping of our [StoredSecret] type as it looks in the table. sqlx can use
+/// this to map selected rows to this rust type.
+#[derive(sqlx::FromRow)]
+pub(crate) struct SecretDB {
+    pub(crate) key_id: Vec<u8>,
+    pub(crate) account_id: i64,
+    pub(crate) secret_type: String,
+    pub(crate) secret: Vec<u8>,
+    pub(crate) retrieved: bool,
+}
+
+/// Mapping of our [AuditEvent] type as it looks in the table. sqlx can use this
+/// to map selected rows to this rust type.
+#[derive(sqlx::FromRow)]
+pub(crate) struct AuditEventDB {
+    /// Unique ID auto-generated by database.
+    pub(crate) audit_event_id: i64,
+    pub(crate) account_id: i64,
+    pub(crate) key_id: Option<Vec<u8>>,
+    pub(crate) request_id: Uuid,
+    pub(crate) client_action_id: i64,
+    pub(crate) event_status: String,
+    pub(crate) timestamp: i64,
+    pub(crate) client_identity: Option<String>,
+    pub(crate) peer_address: Option<String>,
+    pub(crate) failure_code: Option<String>,
+    pub(crate) payload_size: Option<i64>,
+    pub(crate) payload_hash: Option<Vec<u8>>,
+    pub(crate) previous_hash: Option<Vec<u8>>,
+}
+
+/// Mapping of our [AuditCheckpoint] type as it looks in the table. sqlx can
+/// use this to map selected rows to this rust type.
+#[derive(sqlx::FromRow)]
+pub(crate) struct AuditCheckpointDB {
+    pub(crate) account_id: i64,
+    pub(crate) audit_event_id: i64,
+    pub(crate) event_hash: Vec<u8>,
+    pub(crate) timestamp: i64,
+    pub(crate) signature: Vec<u8>,
+}
+
+/// Mapping of our [ServiceCredential] type as it looks in the table. sqlx can
+/// use this to map selected rows to this rust type.
+#[derive(sqlx::FromRow)]
+pub(crate) struct ServiceCredentialDB {
+    pub(crate) credential_id: Uuid,
+    pub(crate) account_id: i64,
+    pub(crate) public_key: Vec<u8>,
+    /// bincode-encoded `Vec<i64>`. SQLite has no array type.
+    pub(crate) allowed_actions: Vec<u8>,
+}
+
+/// Mapping of our [Session] type as it looks in the table. sqlx can use this
+/// to map selected rows to this rust type.
+#[derive(sqlx::FromRow)]
+pub(crate) struct SessionDB {
+    pub(crate) session_id: Uuid,
+    pub(crate) account_id: i64,
+    pub(crate) timestamp: i64,
+    pub(crate) session_key: Vec<u8>,
+    pub(crate) service_credential_id: Option<Uuid>,
+}
+
+impl TryFrom<SecretDB> for StoredSecret {
+    type Error = SqliteError;
+
+    fn try_from(secret: SecretDB) -> Result<Self, Self::Error> {
+        let key_id = secret.key_id.as_slice().try_into()?;
+        Ok(StoredSecret {
+            key_id,
+            account_id: secret.account_id.into(),
+            secret_type: secret.secret_type,
+            bytes: secret.secret,
+            retrieved: secret.retrieved,
+        })
+    }
+}
+
+impl TryFrom<AccountDB> for Account {
+    type Error = SqliteError;
+
+    fn try_from(account: AccountDB) -> Result<Self, Self::Error> {
+        let storage_key = account
+            .storage_key
+            .map(|storage_key| bincode::deserialize(&storage_key))
+            .transpose()?;
+
+        Ok(Account {
+            account_id: account.account_id.into(),
+            user_id: UserId::try_from(account.user_id.as_slice())?,
+            account_name: AccountName::from(account.account_name.as_str()),
+            storage_key,
+            server_registration: bincode::deserialize(&account.server_registration)?,
+        })
+    }
+}
+
+impl From<StoredSecret> for SecretDB {
+    fn from(secret: StoredSecret) -> Self {
+        SecretDB {
+            key_id: secret.key_id.as_bytes().to_vec(),
+            account_id: secret.account_id.into(),
+            secret_type: secret.secret_type,
+            secret: secret.bytes,
+            retrieved: secret.retrieved,
+        }
+    }
+}
+
+impl TryFrom<AuditEventDB> for AuditEvent {
+    type Error = SqliteError;
+
+    fn try_from(event: AuditEventDB) -> Result<Self, Self::Error> {
+        let client_action = ClientAction::try_from(event.client_action_id).map_err(|i| {
+            SqliteError::AuditEventConversion(format!(
+                "ClientAction conversion failed. Unknown integer {i}"
+            ))
+        })?;
+
+        let secret_id: Option<KeyId> = match event.key_id {
+            None => None,
+            Some(key_id) => {
+                let key_id = TryFrom::try_from(key_id.as_slice()).map_err(|e| {
+                    SqliteError::AuditEventConversion(format!("KeyID conversion failed: {e}"))
+                })?;
+                Some(key_id)
+            }
+        };
+        let status = EventStatus::from_str(&event.event_status).map_err(|e| {
+            SqliteError::AuditEventConversion(format!("EventStatus conversion failed {e}"))
+        })?;
+        let peer_address = event
+            .peer_address
+            .map(|address| address.parse())
+            .transpose()
+            .map_err(|e| {
+                SqliteError::AuditEventConversion(format!("Peer address conversion failed: {e}"))
+            })?;
+        let failure_code = event
+            .failure_code
+            .map(|code| FailureCode::from_str(&code))
+            .transpose()
+            .map_err(|e| {
+                SqliteError::AuditEventConversion(format!("FailureCode conversion failed {e}"))
+            })?;
+        let payload_size = event
+            .payload_size
+            .map(u64::try_from)
+            .transpose()
+            .map_err(|e| {
+                SqliteError::AuditEventConversion(format!("Payload size conversion failed: {e}"))
+            })?;
+        let payload_hash = event
+            .payload_hash
+            .map(|hash| PayloadHash::try_from(hash.as_slice()))
+            .transpose()
+            .map_err(|e| {
+                SqliteError::AuditEventConversion(format!("Payload hash conversion failed: {e}"))
+            })?;
+        let previous_hash = event
+            .previous_hash
+            .map(|hash| AuditEventHash::try_from(hash.as_slice()))
+            .transpose()
+            .map_err(|e| {
+                SqliteError::AuditEventConversion(format!("Previous hash conversion failed: {e}"))
+            })?;
+
+        let event = AuditEvent {
+            audit_event_id: event.audit_event_id,
+            request_id: event.request_id,
+            account_id: event.account_id.into(),
+            key_id: secret_id,
+            timestamp: timestamp_from_db(event.timestamp)?,
+            client_action,
+            status,
+            details: AuditEventDetails {
+                client_identity: event.client_identity,
+                peer_address,
+                failure_code,
+                payload_size,
+                payload_hash,
+            },
+            previous_hash,
+        };
+
+        Ok(event)
+    }
+}
+
+/// Used to import archived audit events with all of their original fields.
+impl TryFrom<&AuditEvent> for AuditEventDB {
+    type Error = SqliteError;
+
+    fn try_from(event: &AuditEvent) -> Result<Self, Self::Error> {
+        let payload_size = event
+            .details
+            .payload_size
+            .map(i64::try_from)
+            .transpose()
+            .map_err(|e| {
+                SqliteError::AuditEventConversion(format!("Payload size is too large: {e}"))
+            })?;
+
+        Ok(AuditEventDB {
+            audit_event_id: event.audit_event_id,
+            account_id: event.account_id.into(),
+            key_id: event.key_id.as_ref().map(|k| k.as_bytes().to_vec()),
+            request_id: event.request_id,
+            client_action_id: event.client_action as i64,
+            event_status: event.status.to_string(),
+            timestamp: timestamp_to_db(event.timestamp)?,
+            client_identity: event.details.client_identity.clone(),
+            peer_address: event
+                .details
+                .peer_address
+                .map(|address| address.to_string()),
+            failure_code: event.details.failure_code.map(|code| code.to_string()),
+            payload_size,
+            payload_hash: event
+                .details
+                .payload_hash
+                .as_ref()
+                .map(|hash| hash.as_bytes().to_vec()),
+            previous_hash: event
+                .previous_hash
+                .as_ref()
+                .map(|hash| hash.as_bytes().to_vec()),
+        })
+    }
+}
+
+impl TryFrom<AuditCheckpointDB> for AuditCheckpoint {
+    type Error = SqliteError;
+
+    fn try_from(checkpoint: AuditCheckpointDB) -> Result<Self, Self::Error> {
+        Ok(AuditCheckpoint {
+            account_id: checkpoint.account_id.into(),
+            audit_event_id: checkpoint.audit_event_id,
+            event_hash: checkpoint.event_hash.as_slice().try_into()?,
+            timestamp: timestamp_from_db(checkpoint.timestamp)?,
+            signature: AuditSignature::new(checkpoint.signature),
+        })
+    }
+}
+
+impl TryFrom<ServiceCredentialDB> for ServiceCredential {
+    type Error = SqliteError;
+
+    fn try_from(credential: ServiceCredentialDB) -> Result<Self, Self::Error> {
+        let action_ids: Vec<i64> = bincode::deserialize(&credential.allowed_actions)?;
+        let allowed_actions = action_ids
+            .into_iter()
+            .map(|action| {
+                ClientAction::try_from(action).map_err(|i| {
+                    SqliteError::ServiceCredentialConversion(format!(
+                        "ClientAction conversion failed. Unknown integer {i}"
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ServiceCredential {
+            credential_id: credential.credential_id,
+            account_id: credential.account_id.into(),
+            public_key: bincode::deserialize(&credential.public_key)?,
+            allowed_actions,
+        })
+    }
+}
+
+impl TryFrom<SessionDB> for Session {
+    type Error = SqliteError;
+
+    fn try_from(session: SessionDB) -> Result<Self, Self::Error> {
+        Ok(Session {
+            session_id: session.session_id,
+            account_id: session.account_id.into(),
+            timestamp: timestamp_from_db(session.timestamp)?,
+            session_key: bincode::deserialize(&session.session_key)?,
+            service_credential_id: session.service_credential_id,
+        })
+    }
+}
@@ -0,0 +1,22 @@
+-- These can be found in lock-keeper/src/types/operations.rs
+INSERT INTO ClientActionsTypes (client_action_id, client_action)
+VALUES
+    (18, 'DeleteKey'),
+    (19, 'RegisterServiceCredential'),
+    (20, 'AuthenticateServiceAccount')
+ON CONFLICT (client_action_id) DO NOTHING;
+
+-- Public keys that service accounts use to log in without a password
+CREATE TABLE IF NOT EXISTS ServiceCredentials
+(
+    credential_id UUID DEFAULT uuid_generate_v4 (),
+    account_id BIGINT NOT NULL,
+    public_key BYTEA NOT NULL,
+    -- IDs from ClientActionsTypes
+    allowed_actions BIGINT[] NOT NULL,
+    PRIMARY KEY (credential_id),
+    FOREIGN KEY (account_id) REFERENCES Accounts(account_id) ON DELETE CASCADE
+);
+
+-- Sessions created with a service credential are limited to its allowed actions
+ALTER TABLE Session ADD COLUMN IF NOT EXISTS service_credential_id UUID;
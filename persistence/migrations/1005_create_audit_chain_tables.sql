@@ -0,0 +1,21 @@
+-- Hash of the previous audit event for the same account. NULL for the first event of an account and for
+-- events stored before audit events were hash-chained.
+ALTER TABLE AuditEvents ADD COLUMN IF NOT EXISTS previous_hash BYTEA;
+
+CREATE INDEX IF NOT EXISTS audit_events_account_id ON AuditEvents (account_id, audit_event_id);
+
+-- Audit event hashes signed by the key server's audit key
+CREATE TABLE IF NOT EXISTS AuditCheckpoints
+(
+    checkpoint_id BIGINT GENERATED ALWAYS AS IDENTITY,
+    account_id BIGINT NOT NULL,
+    -- Not a foreign key, so that a checkpoint still detects the removal of the event it covers
+    audit_event_id BIGINT NOT NULL,
+    event_hash BYTEA NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    signature BYTEA NOT NULL,
+    PRIMARY KEY (checkpoint_id),
+    FOREIGN KEY (account_id) REFERENCES Accounts(account_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS audit_checkpoints_account_id ON AuditCheckpoints (account_id, audit_event_id);
@@ -0,0 +1,7 @@
+-- IP address of the connection that made the request
+ALTER TABLE AuditEvents ADD COLUMN IF NOT EXISTS peer_address TEXT;
+-- Non-sensitive reason that the operation failed, e.g. `key-not-found`
+ALTER TABLE AuditEvents ADD COLUMN IF NOT EXISTS failure_code TEXT;
+-- Size and hash of the data that the action operated on
+ALTER TABLE AuditEvents ADD COLUMN IF NOT EXISTS payload_size BIGINT;
+ALTER TABLE AuditEvents ADD COLUMN IF NOT EXISTS payload_hash BYTEA;
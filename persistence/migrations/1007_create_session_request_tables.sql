@@ -0,0 +1,11 @@
+-- Requests made on each session, so that a recorded request can't be replayed
+-- on the same session
+CREATE TABLE IF NOT EXISTS SessionRequests
+(
+    session_id UUID NOT NULL,
+    request_id UUID NOT NULL,
+    -- ID from ClientActionsTypes
+    client_action_id BIGINT NOT NULL,
+    PRIMARY KEY (session_id, request_id, client_action_id),
+    FOREIGN KEY (session_id) REFERENCES Session(session_id) ON DELETE CASCADE
+);